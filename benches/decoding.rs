@@ -0,0 +1,69 @@
+//! Decoding throughput on tracker responses with long peer lists.
+//!
+//! Run with `cargo bench --bench decoding`. Uses only the standard library, so
+//! it prints the mean time per iteration instead of statistics.
+
+use std::time::{Duration, Instant};
+
+use bendy::{
+    decoding::{Decoder, FromBencode},
+    value::Value,
+};
+
+/// A tracker response with `count` peers in the original, non-compact format
+fn peer_list(count: usize) -> Vec<u8> {
+    let mut encoded = b"d8:intervali1800e5:peersl".to_vec();
+    for i in 0..count {
+        let ip = format!("10.{}.{}.{}", i >> 16 & 0xff, i >> 8 & 0xff, i & 0xff);
+        encoded.extend_from_slice(format!("d2:ip{}:{}7:peer id20:", ip.len(), ip).as_bytes());
+        encoded.extend_from_slice(format!("-BD0300-{:012}", i).as_bytes());
+        encoded.extend_from_slice(format!("4:porti{}ee", 6881 + i % 1000).as_bytes());
+    }
+    encoded.extend_from_slice(b"ee");
+    encoded
+}
+
+/// A list of `count` peer IDs, which consists mostly of length prefixes and
+/// short strings
+fn peer_ids(count: usize) -> Vec<u8> {
+    let mut encoded = b"l".to_vec();
+    for i in 0..count {
+        encoded.extend_from_slice(format!("20:-BD0300-{:012}", i).as_bytes());
+    }
+    encoded.push(b'e');
+    encoded
+}
+
+/// Run `f` repeatedly for about a second and print the mean time per run
+fn bench(name: &str, bytes: usize, mut f: impl FnMut()) {
+    f();
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+    let mean = start.elapsed() / runs;
+    let throughput = bytes as f64 / mean.as_secs_f64() / (1024.0 * 1024.0);
+    println!("{:<24} {:>10.1?} {:>10.1} MiB/s", name, mean, throughput);
+}
+
+fn main() {
+    let input = peer_list(20_000);
+
+    bench("tokens", input.len(), || {
+        let count = Decoder::new(&input).tokens().count();
+        assert!(count > 0);
+    });
+
+    bench("value", input.len(), || {
+        let value = Value::from_bencode(&input).unwrap();
+        assert!(matches!(value, Value::Dict(_)));
+    });
+
+    let ids = peer_ids(100_000);
+    bench("peer id tokens", ids.len(), || {
+        let count = Decoder::new(&ids).tokens().count();
+        assert_eq!(count, 100_002);
+    });
+}
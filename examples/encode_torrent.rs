@@ -13,10 +13,9 @@
 //! cargo run --example encode_torrent > example.torrent
 //! ```
 
-use std::io::Write;
+use std::{error::Error, io::Write};
 
 use bendy::encoding::{AsString, Error as EncodingError, SingleItemEncoder, ToBencode};
-use failure::Error;
 
 /// Main struct containing all required information.
 ///
@@ -91,7 +90,7 @@ impl ToBencode for Info {
     }
 }
 
-fn main() -> Result<(), Error> {
+fn main() -> Result<(), Box<dyn Error>> {
     let torrent = MetaInfo {
         announce: "http://bttracker.debian.org:6969/announce".to_owned(),
         comment: Some("\"Debian CD from cdimage.debian.org\"".to_owned()),
@@ -0,0 +1,665 @@
+//! Random values and torrents for property tests.
+//!
+//! With the `proptest` feature, [`Value`](crate::value::Value) and the typed
+//! models of v1 torrents in [`torrent`](crate::torrent) implement
+//! `proptest::arbitrary::Arbitrary`, so that properties such as decoding the
+//! encoding of any value returning it can be checked in a line:
+//!
+//! ```
+//! # #[cfg(feature = "proptest")]
+//! # fn main() {
+//! use bendy::{decoding::FromBencode, encoding::ToBencode, value::Value};
+//! use proptest::prelude::*;
+//!
+//! proptest!(|(value: Value<'static>)| {
+//!     let encoded = value.to_bencode().unwrap();
+//!     prop_assert_eq!(Value::from_bencode(&encoded).unwrap(), value);
+//! });
+//! # }
+//! # #[cfg(not(feature = "proptest"))]
+//! # fn main() {}
+//! ```
+//!
+//! How large the values get is set by the [`Limits`] they are generated with,
+//! using `any_with`.
+//!
+//! With the `quickcheck` feature, the same types implement
+//! `quickcheck::Arbitrary`, within the default limits and the size of the
+//! generator. Failing values shrink to their items and optional fields:
+//!
+//! ```
+//! # #[cfg(feature = "quickcheck")]
+//! # fn main() {
+//! use bendy::{decoding::FromBencode, encoding::ToBencode, value::Value};
+//!
+//! fn round_trips(value: Value<'static>) -> bool {
+//!     Value::from_bencode(&value.to_bencode().unwrap()).unwrap() == value
+//! }
+//!
+//! quickcheck::quickcheck(round_trips as fn(Value<'static>) -> bool);
+//! # }
+//! # #[cfg(not(feature = "quickcheck"))]
+//! # fn main() {}
+//! ```
+
+/// How large generated values may get.
+///
+/// With quickcheck, the items and bytes are also bounded by the size of the
+/// generator.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "proptest")]
+/// # fn main() {
+/// use bendy::{arbitrary::Limits, value::Value};
+/// use proptest::prelude::*;
+///
+/// let flat = Limits::new().with_max_depth(0);
+/// proptest!(|(value in any_with::<Value<'static>>(flat))| {
+///     prop_assert!(!matches!(value, Value::List(_) | Value::Dict(_)));
+/// });
+/// # }
+/// # #[cfg(not(feature = "proptest"))]
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    max_depth: usize,
+    max_items: usize,
+    max_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 4,
+            max_items: 8,
+            max_bytes: 16,
+        }
+    }
+}
+
+impl Limits {
+    /// Create the default limits: nested at most 4 deep, with up to 8 items
+    /// in each list or dict and byte strings of up to 16 bytes
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Set how deeply lists and dicts may be nested
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set how many items a list or dict may have
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = max_items;
+        self
+    }
+
+    /// Set how long byte strings may be
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// The limits of the values nested one level deeper
+    #[cfg(any(feature = "quickcheck", feature = "bittorrent"))]
+    fn nested(self) -> Self {
+        self.with_max_depth(self.max_depth.saturating_sub(1))
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod proptest_impls {
+    #[cfg(feature = "bittorrent")]
+    use std::collections::BTreeMap;
+
+    #[cfg(feature = "bittorrent")]
+    use proptest::{arbitrary::any_with, option};
+    use proptest::{
+        arbitrary::{any, Arbitrary},
+        collection::{btree_map, vec},
+        prop_oneof,
+        strategy::{BoxedStrategy, Strategy},
+    };
+
+    use super::Limits;
+    #[cfg(feature = "bittorrent")]
+    use crate::torrent::{DhtNode, FileEntry, Info, Layout, MetaInfo, UrlList};
+    use crate::value::{ByteString, Value};
+
+    fn bytes(limits: Limits) -> impl Strategy<Value = ByteString<'static>> + Clone {
+        vec(any::<u8>(), 0..=limits.max_bytes).prop_map(ByteString::from)
+    }
+
+    impl Arbitrary for Value<'static> {
+        type Parameters = Limits;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(limits: Limits) -> Self::Strategy {
+            let leaf = prop_oneof![
+                bytes(limits).prop_map(Value::Bytes),
+                any::<i64>().prop_map(Value::Integer),
+            ];
+            let items = limits.max_items;
+            leaf.prop_recursive(
+                limits.max_depth as u32,
+                (items * limits.max_depth).max(1) as u32,
+                items.max(1) as u32,
+                move |inner| {
+                    prop_oneof![
+                        vec(inner.clone(), 0..=items).prop_map(Value::List),
+                        btree_map(bytes(limits), inner, 0..=items).prop_map(Value::Dict),
+                    ]
+                },
+            )
+            .boxed()
+        }
+    }
+
+    /// The `extra` fields of a torrent struct, whose keys start with `x-` so that
+    /// they never collide with the typed ones
+    #[cfg(feature = "bittorrent")]
+    fn extra(limits: Limits) -> impl Strategy<Value = BTreeMap<Vec<u8>, Value<'static>>> {
+        let key =
+            vec(any::<u8>(), 0..=limits.max_bytes).prop_map(|key| [&b"x-"[..], &key].concat());
+        let value = any_with::<Value<'static>>(limits.nested());
+        btree_map(key, value, 0..=limits.max_items.min(2))
+    }
+
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for DhtNode {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            (any::<String>(), any::<u16>())
+                .prop_map(|(host, port)| DhtNode { host, port })
+                .boxed()
+        }
+    }
+
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for UrlList {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            prop_oneof![
+                any::<String>().prop_map(UrlList::Single),
+                vec(any::<String>(), 0..4).prop_map(UrlList::List),
+            ]
+            .boxed()
+        }
+    }
+
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for FileEntry {
+        type Parameters = Limits;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(limits: Limits) -> Self::Strategy {
+            (
+                any::<u64>(),
+                vec(any::<String>(), 0..=limits.max_items),
+                option::of(any::<String>()),
+                extra(limits),
+            )
+                .prop_map(|(length, path, attr, extra)| FileEntry {
+                    length,
+                    path,
+                    attr,
+                    extra,
+                })
+                .boxed()
+        }
+    }
+
+    /// Generates the `info` dicts of v1 torrents, with a `layout` and `pieces`
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for Info {
+        type Parameters = Limits;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(limits: Limits) -> Self::Strategy {
+            let layout = prop_oneof![
+                any::<u64>().prop_map(|length| Layout::SingleFile { length }),
+                vec(any_with::<FileEntry>(limits), 0..=limits.max_items)
+                    .prop_map(|files| Layout::MultiFile { files }),
+            ];
+            let pieces =
+                vec(any::<[u8; 20]>(), 0..=limits.max_items).prop_map(|hashes| hashes.concat());
+            (
+                any::<String>(),
+                any::<u64>(),
+                pieces,
+                layout,
+                option::of(any::<bool>()),
+                option::of(any::<String>()),
+                extra(limits),
+            )
+                .prop_map(
+                    |(name, piece_length, pieces, layout, private, source, extra)| Info {
+                        name,
+                        piece_length,
+                        pieces: Some(pieces),
+                        root_hash: None,
+                        layout: Some(layout),
+                        meta_version: None,
+                        file_tree: None,
+                        private,
+                        source,
+                        extra,
+                    },
+                )
+                .boxed()
+        }
+    }
+
+    /// Generates v1 torrents, without piece layers or signatures
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for MetaInfo {
+        type Parameters = Limits;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(limits: Limits) -> Self::Strategy {
+            let strings = || vec(any::<String>(), 0..=limits.max_items);
+            let text = || option::of(vec(any::<u8>(), 0..=limits.max_bytes));
+            let trackers = (
+                option::of(any::<String>()),
+                option::of(vec(strings(), 0..=limits.max_items)),
+                option::of(vec(any::<DhtNode>(), 0..=limits.max_items)),
+                option::of(strings()),
+                option::of(any::<UrlList>()),
+            );
+            let description = (text(), text(), option::of(any::<i64>()), text());
+            (
+                trackers,
+                description,
+                any_with::<Info>(limits),
+                extra(limits),
+            )
+                .prop_map(
+                    |(
+                        (announce, announce_list, nodes, httpseeds, url_list),
+                        (comment, created_by, creation_date, encoding),
+                        info,
+                        extra,
+                    )| MetaInfo {
+                        announce,
+                        announce_list,
+                        comment,
+                        created_by,
+                        creation_date,
+                        encoding,
+                        info,
+                        nodes,
+                        httpseeds,
+                        piece_layers: None,
+                        signatures: None,
+                        url_list,
+                        extra,
+                    },
+                )
+                .boxed()
+        }
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+mod quickcheck_impls {
+    use std::collections::BTreeMap;
+
+    use quickcheck::{Arbitrary, Gen};
+
+    use super::Limits;
+    #[cfg(feature = "bittorrent")]
+    use crate::torrent::{DhtNode, FileEntry, Info, Layout, MetaInfo, UrlList};
+    use crate::value::{ByteString, Value};
+
+    impl Limits {
+        /// The default limits, with items and bytes also bounded by the size of
+        /// `g`
+        fn of(g: &Gen) -> Self {
+            let limits = Limits::new();
+            limits
+                .with_max_items(limits.max_items.min(g.size()))
+                .with_max_bytes(limits.max_bytes.min(g.size()))
+        }
+    }
+
+    /// A random number up to and including `max`
+    fn up_to(g: &mut Gen, max: usize) -> usize {
+        usize::arbitrary(g) % (max + 1)
+    }
+
+    fn bytes(g: &mut Gen, limits: Limits) -> Vec<u8> {
+        (0..up_to(g, limits.max_bytes))
+            .map(|_| u8::arbitrary(g))
+            .collect()
+    }
+
+    fn value(g: &mut Gen, limits: Limits) -> Value<'static> {
+        let kinds = if limits.max_depth == 0 { 2 } else { 4 };
+        match up_to(g, kinds - 1) {
+            0 => Value::from(bytes(g, limits)),
+            1 => Value::Integer(i64::arbitrary(g)),
+            2 => Value::List(
+                (0..up_to(g, limits.max_items))
+                    .map(|_| value(g, limits.nested()))
+                    .collect(),
+            ),
+            _ => Value::Dict(
+                (0..up_to(g, limits.max_items))
+                    .map(|_| (bytes(g, limits).into(), value(g, limits.nested())))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Shrinks to the items of lists and dicts, then to fewer or smaller items
+    impl Arbitrary for Value<'static> {
+        fn arbitrary(g: &mut Gen) -> Self {
+            value(g, Limits::of(g))
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            match self {
+                Value::Bytes(bytes) => Box::new(bytes.to_vec().shrink().map(Value::from)),
+                Value::Integer(integer) => Box::new(integer.shrink().map(Value::Integer)),
+                Value::List(list) => Box::new(
+                    list.clone()
+                        .into_iter()
+                        .chain(list.shrink().map(Value::List)),
+                ),
+                Value::Dict(dict) => {
+                    let items: Vec<_> = dict.values().cloned().collect();
+                    let dict: BTreeMap<Vec<u8>, Value<'static>> = dict
+                        .iter()
+                        .map(|(key, value)| (key.to_vec(), value.clone()))
+                        .collect();
+                    let smaller = dict.shrink().map(|dict| {
+                        Value::Dict(
+                            dict.into_iter()
+                                .map(|(key, value)| (ByteString::from(key), value))
+                                .collect(),
+                        )
+                    });
+                    Box::new(items.into_iter().chain(smaller))
+                },
+            }
+        }
+    }
+
+    /// The `extra` fields of a torrent struct, like those generated with
+    /// proptest
+    #[cfg(feature = "bittorrent")]
+    fn extra(g: &mut Gen, limits: Limits) -> BTreeMap<Vec<u8>, Value<'static>> {
+        (0..up_to(g, limits.max_items.min(2)))
+            .map(|_| {
+                let key = [&b"x-"[..], &bytes(g, limits)].concat();
+                (key, value(g, limits.nested()))
+            })
+            .collect()
+    }
+
+    /// Copies of `original` with one of `changes` applied, where that changes
+    /// it
+    #[cfg(feature = "bittorrent")]
+    fn without<T: Clone + PartialEq>(original: &T, changes: &[fn(&mut T)]) -> Vec<T> {
+        changes
+            .iter()
+            .filter_map(|change| {
+                let mut smaller = original.clone();
+                change(&mut smaller);
+                Some(smaller).filter(|smaller| smaller != original)
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for DhtNode {
+        fn arbitrary(g: &mut Gen) -> Self {
+            DhtNode::new(String::arbitrary(g), u16::arbitrary(g))
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let node = (self.host.clone(), self.port);
+            Box::new(node.shrink().map(|(host, port)| DhtNode { host, port }))
+        }
+    }
+
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for UrlList {
+        fn arbitrary(g: &mut Gen) -> Self {
+            match bool::arbitrary(g) {
+                true => UrlList::Single(String::arbitrary(g)),
+                false => UrlList::List(Arbitrary::arbitrary(g)),
+            }
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            match self {
+                UrlList::Single(url) => Box::new(url.shrink().map(UrlList::Single)),
+                UrlList::List(urls) => Box::new(urls.shrink().map(UrlList::List)),
+            }
+        }
+    }
+
+    #[cfg(feature = "bittorrent")]
+    fn file_entry(g: &mut Gen, limits: Limits) -> FileEntry {
+        FileEntry {
+            length: u64::arbitrary(g),
+            path: (0..up_to(g, limits.max_items))
+                .map(|_| String::arbitrary(g))
+                .collect(),
+            attr: Arbitrary::arbitrary(g),
+            extra: extra(g, limits),
+        }
+    }
+
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for FileEntry {
+        fn arbitrary(g: &mut Gen) -> Self {
+            file_entry(g, Limits::of(g))
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let mut smaller = without(self, &[|file| file.attr = None, |file| file.extra.clear()]);
+            let file = self.clone();
+            smaller.extend(self.path.shrink().map(|path| FileEntry {
+                path,
+                ..file.clone()
+            }));
+            Box::new(smaller.into_iter())
+        }
+    }
+
+    #[cfg(feature = "bittorrent")]
+    fn info(g: &mut Gen, limits: Limits) -> Info {
+        let layout = match bool::arbitrary(g) {
+            true => Layout::SingleFile {
+                length: u64::arbitrary(g),
+            },
+            false => Layout::MultiFile {
+                files: (0..up_to(g, limits.max_items))
+                    .map(|_| file_entry(g, limits))
+                    .collect(),
+            },
+        };
+        let pieces = (0..up_to(g, limits.max_items) * 20)
+            .map(|_| u8::arbitrary(g))
+            .collect();
+        Info {
+            name: String::arbitrary(g),
+            piece_length: u64::arbitrary(g),
+            pieces: Some(pieces),
+            root_hash: None,
+            layout: Some(layout),
+            meta_version: None,
+            file_tree: None,
+            private: Arbitrary::arbitrary(g),
+            source: Arbitrary::arbitrary(g),
+            extra: extra(g, limits),
+        }
+    }
+
+    /// Generates the `info` dicts of v1 torrents, like those generated with
+    /// proptest
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for Info {
+        fn arbitrary(g: &mut Gen) -> Self {
+            info(g, Limits::of(g))
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let mut smaller = without(
+                self,
+                &[
+                    |info| info.private = None,
+                    |info| info.source = None,
+                    |info| info.extra.clear(),
+                    |info| {
+                        if let Some(pieces) = &mut info.pieces {
+                            pieces.truncate(pieces.len().saturating_sub(20));
+                        }
+                    },
+                ],
+            );
+            if let Some(Layout::MultiFile { files }) = &self.layout {
+                let info = self.clone();
+                smaller.extend(files.shrink().map(|files| Info {
+                    layout: Some(Layout::MultiFile { files }),
+                    ..info.clone()
+                }));
+            }
+            Box::new(smaller.into_iter())
+        }
+    }
+
+    /// Generates v1 torrents, like those generated with proptest
+    #[cfg(feature = "bittorrent")]
+    impl Arbitrary for MetaInfo {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let limits = Limits::of(g);
+            let strings = |g: &mut Gen| -> Vec<String> {
+                (0..up_to(g, limits.max_items))
+                    .map(|_| String::arbitrary(g))
+                    .collect()
+            };
+            let text = |g: &mut Gen| Some(bytes(g, limits)).filter(|_| bool::arbitrary(g));
+            MetaInfo {
+                announce: Arbitrary::arbitrary(g),
+                announce_list: match bool::arbitrary(g) {
+                    true => Some(
+                        (0..up_to(g, limits.max_items))
+                            .map(|_| strings(g))
+                            .collect(),
+                    ),
+                    false => None,
+                },
+                comment: text(g),
+                created_by: text(g),
+                creation_date: Arbitrary::arbitrary(g),
+                encoding: text(g),
+                info: info(g, limits),
+                nodes: match bool::arbitrary(g) {
+                    true => Some(
+                        (0..up_to(g, limits.max_items))
+                            .map(|_| DhtNode::arbitrary(g))
+                            .collect(),
+                    ),
+                    false => None,
+                },
+                httpseeds: Some(strings(g)).filter(|_| bool::arbitrary(g)),
+                piece_layers: None,
+                signatures: None,
+                url_list: Arbitrary::arbitrary(g),
+                extra: extra(g, limits),
+            }
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let mut smaller = without(
+                self,
+                &[
+                    |torrent| torrent.announce = None,
+                    |torrent| torrent.announce_list = None,
+                    |torrent| torrent.comment = None,
+                    |torrent| torrent.created_by = None,
+                    |torrent| torrent.creation_date = None,
+                    |torrent| torrent.encoding = None,
+                    |torrent| torrent.nodes = None,
+                    |torrent| torrent.httpseeds = None,
+                    |torrent| torrent.url_list = None,
+                    |torrent| torrent.extra.clear(),
+                ],
+            );
+            let torrent = self.clone();
+            smaller.extend(self.info.shrink().map(|info| MetaInfo {
+                info,
+                ..torrent.clone()
+            }));
+            Box::new(smaller.into_iter())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "proptest"))]
+mod proptest_tests {
+    use proptest::{arbitrary::any_with, prop_assert_eq, proptest};
+
+    use super::Limits;
+    #[cfg(feature = "bittorrent")]
+    use crate::torrent::MetaInfo;
+    use crate::{decoding::FromBencode, encoding::ToBencode, value::Value};
+
+    proptest! {
+        #[test]
+        fn values_round_trip(value in any_with::<Value<'static>>(Limits::new())) {
+            let encoded = value.to_bencode().unwrap();
+            prop_assert_eq!(Value::from_bencode(&encoded).unwrap(), value);
+        }
+    }
+
+    #[cfg(feature = "bittorrent")]
+    proptest! {
+        #[test]
+        fn torrents_round_trip(torrent in any_with::<MetaInfo>(Limits::new().with_max_items(2))) {
+            let encoded = torrent.to_bencode().unwrap();
+            prop_assert_eq!(MetaInfo::from_bencode(&encoded).unwrap(), torrent);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "quickcheck"))]
+mod quickcheck_tests {
+    use quickcheck::{quickcheck, Arbitrary};
+
+    use crate::{decoding::FromBencode, encoding::ToBencode, value::Value};
+
+    quickcheck! {
+        fn values_round_trip(value: Value<'static>) -> bool {
+            Value::from_bencode(&value.to_bencode().unwrap()).unwrap() == value
+        }
+    }
+
+    #[test]
+    fn values_shrink_to_their_items() {
+        let value = Value::List(vec![Value::Integer(7), Value::from("spam")]);
+        let smaller: Vec<_> = value.shrink().collect();
+        assert_eq!(&smaller[..2], &[Value::Integer(7), Value::from("spam")]);
+        assert!(smaller.contains(&Value::List(vec![Value::from("spam")])));
+    }
+
+    #[cfg(feature = "bittorrent")]
+    quickcheck! {
+        fn torrents_round_trip(torrent: crate::torrent::MetaInfo) -> bool {
+            let encoded = torrent.to_bencode().unwrap();
+            crate::torrent::MetaInfo::from_bencode(&encoded).unwrap() == torrent
+        }
+    }
+}
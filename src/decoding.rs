@@ -62,15 +62,35 @@
 //! #
 //! # assert!(syntax_check(b"i18e"));
 //! ```
+//!
+//! Errors of the first kind are [syntax errors](ErrorClass::Syntax). Errors in decoding valid
+//! bencode as some type, such as a missing field, are [structure errors](ErrorClass::Structure),
+//! after which the decoder can still read the rest of the input.
 
 mod decoder;
+mod digits;
 mod error;
 mod from_bencode;
 mod object;
+#[cfg(feature = "std")]
+mod parallel;
+mod policy;
+#[cfg(feature = "std")]
+mod stream;
+mod validate;
 
+#[cfg(feature = "rayon")]
+pub use self::parallel::decode_list_parallel;
 pub use self::{
     decoder::{Decoder, DictDecoder, ListDecoder, Tokens},
-    error::{Error, ErrorKind, ResultExt},
+    error::{Error, ErrorClass, ErrorKind, Excerpt, ResultExt},
     from_bencode::FromBencode,
-    object::Object,
+    object::{narrow, Object},
+    policy::{EmptyPolicy, Warnings},
+    validate::{is_valid_bencode, validate, Validity},
+};
+#[cfg(feature = "std")]
+pub use self::{
+    parallel::split_list,
+    stream::{DecoderContext, Digest, StreamDecoder, StreamToken},
 };
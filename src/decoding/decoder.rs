@@ -1,8 +1,11 @@
 use alloc::format;
-use core::str;
+use core::{ops::Range, str};
 
 use crate::{
-    decoding::{Error, Object},
+    decoding::{
+        digits::{digit_run, parse_length},
+        EmptyPolicy, Error, ErrorKind, Object, Warnings,
+    },
     state_tracker::{StateTracker, StructureError, Token},
 };
 
@@ -15,8 +18,13 @@ pub struct Decoder<'a> {
     source: &'a [u8],
     offset: usize,
     state: StateTracker<&'a [u8], Error>,
+    error_excerpt: usize,
 }
 
+/// The default number of bytes on either side of a failing token which its
+/// error shows
+const DEFAULT_ERROR_EXCERPT: usize = 8;
+
 impl<'ser> Decoder<'ser> {
     /// Create a new decoder from the given byte array
     pub fn new(buffer: &'ser [u8]) -> Self {
@@ -24,6 +32,7 @@ impl<'ser> Decoder<'ser> {
             source: buffer,
             offset: 0,
             state: StateTracker::new(),
+            error_excerpt: DEFAULT_ERROR_EXCERPT,
         }
     }
 
@@ -35,6 +44,66 @@ impl<'ser> Decoder<'ser> {
         self
     }
 
+    /// Set whether dictionary keys must be unique and in ascending order, as the
+    /// bencode specification requires. This is the default; disabling it allows
+    /// reading the output of encoders which don't sort their keys. Pairs are
+    /// still returned in the order in which they appear.
+    pub fn with_sorted_keys(mut self, required: bool) -> Self {
+        self.state.set_require_sorted_keys(required);
+        self
+    }
+
+    /// Set the maximum length of dictionary keys. Keys are short names in
+    /// most formats, so a limit such as 256 rejects the output of broken
+    /// encoders early, with an error pointing at the whole key. By default,
+    /// keys may be as long as any byte string.
+    ///
+    /// Keys which aren't byte strings, such as the integer keys some encoders
+    /// emit, are always rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::decoding::Decoder;
+    ///
+    /// let mut decoder = Decoder::new(b"d3:key1:v12:overlong key1:ve").with_max_key_len(8);
+    /// let error = decoder.tokens().find_map(Result::err).unwrap();
+    /// assert_eq!(error.code(), "E0107");
+    /// assert_eq!(error.excerpt().unwrap().span(), 9..24);
+    /// ```
+    pub fn with_max_key_len(mut self, max_key_len: usize) -> Self {
+        self.state.set_max_key_len(max_key_len);
+        self
+    }
+
+    /// Set whether dictionary keys may be empty byte strings, which bencode
+    /// allows. By default, they are [accepted](EmptyPolicy::Accept).
+    pub fn with_empty_keys(mut self, policy: EmptyPolicy) -> Self {
+        self.state.set_empty_keys(policy);
+        self
+    }
+
+    /// Set whether byte strings other than dictionary keys may be empty. By
+    /// default, they are [accepted](EmptyPolicy::Accept).
+    pub fn with_empty_strings(mut self, policy: EmptyPolicy) -> Self {
+        self.state.set_empty_strings(policy);
+        self
+    }
+
+    /// The empty strings and keys read so far which the decoder's
+    /// [`EmptyPolicy`] warns about
+    pub fn warnings(&self) -> Warnings {
+        self.state.warnings()
+    }
+
+    /// Set how many bytes of the input on either side of a token which can't
+    /// be read are shown in its error's [`Excerpt`](crate::decoding::Excerpt).
+    /// The default is 8; with 0, the error only shows the offset.
+    pub fn with_error_excerpt(mut self, window: usize) -> Self {
+        self.error_excerpt = window;
+        self
+    }
+
     fn take_byte(&mut self) -> Option<u8> {
         if self.offset < self.source.len() {
             let ret = Some(self.source[self.offset]);
@@ -57,6 +126,18 @@ impl<'ser> Decoder<'ser> {
     }
 
     fn take_int(&mut self, expected_terminator: char) -> Result<&'ser str, StructureError> {
+        // Valid integers are found with a single search for the terminator. The
+        // state machine below is only needed to report where an invalid one goes
+        // wrong
+        let rest = &self.source[self.offset..];
+        if let Some(end) = find_byte(expected_terminator as u8, rest) {
+            if is_integer(&rest[..end]) {
+                self.offset += end + 1;
+                // Integers are ASCII
+                return Ok(unsafe { str::from_utf8_unchecked(&rest[..end]) });
+            }
+        }
+
         enum State {
             Start,
             Sign,
@@ -135,6 +216,24 @@ impl<'ser> Decoder<'ser> {
         Ok(ival)
     }
 
+    /// Read the length prefix of a byte string, including the colon
+    fn take_length(&mut self) -> Result<usize, StructureError> {
+        let rest = &self.source[self.offset..];
+        if let Some((len, digits)) = parse_length(rest) {
+            if rest.get(digits) == Some(&b':') {
+                self.offset += digits + 1;
+                return Ok(len);
+            }
+        }
+
+        // Report what's wrong with the prefix
+        let curpos = self.offset;
+        let ival = self.take_int(':')?;
+        usize::from_str_radix(ival, 10).map_err(|_| {
+            StructureError::SyntaxError(format!("Invalid integer at offset {}", curpos))
+        })
+    }
+
     fn raw_next_token(&mut self) -> Result<Token<'ser>, Error> {
         let token = match self.take_byte().ok_or(StructureError::UnexpectedEof)? as char {
             'e' => Token::End,
@@ -143,12 +242,7 @@ impl<'ser> Decoder<'ser> {
             'i' => Token::Num(self.take_int('e')?),
             c if c >= '0' && c <= '9' => {
                 self.offset -= 1;
-
-                let curpos = self.offset;
-                let ival = self.take_int(':')?;
-                let len = usize::from_str_radix(ival, 10).map_err(|_| {
-                    StructureError::SyntaxError(format!("Invalid integer at offset {}", curpos))
-                })?;
+                let len = self.take_length()?;
                 Token::String(self.take_chunk(len).ok_or(StructureError::UnexpectedEof)?)
             },
             tok => {
@@ -164,21 +258,72 @@ impl<'ser> Decoder<'ser> {
     }
 
     /// Read the next token. Returns Ok(Some(token)) if a token was successfully read,
-    fn next_token(&mut self) -> Result<Option<Token<'ser>>, Error> {
+    pub(super) fn next_token(&mut self) -> Result<Option<Token<'ser>>, Error> {
         self.state.check_error()?;
 
-        if self.offset == self.source.len() {
-            self.state.observe_eof()?;
+        self.read_token().map_err(|(error, span)| {
+            let span = match error.kind() {
+                ErrorKind::StructureError(StructureError::UnexpectedEof) => {
+                    self.source.len()..self.source.len()
+                },
+                _ => span,
+            };
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                code = error.code(),
+                offset = span.start,
+                depth = self.state.depth(),
+                "decoding failed"
+            );
+            let error = error.with_excerpt(self.source, span, self.error_excerpt);
+            self.state.replace_err(error.clone());
+            error
+        })
+    }
+
+    /// Read the next token, or fail with the span of the input to blame
+    fn read_token(&mut self) -> Result<Option<Token<'ser>>, (Error, Range<usize>)> {
+        let start = self.offset;
+        if start == self.source.len() {
+            self.state
+                .observe_eof()
+                .map_err(|error| (error, start..start))?;
             return Ok(None);
         }
 
         let tok_result = self.raw_next_token();
-        let tok = self.state.latch_err(tok_result)?;
-
-        self.state.observe_token(&tok)?;
+        let tok = self
+            .state
+            .latch_err(tok_result)
+            .map_err(|error| (error, start..start + 1))?;
+
+        // The whole token was read, so a rejected key is blamed in full
+        self.state
+            .observe_token(&tok)
+            .map_err(|error| (error, start..self.offset))?;
         Ok(Some(tok))
     }
 
+    /// The number of lists and dicts the next token is nested in
+    pub(super) fn depth(&self) -> usize {
+        self.state.depth()
+    }
+
+    /// Whether keys were read that were out of order, which is only possible
+    /// if [`with_sorted_keys`](Decoder::with_sorted_keys) allows it
+    pub(super) fn saw_unsorted_keys(&self) -> bool {
+        self.state.saw_unsorted_keys()
+    }
+
+    /// Attach an excerpt of the input at the current offset to `error`, and
+    /// return it from every later call
+    pub(super) fn fail(&mut self, error: Error) -> Error {
+        let end = self.source.len().min(self.offset + 1);
+        let error = error.with_excerpt(self.source, self.offset..end, self.error_excerpt);
+        self.state.replace_err(error.clone());
+        error
+    }
+
     /// Iterate over the tokens in the input stream. This guarantees that the resulting stream
     /// of tokens constitutes a valid bencoded structure.
     pub fn tokens(self) -> Tokens<'ser> {
@@ -186,6 +331,29 @@ impl<'ser> Decoder<'ser> {
     }
 }
 
+#[cfg(feature = "memchr")]
+fn find_byte(needle: u8, haystack: &[u8]) -> Option<usize> {
+    memchr::memchr(needle, haystack)
+}
+
+#[cfg(not(feature = "memchr"))]
+fn find_byte(needle: u8, haystack: &[u8]) -> Option<usize> {
+    haystack.iter().position(|&byte| byte == needle)
+}
+
+/// Whether `bytes` is an integer without leading zeros, as `take_int` accepts.
+pub(super) fn is_integer(bytes: &[u8]) -> bool {
+    let digits = match bytes {
+        [b'-', digits @ ..] => digits,
+        digits => digits,
+    };
+    match digits {
+        [b'0'] => digits.len() == bytes.len(),
+        [b'1'..=b'9', rest @ ..] => digit_run(rest) == rest.len(),
+        _ => false,
+    }
+}
+
 /// Iterator over the tokens in the input stream. This guarantees that the resulting stream
 /// of tokens constitutes a valid bencoded structure.
 pub struct Tokens<'a>(Decoder<'a>);
@@ -228,6 +396,15 @@ impl<'ser> Decoder<'ser> {
             Some(Num(s)) => Some(Object::Integer(s)),
         })
     }
+
+    /// The part of the input after everything read so far.
+    ///
+    /// Once an object returned by [`Decoder::next_object()`] has been read completely,
+    /// this is the data following it, such as the binary payload some protocols append
+    /// to a bencoded header.
+    pub fn remaining(&self) -> &'ser [u8] {
+        &self.source[self.offset..]
+    }
 }
 
 /// A dictionary read from the input stream
@@ -236,6 +413,8 @@ pub struct DictDecoder<'obj, 'ser: 'obj> {
     decoder: &'obj mut Decoder<'ser>,
     finished: bool,
     start_point: usize,
+    /// The nesting of the last token read with `next_token` within this one
+    depth: usize,
 }
 
 /// A list read from the input stream
@@ -244,6 +423,8 @@ pub struct ListDecoder<'obj, 'ser: 'obj> {
     decoder: &'obj mut Decoder<'ser>,
     finished: bool,
     start_point: usize,
+    /// The nesting of the last token read with `next_token` within this one
+    depth: usize,
 }
 
 impl<'obj, 'ser: 'obj> DictDecoder<'obj, 'ser> {
@@ -253,6 +434,7 @@ impl<'obj, 'ser: 'obj> DictDecoder<'obj, 'ser> {
             decoder,
             finished: false,
             start_point: offset,
+            depth: 0,
         }
     }
 
@@ -284,12 +466,19 @@ impl<'obj, 'ser: 'obj> DictDecoder<'obj, 'ser> {
     /// dictionary. This method should be used to check for encoding errors if
     /// [`DictDecoder::next_pair`] is not called until it returns `Ok(None)`.
     pub fn consume_all(&mut self) -> Result<(), Error> {
-        while let Some(_) = self.next_pair()? {
-            // just drop the items
+        while let Some(_) = self.next_token()? {
+            // just drop the tokens
         }
         Ok(())
     }
 
+    /// Read the next token of the rest of the dictionary, including nested
+    /// ones, up to and including its end. Unlike reading nested objects, this
+    /// doesn't recurse however deeply the dictionary is nested.
+    pub(crate) fn next_token(&mut self) -> Result<Option<Token<'ser>>, Error> {
+        next_nested_token(self.decoder, &mut self.finished, &mut self.depth)
+    }
+
     /// Get the raw bytes that made up this dictionary
     pub fn into_raw(mut self) -> Result<&'ser [u8], Error> {
         self.consume_all()?;
@@ -311,6 +500,7 @@ impl<'obj, 'ser: 'obj> ListDecoder<'obj, 'ser> {
             decoder,
             finished: false,
             start_point: offset,
+            depth: 0,
         }
     }
 
@@ -328,18 +518,37 @@ impl<'obj, 'ser: 'obj> ListDecoder<'obj, 'ser> {
         Ok(item)
     }
 
+    /// Get the raw bytes of the next item from the list, after validating its
+    /// structure. Returns `Ok(None)` at the end of the list
+    pub fn next_raw(&mut self) -> Result<Option<&'ser [u8]>, Error> {
+        let start = self.decoder.offset;
+        match self.next_object()? {
+            None => return Ok(None),
+            Some(Object::List(mut list)) => list.consume_all()?,
+            Some(Object::Dict(mut dict)) => dict.consume_all()?,
+            Some(Object::Bytes(_)) | Some(Object::Integer(_)) => {},
+        }
+        Ok(Some(&self.decoder.source[start..self.decoder.offset]))
+    }
+
     /// Consume (and validate the structure of) the rest of the items from the
     /// list. This method should be used to check for encoding errors if
     /// [`ListDecoder::next_object`] is not called until it returns [`Ok(())`].
     ///
     /// [`Ok(())`]: https://doc.rust-lang.org/std/result/enum.Result.html#variant.Ok
     pub fn consume_all(&mut self) -> Result<(), Error> {
-        while let Some(_) = self.next_object()? {
-            // just drop the items
+        while let Some(_) = self.next_token()? {
+            // just drop the tokens
         }
         Ok(())
     }
 
+    /// Read the next token of the rest of the list, like
+    /// [`DictDecoder::next_token`]
+    pub(crate) fn next_token(&mut self) -> Result<Option<Token<'ser>>, Error> {
+        next_nested_token(self.decoder, &mut self.finished, &mut self.depth)
+    }
+
     /// Get the raw bytes that made up this list
     pub fn into_raw(mut self) -> Result<&'ser [u8], Error> {
         self.consume_all()?;
@@ -354,9 +563,32 @@ impl<'obj, 'ser: 'obj> Drop for ListDecoder<'obj, 'ser> {
     }
 }
 
+/// Read the next token of a list or dict, tracking the depth of nested
+/// structures, until the end of the list or dict itself
+fn next_nested_token<'ser>(
+    decoder: &mut Decoder<'ser>,
+    finished: &mut bool,
+    depth: &mut usize,
+) -> Result<Option<Token<'ser>>, Error> {
+    if *finished {
+        return Ok(None);
+    }
+
+    let token = decoder.next_token()?.ok_or(StructureError::UnexpectedEof)?;
+    match token {
+        Token::List | Token::Dict => *depth += 1,
+        Token::End if *depth == 0 => *finished = true,
+        Token::End => *depth -= 1,
+        Token::String(_) | Token::Num(_) => {},
+    }
+    Ok(Some(token))
+}
+
 #[cfg(test)]
 mod test {
 
+    #[cfg(not(feature = "std"))]
+    use alloc::string::ToString;
     #[cfg(not(feature = "std"))]
     use alloc::{vec, vec::Vec};
     use core::iter;
@@ -364,6 +596,7 @@ mod test {
     use regex;
 
     use super::*;
+    use crate::decoding::ErrorClass;
 
     static SIMPLE_MSG: &'static [u8] = b"d3:bari1e3:fooli2ei3eee";
 
@@ -434,6 +667,25 @@ mod test {
         assert_eq!(tokens, vec![Num(&"0"), Num(&"-1")],);
     }
 
+    #[test]
+    fn delimiters_in_strings_are_skipped() {
+        use self::Token::*;
+        let tokens: Vec<_> = decode_tokens(b"l3:1:e10:i1e:2:e:eei12345678901234567890ee");
+        assert_eq!(
+            tokens,
+            vec![
+                List,
+                String(&b"1:e"[..]),
+                String(&b"i1e:2:e:ee"[..]),
+                Num("12345678901234567890"),
+                End,
+            ]
+        );
+        decode_err(b"i1-2e", "got '-'");
+        decode_err(b"-1:a", "Invalid token");
+        decode_err(b"01:a", "got '1'");
+    }
+
     #[test]
     fn negative_zero_is_illegal() {
         decode_err(b"i-0e", "got '0'");
@@ -460,6 +712,77 @@ mod test {
         decode_err(b"d3:fooi1e3:fooi1ee", r"Keys were not sorted");
     }
 
+    #[test]
+    fn remaining_data_follows_the_object() {
+        let mut decoder = Decoder::new(b"d1:ai1eexyz");
+        assert_eq!(decoder.remaining(), b"d1:ai1eexyz");
+        decoder
+            .next_object()
+            .unwrap()
+            .unwrap()
+            .try_into_dictionary()
+            .unwrap()
+            .consume_all()
+            .unwrap();
+        assert_eq!(decoder.remaining(), b"xyz");
+    }
+
+    #[test]
+    fn unsorted_keys_can_be_allowed() {
+        let tokens: Vec<_> = Decoder::new(b"d3:fooi1e3:bari2e3:bari3ee")
+            .with_sorted_keys(false)
+            .tokens()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens.len(), 8);
+    }
+
+    #[test]
+    fn keys_can_be_limited() {
+        let first_error = |decoder: Decoder| decoder.tokens().find_map(Result::err).unwrap();
+
+        let error = first_error(Decoder::new(b"d1:ai1e4:spami2ee").with_max_key_len(3));
+        assert_eq!(error.excerpt().unwrap().span(), 7..13);
+        let message = error.to_string();
+        assert!(message.contains("Invalid dict key: 4 bytes long, more than the limit of 3"));
+        assert!(message.ends_with("|d1:ai1e[4][:][s][p][a][m]i2ee|"));
+
+        let error = first_error(Decoder::new(b"d1:ad0:i1eee").with_empty_keys(EmptyPolicy::Reject));
+        assert_eq!(error.excerpt().unwrap().span(), 5..7);
+        assert_eq!(error.class(), ErrorClass::Structure);
+
+        let error = first_error(Decoder::new(b"di1ei2ee").with_max_key_len(8));
+        assert_eq!(error.excerpt().unwrap().span(), 1..4);
+
+        let tokens = Decoder::new(b"d0:i1e3:fooi2ee")
+            .with_max_key_len(3)
+            .tokens();
+        assert_eq!(tokens.collect::<Result<Vec<_>, _>>().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn empty_strings_follow_their_policy() {
+        let input = b"d0:l0:e1:a0:e";
+        let read = |mut decoder: Decoder| {
+            while decoder.next_token()?.is_some() {}
+            Ok::<_, Error>(decoder.warnings())
+        };
+
+        assert!(read(Decoder::new(input)).unwrap().is_empty());
+
+        let warnings = read(Decoder::new(input).with_empty_keys(EmptyPolicy::Warn)).unwrap();
+        assert_eq!((warnings.empty_keys(), warnings.empty_strings()), (1, 0));
+        let warnings = read(Decoder::new(input).with_empty_strings(EmptyPolicy::Warn)).unwrap();
+        assert_eq!((warnings.empty_keys(), warnings.empty_strings()), (0, 2));
+
+        let error = read(Decoder::new(input).with_empty_strings(EmptyPolicy::Reject)).unwrap_err();
+        assert_eq!(error.code(), "E0108");
+        assert_eq!(error.excerpt().unwrap().span(), 4..6);
+        let error = read(Decoder::new(input).with_empty_keys(EmptyPolicy::Reject)).unwrap_err();
+        assert_eq!(error.code(), "E0107");
+        assert_eq!(error.excerpt().unwrap().span(), 1..3);
+    }
+
     #[test]
     fn map_keys_must_have_values() {
         decode_err(b"d3:fooe", r"Missing map value");
@@ -475,6 +798,31 @@ mod test {
         decode_err(b"ie", r"Expected.*got 'e'");
     }
 
+    #[test]
+    fn errors_show_the_input_around_them() {
+        decode_err(
+            b"li1ei-0ee",
+            r"at byte 4: .* \[69\] 2d 30 .*\|li1e\[i\]-0ee\|$",
+        );
+        decode_err(
+            b"d1:b0:1:a0:e",
+            r"at byte 6: 64 31 3a 62 30 3a \[31\] .*\|d1:b0:\[1\]",
+        );
+
+        let error = Decoder::new(b"l3:ab")
+            .tokens()
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(error.excerpt().unwrap().offset(), 5);
+
+        let error = Decoder::new(b"lxe")
+            .with_error_excerpt(0)
+            .tokens()
+            .find_map(Result::err)
+            .unwrap();
+        assert!(error.to_string().ends_with(" at byte 1"));
+    }
+
     #[test]
     fn recursion_should_be_limited() {
         let mut msg = Vec::new();
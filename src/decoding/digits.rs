@@ -0,0 +1,114 @@
+//! Validating and parsing runs of ASCII digits eight bytes at a time.
+//!
+//! Length prefixes and integers make up a good part of bencoded inputs with
+//! many small values, such as peer lists. Treating eight digits as a single
+//! `u64` replaces a comparison per byte with a few arithmetic operations per
+//! word.
+
+use core::convert::TryInto;
+
+const ONES: u64 = 0x0101_0101_0101_0101;
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+/// A mask with the high bit of every byte of `word` set that's not an ASCII
+/// digit
+fn non_digits(word: u64) -> u64 {
+    let low = word & !HIGH_BITS;
+    // With the high bit cleared, neither addition carries into the next byte.
+    // The high bit of a byte of `at_least_0` is set if it's at least b'0', and
+    // that of `above_9` if it's greater than b'9'.
+    let at_least_0 = low + ONES * (0x80 - u64::from(b'0'));
+    let above_9 = low + ONES * (0x80 - u64::from(b'9') - 1);
+    (word | !at_least_0 | above_9) & HIGH_BITS
+}
+
+/// The number of ASCII digits at the start of `bytes`
+pub(super) fn digit_run(bytes: &[u8]) -> usize {
+    let mut chunks = bytes.chunks_exact(8);
+    let mut count = 0;
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().expect("Chunks have 8 bytes"));
+        let non_digits = non_digits(word);
+        if non_digits != 0 {
+            // The first byte is the least significant one
+            return count + non_digits.trailing_zeros() as usize / 8;
+        }
+        count += 8;
+    }
+    count
+        + chunks
+            .remainder()
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count()
+}
+
+/// The value of eight ASCII digits
+fn parse_eight(chunk: &[u8]) -> u32 {
+    let word = u64::from_le_bytes(chunk.try_into().expect("Chunks have 8 bytes")) - ONES * 0x30;
+    // Combine neighbouring digits, then pairs of them, then quadruples. The
+    // first digit is the least significant byte, so it's multiplied by ten
+    // and added to the next one.
+    let pairs = (word.wrapping_mul(10 << 8 | 1) >> 8) & 0x00ff_00ff_00ff_00ff;
+    let quadruples = (pairs.wrapping_mul(100 << 16 | 1) >> 16) & 0x0000_ffff_0000_ffff;
+    (quadruples.wrapping_mul(10_000 << 32 | 1) >> 32) as u32
+}
+
+/// Parse the length prefix of a byte string, the ASCII digits at the start of
+/// `bytes` without leading zeros, and return it with the number of digits.
+/// Returns `None` if there are no digits, a leading zero or an overflow.
+pub(super) fn parse_length(bytes: &[u8]) -> Option<(usize, usize)> {
+    let digits = digit_run(bytes);
+    if digits == 0 || (bytes[0] == b'0' && digits > 1) {
+        return None;
+    }
+
+    let mut chunks = bytes[..digits].chunks_exact(8);
+    let mut length: usize = 0;
+    for chunk in &mut chunks {
+        length = length
+            .checked_mul(100_000_000)?
+            .checked_add(parse_eight(chunk) as usize)?;
+    }
+    for &digit in chunks.remainder() {
+        length = length
+            .checked_mul(10)?
+            .checked_add(usize::from(digit - b'0'))?;
+    }
+    Some((length, digits))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, vec};
+
+    use super::*;
+
+    #[test]
+    fn digit_runs_end_at_any_byte() {
+        for len in 0..20 {
+            for &end in &[b'/', b':', b'e', 0xb0, 0x00, 0xff] {
+                let mut bytes = vec![b'7'; len];
+                bytes.push(end);
+                bytes.extend_from_slice(b"123456789");
+                assert_eq!(digit_run(&bytes), len, "{:?}", bytes);
+            }
+        }
+        assert_eq!(digit_run(b"0123456789"), 10);
+    }
+
+    #[test]
+    fn lengths_are_parsed() {
+        for &length in &[0u64, 1, 9, 10, 12_345_678, 123_456_789, 9_876_543_210_123] {
+            let encoded = format!("{}:", length);
+            assert_eq!(
+                parse_length(encoded.as_bytes()),
+                Some((length as usize, encoded.len() - 1))
+            );
+        }
+        assert_eq!(parse_length(b"012:"), None);
+        assert_eq!(parse_length(b":"), None);
+        assert_eq!(parse_length(b"99999999999999999999999:"), None);
+    }
+}
@@ -1,74 +1,341 @@
-#[cfg(not(feature = "std"))]
-use alloc::{str::Utf8Error, string::FromUtf8Error};
-#[cfg(not(feature = "std"))]
-use core::num::ParseIntError;
-
 use alloc::{
+    boxed::Box,
     format,
-    string::{String, ToString},
+    str::Utf8Error,
+    string::{FromUtf8Error, String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Display, Formatter},
+    num::{ParseIntError, TryFromIntError},
+    ops::Range,
 };
-use core::fmt::{self, Display, Formatter};
 
 #[cfg(feature = "std")]
-use std::{error::Error as StdError, sync::Arc};
-
-use failure::Fail;
+use std::{error::Error as StdError, io, sync::Arc};
 
 use crate::state_tracker::StructureError;
 
-#[derive(Debug, Clone, Fail)]
+/// An error raised while decoding, with the path of the field it occurred in.
+///
+/// With the `std` feature, this implements [`std::error::Error`], and
+/// [`source`](std::error::Error::source) returns the error that caused
+/// malformed content, such as an I/O or UTF-8 error, or the structure error.
+#[derive(Debug, Clone)]
 pub struct Error {
-    #[fail(context)]
-    context: Option<String>,
-    #[fail(cause)]
     error: ErrorKind,
+    /// Boxed, since it's rarely set and results of decoding are moved a lot
+    location: Option<Box<Location>>,
+}
+
+/// Where in the decoded structure and the input an [`Error`] occurred
+#[derive(Debug, Clone, Default)]
+struct Location {
+    context: Option<String>,
+    excerpt: Option<Excerpt>,
+}
+
+/// The input around the token a [`Decoder`](crate::decoding::Decoder) failed
+/// to read, which the message of its error ends with.
+///
+/// The bytes are shown in hex and as ASCII, with the first byte of the token
+/// in brackets:
+///
+/// ```
+/// use bendy::decoding::Decoder;
+///
+/// let mut decoder = Decoder::new(b"li1ei2exi3ee");
+/// let error = decoder.tokens().find_map(Result::err).unwrap();
+/// let excerpt = error.excerpt().unwrap();
+/// assert_eq!(excerpt.offset(), 7);
+/// assert_eq!(
+///     excerpt.to_string(),
+///     "at byte 7: 6c 69 31 65 69 32 65 [78] 69 33 65 65 |li1ei2e[x]i3ee|"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Excerpt {
+    offset: usize,
+    end: usize,
+    start: usize,
+    bytes: Vec<u8>,
+}
+
+impl Excerpt {
+    /// The excerpt of `source` with up to `window` bytes on either side of
+    /// `span`
+    pub(crate) fn new(source: &[u8], span: Range<usize>, window: usize) -> Self {
+        let start = span.start.saturating_sub(window).min(source.len());
+        let end = match window {
+            0 => start,
+            _ => (span.end.max(span.start + 1) + window - 1).min(source.len()),
+        };
+        Excerpt {
+            offset: span.start,
+            end: span.end,
+            start,
+            bytes: source[start..end].to_vec(),
+        }
+    }
+
+    /// The offset of the token which couldn't be read, or the length of the
+    /// input if it ended too early
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The range of the input the error is about: the first byte of the token
+    /// which couldn't be read, or all of a token which was read but rejected,
+    /// such as a dict key. Empty if the input ended too early.
+    pub fn span(&self) -> Range<usize> {
+        self.offset..self.end
+    }
+
+    /// The offset of the first byte of the excerpt
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The bytes around the span
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Display for Excerpt {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "at byte {}", self.offset)?;
+        if self.bytes.is_empty() {
+            return Ok(());
+        }
+
+        let marked = self.offset - self.start..self.end - self.start;
+        write!(f, ":")?;
+        for (index, byte) in self.bytes.iter().enumerate() {
+            if marked.contains(&index) {
+                write!(f, " [{:02x}]", byte)?;
+            } else {
+                write!(f, " {:02x}", byte)?;
+            }
+        }
+        write!(f, " |")?;
+        for (index, &byte) in self.bytes.iter().enumerate() {
+            let shown = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            };
+            if marked.contains(&index) {
+                write!(f, "[{}]", shown)?;
+            } else {
+                write!(f, "{}", shown)?;
+            }
+        }
+        write!(f, "|")
+    }
 }
 
 /// An enumeration of potential errors that appear during bencode deserialization.
-#[derive(Debug, Clone, Fail)]
+///
+/// More kinds may be added without a major release, so matches on this enum
+/// need a wildcard arm.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ErrorKind {
     /// Error that occurs if the serialized structure contains invalid semantics.
     #[cfg(feature = "std")]
-    #[fail(display = "malformed content discovered: {}", _0)]
-    MalformedContent(Arc<failure::Error>),
+    MalformedContent(Arc<dyn StdError + Send + Sync>),
     /// Error that occurs if the serialized structure contains invalid semantics.
     #[cfg(not(feature = "std"))]
-    #[fail(display = "malformed content discovered")]
     MalformedContent,
+    /// Error that occurs if an integer doesn't fit into the type it's decoded as.
+    IntegerOverflow(String),
     /// Error that occurs if the serialized structure is incomplete.
-    #[fail(display = "missing field: {}", _0)]
     MissingField(String),
     /// Error in the bencode structure (e.g. a missing field end separator).
-    #[fail(display = "bencode encoding corrupted ({})", _0)]
-    StructureError(#[fail(cause)] StructureError),
+    StructureError(StructureError),
+    /// Error that occurs if bytes remain after a value that must make up the
+    /// whole input.
+    TrailingData,
     /// Error that occurs if the serialized structure contains an unexpected field.
-    #[fail(display = "unexpected field: {}", _0)]
     UnexpectedField(String),
     /// Error through an unexpected bencode token during deserialization.
-    #[fail(display = "discovered {} but expected {}", _0, _1)]
-    UnexpectedToken(String, String),
+    UnexpectedToken {
+        /// What the decoded type expected
+        expected: String,
+        /// What the input contained instead
+        discovered: String,
+    },
+}
+
+/// The class of a decoding [`Error`], for reacting to each differently.
+///
+/// More classes may be added without a major release, so matches on this enum
+/// need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorClass {
+    /// The input isn't valid bencode, such as a malformed token, unsorted
+    /// keys or an input which ends in the middle of a value. A decoder
+    /// returns the same error from then on, so the rest of the input can't be
+    /// read.
+    Syntax,
+    /// The input is valid bencode which doesn't fit what is decoded from it,
+    /// such as a field of the wrong type or with an invalid value, a missing
+    /// field, or nesting deeper than the decoder allows. The value can be
+    /// skipped, and decoding continue after it.
+    Structure,
 }
 
-pub trait ResultExt {
-    fn context(self, context: impl Display) -> Self;
+/// Adds the path of a field to the error of a result, like [`Error::context`].
+///
+/// This is implemented for results with any error which converts into a
+/// decoding [`Error`], which keeps it as its
+/// [`source`](std::error::Error::source). Errors of other libraries, or of
+/// your own type after implementing `From<YourError> for Error`, can be
+/// returned from [`FromBencode`](crate::decoding::FromBencode) with the field
+/// they occurred in:
+///
+/// ```
+/// use bendy::decoding::{Error, ResultExt};
+///
+/// fn parse_port(text: &str) -> Result<u16, Error> {
+///     text.parse::<u16>().context("port")
+/// }
+///
+/// let error = parse_port("http").unwrap_err();
+/// assert_eq!(error.context_path(), Some("port"));
+/// ```
+pub trait ResultExt<T> {
+    fn context(self, context: impl Display) -> Result<T, Error>;
 }
 
 impl Error {
+    /// Prepend a segment to the path of the field the error occurred in, such
+    /// as a dictionary key or list index. Each caller adds the name of the
+    /// field it was decoding on the way out, so the path reads from the
+    /// outermost field inwards:
+    ///
+    /// ```
+    /// use bendy::decoding::Error;
+    ///
+    /// let error = Error::missing_field("length").context(0).context("files");
+    /// assert_eq!(error.context_path(), Some("files.0"));
+    /// assert_eq!(error.to_string(), "Error: missing field: length in files.0");
+    /// ```
     pub fn context(mut self, context: impl Display) -> Self {
-        if let Some(current) = self.context.as_mut() {
+        let location = self.location.get_or_insert_with(Box::default);
+        if let Some(current) = location.context.as_mut() {
             *current = format!("{}.{}", context, current);
         } else {
-            self.context = Some(context.to_string());
+            location.context = Some(context.to_string());
         }
 
         self
     }
 
+    /// What went wrong, for handling errors programmatically.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::decoding::{ErrorKind, FromBencode};
+    ///
+    /// let error = u8::from_bencode(b"i300e").unwrap_err();
+    /// assert!(matches!(error.kind(), ErrorKind::IntegerOverflow(_)));
+    /// ```
+    pub fn kind(&self) -> &ErrorKind {
+        &self.error
+    }
+
+    /// Whether this is a syntax or a structure error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::decoding::{ErrorClass, FromBencode};
+    ///
+    /// let error = String::from_bencode(b"3:ab").unwrap_err();
+    /// assert_eq!(error.class(), ErrorClass::Syntax);
+    ///
+    /// let error = String::from_bencode(b"i1e").unwrap_err();
+    /// assert_eq!(error.class(), ErrorClass::Structure);
+    /// ```
+    pub fn class(&self) -> ErrorClass {
+        self.error.class()
+    }
+
+    /// Report a syntax error as a structure error, such as when it occurred
+    /// decoding bencode embedded in a byte string of a valid outer value.
+    /// Structure errors are returned unchanged.
+    pub fn into_structure(self) -> Self {
+        match self.class() {
+            ErrorClass::Syntax => Self::malformed_content(self),
+            _ => self,
+        }
+    }
+
+    /// A stable code for the kind of this error, for aggregating errors in
+    /// logs and monitoring. Codes never change meaning between versions, and
+    /// new kinds get new codes.
+    ///
+    /// | Code    | Kind                                         |
+    /// |---------|----------------------------------------------|
+    /// | `E0101` | `StructureError::UnsortedKeys`               |
+    /// | `E0102` | `StructureError::UnexpectedEof`              |
+    /// | `E0103` | `StructureError::SyntaxError`                |
+    /// | `E0104` | `StructureError::InvalidState`               |
+    /// | `E0105` | `StructureError::NestingTooDeep`             |
+    /// | `E0106` | [`TrailingData`](ErrorKind::TrailingData)         |
+    /// | `E0107` | `StructureError::InvalidKey`                 |
+    /// | `E0108` | `StructureError::EmptyString`                |
+    /// | `E0201` | [`MalformedContent`](ErrorKind::MalformedContent) |
+    /// | `E0202` | [`IntegerOverflow`](ErrorKind::IntegerOverflow)   |
+    /// | `E0203` | [`MissingField`](ErrorKind::MissingField)         |
+    /// | `E0204` | [`UnexpectedField`](ErrorKind::UnexpectedField)   |
+    /// | `E0205` | [`UnexpectedToken`](ErrorKind::UnexpectedToken)   |
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::decoding::FromBencode;
+    ///
+    /// let error = u8::from_bencode(b"i300e").unwrap_err();
+    /// assert_eq!(error.code(), "E0202");
+    /// ```
+    pub fn code(&self) -> &'static str {
+        self.error.code()
+    }
+
+    /// The field path set with [`context`](Error::context), if any
+    pub fn context_path(&self) -> Option<&str> {
+        self.location.as_ref()?.context.as_deref()
+    }
+
+    /// The input around the position of the error, if it was raised by a
+    /// [`Decoder`](crate::decoding::Decoder) reading the input
+    pub fn excerpt(&self) -> Option<&Excerpt> {
+        self.location.as_ref()?.excerpt.as_ref()
+    }
+
+    /// Attach the excerpt of `source` around `span`, unless there already is
+    /// one
+    pub(crate) fn with_excerpt(mut self, source: &[u8], span: Range<usize>, window: usize) -> Self {
+        let location = self.location.get_or_insert_with(Box::default);
+        if location.excerpt.is_none() {
+            location.excerpt = Some(Excerpt::new(source, span, window));
+        }
+        self
+    }
+
     /// Raised when there is a general error while deserializing a type.
     /// The message should not be capitalized and should not end with a period.
+    ///
+    /// `cause` is either an error, which becomes the
+    /// [`source`](std::error::Error::source) of this one, or a message.
     #[cfg(feature = "std")]
-    pub fn malformed_content(cause: impl Into<failure::Error>) -> Error {
-        let error = Arc::new(cause.into());
+    pub fn malformed_content(cause: impl Into<Box<dyn StdError + Send + Sync>>) -> Error {
+        let error = Arc::from(cause.into());
         Self::from(ErrorKind::MalformedContent(error))
     }
 
@@ -77,6 +344,16 @@ impl Error {
         Self::from(ErrorKind::MalformedContent)
     }
 
+    /// Returns an `ErrorKind::IntegerOverflow` error for the integer `text`.
+    pub fn integer_overflow(text: impl Display) -> Error {
+        Self::from(ErrorKind::IntegerOverflow(text.to_string()))
+    }
+
+    /// Returns an `ErrorKind::TrailingData` error.
+    pub fn trailing_data() -> Error {
+        Self::from(ErrorKind::TrailingData)
+    }
+
     /// Returns a `Error::MissingField` which contains the name of the field.
     pub fn missing_field(field_name: impl Display) -> Error {
         Self::from(ErrorKind::MissingField(field_name.to_string()))
@@ -89,67 +366,355 @@ impl Error {
 
     /// Returns a `Error::UnexpectedElement` which contains a custom error message.
     pub fn unexpected_token(expected: impl Display, discovered: impl Display) -> Error {
-        Self::from(ErrorKind::UnexpectedToken(
-            expected.to_string(),
-            discovered.to_string(),
-        ))
+        Self::from(ErrorKind::UnexpectedToken {
+            expected: expected.to_string(),
+            discovered: discovered.to_string(),
+        })
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match &self.context {
-            Some(context) => write!(f, "Error: {} in {}", self.error, context),
-            None => write!(f, "Error: {}", self.error),
+        write!(f, "Error: {}", self.error)?;
+        if let Some(context) = self.context_path() {
+            write!(f, " in {}", context)?;
+        }
+        match self.excerpt() {
+            Some(excerpt) => write!(f, " {}", excerpt),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        // The kind is part of the message, so its cause is the next one
+        self.error.source()
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            ErrorKind::MalformedContent(cause) => {
+                write!(f, "malformed content discovered: {}", cause)
+            },
+            #[cfg(not(feature = "std"))]
+            ErrorKind::MalformedContent => write!(f, "malformed content discovered"),
+            ErrorKind::IntegerOverflow(text) => write!(f, "integer out of range: {}", text),
+            ErrorKind::MissingField(field) => write!(f, "missing field: {}", field),
+            ErrorKind::StructureError(error) => {
+                write!(f, "bencode encoding corrupted ({})", error)
+            },
+            ErrorKind::TrailingData => write!(f, "trailing bytes after the value"),
+            ErrorKind::UnexpectedField(field) => write!(f, "unexpected field: {}", field),
+            ErrorKind::UnexpectedToken {
+                expected,
+                discovered,
+            } => write!(f, "discovered {} but expected {}", discovered, expected),
+        }
+    }
+}
+
+impl ErrorKind {
+    /// The stable code of this kind, as listed for [`Error::code`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::StructureError(error) => error.code(),
+            ErrorKind::TrailingData => "E0106",
+            #[cfg(feature = "std")]
+            ErrorKind::MalformedContent(_) => "E0201",
+            #[cfg(not(feature = "std"))]
+            ErrorKind::MalformedContent => "E0201",
+            ErrorKind::IntegerOverflow(_) => "E0202",
+            ErrorKind::MissingField(_) => "E0203",
+            ErrorKind::UnexpectedField(_) => "E0204",
+            ErrorKind::UnexpectedToken { .. } => "E0205",
+        }
+    }
+
+    /// Whether this is a syntax or a structure error
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            ErrorKind::StructureError(StructureError::NestingTooDeep)
+            | ErrorKind::StructureError(StructureError::InvalidKey(_))
+            | ErrorKind::StructureError(StructureError::EmptyString) => ErrorClass::Structure,
+            ErrorKind::StructureError(_) | ErrorKind::TrailingData => ErrorClass::Syntax,
+            _ => ErrorClass::Structure,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for ErrorKind {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ErrorKind::MalformedContent(cause) => Some(&**cause),
+            ErrorKind::StructureError(error) => Some(error),
+            _ => None,
         }
     }
 }
 
+/// Reports point at the [span](Excerpt::span) of the error in the [`Excerpt`]
+/// of the input, which is the source code
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.code()))
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        Some(self.excerpt()?)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let excerpt = self.excerpt()?;
+        let shown = excerpt.start + excerpt.bytes.len();
+        let len = excerpt.end.min(shown).saturating_sub(excerpt.offset);
+        let label = miette::LabeledSpan::new_primary_with_span(
+            Some(self.error.to_string()),
+            (excerpt.offset, len),
+        );
+        Some(Box::new(core::iter::once(label)))
+    }
+}
+
+/// Spans are offsets into the whole input
+#[cfg(feature = "miette")]
+impl miette::SourceCode for Excerpt {
+    fn read_span<'a>(
+        &'a self,
+        span: &miette::SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn miette::SpanContents<'a> + 'a>, miette::MietteError> {
+        let local =
+            miette::SourceSpan::new(span.offset().saturating_sub(self.start).into(), span.len());
+        let contents = self
+            .bytes
+            .read_span(&local, context_lines_before, context_lines_after)?;
+        let column = match contents.line() {
+            0 => contents.column() + self.start,
+            _ => contents.column(),
+        };
+        Ok(Box::new(miette::MietteSpanContents::new(
+            contents.data(),
+            miette::SourceSpan::new(
+                (contents.span().offset() + self.start).into(),
+                contents.span().len(),
+            ),
+            contents.line(),
+            column,
+            contents.line_count(),
+        )))
+    }
+}
+
 impl From<StructureError> for Error {
     fn from(error: StructureError) -> Self {
         Self::from(ErrorKind::StructureError(error))
     }
 }
 
+impl From<&Error> for ErrorClass {
+    fn from(error: &Error) -> Self {
+        error.class()
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Self {
         Self {
-            context: None,
             error: kind,
+            location: None,
         }
     }
 }
 
-#[cfg(not(feature = "std"))]
-impl From<FromUtf8Error> for Error {
-    fn from(err: FromUtf8Error) -> Self {
-        Self::malformed_content(err)
-    }
+macro_rules! impl_from_cause {
+    ($($cause:ty)*) => {$(
+        impl From<$cause> for Error {
+            fn from(cause: $cause) -> Self {
+                Self::malformed_content(cause)
+            }
+        }
+    )*}
 }
 
-#[cfg(not(feature = "std"))]
-impl From<Utf8Error> for Error {
-    fn from(err: Utf8Error) -> Self {
-        Self::malformed_content(err)
+impl_from_cause!(FromUtf8Error Utf8Error ParseIntError TryFromIntError);
+#[cfg(feature = "std")]
+impl_from_cause!(io::Error);
+
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: Into<Error>,
+{
+    fn context(self, context: impl Display) -> Result<T, Error> {
+        self.map_err(|err| err.into().context(context))
     }
 }
 
-#[cfg(not(feature = "std"))]
-impl From<ParseIntError> for Error {
-    fn from(err: ParseIntError) -> Self {
-        Self::malformed_content(err)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decoding::FromBencode, value::RawValue};
+
+    #[test]
+    fn kinds_can_be_matched() {
+        let error = Error::unexpected_token("List", "Num").context("field");
+        match error.kind() {
+            ErrorKind::UnexpectedToken {
+                expected,
+                discovered,
+            } => assert_eq!((&expected[..], &discovered[..]), ("List", "Num")),
+            other => panic!("unexpected kind {:?}", other),
+        }
+        assert_eq!(error.context_path(), Some("field"));
+        assert_eq!(
+            error.to_string(),
+            "Error: discovered Num but expected List in field"
+        );
+
+        let error = i8::from_bencode(b"i-129e").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::IntegerOverflow(text) if text == "-129"));
+
+        let error = RawValue::from_bytes(&b"i1ei2e"[..]).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::TrailingData));
+
+        let error = u8::from_bencode(b"i1").unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::StructureError(StructureError::UnexpectedEof)
+        ));
     }
-}
 
-#[cfg(feature = "std")]
-impl<T: StdError + Send + Sync + 'static> From<T> for Error {
-    fn from(error: T) -> Self {
-        Self::malformed_content(error)
+    #[test]
+    fn codes_are_stable() {
+        let codes = [
+            Error::from(StructureError::UnsortedKeys).code(),
+            u8::from_bencode(b"i1").unwrap_err().code(),
+            u8::from_bencode(b"ix").unwrap_err().code(),
+            Error::from(StructureError::NestingTooDeep).code(),
+            RawValue::from_bytes(&b"i1ei2e"[..]).unwrap_err().code(),
+            Error::from(StructureError::InvalidKey(String::new())).code(),
+            Error::from(StructureError::EmptyString).code(),
+            String::from_bencode(b"1:\xff").unwrap_err().code(),
+            u8::from_bencode(b"i300e").unwrap_err().code(),
+            Error::missing_field("info").code(),
+            Error::unexpected_field("info").code(),
+            String::from_bencode(b"i1e").unwrap_err().code(),
+        ];
+        assert_eq!(
+            codes,
+            [
+                "E0101", "E0102", "E0103", "E0105", "E0106", "E0107", "E0108", "E0201", "E0202",
+                "E0203", "E0204", "E0205"
+            ]
+        );
     }
-}
 
-impl<T> ResultExt for Result<T, Error> {
-    fn context(self, context: impl Display) -> Result<T, Error> {
-        self.map_err(|err| err.context(context))
+    #[test]
+    fn errors_are_classified() {
+        let error = u8::from_bencode(b"i1").unwrap_err();
+        assert_eq!(error.class(), ErrorClass::Syntax);
+        assert_eq!(ErrorClass::from(&error), ErrorClass::Syntax);
+        assert_eq!(error.into_structure().class(), ErrorClass::Structure);
+
+        let error = RawValue::from_bytes(&b"i1ei2e"[..]).unwrap_err();
+        assert_eq!(error.class(), ErrorClass::Syntax);
+
+        let error = Error::from(StructureError::NestingTooDeep);
+        assert_eq!(error.class(), ErrorClass::Structure);
+
+        let error = u8::from_bencode(b"i300e").unwrap_err();
+        assert_eq!(error.class(), ErrorClass::Structure);
+        assert!(matches!(
+            error.into_structure().kind(),
+            ErrorKind::IntegerOverflow(_)
+        ));
+    }
+
+    #[cfg(feature = "miette")]
+    #[test]
+    fn diagnostics_point_at_the_input() {
+        use miette::Diagnostic;
+
+        let error = crate::decoding::Decoder::new(b"l4:spami-0ee")
+            .with_error_excerpt(4)
+            .tokens()
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(Diagnostic::code(&error).unwrap().to_string(), "E0103");
+
+        let label = error.labels().unwrap().next().unwrap();
+        assert_eq!((label.offset(), label.len()), (7, 1));
+
+        let contents = error
+            .source_code()
+            .unwrap()
+            .read_span(label.inner(), 1, 1)
+            .unwrap();
+        assert_eq!(contents.data(), b"spami-0e");
+        assert_eq!((contents.span().offset(), contents.column()), (3, 3));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn foreign_errors_get_context() {
+        #[derive(Debug)]
+        struct Unknown;
+
+        impl Display for Unknown {
+            fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+                write!(f, "unknown flag")
+            }
+        }
+
+        impl StdError for Unknown {}
+
+        impl From<Unknown> for Error {
+            fn from(error: Unknown) -> Self {
+                Error::malformed_content(error)
+            }
+        }
+
+        let error = Err::<(), _>(Unknown).context("flags").unwrap_err();
+        assert_eq!(error.context_path(), Some("flags"));
+        assert!(error.source().unwrap().is::<Unknown>());
+
+        let error = Err::<(), _>(io::Error::other("gone"))
+            .context("piece")
+            .context("info")
+            .unwrap_err();
+        assert_eq!(error.context_path(), Some("info.piece"));
+        assert!(error.source().unwrap().is::<io::Error>());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn sources_are_chained() {
+        let error = Error::from(io::Error::other("disk on fire")).context("info");
+        let source = error.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<io::Error>().unwrap().to_string(),
+            "disk on fire"
+        );
+
+        let error = Error::from(String::from_utf8(vec![0xff]).unwrap_err());
+        assert!(error.source().unwrap().is::<FromUtf8Error>());
+
+        let error = Error::from(StructureError::UnsortedKeys);
+        assert_eq!(
+            error.source().unwrap().downcast_ref(),
+            Some(&StructureError::UnsortedKeys)
+        );
+
+        assert!(Error::missing_field("info").source().is_none());
+
+        let error = crate::encoding::Error::malformed_content(io::Error::other("full"));
+        assert!(error.source().unwrap().is::<io::Error>());
     }
 }
@@ -11,6 +11,7 @@ use std::{
 use crate::{
     decoding::{Decoder, Error, Object},
     encoding::AsString,
+    instrument::{self, Phase},
     state_tracker::StructureError,
 };
 
@@ -24,13 +25,16 @@ pub trait FromBencode {
     where
         Self: Sized,
     {
-        let mut decoder = Decoder::new(bytes).with_max_depth(Self::EXPECTED_RECURSION_DEPTH);
-        let object = decoder.next_object()?;
-
-        object.map_or(
-            Err(Error::from(StructureError::UnexpectedEof)),
-            Self::decode_bencode_object,
-        )
+        instrument::measure(Phase::Decoding, || {
+            let mut decoder = Decoder::new(bytes).with_max_depth(Self::EXPECTED_RECURSION_DEPTH);
+            let result = decoder.next_object().and_then(|object| {
+                object.map_or(
+                    Err(Error::from(StructureError::UnexpectedEof)),
+                    Self::decode_bencode_object,
+                )
+            });
+            (result, bytes.len())
+        })
     }
 
     /// Deserialize an object from its intermediate bencode representation.
@@ -49,7 +53,10 @@ macro_rules! impl_from_bencode_for_integer {
                 Self: Sized,
             {
                 let content = object.try_into_integer()?;
-                let number = content.parse::<$type>()?;
+                // The decoder already checked the syntax of the integer
+                let number = content
+                    .parse::<$type>()
+                    .map_err(|_| Error::integer_overflow(content))?;
 
                 Ok(number)
             }
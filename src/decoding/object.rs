@@ -1,8 +1,29 @@
+use core::convert::TryFrom;
+
 use crate::{
     decoding::{DictDecoder, Error, ListDecoder},
     state_tracker::Token,
 };
 
+/// Convert an integer which was already decoded, such as that of a
+/// [`Value`](crate::value::Value), to `T`, returning an
+/// [`IntegerOverflow`](crate::decoding::ErrorKind::IntegerOverflow) error if
+/// it doesn't fit, like [`Object::try_into_narrowed`].
+///
+/// # Examples
+///
+/// ```
+/// use bendy::decoding::{self, ResultExt};
+///
+/// assert_eq!(decoding::narrow::<u8>(255).unwrap(), 255);
+///
+/// let error = decoding::narrow::<u8>(-1).context("flags").unwrap_err();
+/// assert_eq!(error.context_path(), Some("flags"));
+/// ```
+pub fn narrow<T: TryFrom<i64>>(number: i64) -> Result<T, Error> {
+    T::try_from(number).map_err(|_| Error::integer_overflow(number))
+}
+
 /// An object read from a decoder
 pub enum Object<'obj, 'ser: 'obj> {
     /// A list of arbitrary objects
@@ -202,6 +223,34 @@ impl<'obj, 'ser: 'obj> Object<'obj, 'ser> {
         self.integer_or_else(|obj| Err(Error::unexpected_token("Num", obj.into_token().name())))
     }
 
+    /// Try to treat the object as an integer and convert it to `T`, such as a
+    /// narrower integer type. An integer which doesn't fit into `i64` or `T`
+    /// returns an [`IntegerOverflow`](crate::decoding::ErrorKind::IntegerOverflow)
+    /// error, to which the field can be added with
+    /// [`context`](crate::decoding::ResultExt::context).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::decoding::{Object, ResultExt};
+    ///
+    /// let x = Object::Integer("65536");
+    /// assert_eq!(65536, x.try_into_narrowed::<u32>().unwrap());
+    ///
+    /// let x = Object::Integer("65536");
+    /// let error = x.try_into_narrowed::<u16>().context("length").unwrap_err();
+    /// assert_eq!(error.to_string(), "Error: integer out of range: 65536 in length");
+    /// ```
+    pub fn try_into_narrowed<T: TryFrom<i64>>(self) -> Result<T, Error> {
+        let content = self.try_into_integer()?;
+        // The decoder already checked the syntax of the integer
+        content
+            .parse::<i64>()
+            .ok()
+            .and_then(|number| T::try_from(number).ok())
+            .ok_or_else(|| Error::integer_overflow(content))
+    }
+
     /// Try to treat the object as a list and return the internal list content decoder,
     /// mapping [`Object::List(v)`] into [`Ok(v)`]. Any other variant returns the given
     /// default value.
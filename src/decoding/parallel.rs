@@ -0,0 +1,118 @@
+//! Decoding the items of large lists on several threads.
+//!
+//! Archives of bencoded records are often one long list. Its structure can be
+//! scanned much faster than its items can be decoded into typed values, so
+//! [`split_list`] first finds the raw bytes of every item, and with the
+//! `rayon` feature, [`decode_list_parallel`] then decodes them independently
+//! on rayon's global thread pool.
+//!
+//! To process a list nested inside a document, get its raw bytes with
+//! [`ListDecoder::into_raw`](crate::decoding::ListDecoder::into_raw) first.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::decoding::{Decoder, Error};
+#[cfg(feature = "rayon")]
+use crate::decoding::{FromBencode, ResultExt};
+
+/// The raw bytes of every item of the list `encoded`, after validating the
+/// structure of the whole list.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::decoding::split_list;
+///
+/// let items = split_list(b"li1e3:abcli2eee").unwrap();
+/// assert_eq!(items, vec![&b"i1e"[..], b"3:abc", b"li2ee"]);
+/// ```
+pub fn split_list(encoded: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    split_list_with(Decoder::new(encoded))
+}
+
+fn split_list_with(mut decoder: Decoder<'_>) -> Result<Vec<&[u8]>, Error> {
+    let mut list = match decoder.next_object()? {
+        Some(object) => object.try_into_list()?,
+        None => return Err(Error::unexpected_token("List", "EOF")),
+    };
+
+    let mut items = Vec::new();
+    while let Some(item) = list.next_raw()? {
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// Decode every item of the list `encoded`, spreading the items over the
+/// threads of rayon's global thread pool.
+///
+/// The result is the same as decoding a `Vec<T>`, but errors refer to the
+/// index of the first item which failed.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::decoding::decode_list_parallel;
+///
+/// let numbers: Vec<u32> = decode_list_parallel(b"li1ei2ei3ee").unwrap();
+/// assert_eq!(numbers, vec![1, 2, 3]);
+/// ```
+#[cfg(feature = "rayon")]
+pub fn decode_list_parallel<T>(encoded: &[u8]) -> Result<Vec<T>, Error>
+where
+    T: FromBencode + Send,
+{
+    let decoder = Decoder::new(encoded).with_max_depth(T::EXPECTED_RECURSION_DEPTH + 1);
+    let items = split_list_with(decoder)?;
+
+    // Collecting the results first reports the first failing item, rather
+    // than whichever thread failed first
+    let results: Vec<Result<T, Error>> = items
+        .par_iter()
+        .enumerate()
+        .map(|(index, item)| T::from_bencode(item).context(index))
+        .collect();
+    results.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn items_are_decoded_in_order() {
+        let mut encoded = b"l".to_vec();
+        for i in 0..1000 {
+            encoded.extend_from_slice(format!("i{}e", i).as_bytes());
+        }
+        encoded.push(b'e');
+
+        let numbers: Vec<u32> = decode_list_parallel(&encoded).unwrap();
+        assert_eq!(numbers, (0..1000).collect::<Vec<_>>());
+        assert!(decode_list_parallel::<u32>(b"le").unwrap().is_empty());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn errors_name_the_item() {
+        let error = decode_list_parallel::<u32>(b"li1ei2e3:abce").unwrap_err();
+        assert!(error.to_string().ends_with(" in 2"), "{}", error);
+
+        let mut encoded = b"l".to_vec();
+        for i in 0..1000 {
+            encoded.extend_from_slice(format!("i{}e", i).as_bytes());
+        }
+        encoded.extend_from_slice(b"1:a1:be");
+        let error = decode_list_parallel::<u32>(&encoded).unwrap_err();
+        assert!(error.to_string().ends_with(" in 1000"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_lists_are_not_split() {
+        assert!(split_list(b"li1e").is_err());
+        assert!(split_list(b"i1e").is_err());
+        assert!(split_list(b"").is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! Policies for valid but unusual inputs, and the warnings they raise.
+
+/// What a decoder does with empty byte strings or empty dict keys, which
+/// bencode allows but some protocols don't expect
+///
+/// # Examples
+///
+/// ```
+/// use bendy::decoding::{Decoder, EmptyPolicy};
+///
+/// let mut decoder = Decoder::new(b"d0:i1e4:name0:e")
+///     .with_empty_keys(EmptyPolicy::Warn)
+///     .with_empty_strings(EmptyPolicy::Warn);
+/// let object = decoder.next_object().unwrap().unwrap();
+/// object.try_into_dictionary().unwrap().consume_all().unwrap();
+/// assert_eq!(decoder.warnings().empty_keys(), 1);
+/// assert_eq!(decoder.warnings().empty_strings(), 1);
+///
+/// let decoder = Decoder::new(b"l0:e").with_empty_strings(EmptyPolicy::Reject);
+/// assert_eq!(decoder.tokens().nth(1).unwrap().unwrap_err().code(), "E0108");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EmptyPolicy {
+    /// Accept them silently, as the specification does. This is the default.
+    Accept,
+    /// Accept them, but count them in the decoder's [`Warnings`], and with
+    /// the `tracing` feature, emit a warning event
+    Warn,
+    /// Fail with an error pointing at them
+    Reject,
+}
+
+/// The inputs a decoder accepted because of an [`EmptyPolicy::Warn`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Warnings {
+    pub(crate) empty_strings: usize,
+    pub(crate) empty_keys: usize,
+}
+
+impl Warnings {
+    /// How many empty byte strings were read, other than dict keys
+    pub fn empty_strings(&self) -> usize {
+        self.empty_strings
+    }
+
+    /// How many empty dict keys were read
+    pub fn empty_keys(&self) -> usize {
+        self.empty_keys
+    }
+
+    /// Whether nothing was warned about
+    pub fn is_empty(&self) -> bool {
+        *self == Warnings::default()
+    }
+}
@@ -0,0 +1,841 @@
+//! Decoding documents from a reader in bounded memory.
+//!
+//! [`Decoder`](crate::decoding::Decoder) needs the whole document in memory.
+//! A [`StreamDecoder`] reads tokens from a [`BufRead`] instead, and only
+//! materializes byte strings up to a threshold. Longer byte strings are
+//! returned as [`StreamToken::LargeBytes`] with their position in the input,
+//! and their contents are either copied to a writer with
+//! [`StreamDecoder::copy_large_bytes`] or skipped, so memory use doesn't
+//! depend on the size of the document.
+//!
+//! ```
+//! use bendy::decoding::{StreamDecoder, StreamToken};
+//!
+//! let input = &b"d4:data10:01234567894:name4:dumpe"[..];
+//! let mut decoder = StreamDecoder::new(input).with_threshold(8);
+//! let mut data = Vec::new();
+//!
+//! while let Some(token) = decoder.next_token().unwrap() {
+//!     if let StreamToken::LargeBytes { offset, length } = token {
+//!         assert_eq!((offset, length), (10, 10));
+//!         decoder.copy_large_bytes(&mut data).unwrap();
+//!     }
+//! }
+//!
+//! assert_eq!(data, b"0123456789");
+//! ```
+
+use std::{
+    convert::TryFrom,
+    io::{BufRead, Write},
+    str,
+};
+
+use crate::{
+    decoding::{decoder::is_integer, EmptyPolicy, Error, Warnings},
+    state_tracker::{Key, StateTracker, StructureError, Token},
+};
+
+/// A token read by a [`StreamDecoder`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamToken<'a> {
+    /// The beginning of a list
+    List,
+    /// The beginning of a dictionary
+    Dict,
+    /// An integer, as a string, like [`Object::Integer`](crate::decoding::Object::Integer)
+    Integer(&'a str),
+    /// A byte string no longer than the threshold
+    Bytes(&'a [u8]),
+    /// A byte string longer than the threshold, which hasn't been read yet
+    LargeBytes {
+        /// The position of the first byte of the string in the input
+        offset: u64,
+        /// The length of the string
+        length: u64,
+    },
+    /// The end of a list or dictionary
+    End,
+}
+
+/// A token without the bytes it borrows from the scratch buffer
+enum RawToken {
+    List,
+    Dict,
+    Integer,
+    Bytes,
+    LargeBytes { offset: u64, length: u64 },
+    End,
+}
+
+/// Allocations of a [`StreamDecoder`] which can be reused by the next one.
+///
+/// Servers decoding many messages can keep a context around instead of
+/// allocating a new scratch buffer and nesting stack for every message:
+///
+/// ```
+/// use bendy::decoding::{DecoderContext, StreamDecoder};
+///
+/// let mut context = DecoderContext::new();
+/// for message in &[&b"d1:ai1ee"[..], b"l3:abce"] {
+///     let mut decoder = StreamDecoder::new(*message).with_context(context);
+///     while let Some(token) = decoder.next_token().unwrap() {
+///         // ...
+///     }
+///     context = decoder.into_context();
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct DecoderContext {
+    scratch: Vec<u8>,
+    state: StateTracker<Key, Error>,
+}
+
+impl DecoderContext {
+    /// Create an empty context
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+}
+
+/// A hash function, or anything else, which the raw bytes of a value can be
+/// fed into while a [`StreamDecoder`] reads them. See
+/// [`StreamDecoder::with_digest`].
+pub trait Digest {
+    /// Feed the next bytes of the value
+    fn update(&mut self, bytes: &[u8]);
+}
+
+/// No digest, the default of [`StreamDecoder`]
+impl Digest for () {
+    fn update(&mut self, _bytes: &[u8]) {}
+}
+
+impl<D: Digest + ?Sized> Digest for &mut D {
+    fn update(&mut self, bytes: &[u8]) {
+        D::update(self, bytes)
+    }
+}
+
+/// How far a [`StreamDecoder`] is through the value it feeds into its digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Digesting {
+    /// The key of the value hasn't been read yet
+    Waiting,
+    /// The key was read, so the next token starts the value
+    Armed,
+    /// The decoder is reading the value
+    Active,
+    /// The value was read completely, or no digest is used
+    Done,
+}
+
+/// A bencode decoder reading from a [`BufRead`] in bounded memory
+///
+/// See the [module documentation](self) for an example.
+#[derive(Debug)]
+pub struct StreamDecoder<R, D = ()> {
+    reader: R,
+    /// The number of bytes read so far
+    position: u64,
+    threshold: usize,
+    /// The contents of the last integer or small byte string
+    scratch: Vec<u8>,
+    /// The unread bytes of the last large byte string
+    pending: u64,
+    state: StateTracker<Key, Error>,
+    digest: D,
+    /// The key of the top-level dict whose value is fed into `digest`
+    digest_key: Vec<u8>,
+    digesting: Digesting,
+}
+
+/// The most characters an integer may have, which is enough for any `i64`
+const MAX_INTEGER_DIGITS: usize = 20;
+
+/// The default threshold and maximum key length of a [`StreamDecoder`]
+const DEFAULT_THRESHOLD: usize = 64 * 1024;
+
+impl<R: BufRead> StreamDecoder<R> {
+    /// Create a new decoder reading from `reader`, with a threshold and a
+    /// maximum key length of 64 KiB
+    pub fn new(reader: R) -> Self {
+        let mut state = StateTracker::new();
+        state.set_max_key_len(DEFAULT_THRESHOLD);
+        StreamDecoder {
+            reader,
+            position: 0,
+            threshold: DEFAULT_THRESHOLD,
+            scratch: Vec::new(),
+            pending: 0,
+            state,
+            digest: (),
+            digest_key: Vec::new(),
+            digesting: Digesting::Done,
+        }
+    }
+
+    /// Feed the raw bytes of the value of `key` in the top-level dict into
+    /// `digest` while they are read, such as the `info` dict of a torrent to
+    /// compute its infohash without a second pass over it.
+    ///
+    /// The contents of large byte strings are fed into the digest whether they
+    /// are copied or skipped. Once the whole value is read,
+    /// [`digest`](StreamDecoder::digest) returns the digest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::decoding::{Digest, StreamDecoder};
+    ///
+    /// #[derive(Default)]
+    /// struct Length(usize);
+    ///
+    /// impl Digest for Length {
+    ///     fn update(&mut self, bytes: &[u8]) {
+    ///         self.0 += bytes.len();
+    ///     }
+    /// }
+    ///
+    /// let input = &b"d4:infod4:name3:abce4:sizei3ee"[..];
+    /// let mut decoder = StreamDecoder::new(input).with_digest(b"info", Length::default());
+    /// while decoder.next_token().unwrap().is_some() {}
+    ///
+    /// assert_eq!(decoder.digest().unwrap().0, b"d4:name3:abce".len());
+    /// ```
+    pub fn with_digest<D: Digest>(self, key: &[u8], digest: D) -> StreamDecoder<R, D> {
+        StreamDecoder {
+            reader: self.reader,
+            position: self.position,
+            threshold: self.threshold,
+            scratch: self.scratch,
+            pending: self.pending,
+            state: self.state,
+            digest,
+            digest_key: key.to_vec(),
+            digesting: Digesting::Waiting,
+        }
+    }
+}
+
+impl<R: BufRead, D: Digest> StreamDecoder<R, D> {
+    /// Set the length above which byte strings are returned as
+    /// [`StreamToken::LargeBytes`].
+    ///
+    /// Dict keys are always read into memory, up to the limit set with
+    /// [`with_max_key_len`](StreamDecoder::with_max_key_len). Integers are
+    /// rejected if they are longer than 20 characters, more than any `i64`
+    /// needs.
+    #[must_use]
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the maximum nesting depth of the decoder, like
+    /// [`Decoder::with_max_depth`](crate::decoding::Decoder::with_max_depth)
+    #[must_use]
+    pub fn with_max_depth(mut self, new_max_depth: usize) -> Self {
+        self.state.set_max_depth(new_max_depth);
+        self
+    }
+
+    /// Set whether dictionary keys must be unique and in ascending order, like
+    /// [`Decoder::with_sorted_keys`](crate::decoding::Decoder::with_sorted_keys)
+    #[must_use]
+    pub fn with_sorted_keys(mut self, required: bool) -> Self {
+        self.state.set_require_sorted_keys(required);
+        self
+    }
+
+    /// Set the maximum length of dictionary keys, like
+    /// [`Decoder::with_max_key_len`](crate::decoding::Decoder::with_max_key_len).
+    /// Keys are read into memory whatever the threshold, so this defaults to
+    /// 64 KiB.
+    #[must_use]
+    pub fn with_max_key_len(mut self, max_key_len: usize) -> Self {
+        self.state.set_max_key_len(max_key_len);
+        self
+    }
+
+    /// Set whether dictionary keys may be empty, like
+    /// [`Decoder::with_empty_keys`](crate::decoding::Decoder::with_empty_keys)
+    #[must_use]
+    pub fn with_empty_keys(mut self, policy: EmptyPolicy) -> Self {
+        self.state.set_empty_keys(policy);
+        self
+    }
+
+    /// Set whether other byte strings may be empty, like
+    /// [`Decoder::with_empty_strings`](crate::decoding::Decoder::with_empty_strings)
+    #[must_use]
+    pub fn with_empty_strings(mut self, policy: EmptyPolicy) -> Self {
+        self.state.set_empty_strings(policy);
+        self
+    }
+
+    /// Reuse the allocations of `context`, which was taken from an earlier
+    /// decoder with [`into_context`](StreamDecoder::into_context). The
+    /// settings of this decoder are kept.
+    #[must_use]
+    pub fn with_context(mut self, context: DecoderContext) -> Self {
+        self.scratch = context.scratch;
+        self.scratch.clear();
+        self.state.recycle(context.state);
+        self
+    }
+
+    /// Give up the decoder, keeping its allocations for the next one
+    pub fn into_context(self) -> DecoderContext {
+        DecoderContext {
+            scratch: self.scratch,
+            state: self.state,
+        }
+    }
+
+    /// The number of bytes of the input read so far
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The empty strings and keys read so far which the decoder's
+    /// [`EmptyPolicy`] warns about
+    pub fn warnings(&self) -> Warnings {
+        self.state.warnings()
+    }
+
+    /// The digest set with [`with_digest`](StreamDecoder::with_digest), once
+    /// the whole value has been fed into it
+    pub fn digest(&self) -> Option<&D> {
+        match self.digesting {
+            Digesting::Done if !self.digest_key.is_empty() => Some(&self.digest),
+            _ => None,
+        }
+    }
+
+    /// Give up the decoder, returning its digest like
+    /// [`digest`](StreamDecoder::digest)
+    pub fn into_digest(self) -> Option<D> {
+        if self.digest().is_some() {
+            Some(self.digest)
+        } else {
+            None
+        }
+    }
+
+    /// Read the next token. Returns `Ok(None)` at the end of the input, if the
+    /// document is complete.
+    ///
+    /// The contents of the previous [`StreamToken::LargeBytes`] are skipped
+    /// if they haven't been copied.
+    pub fn next_token(&mut self) -> Result<Option<StreamToken<'_>>, Error> {
+        self.state.check_error()?;
+
+        let skipped = self.skip_pending();
+        self.state.latch_err(skipped)?;
+        finish_digest(&mut self.digesting, &self.state, self.pending);
+
+        let at_eof = self.peek_byte().map(|byte| byte.is_none());
+        if self.state.latch_err(at_eof)? {
+            self.state.observe_eof()?;
+            return Ok(None);
+        }
+
+        if self.digesting == Digesting::Armed {
+            self.digesting = Digesting::Active;
+        }
+        let at_key = self.state.expects_key() && self.state.depth() == 1;
+
+        let raw = self.raw_next_token();
+        let raw = self.state.latch_err(raw)?;
+
+        let scratch = &self.scratch[..];
+        // Large byte strings are observed without their contents, which
+        // haven't been read yet
+        let (token, stream_token) = match raw {
+            RawToken::List => (Some(Token::List), StreamToken::List),
+            RawToken::Dict => (Some(Token::Dict), StreamToken::Dict),
+            RawToken::End => (Some(Token::End), StreamToken::End),
+            RawToken::Integer => {
+                let integer = str::from_utf8(scratch).expect("Integers are ASCII");
+                (Some(Token::Num(integer)), StreamToken::Integer(integer))
+            },
+            RawToken::Bytes => (Some(Token::String(scratch)), StreamToken::Bytes(scratch)),
+            RawToken::LargeBytes { offset, length } => {
+                (None, StreamToken::LargeBytes { offset, length })
+            },
+        };
+        match token {
+            Some(ref token) => self.state.observe_token(token)?,
+            None => self.state.observe_large_string()?,
+        }
+
+        if at_key && self.digesting == Digesting::Waiting {
+            if let Some(Token::String(key)) = token {
+                if key == &self.digest_key[..] {
+                    self.digesting = Digesting::Armed;
+                }
+            }
+        }
+        finish_digest(&mut self.digesting, &self.state, self.pending);
+
+        Ok(Some(stream_token))
+    }
+
+    /// Copy the contents of the last [`StreamToken::LargeBytes`] to `writer`,
+    /// returning the number of bytes copied. Returns 0 if they were already
+    /// copied or the last token was something else.
+    pub fn copy_large_bytes<W: Write + ?Sized>(&mut self, writer: &mut W) -> Result<u64, Error> {
+        self.state.check_error()?;
+
+        let mut copied = 0;
+        while self.pending > 0 {
+            let buffer = match fill_buf(&mut self.reader) {
+                Ok(buffer) => buffer,
+                Err(error) => return self.state.latch_err(Err(error)),
+            };
+            let count = buffer.len().min(self.pending as usize);
+            writer.write_all(&buffer[..count])?;
+            self.consume(count);
+            self.pending -= count as u64;
+            copied += count as u64;
+        }
+        finish_digest(&mut self.digesting, &self.state, self.pending);
+
+        Ok(copied)
+    }
+
+    /// Append the contents of the last [`StreamToken::LargeBytes`] to
+    /// `buffer`, reserving exactly their length first, and return their length
+    /// like [`copy_large_bytes`](StreamDecoder::copy_large_bytes).
+    ///
+    /// With a threshold of 0, every byte string other than a dict key is a
+    /// [`StreamToken::LargeBytes`], so the caller decides where all of them
+    /// are stored. Keys and integers are still returned as usual.
+    pub fn read_large_bytes(&mut self, buffer: &mut Vec<u8>) -> Result<u64, Error> {
+        self.state.check_error()?;
+        let length = usize::try_from(self.pending).map_err(|_| {
+            Error::from(StructureError::SyntaxError(format!(
+                "String of {} bytes doesn't fit into memory",
+                self.pending
+            )))
+        })?;
+        buffer.reserve_exact(length);
+        self.copy_large_bytes(buffer)
+    }
+
+    /// Read the contents of the last [`StreamToken::LargeBytes`] into
+    /// `buffer`, which must have exactly their length, such as a buffer for a
+    /// hash. If the lengths differ, nothing is read.
+    pub fn read_large_bytes_exact(&mut self, mut buffer: &mut [u8]) -> Result<(), Error> {
+        self.state.check_error()?;
+        if buffer.len() as u64 != self.pending {
+            return Err(Error::unexpected_token(
+                format!("String of {} bytes", buffer.len()),
+                format!("string of {} bytes", self.pending),
+            ));
+        }
+        self.copy_large_bytes(&mut buffer).map(|_| ())
+    }
+
+    fn skip_pending(&mut self) -> Result<(), Error> {
+        while self.pending > 0 {
+            let count = fill_buf(&mut self.reader)?.len().min(self.pending as usize);
+            self.consume(count);
+            self.pending -= count as u64;
+        }
+        Ok(())
+    }
+
+    fn consume(&mut self, count: usize) {
+        if self.digesting == Digesting::Active {
+            // The bytes were just returned by `fill_buf` and aren't consumed yet,
+            // so this returns them again without reading
+            if let Ok(buffer) = self.reader.fill_buf() {
+                self.digest.update(&buffer[..count]);
+            }
+        }
+        self.reader.consume(count);
+        self.position += count as u64;
+    }
+
+    fn peek_byte(&mut self) -> Result<Option<u8>, Error> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn take_byte(&mut self) -> Result<u8, Error> {
+        let byte = fill_buf(&mut self.reader)?[0];
+        self.consume(1);
+        Ok(byte)
+    }
+
+    /// Read an integer terminated by `terminator` into the scratch buffer
+    fn take_int(&mut self, terminator: u8) -> Result<(), Error> {
+        let start = self.position;
+        self.scratch.clear();
+        loop {
+            let byte = self.take_byte()?;
+            if byte == terminator {
+                break;
+            }
+            if self.scratch.len() == MAX_INTEGER_DIGITS {
+                return Err(StructureError::SyntaxError(format!(
+                    "Integer at offset {} is longer than {} characters",
+                    start, MAX_INTEGER_DIGITS
+                ))
+                .into());
+            }
+            self.scratch.push(byte);
+        }
+
+        if is_integer(&self.scratch) {
+            Ok(())
+        } else {
+            Err(StructureError::SyntaxError(format!("Invalid integer at offset {}", start)).into())
+        }
+    }
+
+    /// Read `length` bytes into the scratch buffer
+    fn take_chunk(&mut self, length: usize) -> Result<(), Error> {
+        self.scratch.clear();
+        self.scratch.reserve(length);
+        while self.scratch.len() < length {
+            let buffer = fill_buf(&mut self.reader)?;
+            let count = buffer.len().min(length - self.scratch.len());
+            self.scratch.extend_from_slice(&buffer[..count]);
+            self.consume(count);
+        }
+        Ok(())
+    }
+
+    fn raw_next_token(&mut self) -> Result<RawToken, Error> {
+        let offset = self.position;
+        let token = match self.take_byte()? {
+            b'e' => RawToken::End,
+            b'l' => RawToken::List,
+            b'd' => RawToken::Dict,
+            b'i' => {
+                self.take_int(b'e')?;
+                RawToken::Integer
+            },
+            first @ b'0'..=b'9' => {
+                self.scratch.clear();
+                self.scratch.push(first);
+                let length = self.take_length(offset)?;
+                let is_key = self.state.expects_key();
+                let limit = match is_key {
+                    true => self.state.max_key_len(),
+                    false => self.threshold,
+                };
+                match usize::try_from(length) {
+                    Ok(length) if length <= limit => {
+                        self.take_chunk(length)?;
+                        RawToken::Bytes
+                    },
+                    _ if is_key => {
+                        return Err(StructureError::InvalidKey(format!(
+                            "{} bytes long at offset {}, more than the limit of {}",
+                            length, offset, limit
+                        ))
+                        .into());
+                    },
+                    _ => {
+                        self.pending = length;
+                        RawToken::LargeBytes {
+                            offset: self.position,
+                            length,
+                        }
+                    },
+                }
+            },
+            token => {
+                return Err(StructureError::SyntaxError(format!(
+                    "Invalid token starting with {:?} at offset {}",
+                    token as char, offset
+                ))
+                .into());
+            },
+        };
+
+        Ok(token)
+    }
+
+    /// Read the rest of a length prefix, whose first digit is already in the
+    /// scratch buffer
+    fn take_length(&mut self, offset: u64) -> Result<u64, Error> {
+        let invalid = || {
+            Error::from(StructureError::SyntaxError(format!(
+                "Invalid integer at offset {}",
+                offset
+            )))
+        };
+        loop {
+            match self.take_byte()? {
+                b':' => break,
+                digit @ b'0'..=b'9' if self.scratch.len() < 20 => self.scratch.push(digit),
+                _ => return Err(invalid()),
+            }
+        }
+
+        if !is_integer(&self.scratch) {
+            return Err(invalid());
+        }
+        str::from_utf8(&self.scratch)
+            .expect("Digits are ASCII")
+            .parse()
+            .map_err(|_| invalid())
+    }
+}
+
+/// Stop feeding the digest once its value is complete. This is a function of
+/// the fields involved so that it can be called while a token borrows the
+/// scratch buffer.
+fn finish_digest(digesting: &mut Digesting, state: &StateTracker<Key, Error>, pending: u64) {
+    if *digesting == Digesting::Active && state.depth() == 1 && pending == 0 {
+        *digesting = Digesting::Done;
+    }
+}
+
+/// The buffered input of `reader`, which is only empty in the middle of a
+/// document if it was truncated
+fn fill_buf(reader: &mut impl BufRead) -> Result<&[u8], Error> {
+    let buffer = reader.fill_buf()?;
+    if buffer.is_empty() {
+        Err(StructureError::UnexpectedEof.into())
+    } else {
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::decoding::Decoder;
+
+    fn tokens(input: &[u8], threshold: usize) -> Result<Vec<String>, Error> {
+        let mut decoder = StreamDecoder::new(input).with_threshold(threshold);
+        let mut tokens = Vec::new();
+        while let Some(token) = decoder.next_token()? {
+            tokens.push(format!("{:?}", token));
+        }
+        Ok(tokens)
+    }
+
+    #[test]
+    fn small_documents_match_the_decoder() {
+        let input = b"d1:ali-12ei0e0:e1:bd1:c3:xyzee";
+        let expected: Vec<String> = Decoder::new(input)
+            .tokens()
+            .map(|token| match token.unwrap() {
+                Token::List => "List".to_string(),
+                Token::Dict => "Dict".to_string(),
+                Token::End => "End".to_string(),
+                Token::Num(integer) => format!("{:?}", StreamToken::Integer(integer)),
+                Token::String(bytes) => format!("{:?}", StreamToken::Bytes(bytes)),
+            })
+            .collect();
+        assert_eq!(tokens(input, 16).unwrap(), expected);
+    }
+
+    #[test]
+    fn large_strings_are_skipped_or_copied() {
+        let input = b"l5:abcde6:fghijk1:le";
+        assert_eq!(
+            tokens(input, 4).unwrap(),
+            vec![
+                "List".to_string(),
+                "LargeBytes { offset: 3, length: 5 }".to_string(),
+                "LargeBytes { offset: 10, length: 6 }".to_string(),
+                "Bytes([108])".to_string(),
+                "End".to_string(),
+            ]
+        );
+
+        let mut decoder = StreamDecoder::new(&input[..]).with_threshold(4);
+        let mut copied = Vec::new();
+        decoder.next_token().unwrap();
+        decoder.next_token().unwrap();
+        assert_eq!(decoder.copy_large_bytes(&mut copied).unwrap(), 5);
+        assert_eq!(decoder.copy_large_bytes(&mut copied).unwrap(), 0);
+        decoder.next_token().unwrap();
+        assert_eq!(
+            decoder.next_token().unwrap(),
+            Some(StreamToken::Bytes(b"l"))
+        );
+        assert_eq!(copied, b"abcde");
+    }
+
+    #[test]
+    fn large_strings_are_read_into_buffers() {
+        let input = b"l5:abcde3:fghi1ed3:key1:vee";
+        let mut decoder = StreamDecoder::new(&input[..]).with_threshold(0);
+        let mut buffer = b"xy".to_vec();
+        let mut hash = [0; 3];
+
+        decoder.next_token().unwrap();
+        decoder.next_token().unwrap();
+        assert!(decoder.read_large_bytes_exact(&mut hash).is_err());
+        assert_eq!(decoder.read_large_bytes(&mut buffer).unwrap(), 5);
+        assert_eq!(buffer, b"xyabcde");
+        assert_eq!(buffer.capacity(), 7);
+
+        decoder.next_token().unwrap();
+        decoder.read_large_bytes_exact(&mut hash).unwrap();
+        assert_eq!(&hash, b"fgh");
+        assert_eq!(
+            decoder.next_token().unwrap(),
+            Some(StreamToken::Integer("1"))
+        );
+
+        assert_eq!(decoder.next_token().unwrap(), Some(StreamToken::Dict));
+        assert_eq!(
+            decoder.next_token().unwrap(),
+            Some(StreamToken::Bytes(b"key"))
+        );
+        let mut value = Vec::new();
+        decoder.next_token().unwrap();
+        assert_eq!(decoder.read_large_bytes(&mut value).unwrap(), 1);
+        assert_eq!(value, b"v");
+        assert_eq!(decoder.next_token().unwrap(), Some(StreamToken::End));
+        assert_eq!(decoder.next_token().unwrap(), Some(StreamToken::End));
+    }
+
+    #[test]
+    fn integers_and_keys_ignore_the_threshold() {
+        assert_eq!(
+            tokens(b"d3:fooli-9223372036854775808eee", 0).unwrap(),
+            vec![
+                "Dict".to_string(),
+                "Bytes([102, 111, 111])".to_string(),
+                "List".to_string(),
+                "Integer(\"-9223372036854775808\")".to_string(),
+                "End".to_string(),
+                "End".to_string(),
+            ]
+        );
+
+        let error = tokens(b"i123456789012345678901e", 64).unwrap_err();
+        assert!(error.to_string().contains("longer than 20 characters"));
+
+        let mut decoder = StreamDecoder::new(&b"d4:spami1ee"[..]).with_max_key_len(3);
+        decoder.next_token().unwrap();
+        let error = decoder.next_token().unwrap_err();
+        assert_eq!(error.code(), "E0107");
+        assert!(error.to_string().contains("4 bytes long at offset 1"));
+    }
+
+    #[test]
+    fn large_strings_are_not_empty() {
+        let input = b"l5:abcde0:e";
+        let mut decoder = StreamDecoder::new(&input[..])
+            .with_threshold(2)
+            .with_empty_strings(EmptyPolicy::Warn);
+        while decoder.next_token().unwrap().is_some() {}
+        assert_eq!(decoder.warnings().empty_strings(), 1);
+
+        let mut decoder = StreamDecoder::new(&input[..])
+            .with_threshold(2)
+            .with_empty_strings(EmptyPolicy::Reject);
+        decoder.next_token().unwrap();
+        assert_eq!(
+            decoder.next_token().unwrap(),
+            Some(StreamToken::LargeBytes {
+                offset: 3,
+                length: 5
+            })
+        );
+        assert_eq!(decoder.next_token().unwrap_err().code(), "E0108");
+    }
+
+    #[test]
+    fn memory_use_is_bounded() {
+        let mut input = b"d4:data1048576:".to_vec();
+        input.resize(input.len() + (1 << 20), b'x');
+        input.push(b'e');
+
+        let mut decoder = StreamDecoder::new(&input[..]).with_threshold(1024);
+        let mut length = 0;
+        while let Some(token) = decoder.next_token().unwrap() {
+            if let StreamToken::LargeBytes { length: large, .. } = token {
+                length = large;
+            }
+        }
+        assert_eq!(length, 1 << 20);
+        assert!(decoder.scratch.capacity() <= 1024);
+    }
+
+    #[test]
+    fn contexts_are_reused() {
+        fn decode(input: &[u8], context: DecoderContext) -> (bool, DecoderContext) {
+            let mut decoder = StreamDecoder::new(input)
+                .with_max_depth(64)
+                .with_context(context);
+            let valid = loop {
+                match decoder.next_token() {
+                    Ok(Some(_)) => {},
+                    Ok(None) => break true,
+                    Err(_) => break false,
+                }
+            };
+            (valid, decoder.into_context())
+        }
+
+        let mut deep = b"d3:key".to_vec();
+        deep.extend_from_slice(&[b'l'; 40]);
+        deep.extend_from_slice(b"5:abcde");
+        deep.extend_from_slice(&[b'e'; 41]);
+
+        let (valid, context) = decode(&deep, DecoderContext::new());
+        assert!(valid);
+        let scratch = context.scratch.as_ptr();
+
+        // Errors of one document don't affect the next one
+        let (valid, context) = decode(b"l5:abcd", context);
+        assert!(!valid);
+        let (valid, context) = decode(&deep, context);
+        assert!(valid);
+        assert_eq!(context.scratch.as_ptr(), scratch);
+    }
+
+    #[test]
+    fn digests_get_the_raw_value() {
+        #[derive(Default)]
+        struct Recorder(Vec<u8>);
+
+        impl Digest for Recorder {
+            fn update(&mut self, bytes: &[u8]) {
+                self.0.extend_from_slice(bytes);
+            }
+        }
+
+        let input = b"d1:ai1e4:infod1:xl5:abcdeee1:zi2ee";
+        for &(key, value) in &[(&b"info"[..], &b"d1:xl5:abcdeee"[..]), (b"z", b"i2e")] {
+            let mut decoder = StreamDecoder::new(&input[..])
+                .with_threshold(4)
+                .with_digest(key, Recorder::default());
+            assert!(decoder.digest().is_none());
+            while decoder.next_token().unwrap().is_some() {}
+            assert_eq!(decoder.into_digest().unwrap().0, value);
+        }
+
+        // Only keys of the top-level dict count
+        let mut decoder = StreamDecoder::new(&b"d1:ad1:xi1eee"[..]).with_digest(b"x", ());
+        while decoder.next_token().unwrap().is_some() {}
+        assert!(decoder.digest().is_none());
+    }
+
+    #[test]
+    fn invalid_documents_are_rejected() {
+        for input in &[
+            &b"l10:abce"[..],
+            b"d1:bi1e1:ai2ee",
+            b"li01ee",
+            b"l01:ae",
+            b"x",
+            b"l",
+        ] {
+            assert!(tokens(input, 4).is_err(), "{:?}", input);
+        }
+    }
+}
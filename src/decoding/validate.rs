@@ -0,0 +1,113 @@
+//! Checking that an input is bencode without decoding it.
+
+use crate::{
+    decoding::{Decoder, Error},
+    state_tracker::StructureError,
+};
+
+/// How an input that [`validate`] accepted conforms to the specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Validity {
+    /// Every dict has its keys unique and in ascending order, so the input is
+    /// the only encoding of its value and can be decoded by [`Decoder`]
+    Canonical,
+    /// Some dict has keys out of order or twice, which only decoders allowing
+    /// it with [`Decoder::with_sorted_keys`] accept
+    UnsortedKeys,
+}
+
+impl Validity {
+    /// Whether the input is the canonical encoding of its value
+    pub fn is_canonical(self) -> bool {
+        self == Validity::Canonical
+    }
+}
+
+/// Check that `bytes` is a single bencoded value, with nesting no deeper than
+/// `max_depth`, and whether it's canonical.
+///
+/// This reads the tokens of the input like a [`Decoder`], but builds no
+/// values, so it's cheap enough to check untrusted inputs before decoding
+/// them. Apart from the input, its memory only depends on the nesting depth.
+/// Like every decoding function, it rejects integers with leading zeros and
+/// negative zero.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::decoding::{validate, Validity};
+///
+/// assert_eq!(validate(b"d1:ai1e1:bi2ee", 8).unwrap(), Validity::Canonical);
+/// assert_eq!(validate(b"d1:bi1e1:ai2ee", 8).unwrap(), Validity::UnsortedKeys);
+///
+/// let error = validate(b"li1ei-0ee", 8).unwrap_err();
+/// assert_eq!(error.excerpt().unwrap().offset(), 4);
+/// ```
+pub fn validate(bytes: &[u8], max_depth: usize) -> Result<Validity, Error> {
+    let mut decoder = Decoder::new(bytes)
+        .with_max_depth(max_depth)
+        .with_sorted_keys(false);
+
+    if decoder.next_token()?.is_none() {
+        return Err(decoder.fail(Error::from(StructureError::UnexpectedEof)));
+    }
+    while decoder.depth() > 0 {
+        decoder.next_token()?;
+    }
+    if !decoder.remaining().is_empty() {
+        return Err(decoder.fail(Error::trailing_data()));
+    }
+
+    match decoder.saw_unsorted_keys() {
+        true => Ok(Validity::UnsortedKeys),
+        false => Ok(Validity::Canonical),
+    }
+}
+
+/// Whether `bytes` is a single bencoded value, which may have unsorted keys,
+/// as checked by [`validate`] with the default depth limit of a [`Decoder`].
+///
+/// # Examples
+///
+/// ```
+/// use bendy::decoding::is_valid_bencode;
+///
+/// assert!(is_valid_bencode(b"l4:spami42ee"));
+/// assert!(!is_valid_bencode(b"l4:spami42e"));
+/// assert!(!is_valid_bencode(b"i42ei43e"));
+/// ```
+pub fn is_valid_bencode(bytes: &[u8]) -> bool {
+    validate(bytes, DEFAULT_MAX_DEPTH).is_ok()
+}
+
+/// The depth limit of a new [`Decoder`]
+const DEFAULT_MAX_DEPTH: usize = 2048;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoding::ErrorKind;
+
+    #[test]
+    fn only_single_complete_values_are_valid() {
+        for valid in &[&b"i0e"[..], b"0:", b"le", b"de", b"d1:ald1:bi1eeee"] {
+            assert_eq!(validate(valid, 4).unwrap(), Validity::Canonical);
+        }
+        for invalid in &[&b""[..], b"i", b"i01e", b"l", b"d1:ae", b"di1ei2ee", b"lex"] {
+            assert!(!is_valid_bencode(invalid), "{:?}", invalid);
+        }
+
+        let error = validate(b"i1ei2e", 4).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::TrailingData));
+        assert_eq!(error.excerpt().unwrap().offset(), 3);
+    }
+
+    #[test]
+    fn nested_keys_are_checked() {
+        assert_eq!(
+            validate(b"d1:ad1:bi1e1:bi2ee1:ci3ee", 4).unwrap(),
+            Validity::UnsortedKeys
+        );
+        assert!(validate(b"llllleeeee", 4).is_err());
+    }
+}
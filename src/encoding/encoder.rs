@@ -1,28 +1,80 @@
 #[cfg(not(feature = "std"))]
-use alloc::{
-    borrow::ToOwned,
+use alloc::{borrow::ToOwned, collections::BTreeMap, format, string::String, vec::Vec};
+#[cfg(not(feature = "std"))]
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::{
     collections::BTreeMap,
-    format,
-    string::{String, ToString},
+    fmt,
+    io::{self, IoSlice, Write},
     vec::Vec,
 };
-#[cfg(feature = "std")]
-use std::{collections::BTreeMap, vec::Vec};
 
 use crate::{
     encoding::{Error, PrintableInteger, ToBencode},
-    state_tracker::{StateTracker, StructureError, Token},
+    state_tracker::{Key, StateTracker, StructureError, Token},
 };
 
+/// The number of buffered bytes at which an encoder writing through to a
+/// writer passes them on, and the length from which byte strings are written
+/// directly instead of being copied into the buffer first
+#[cfg(feature = "std")]
+const WRITE_THROUGH_LEN: usize = 8 * 1024;
+
 /// The actual encoder. Unlike the decoder, this is not zero-copy, as that would
 /// result in a horrible interface
+///
+/// The lifetime is that of the writer set with [`with_writer`](Encoder::with_writer),
+/// if any.
 #[derive(Default, Debug)]
-pub struct Encoder {
-    state: StateTracker<Vec<u8>, Error>,
+pub struct Encoder<'w> {
+    state: StateTracker<Key, Error>,
     output: Vec<u8>,
+    byte_strings: bool,
+    #[cfg(feature = "std")]
+    sink: Sink<'w>,
+    #[cfg(not(feature = "std"))]
+    sink: PhantomData<&'w mut ()>,
+}
+
+/// The writer an encoder writes through to
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct Sink<'w> {
+    writer: Option<&'w mut dyn Write>,
+    /// The number of bytes passed on to the writer
+    written: usize,
 }
 
-impl Encoder {
+#[cfg(feature = "std")]
+impl<'w> fmt::Debug for Sink<'w> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sink")
+            .field("written", &self.written)
+            .finish()
+    }
+}
+
+/// Write all of `head` and then all of `tail` to `writer`, passing both to
+/// every [`write_vectored`](Write::write_vectored) call until the writer has
+/// taken them
+#[cfg(feature = "std")]
+fn write_all_vectored(writer: &mut dyn Write, mut head: &[u8], mut tail: &[u8]) -> io::Result<()> {
+    while !head.is_empty() || !tail.is_empty() {
+        let written = match writer.write_vectored(&[IoSlice::new(head), IoSlice::new(tail)]) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(written) => written,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        let from_head = written.min(head.len());
+        head = &head[from_head..];
+        tail = &tail[written - from_head..];
+    }
+    Ok(())
+}
+
+impl<'w> Encoder<'w> {
     /// Create a new encoder
     pub fn new() -> Self {
         <Self as Default>::default()
@@ -35,6 +87,71 @@ impl Encoder {
         self
     }
 
+    /// Write the output to `buffer`, replacing its contents, rather than a new
+    /// `Vec`. Recycling buffers avoids allocating for every encoded value.
+    #[must_use]
+    pub fn with_buffer(mut self, mut buffer: Vec<u8>) -> Self {
+        buffer.clear();
+        self.output = buffer;
+        self
+    }
+
+    /// Append the output to `buffer`, keeping its contents
+    pub(crate) fn appending_to(mut self, buffer: Vec<u8>) -> Self {
+        self.output = buffer;
+        self
+    }
+
+    /// Encode `Vec<u8>` and `&[u8]` as byte strings rather than lists of
+    /// integers, so that byte fields don't need to be wrapped in
+    /// [`AsString`](crate::encoding::AsString).
+    ///
+    /// This applies to every value emitted with this encoder, including the
+    /// fields of nested structures, but not to other collections of `u8` such as
+    /// `VecDeque<u8>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::encoding::Encoder;
+    ///
+    /// let mut encoder = Encoder::new().with_byte_strings(true);
+    /// encoder.emit(vec![b"abc".to_vec()]).unwrap();
+    /// assert_eq!(encoder.get_output().unwrap(), b"l3:abce");
+    /// ```
+    #[must_use]
+    pub fn with_byte_strings(mut self, byte_strings: bool) -> Self {
+        self.byte_strings = byte_strings;
+        self
+    }
+
+    /// Write the output through to `writer` as it's produced, rather than
+    /// keeping all of it in memory.
+    ///
+    /// The encoder passes the output on in chunks of a few kilobytes and
+    /// writes long byte strings directly. Only the values of unsorted
+    /// dictionaries are still buffered, as they have to be sorted before they
+    /// can be written. Call [`finish`](Encoder::finish) to write the rest of
+    /// the output once all objects are emitted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::encoding::Encoder;
+    ///
+    /// let mut file = Vec::new();
+    /// let mut encoder = Encoder::new().with_writer(&mut file);
+    /// encoder.emit(vec!["a", "b"]).unwrap();
+    /// encoder.finish().unwrap();
+    /// assert_eq!(file, b"l1:a1:be");
+    /// ```
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn with_writer(mut self, writer: &'w mut dyn Write) -> Self {
+        self.sink.writer = Some(writer);
+        self
+    }
+
     /// Emit a single token to the encoder
     pub(crate) fn emit_token(&mut self, token: Token) -> Result<(), Error> {
         self.state.check_error()?;
@@ -43,11 +160,9 @@ impl Encoder {
             Token::List => self.output.push(b'l'),
             Token::Dict => self.output.push(b'd'),
             Token::String(s) => {
-                // Writing to a vec can't fail
-                let length = s.len().to_string();
-                self.output.extend_from_slice(length.as_bytes());
+                s.len().write_decimal(&mut self.output);
                 self.output.push(b':');
-                self.output.extend_from_slice(s);
+                return self.extend_output(s);
             },
             Token::Num(num) => {
                 // Alas, this doesn't verify that the given number is valid
@@ -58,9 +173,82 @@ impl Encoder {
             Token::End => self.output.push(b'e'),
         }
 
+        self.write_through()
+    }
+
+    /// Append `bytes` to the output. When writing through, long byte strings
+    /// skip the buffer, and are written together with the buffered output
+    /// before them, such as their length prefix.
+    fn extend_output(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        {
+            if self.sink.writer.is_some() && bytes.len() >= WRITE_THROUGH_LEN {
+                return self.write_buffered_then(bytes);
+            }
+        }
+        self.output.extend_from_slice(bytes);
+        self.write_through()
+    }
+
+    /// Pass the buffered output on to the writer if there is enough of it
+    fn write_through(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        {
+            if self.sink.writer.is_some() && self.output.len() >= WRITE_THROUGH_LEN {
+                return self.write_buffered();
+            }
+        }
         Ok(())
     }
 
+    /// Pass all of the buffered output on to the writer, if there is one
+    #[cfg(feature = "std")]
+    fn write_buffered(&mut self) -> Result<(), Error> {
+        self.write_buffered_then(&[])
+    }
+
+    /// Pass all of the buffered output and then `bytes` on to the writer, if
+    /// there is one
+    #[cfg(feature = "std")]
+    fn write_buffered_then(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if self.sink.writer.is_none() {
+            return Ok(());
+        }
+        let output = core::mem::take(&mut self.output);
+        let result = self.write(&output, bytes);
+        self.output = output;
+        self.output.clear();
+        result
+    }
+
+    /// Write `head` and then `tail` to the writer, latching any error
+    #[cfg(feature = "std")]
+    fn write(&mut self, head: &[u8], tail: &[u8]) -> Result<(), Error> {
+        if let Some(writer) = self.sink.writer.as_mut() {
+            let result = write_all_vectored(writer, head, tail).map_err(Error::from);
+            self.state.latch_err(result)?;
+            self.sink.written += head.len() + tail.len();
+        }
+        Ok(())
+    }
+
+    /// The number of bytes produced so far, whether they were written through
+    /// or not
+    #[cfg(feature = "std")]
+    pub(crate) fn len(&self) -> usize {
+        self.sink.written + self.output.len()
+    }
+
+    /// Emit an already encoded value verbatim. The caller must ensure that `raw`
+    /// consists of exactly one valid bencode value.
+    pub(crate) fn emit_raw(&mut self, raw: &[u8]) -> Result<(), Error> {
+        self.state.check_error()?;
+        // The raw value is a single complete object, so to the state tracker it looks
+        // like any other non-string token
+        self.state.observe_token(&Token::Num(""))?;
+        self.extend_output(raw)
+    }
+
     /// Emit an arbitrary encodable object
     pub fn emit<E: ToBencode>(&mut self, value: E) -> Result<(), Error> {
         self.emit_with(|e| value.encode(e))
@@ -101,9 +289,9 @@ impl Encoder {
         // possible (for performance)
         self.state.observe_token(&Token::Num(""))?;
         self.output.push(b'i');
-        self.output.extend_from_slice(value.to_string().as_bytes());
+        value.write_decimal(&mut self.output);
         self.output.push(b'e');
-        Ok(())
+        self.write_through()
     }
 
     /// Emit a string
@@ -163,7 +351,7 @@ impl Encoder {
     /// ```
     pub fn emit_list<F>(&mut self, list_cb: F) -> Result<(), Error>
     where
-        F: FnOnce(&mut Encoder) -> Result<(), Error>,
+        F: FnOnce(&mut Encoder<'w>) -> Result<(), Error>,
     {
         self.emit_token(Token::List)?;
         list_cb(self)?;
@@ -199,17 +387,54 @@ impl Encoder {
         self.end_unsorted_dict(encoder)
     }
 
-    /// Return the encoded string, if all objects written are complete
+    /// Return the encoded string, if all objects written are complete. If the
+    /// encoder writes through to a writer, the rest of the output is written
+    /// and the returned string is empty.
     pub fn get_output(mut self) -> Result<Vec<u8>, Error> {
         self.state.observe_eof()?;
+        #[cfg(feature = "std")]
+        self.write_buffered()?;
         Ok(self.output)
     }
 
+    /// Write the rest of the output to the writer set with
+    /// [`with_writer`](Encoder::with_writer) and flush it, if all objects
+    /// written are complete
+    #[cfg(feature = "std")]
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.state.observe_eof()?;
+        self.write_buffered()?;
+        if let Some(writer) = self.sink.writer.as_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Move the output into `buffer` if `result` and the end of encoding are
+    /// successful, or leave only its first `keep` bytes otherwise. Either way,
+    /// `buffer` keeps the allocation of the output.
+    pub(crate) fn finish_into<E: From<Error>>(
+        mut self,
+        result: Result<(), E>,
+        buffer: &mut Vec<u8>,
+        keep: usize,
+    ) -> Result<(), E> {
+        let result = result.and_then(|()| self.state.observe_eof().map_err(E::from));
+        *buffer = self.output;
+        if result.is_err() {
+            buffer.truncate(keep);
+        }
+        result
+    }
+
     pub(crate) fn begin_unsorted_dict(&mut self) -> Result<UnsortedDictEncoder, Error> {
         // emit the dict token so that a pre-existing state error is reported early
         self.emit_token(Token::Dict)?;
 
-        Ok(UnsortedDictEncoder::new(self.state.remaining_depth()))
+        Ok(UnsortedDictEncoder::new(
+            self.state.remaining_depth(),
+            self.byte_strings,
+        ))
     }
 
     pub(crate) fn end_unsorted_dict(&mut self, encoder: UnsortedDictEncoder) -> Result<(), Error> {
@@ -219,7 +444,7 @@ impl Encoder {
             self.emit_bytes(&k)?;
             // We know that the output is a single object by construction
             self.state.observe_token(&Token::Num(""))?;
-            self.output.extend_from_slice(&v);
+            self.extend_output(&v)?;
         }
 
         self.emit_token(Token::End)?;
@@ -231,15 +456,15 @@ impl Encoder {
 /// An encoder that can only encode a single item. See [`Encoder`]
 /// for usage examples; the only difference between these classes is
 /// that `SingleItemEncoder` can only be used once.
-pub struct SingleItemEncoder<'a> {
-    encoder: &'a mut Encoder,
+pub struct SingleItemEncoder<'a, 'w> {
+    encoder: &'a mut Encoder<'w>,
     /// Whether we attempted to write a value to the encoder. The value
     /// of the referent of this field is meaningless if the encode method
     /// failed.
     value_written: &'a mut bool,
 }
 
-impl<'a> SingleItemEncoder<'a> {
+impl<'a, 'w> SingleItemEncoder<'a, 'w> {
     /// Emit an arbitrary encodable object
     pub fn emit<E: ToBencode + ?Sized>(self, value: &E) -> Result<(), Error> {
         value.encode(self)
@@ -271,10 +496,29 @@ impl<'a> SingleItemEncoder<'a> {
         self.encoder.emit_bytes(value)
     }
 
+    /// Whether `Vec<u8>` and `&[u8]` are encoded as byte strings. See
+    /// [`Encoder::with_byte_strings`].
+    pub fn byte_strings(&self) -> bool {
+        self.encoder.byte_strings
+    }
+
+    /// Emit an already encoded value verbatim. See [`Encoder::emit_raw`].
+    pub(crate) fn emit_raw(self, raw: &[u8]) -> Result<(), Error> {
+        *self.value_written = true;
+        self.encoder.emit_raw(raw)
+    }
+
+    /// Take the underlying encoder to emit the value token by token. The
+    /// caller must emit exactly one complete value.
+    pub(crate) fn into_encoder(self) -> &'a mut Encoder<'w> {
+        *self.value_written = true;
+        self.encoder
+    }
+
     /// Emit an arbitrary list
     pub fn emit_list<F>(self, list_cb: F) -> Result<(), Error>
     where
-        F: FnOnce(&mut Encoder) -> Result<(), Error>,
+        F: FnOnce(&mut Encoder<'w>) -> Result<(), Error>,
     {
         *self.value_written = true;
         self.encoder.emit_list(list_cb)
@@ -318,11 +562,11 @@ impl<'a> SingleItemEncoder<'a> {
 }
 
 /// Encodes a map with pre-sorted keys
-pub struct SortedDictEncoder<'a> {
-    encoder: &'a mut Encoder,
+pub struct SortedDictEncoder<'a, 'w> {
+    encoder: &'a mut Encoder<'w>,
 }
 
-impl<'a> SortedDictEncoder<'a> {
+impl<'a, 'w> SortedDictEncoder<'a, 'w> {
     /// Emit a key/value pair
     pub fn emit_pair<E>(&mut self, key: &[u8], value: E) -> Result<(), Error>
     where
@@ -350,14 +594,16 @@ pub struct UnsortedDictEncoder {
     content: BTreeMap<Vec<u8>, Vec<u8>>,
     error: Result<(), Error>,
     remaining_depth: usize,
+    byte_strings: bool,
 }
 
 impl UnsortedDictEncoder {
-    pub(crate) fn new(remaining_depth: usize) -> Self {
+    pub(crate) fn new(remaining_depth: usize, byte_strings: bool) -> Self {
         Self {
             content: BTreeMap::new(),
             error: Ok(()),
             remaining_depth,
+            byte_strings,
         }
     }
 
@@ -376,7 +622,9 @@ impl UnsortedDictEncoder {
     {
         let mut value_written = false;
 
-        let mut encoder = Encoder::new().with_max_depth(self.remaining_depth);
+        let mut encoder = Encoder::new()
+            .with_max_depth(self.remaining_depth)
+            .with_byte_strings(self.byte_strings);
 
         let ret = value_cb(SingleItemEncoder {
             encoder: &mut encoder,
@@ -451,6 +699,8 @@ impl UnsortedDictEncoder {
 #[cfg(test)]
 mod test {
     use super::*;
+    #[cfg(feature = "std")]
+    use crate::encoding::{AsString, ErrorKind};
 
     #[test]
     pub fn simple_encoding_works() {
@@ -479,4 +729,144 @@ mod test {
         let mut encoder = Encoder::new();
         assert!(encoder.emit_with(|_| Ok(())).is_err());
     }
+
+    /// A writer which records the length of every write
+    #[cfg(feature = "std")]
+    #[derive(Default)]
+    struct Recorder {
+        output: Vec<u8>,
+        writes: Vec<usize>,
+    }
+
+    #[cfg(feature = "std")]
+    impl Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            self.writes.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn output_is_written_through() {
+        let numbers: Vec<u32> = (0..10_000).collect();
+        let long = vec![b'x'; WRITE_THROUGH_LEN * 4];
+
+        let mut recorder = Recorder::default();
+        let mut encoder = Encoder::new().with_writer(&mut recorder);
+        encoder.emit(&numbers).unwrap();
+        encoder
+            .emit_and_sort_dict(|e| {
+                e.emit_pair(b"b", AsString(&long))?;
+                e.emit_pair(b"a", 1)
+            })
+            .unwrap();
+        encoder.finish().unwrap();
+
+        let mut expected = numbers.to_bencode().unwrap();
+        expected.extend_from_slice(b"d1:ai1e1:b32768:");
+        expected.extend_from_slice(&long);
+        expected.push(b'e');
+        assert_eq!(recorder.output, expected);
+        assert!(recorder.writes.len() > 2);
+        // The buffered value of the unsorted dictionary is written directly
+        let value_len = b"32768:".len() + long.len();
+        assert!(recorder.writes.contains(&value_len));
+        assert!(recorder
+            .writes
+            .iter()
+            .all(|&len| len < WRITE_THROUGH_LEN + 16 || len == value_len));
+    }
+
+    /// A vectored writer which takes at most `limit` bytes per write, and
+    /// records the lengths of the slices it was offered by every write
+    #[cfg(feature = "std")]
+    struct Trickle {
+        limit: usize,
+        output: Vec<u8>,
+        writes: Vec<Vec<usize>>,
+    }
+
+    #[cfg(feature = "std")]
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice]) -> std::io::Result<usize> {
+            self.writes.push(bufs.iter().map(|buf| buf.len()).collect());
+            let mut written = 0;
+            for buf in bufs {
+                let count = buf.len().min(self.limit - written);
+                self.output.extend_from_slice(&buf[..count]);
+                written += count;
+            }
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn long_strings_are_written_with_their_prefix() {
+        let long = vec![b'x'; WRITE_THROUGH_LEN];
+        let mut writer = Trickle {
+            limit: 3000,
+            output: Vec::new(),
+            writes: Vec::new(),
+        };
+        let mut encoder = Encoder::new().with_writer(&mut writer);
+        encoder.emit_list(|e| e.emit_bytes(&long)).unwrap();
+        encoder.finish().unwrap();
+
+        let mut expected = b"l8192:".to_vec();
+        expected.extend_from_slice(&long);
+        expected.push(b'e');
+        assert_eq!(writer.output, expected);
+
+        // The prefix and the string are offered together, and what's left of
+        // them after every partial write is offered again
+        assert_eq!(
+            writer.writes,
+            vec![vec![6, 8192], vec![0, 5198], vec![0, 2198], vec![1, 0]]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_errors_are_latched() {
+        struct Broken;
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let long = vec![b'x'; WRITE_THROUGH_LEN];
+        let mut writer = Broken;
+        let mut encoder = Encoder::new().with_writer(&mut writer);
+        let error = encoder.emit_bytes(&long).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Io(_)));
+        assert_eq!(error.code(), "E0302");
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(
+            source.downcast_ref::<std::io::Error>().unwrap().kind(),
+            std::io::ErrorKind::BrokenPipe
+        );
+        assert!(encoder.emit_int(1).is_err());
+        assert!(encoder.finish().is_err());
+        assert!(Vec::<u8>::new().to_writer(Broken).is_err());
+    }
 }
@@ -1,39 +1,71 @@
+use core::fmt::{self, Display, Formatter};
 #[cfg(feature = "std")]
-use std::sync::Arc;
-
-use failure::Fail;
+use std::{error::Error as StdError, io, sync::Arc};
 
 use crate::state_tracker::StructureError;
 
-#[derive(Debug, Clone, Fail)]
-#[fail(display = "encoding failed: {}", _0)]
-pub struct Error(#[fail(cause)] pub ErrorKind);
+/// An error raised while encoding.
+///
+/// With the `std` feature, this implements [`std::error::Error`], and
+/// [`source`](std::error::Error::source) returns the error that caused
+/// malformed content, the I/O error of a writer, or the structure error.
+#[derive(Debug, Clone)]
+pub struct Error(pub ErrorKind);
 
 /// An enumeration of potential errors that appear during bencode encoding.
-#[derive(Debug, Clone, Fail)]
+///
+/// More kinds may be added without a major release, so matches on this enum
+/// need a wildcard arm.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ErrorKind {
     /// Error that occurs if the serialized structure contains invalid semantics.
     #[cfg(feature = "std")]
-    #[fail(display = "malformed content discovered: {}", _0)]
-    MalformedContent(Arc<failure::Error>),
+    MalformedContent(Arc<dyn StdError + Send + Sync>),
     /// Error that occurs if the serialized structure contains invalid semantics.
     #[cfg(not(feature = "std"))]
-    #[fail(display = "malformed content discovered")]
     MalformedContent,
     /// Error in the bencode structure (e.g. a missing field end separator).
-    #[fail(display = "bencode encoding corrupted")]
-    StructureError(#[fail(cause)] StructureError),
+    StructureError(StructureError),
+    /// Error that occurs if the writer set with
+    /// [`Encoder::with_writer`](crate::encoding::Encoder::with_writer) fails.
+    #[cfg(feature = "std")]
+    Io(Arc<io::Error>),
 }
 
 impl Error {
+    /// What went wrong, for handling errors programmatically
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0
+    }
+
+    /// A stable code for the kind of this error, like
+    /// [`decoding::Error::code`](crate::decoding::Error::code). Structure
+    /// errors have the same codes as when decoding, malformed content is
+    /// `E0301` and errors of the writer are `E0302`.
+    pub fn code(&self) -> &'static str {
+        match &self.0 {
+            ErrorKind::StructureError(error) => error.code(),
+            #[cfg(feature = "std")]
+            ErrorKind::MalformedContent(_) => "E0301",
+            #[cfg(not(feature = "std"))]
+            ErrorKind::MalformedContent => "E0301",
+            #[cfg(feature = "std")]
+            ErrorKind::Io(_) => "E0302",
+        }
+    }
+
     /// Raised when there is a general error while deserializing a type.
     /// The message should not be capitalized and should not end with a period.
     ///
+    /// `cause` is either an error, which becomes the
+    /// [`source`](std::error::Error::source) of this one, or a message.
+    ///
     /// Note that, when building with no_std, this method accepts any type as
     /// its argument.
     #[cfg(feature = "std")]
-    pub fn malformed_content(cause: impl Into<failure::Error>) -> Error {
-        let error = Arc::new(cause.into());
+    pub fn malformed_content(cause: impl Into<Box<dyn StdError + Send + Sync>>) -> Error {
+        let error = Arc::from(cause.into());
         Self(ErrorKind::MalformedContent(error))
     }
 
@@ -43,12 +75,60 @@ impl Error {
     }
 }
 
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "encoding failed: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        // The kind is part of the message, so its cause is the next one
+        self.0.source()
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            ErrorKind::MalformedContent(cause) => {
+                write!(f, "malformed content discovered: {}", cause)
+            },
+            #[cfg(not(feature = "std"))]
+            ErrorKind::MalformedContent => write!(f, "malformed content discovered"),
+            ErrorKind::StructureError(_) => write!(f, "bencode encoding corrupted"),
+            #[cfg(feature = "std")]
+            ErrorKind::Io(error) => write!(f, "writing failed: {}", error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for ErrorKind {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ErrorKind::MalformedContent(cause) => Some(&**cause),
+            ErrorKind::StructureError(error) => Some(error),
+            ErrorKind::Io(error) => Some(&**error),
+        }
+    }
+}
+
 impl From<StructureError> for Error {
     fn from(error: StructureError) -> Self {
         Self(ErrorKind::StructureError(error))
     }
 }
 
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self(ErrorKind::Io(Arc::new(error)))
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Self {
         Self(kind)
@@ -1,15 +1,91 @@
 #[cfg(not(feature = "std"))]
+use alloc::{string::ToString, vec::Vec};
+#[cfg(not(feature = "std"))]
 use core::fmt::Display;
 #[cfg(feature = "std")]
-use std::fmt::Display;
+use std::{fmt::Display, vec::Vec};
 
 /// A value that can be formatted as a decimal integer
-pub trait PrintableInteger: Display {}
+pub trait PrintableInteger: Display {
+    /// Append the decimal representation of the value to `output`
+    ///
+    /// The default implementation uses [`Display`]. The implementations for
+    /// the primitive integer types format on the stack instead, so that they
+    /// don't allocate.
+    fn write_decimal(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(self.to_string().as_bytes());
+    }
+}
+
+/// Enough bytes for the digits and the sign of any primitive integer
+const MAX_DECIMAL_LEN: usize = 40;
+
+/// Append the digits of `value`, preceded by a `-` if `negative`
+macro_rules! write_digits {
+    ($value:expr, $negative:expr, $output:expr) => {{
+        let mut value = $value;
+        let mut buffer = [0u8; MAX_DECIMAL_LEN];
+        let mut start = buffer.len();
+        loop {
+            start -= 1;
+            buffer[start] = b'0' + (value % 10) as u8;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+        if $negative {
+            start -= 1;
+            buffer[start] = b'-';
+        }
+        $output.extend_from_slice(&buffer[start..]);
+    }};
+}
+
+macro_rules! impl_unsigned {
+    ($($type:ty)*) => {$(
+        impl PrintableInteger for $type {
+            fn write_decimal(&self, output: &mut Vec<u8>) {
+                write_digits!(*self, false, output)
+            }
+        }
+    )*}
+}
 
-macro_rules! impl_integer {
+macro_rules! impl_signed {
     ($($type:ty)*) => {$(
-        impl PrintableInteger for $type {}
+        impl PrintableInteger for $type {
+            fn write_decimal(&self, output: &mut Vec<u8>) {
+                write_digits!(self.unsigned_abs(), *self < 0, output)
+            }
+        }
     )*}
 }
 
-impl_integer!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+impl_unsigned!(u8 u16 u32 u64 u128 usize);
+impl_signed!(i8 i16 i32 i64 i128 isize);
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, vec::Vec};
+
+    use super::*;
+
+    fn decimal<T: PrintableInteger>(value: T) -> Vec<u8> {
+        let mut output = Vec::new();
+        value.write_decimal(&mut output);
+        output
+    }
+
+    #[test]
+    fn extremes_are_formatted() {
+        assert_eq!(decimal(0u8), b"0");
+        assert_eq!(decimal(u8::MAX), b"255");
+        assert_eq!(decimal(i8::MIN), b"-128");
+        assert_eq!(decimal(-1i32), b"-1");
+        assert_eq!(decimal(u128::MAX), format!("{}", u128::MAX).as_bytes());
+        assert_eq!(decimal(i128::MIN), format!("{}", i128::MIN).as_bytes());
+        assert_eq!(decimal(i64::MAX), format!("{}", i64::MAX).as_bytes());
+    }
+}
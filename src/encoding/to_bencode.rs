@@ -11,11 +11,15 @@ use alloc::{
 use std::{
     collections::{BTreeMap, HashMap, LinkedList, VecDeque},
     hash::{BuildHasher, Hash},
+    io::Write,
     rc::Rc,
     sync::Arc,
 };
 
-use crate::encoding::{Encoder, Error, SingleItemEncoder};
+use crate::{
+    encoding::{Encoder, Error, SingleItemEncoder},
+    instrument::{self, Phase},
+};
 
 /// An object that can be encoded into a single bencode object
 pub trait ToBencode {
@@ -26,14 +30,124 @@ pub trait ToBencode {
     /// Encode this object into the bencode stream
     fn encode(&self, encoder: SingleItemEncoder) -> Result<(), Error>;
 
+    /// Encode a `Vec` or slice of objects of this type. By default, they are
+    /// encoded as a list; `u8` overrides this to encode a byte string if the
+    /// encoder was configured [`with_byte_strings`](Encoder::with_byte_strings).
+    fn encode_slice(items: &[Self], encoder: SingleItemEncoder) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        emit_items(items, encoder)
+    }
+
     /// Encode this object to a byte string
     fn to_bencode(&self) -> Result<Vec<u8>, Error> {
-        let mut encoder = Encoder::new().with_max_depth(Self::MAX_DEPTH);
-        encoder.emit_with(|e| self.encode(e).map_err(Error::into))?;
+        instrument::measure(Phase::Encoding, || {
+            let mut encoder = Encoder::new().with_max_depth(Self::MAX_DEPTH);
+            let result = encoder
+                .emit_with(|e| self.encode(e))
+                .and_then(|()| encoder.get_output());
+            let len = result.as_ref().map_or(0, Vec::len);
+            (result, len)
+        })
+    }
 
-        let bytes = encoder.get_output()?;
-        Ok(bytes)
+    /// Encode this object into `buffer`, replacing its contents.
+    ///
+    /// Unlike [`to_bencode`](ToBencode::to_bencode), this reuses the allocation
+    /// of `buffer`, so encoding many objects with the same buffer doesn't
+    /// allocate once it's large enough. If encoding fails, `buffer` is left
+    /// empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::encoding::ToBencode;
+    ///
+    /// let mut buffer = Vec::new();
+    /// for i in 0..3 {
+    ///     i.to_bencode_into(&mut buffer).unwrap();
+    ///     assert_eq!(buffer, format!("i{}e", i).as_bytes());
+    /// }
+    /// ```
+    fn to_bencode_into(&self, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        instrument::measure(Phase::Encoding, || {
+            let mut encoder = Encoder::new()
+                .with_max_depth(Self::MAX_DEPTH)
+                .with_buffer(core::mem::take(buffer));
+            let result = encoder.emit_with(|e| self.encode(e));
+            let result = encoder.finish_into(result, buffer, 0);
+            (result, buffer.len())
+        })
     }
+
+    /// Append the encoding of this object to `buffer`, keeping its contents.
+    ///
+    /// This suits framed messages whose header precedes the payload: the
+    /// payload is encoded in place rather than being copied after the header.
+    /// If encoding fails, `buffer` is truncated to its original length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::encoding::ToBencode;
+    ///
+    /// // A four byte length prefix, filled in once the length is known
+    /// let mut frame = vec![0; 4];
+    /// vec![1, 2].encode_into(&mut frame).unwrap();
+    /// let length = (frame.len() - 4) as u32;
+    /// frame[..4].copy_from_slice(&length.to_be_bytes());
+    /// assert_eq!(frame, b"\0\0\0\x08li1ei2ee");
+    /// ```
+    fn encode_into(&self, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        instrument::measure(Phase::Encoding, || {
+            let start = buffer.len();
+            let mut encoder = Encoder::new()
+                .with_max_depth(Self::MAX_DEPTH)
+                .appending_to(core::mem::take(buffer));
+            let result = encoder.emit_with(|e| self.encode(e));
+            let result = encoder.finish_into(result, buffer, start);
+            (result, buffer.len() - start)
+        })
+    }
+
+    /// Encode this object to `writer`.
+    ///
+    /// The output is written as it's produced rather than being collected
+    /// first, so only a few kilobytes of it are buffered at a time, except for
+    /// the contents of unsorted dictionaries. See
+    /// [`Encoder::with_writer`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::encoding::ToBencode;
+    ///
+    /// let mut file = Vec::new();
+    /// vec![1, 2].to_writer(&mut file).unwrap();
+    /// assert_eq!(file, b"li1ei2ee");
+    /// ```
+    #[cfg(feature = "std")]
+    fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        instrument::measure(Phase::Encoding, || {
+            let mut encoder = Encoder::new()
+                .with_max_depth(Self::MAX_DEPTH)
+                .with_writer(&mut writer);
+            let result = encoder.emit_with(|e| self.encode(e));
+            let len = encoder.len();
+            (result.and_then(|()| encoder.finish()), len)
+        })
+    }
+}
+
+/// Encode `items` as a list
+fn emit_items<E: ToBencode>(items: &[E], encoder: SingleItemEncoder) -> Result<(), Error> {
+    encoder.emit_list(|e| {
+        for item in items {
+            e.emit(item)?;
+        }
+        Ok(())
+    })
 }
 
 /// Wrapper to allow `Vec<u8>` encoding as bencode string element.
@@ -103,7 +217,22 @@ macro_rules! impl_encodable_integer {
     )*}
 }
 
-impl_encodable_integer!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+impl ToBencode for u8 {
+    const MAX_DEPTH: usize = 1;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), Error> {
+        encoder.emit_int(*self)
+    }
+
+    fn encode_slice(items: &[Self], encoder: SingleItemEncoder) -> Result<(), Error> {
+        if encoder.byte_strings() {
+            return encoder.emit_bytes(items);
+        }
+        emit_items(items, encoder)
+    }
+}
+
+impl_encodable_integer!(u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
 
 macro_rules! impl_encodable_iterable {
     ($($type:ident)*) => {$(
@@ -127,23 +256,27 @@ macro_rules! impl_encodable_iterable {
     )*}
 }
 
-impl_encodable_iterable!(Vec VecDeque LinkedList);
+impl_encodable_iterable!(VecDeque LinkedList);
 
-impl<'a, ContentT> ToBencode for &'a [ContentT]
+impl<ContentT> ToBencode for Vec<ContentT>
 where
     ContentT: ToBencode,
 {
     const MAX_DEPTH: usize = ContentT::MAX_DEPTH + 1;
 
     fn encode(&self, encoder: SingleItemEncoder) -> Result<(), Error> {
-        encoder.emit_list(|e| {
-            for item in *self {
-                e.emit(item)?;
-            }
-            Ok(())
-        })?;
+        ContentT::encode_slice(self, encoder)
+    }
+}
 
-        Ok(())
+impl<'a, ContentT> ToBencode for &'a [ContentT]
+where
+    ContentT: ToBencode,
+{
+    const MAX_DEPTH: usize = ContentT::MAX_DEPTH + 1;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), Error> {
+        ContentT::encode_slice(self, encoder)
     }
 }
 
@@ -173,13 +306,26 @@ where
 
     fn encode(&self, encoder: SingleItemEncoder) -> Result<(), Error> {
         encoder.emit_dict(|mut e| {
-            let mut pairs = self
-                .iter()
-                .map(|(k, v)| (k.as_ref(), v))
-                .collect::<Vec<_>>();
-            pairs.sort_by_key(|&(k, _)| k);
-            for (k, v) in pairs {
-                e.emit_pair(k, v)?;
+            // Small maps are sorted on the stack, so encoding them doesn't allocate
+            if self.len() <= INLINE_PAIRS {
+                let mut inline = [None; INLINE_PAIRS];
+                for (slot, (k, v)) in inline.iter_mut().zip(self) {
+                    *slot = Some((k.as_ref(), v));
+                }
+                let pairs = &mut inline[..self.len()];
+                pairs.sort_unstable_by_key(|pair| pair.map(|(k, _)| k));
+                for &(k, v) in pairs.iter().flatten() {
+                    e.emit_pair(k, v)?;
+                }
+            } else {
+                let mut pairs = self
+                    .iter()
+                    .map(|(k, v)| (k.as_ref(), v))
+                    .collect::<Vec<_>>();
+                pairs.sort_unstable_by_key(|&(k, _)| k);
+                for (k, v) in pairs {
+                    e.emit_pair(k, v)?;
+                }
             }
             Ok(())
         })?;
@@ -188,6 +334,10 @@ where
     }
 }
 
+/// The number of pairs up to which a `HashMap` is sorted without allocating.
+#[cfg(feature = "std")]
+const INLINE_PAIRS: usize = 16;
+
 impl<I> ToBencode for AsString<I>
 where
     I: AsRef<[u8]>,
@@ -262,4 +412,93 @@ mod test {
             &b"d3:bari5e3:bazl3:foo3:bare3:qux3:quxe"[..]
         );
     }
+
+    #[test]
+    fn buffers_are_reused() {
+        let mut buffer = Vec::with_capacity(64);
+        let capacity = buffer.capacity();
+        vec![1, 2, 3].to_bencode_into(&mut buffer).unwrap();
+        assert_eq!(buffer, b"li1ei2ei3ee");
+        b"abc".to_vec().to_bencode_into(&mut buffer).unwrap();
+        assert_eq!(buffer, b"li97ei98ei99ee");
+        assert_eq!(buffer.capacity(), capacity);
+
+        struct Invalid;
+        impl ToBencode for Invalid {
+            const MAX_DEPTH: usize = 0;
+
+            fn encode(&self, encoder: SingleItemEncoder) -> Result<(), Error> {
+                encoder.emit_list(|_| Ok(()))
+            }
+        }
+        assert!(Invalid.to_bencode_into(&mut buffer).is_err());
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn encodings_are_appended() {
+        let mut buffer = b"header".to_vec();
+        vec![1, 2].encode_into(&mut buffer).unwrap();
+        "abc".encode_into(&mut buffer).unwrap();
+        assert_eq!(buffer, b"headerli1ei2ee3:abc");
+
+        struct Invalid;
+        impl ToBencode for Invalid {
+            const MAX_DEPTH: usize = 0;
+
+            fn encode(&self, encoder: SingleItemEncoder) -> Result<(), Error> {
+                encoder.emit_list(|_| Ok(()))
+            }
+        }
+        assert!(Invalid.encode_into(&mut buffer).is_err());
+        assert_eq!(buffer, b"headerli1ei2ee3:abc");
+    }
+
+    #[test]
+    fn byte_vectors_can_be_encoded_as_strings() {
+        struct Packet {
+            id: Vec<u8>,
+            payload: Vec<Vec<u8>>,
+        }
+
+        impl ToBencode for Packet {
+            const MAX_DEPTH: usize = 3;
+
+            fn encode(&self, encoder: SingleItemEncoder) -> Result<(), Error> {
+                encoder.emit_dict(|mut e| {
+                    e.emit_pair(b"id", &self.id)?;
+                    e.emit_pair(b"payload", &self.payload)
+                })
+            }
+        }
+
+        let packet = Packet {
+            id: b"ab".to_vec(),
+            payload: vec![b"c".to_vec()],
+        };
+        let mut encoder = Encoder::new().with_byte_strings(true);
+        encoder.emit(&packet).unwrap();
+        encoder.emit(&b"xyz"[..]).unwrap();
+        encoder
+            .emit_and_sort_dict(|e| e.emit_pair(b"k", b"v".to_vec()))
+            .unwrap();
+        assert_eq!(
+            encoder.get_output().unwrap(),
+            b"d2:id2:ab7:payloadl1:cee3:xyzd1:k1:ve"
+        );
+
+        let default = packet.to_bencode().unwrap();
+        assert_eq!(default, &b"d2:idli97ei98ee7:payloadlli99eeee"[..]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn hash_maps_are_sorted() {
+        for &len in &[0, 1, INLINE_PAIRS, INLINE_PAIRS + 1, 100] {
+            let map: HashMap<String, usize> = (0..len).map(|i| (format!("{:03}", i), i)).collect();
+            let sorted: BTreeMap<String, usize> = map.clone().into_iter().collect();
+            assert_eq!(map.to_bencode().unwrap(), sorted.to_bencode().unwrap());
+        }
+    }
 }
@@ -0,0 +1,274 @@
+//! Inputs and entry points for fuzzing decoders.
+//!
+//! [`fuzz_decode`] and [`fuzz_type`] take the raw input of a fuzzer, such as
+//! `cargo fuzz` or AFL, and panic if decoding it breaks an invariant of the
+//! crate or of a type. A `cargo fuzz` target for your own type is a single
+//! line:
+//!
+//! ```ignore
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| bendy::fuzzing::fuzz_type::<MyType>(data));
+//! ```
+//!
+//! Random inputs are rarely valid bencode, so most of them fail on the first
+//! byte. A [`Generator`] builds inputs which get further: random valid
+//! documents for seeding a corpus, and documents with a few bytes mutated,
+//! which exercise the error paths deep inside a value:
+//!
+//! ```
+//! use bendy::fuzzing::{self, Generator};
+//!
+//! let mut generator = Generator::new(7);
+//! for _ in 0..100 {
+//!     fuzzing::fuzz_decode(&generator.document());
+//!     fuzzing::fuzz_decode(&generator.mutated());
+//! }
+//! ```
+
+use std::{collections::BTreeMap, fmt::Debug};
+
+use crate::{
+    decoding::{self, Decoder, FromBencode, Validity},
+    encoding::ToBencode,
+    value::{ByteString, Value},
+};
+
+/// Decode `data` in every way the crate can, and panic if they disagree.
+///
+/// Decoding may fail, but if [`decoding::validate`] finds the input
+/// canonical, then the [`Decoder`] must read all of its tokens, and if it
+/// decodes as a [`Value`], encoding that must return the input.
+pub fn fuzz_decode(data: &[u8]) {
+    let max_depth = <Value as FromBencode>::EXPECTED_RECURSION_DEPTH;
+    let validity = decoding::validate(data, max_depth);
+
+    let tokens = Decoder::new(data)
+        .with_max_depth(max_depth)
+        .tokens()
+        .collect::<Result<Vec<_>, _>>();
+    if let Ok(Validity::Canonical) = validity {
+        if let Err(error) = tokens {
+            panic!("valid input failed to decode: {}", error);
+        }
+
+        // Integers beyond `i64` are valid, but not `Value`s
+        if let Ok(value) = Value::from_bencode(data) {
+            let encoded = value.to_bencode().expect("values can be encoded");
+            assert_eq!(encoded, data, "re-encoding {:?} changed it", value);
+        }
+    }
+}
+
+/// Decode `data` as a `T`, and if that succeeds, panic unless encoding and
+/// decoding the result again returns an equal value.
+pub fn fuzz_type<T>(data: &[u8])
+where
+    T: FromBencode + ToBencode + PartialEq + Debug,
+{
+    let value = match T::from_bencode(data) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+
+    let encoded = match value.to_bencode() {
+        Ok(encoded) => encoded,
+        Err(error) => panic!("decoded {:?} can't be encoded: {}", value, error),
+    };
+    match T::from_bencode(&encoded) {
+        Ok(decoded) => assert_eq!(value, decoded, "round trip through {:?}", encoded),
+        Err(error) => panic!("encoding of {:?} can't be decoded: {}", value, error),
+    }
+}
+
+/// A deterministic generator of random bencoded documents.
+///
+/// The same seed always produces the same documents, so a failing input can
+/// be reproduced from the seed alone.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    state: u64,
+    max_depth: usize,
+    max_items: usize,
+    max_bytes: usize,
+}
+
+/// Bytes which start or end tokens, which mutations favour
+const SIGNIFICANT_BYTES: &[u8] = b"ilde:-0123456789";
+
+impl Generator {
+    /// Create a generator for documents nested at most 4 deep, with up to 8
+    /// items in each list or dict and byte strings of up to 16 bytes
+    pub fn new(seed: u64) -> Self {
+        Generator {
+            state: seed,
+            max_depth: 4,
+            max_items: 8,
+            max_bytes: 16,
+        }
+    }
+
+    /// Create a generator seeded with the first eight bytes of `data`, such
+    /// as the input of a fuzzer
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let mut seed = [0; 8];
+        let len = data.len().min(8);
+        seed[..len].copy_from_slice(&data[..len]);
+        Self::new(u64::from_le_bytes(seed))
+    }
+
+    /// Set how deeply lists and dicts may be nested
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set how many items a list or dict may have
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = max_items;
+        self
+    }
+
+    /// Set how long byte strings may be
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// The next random number, from SplitMix64
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A random number up to and including `max`
+    fn up_to(&mut self, max: usize) -> usize {
+        (self.next_u64() % (max as u64 + 1)) as usize
+    }
+
+    /// A random value within the limits of the generator
+    pub fn value(&mut self) -> Value<'static> {
+        self.value_within(self.max_depth)
+    }
+
+    fn value_within(&mut self, depth: usize) -> Value<'static> {
+        let kinds = if depth == 0 { 2 } else { 4 };
+        match self.up_to(kinds - 1) {
+            0 => Value::Bytes(self.bytes()),
+            1 => Value::Integer(self.integer()),
+            2 => {
+                let len = self.up_to(self.max_items);
+                Value::List((0..len).map(|_| self.value_within(depth - 1)).collect())
+            },
+            _ => {
+                let len = self.up_to(self.max_items);
+                let dict: BTreeMap<_, _> = (0..len)
+                    .map(|_| (self.bytes(), self.value_within(depth - 1)))
+                    .collect();
+                Value::Dict(dict)
+            },
+        }
+    }
+
+    fn bytes(&mut self) -> ByteString<'static> {
+        let len = self.up_to(self.max_bytes);
+        // Mostly printable bytes, so that keys collide now and then
+        let bytes: Vec<u8> = (0..len)
+            .map(|_| match self.up_to(3) {
+                0 => self.next_u64() as u8,
+                _ => b'a' + self.up_to(5) as u8,
+            })
+            .collect();
+        ByteString::from(bytes)
+    }
+
+    fn integer(&mut self) -> i64 {
+        match self.up_to(3) {
+            0 => self.next_u64() as i64,
+            1 => [0, 1, -1, i64::MIN, i64::MAX][self.up_to(4)],
+            _ => self.up_to(1000) as i64 - 500,
+        }
+    }
+
+    /// The canonical encoding of a random [`value`](Generator::value)
+    pub fn document(&mut self) -> Vec<u8> {
+        self.value()
+            .to_bencode()
+            .expect("generated values can be encoded")
+    }
+
+    /// A random [`document`](Generator::document) with one to three
+    /// mutations: bytes replaced, inserted or removed, a range duplicated, or
+    /// the end cut off. Most of these are invalid, but only near the mutation.
+    pub fn mutated(&mut self) -> Vec<u8> {
+        let mut document = self.document();
+        for _ in 0..=self.up_to(2) {
+            self.mutate(&mut document);
+        }
+        document
+    }
+
+    fn mutate(&mut self, document: &mut Vec<u8>) {
+        let at = self.up_to(document.len());
+        let byte = match self.up_to(1) {
+            0 => SIGNIFICANT_BYTES[self.up_to(SIGNIFICANT_BYTES.len() - 1)],
+            _ => self.next_u64() as u8,
+        };
+        match self.up_to(4) {
+            0 if at < document.len() => document[at] = byte,
+            1 => document.insert(at, byte),
+            2 if at < document.len() => {
+                document.remove(at);
+            },
+            3 => {
+                let end = at + self.up_to(document.len() - at);
+                let range = document[at..end].to_vec();
+                document.splice(end..end, range);
+            },
+            _ => document.truncate(at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_documents_are_canonical() {
+        let mut generator = Generator::new(1);
+        for _ in 0..200 {
+            let document = generator.document();
+            let validity = decoding::validate(&document, 8);
+            assert_eq!(validity.unwrap(), Validity::Canonical, "{:?}", document);
+            fuzz_decode(&document);
+        }
+    }
+
+    #[test]
+    fn mutated_documents_keep_invariants() {
+        let mut generator = Generator::from_bytes(b"bendy").with_max_items(4);
+        let mut invalid = 0;
+        for _ in 0..1000 {
+            let document = generator.mutated();
+            if !decoding::is_valid_bencode(&document) {
+                invalid += 1;
+            }
+            fuzz_decode(&document);
+            fuzz_type::<Value>(&document);
+            fuzz_type::<Vec<i64>>(&document);
+        }
+        assert!(100 < invalid && invalid < 1000, "{} invalid", invalid);
+    }
+
+    #[test]
+    fn seeds_reproduce_documents() {
+        let documents = |seed| {
+            let mut generator = Generator::new(seed).with_max_depth(2);
+            (0..10).map(|_| generator.mutated()).collect::<Vec<_>>()
+        };
+        assert_eq!(documents(5), documents(5));
+        assert_ne!(documents(5), documents(6));
+    }
+}
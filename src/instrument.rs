@@ -0,0 +1,223 @@
+//! Counters for profiling encoding and decoding.
+//!
+//! Every call of
+//! [`FromBencode::from_bencode`](crate::decoding::FromBencode::from_bencode),
+//! [`ToBencode::to_bencode`](crate::encoding::ToBencode::to_bencode) and the
+//! serde functions is counted, along with the bytes it read or wrote and the
+//! time it took. Calls made while another is running, such as decoding a
+//! nested value, are part of the outer call.
+//!
+//! Allocations can only be counted by the global allocator, so they are only
+//! counted if [`CountingAllocator`] is installed with `#[global_allocator]`:
+//!
+//! ```
+//! use bendy::{
+//!     decoding::FromBencode,
+//!     instrument::{self, CountingAllocator},
+//!     value::Value,
+//! };
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+//!
+//! Value::from_bencode(b"l3:fooe").unwrap();
+//!
+//! let stats = instrument::stats();
+//! assert!(stats.decoding.calls >= 1);
+//! assert!(stats.decoding.allocations >= 1);
+//! ```
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// What a measured call does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Phase {
+    Decoding,
+    Encoding,
+}
+
+/// Run `call`, which returns its result and the number of bytes it read or
+/// wrote, and add it to the counters of `phase`
+pub(crate) fn measure<T>(phase: Phase, call: impl FnOnce() -> (T, usize)) -> T {
+    if ACTIVE.with(Cell::get).is_some() {
+        return call().0;
+    }
+
+    let active = Active::enter(phase);
+    let start = Instant::now();
+    let (result, bytes) = crate::trace::traced(phase, call);
+    let elapsed = start.elapsed();
+    drop(active);
+
+    let counters = Counters::of(phase);
+    counters.calls.fetch_add(1, Ordering::Relaxed);
+    counters.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    counters
+        .nanos
+        .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    result
+}
+
+thread_local! {
+    /// The phase of the outermost measured call running on this thread
+    static ACTIVE: Cell<Option<Phase>> = const { Cell::new(None) };
+}
+
+/// Marks a measured call as running until it is dropped, even if the call
+/// panics
+struct Active;
+
+impl Active {
+    fn enter(phase: Phase) -> Self {
+        ACTIVE.with(|active| active.set(Some(phase)));
+        Active
+    }
+}
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| active.set(None));
+    }
+}
+
+struct Counters {
+    calls: AtomicU64,
+    bytes: AtomicU64,
+    nanos: AtomicU64,
+    allocations: AtomicU64,
+    allocated_bytes: AtomicU64,
+}
+
+static DECODING: Counters = Counters::new();
+static ENCODING: Counters = Counters::new();
+
+impl Counters {
+    const fn new() -> Self {
+        Counters {
+            calls: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            nanos: AtomicU64::new(0),
+            allocations: AtomicU64::new(0),
+            allocated_bytes: AtomicU64::new(0),
+        }
+    }
+
+    fn of(phase: Phase) -> &'static Counters {
+        match phase {
+            Phase::Decoding => &DECODING,
+            Phase::Encoding => &ENCODING,
+        }
+    }
+
+    fn snapshot(&self) -> PhaseStats {
+        PhaseStats {
+            calls: self.calls.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            time: Duration::from_nanos(self.nanos.load(Ordering::Relaxed)),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            allocated_bytes: self.allocated_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        for counter in &[
+            &self.calls,
+            &self.bytes,
+            &self.nanos,
+            &self.allocations,
+            &self.allocated_bytes,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The counters of all threads, as returned by [`stats`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Decoding with `FromBencode` or serde
+    pub decoding: PhaseStats,
+    /// Encoding with `ToBencode` or serde
+    pub encoding: PhaseStats,
+}
+
+/// The counters of decoding or encoding
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PhaseStats {
+    /// The number of calls
+    pub calls: u64,
+    /// The number of bytes decoded or encoded
+    pub bytes: u64,
+    /// The total time of the calls
+    pub time: Duration,
+    /// The number of allocations made by the calls, if [`CountingAllocator`]
+    /// is installed
+    pub allocations: u64,
+    /// The number of bytes allocated by the calls, if [`CountingAllocator`]
+    /// is installed
+    pub allocated_bytes: u64,
+}
+
+/// The counters accumulated since the start of the program or the last
+/// [`reset_stats`]
+pub fn stats() -> Stats {
+    Stats {
+        decoding: DECODING.snapshot(),
+        encoding: ENCODING.snapshot(),
+    }
+}
+
+/// Set all counters to zero
+pub fn reset_stats() {
+    DECODING.reset();
+    ENCODING.reset();
+}
+
+/// A global allocator which counts the allocations made while decoding or
+/// encoding, and otherwise forwards to another allocator
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A = System>(pub A);
+
+impl CountingAllocator<System> {
+    /// Count the allocations made with the system allocator
+    pub const fn system() -> Self {
+        CountingAllocator(System)
+    }
+}
+
+fn count_allocation(size: usize) {
+    // `try_with` fails while the thread is being torn down
+    if let Ok(Some(phase)) = ACTIVE.try_with(Cell::get) {
+        let counters = Counters::of(phase);
+        counters.allocations.fetch_add(1, Ordering::Relaxed);
+        counters
+            .allocated_bytes
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation(layout.size());
+        self.0.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation(layout.size());
+        self.0.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation(new_size);
+        self.0.realloc(ptr, layout, new_size)
+    }
+}
@@ -13,11 +13,50 @@ extern crate alloc;
 #[macro_use]
 mod assert_matches;
 
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+pub mod arbitrary;
 pub mod decoding;
 pub mod encoding;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+#[cfg(feature = "instrument")]
+pub mod instrument;
+#[cfg(not(feature = "instrument"))]
+mod instrument {
+    //! Without the `instrument` feature, measured calls are only run.
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub(crate) enum Phase {
+        Decoding,
+        Encoding,
+    }
+
+    #[inline]
+    pub(crate) fn measure<T>(phase: Phase, call: impl FnOnce() -> (T, usize)) -> T {
+        crate::trace::traced(phase, call).0
+    }
+}
 pub mod state_tracker;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(not(feature = "tracing"))]
+mod trace {
+    //! Without the `tracing` feature, traced calls are only run.
+
+    use crate::instrument::Phase;
+
+    #[inline]
+    pub(crate) fn traced<T>(_phase: Phase, call: impl FnOnce() -> (T, usize)) -> (T, usize) {
+        call()
+    }
+}
 
 #[cfg(feature = "serde")]
 pub mod serde;
 
 pub mod value;
+
+#[cfg(feature = "bittorrent")]
+pub mod torrent;
@@ -71,23 +71,23 @@
 /// map.insert("bar", 2);
 /// repr(map, "d3:bari2e3:fooi1ee");
 ///
-/// #[serde(crate = "serde_")]
 /// #[derive(Serialize)]
+/// #[serde(crate = "serde_")]
 /// struct Unit;
 /// repr(Unit, "le");
 ///
-/// #[serde(crate = "serde_")]
 /// #[derive(Serialize)]
+/// #[serde(crate = "serde_")]
 /// struct Newtype(String);
 /// repr(Newtype("foo".into()), "3:foo");
 ///
-/// #[serde(crate = "serde_")]
 /// #[derive(Serialize)]
+/// #[serde(crate = "serde_")]
 /// struct Tuple(bool, i32);
 /// repr(Tuple(false, 100), "li0ei100ee");
 ///
-/// #[serde(crate = "serde_")]
 /// #[derive(Serialize)]
+/// #[serde(crate = "serde_")]
 /// struct Record {
 ///     a: String,
 ///     b: bool,
@@ -101,8 +101,8 @@
 ///     "d1:a5:hello1:bi0ee",
 /// );
 ///
-/// #[serde(crate = "serde_")]
 /// #[derive(Serialize)]
+/// #[serde(crate = "serde_")]
 /// enum Enum {
 ///     Unit,
 ///     Newtype(i32),
@@ -115,9 +115,9 @@
 /// repr(Enum::Tuple(true, 10), "d5:Tupleli1ei10eee");
 /// repr(Enum::Struct { a: 'x', b: true }, "d6:Structd1:a1:x1:bi1eee");
 ///
-/// #[serde(untagged)]
-/// #[serde(crate = "serde_")]
 /// #[derive(Serialize)]
+/// #[serde(crate = "serde_")]
+/// #[serde(untagged)]
 /// enum Untagged {
 ///     Foo { x: i32 },
 ///     Bar { y: char },
@@ -134,7 +134,7 @@ pub mod ser;
 
 pub use de::{from_bytes, Deserializer};
 pub use error::{Error, Result};
-pub use ser::{to_bytes, Serializer};
+pub use ser::{to_bytes, to_bytes_into, Serializer};
 
 #[cfg(test)]
 mod tests {
@@ -144,7 +144,7 @@ mod tests {
 
     use super::{
         de::{from_bytes, Deserializer},
-        ser::to_bytes,
+        ser::{to_bytes, to_bytes_into},
     };
 
     use serde::{de::DeserializeOwned, ser::Serialize};
@@ -172,6 +172,10 @@ mod tests {
             Err(err) => panic!("Failed to serialize `{:?}`: {}", value, err),
         };
 
+        let mut buffer = b"stale".to_vec();
+        to_bytes_into(&value, &mut buffer).unwrap();
+        assert_eq!(buffer, encoded);
+
         let deserialized = match from_bytes::<V>(&encoded) {
             Ok(deserialized) => deserialized,
             Err(error) => panic!(
@@ -346,6 +350,13 @@ mod tests {
         map.insert(1, 1);
         map.insert(2, 2);
         assert_matches!(to_bytes(&map), Err(Error::ArbitraryMapKeysUnsupported));
+
+        let mut buffer = b"stale".to_vec();
+        assert_matches!(
+            to_bytes_into(&map, &mut buffer),
+            Err(Error::ArbitraryMapKeysUnsupported)
+        );
+        assert!(buffer.is_empty());
     }
 
     #[test]
@@ -431,9 +442,9 @@ mod tests {
 
     #[test]
     fn untagged_enum() {
-        #[serde(untagged)]
         #[derive(Serialize, Deserialize, Debug, PartialEq)]
         #[serde(crate = "serde_")]
+        #[serde(untagged)]
         enum Untagged {
             Foo { x: i32 },
             Bar { y: String },
@@ -504,8 +515,7 @@ mod tests {
 
     #[test]
     fn borrowed_value() {
-        use std::borrow::Cow;
-        use crate::value::Value;
+        use crate::value::{ByteString, Value};
 
         #[derive(Debug, Deserialize, PartialEq, Eq)]
         #[serde(crate = "serde_")]
@@ -515,8 +525,12 @@ mod tests {
         }
 
         assert_eq!(
-            Deserializer::from_bytes(b"d1:v3:\x01\x02\x03e").deserialize::<Dict<'_>>().unwrap(),
-            Dict { v: Value::Bytes(Cow::Owned(vec![1, 2, 3]))},
+            Deserializer::from_bytes(b"d1:v3:\x01\x02\x03e")
+                .deserialize::<Dict<'_>>()
+                .unwrap(),
+            Dict {
+                v: Value::Bytes(ByteString::from(vec![1, 2, 3]))
+            },
         );
     }
 }
@@ -27,4 +27,5 @@ pub(crate) use crate::{
     encoding::{self, Encoder, UnsortedDictEncoder},
     serde::{ser::Serializer, Error, Result},
     state_tracker::{StructureError, Token},
+    value::RAW_VALUE_TOKEN,
 };
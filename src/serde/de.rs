@@ -1,13 +1,18 @@
 //! Serde bencode deserialization.
 
-use crate::serde::common::*;
+use crate::{
+    instrument::{self, Phase},
+    serde::common::*,
+};
 
 /// Deserialize an instance of `T` from bencode
 pub fn from_bytes<'a, T>(s: &'a [u8]) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    Deserializer::from_bytes(s).deserialize()
+    instrument::measure(Phase::Decoding, || {
+        (Deserializer::from_bytes(s).deserialize(), s.len())
+    })
 }
 
 /// Bencode deserializer
@@ -97,6 +102,29 @@ impl<'de> Deserializer<'de> {
         }
     }
 
+    /// Consume the tokens of the next complete value and return its encoding
+    fn next_raw(&mut self) -> Result<Vec<u8>> {
+        let mut encoder = Encoder::new().with_max_depth(usize::MAX);
+        let mut depth = 0usize;
+
+        loop {
+            let token = self.next_token()?;
+            match token {
+                Token::List | Token::Dict => depth += 1,
+                Token::End if depth == 0 => {
+                    return Err(decoding::Error::unexpected_token("any value", "End").into())
+                },
+                Token::End => depth -= 1,
+                Token::String(_) | Token::Num(_) => {},
+            }
+            encoder.emit_token(token)?;
+
+            if depth == 0 {
+                return Ok(encoder.get_output()?);
+            }
+        }
+    }
+
     fn expect_empty_list(&mut self) -> Result<()> {
         self.expect_list_begin()?;
         self.expect_end()?;
@@ -311,10 +339,14 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == RAW_VALUE_TOKEN {
+            return visitor.visit_byte_buf(self.next_raw()?);
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
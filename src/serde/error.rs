@@ -105,4 +105,13 @@ impl Display for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        // Encoding and decoding errors are displayed as they are
+        match self {
+            Error::Encode(error) => error.source(),
+            Error::Decode(error) => error.source(),
+            _ => None,
+        }
+    }
+}
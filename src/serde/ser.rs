@@ -1,6 +1,9 @@
 //! Serde bencode serialization.
 
-use crate::serde::common::*;
+use crate::{
+    instrument::{self, Phase},
+    serde::common::*,
+};
 
 pub use map_serializer::MapSerializer;
 pub use struct_serializer::StructSerializer;
@@ -13,14 +16,38 @@ pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>>
 where
     T: ?Sized + Serialize,
 {
-    let mut serializer = Serializer::new();
-    value.serialize(&mut serializer)?;
-    serializer.into_bytes()
+    instrument::measure(Phase::Encoding, || {
+        let mut serializer = Serializer::new();
+        let result = value
+            .serialize(&mut serializer)
+            .and_then(|()| serializer.into_bytes());
+        let len = result.as_ref().map_or(0, Vec::len);
+        (result, len)
+    })
+}
+
+/// Serialize an instance of `T` to bencode in `buffer`, replacing its contents
+///
+/// The allocation of `buffer` is reused, so serializing many values with the
+/// same buffer doesn't allocate once it's large enough. If serialization
+/// fails, `buffer` is left empty.
+pub fn to_bytes_into<T>(value: &T, buffer: &mut Vec<u8>) -> Result<()>
+where
+    T: ?Sized + Serialize,
+{
+    instrument::measure(Phase::Encoding, || {
+        let mut serializer = Serializer {
+            encoder: Encoder::new().with_buffer(core::mem::take(buffer)),
+        };
+        let result = value.serialize(&mut serializer);
+        let result = serializer.encoder.finish_into(result, buffer, 0);
+        (result, buffer.len())
+    })
 }
 
 /// A serde Bencode serializer
 pub struct Serializer {
-    encoder: Encoder,
+    encoder: Encoder<'static>,
 }
 
 impl Serializer {
@@ -171,10 +198,25 @@ impl<'a> serde::ser::Serializer for &'a mut Serializer {
         self.emit_empty_list()
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        if name == RAW_VALUE_TOKEN {
+            // A `RawValue` serializes its encoded bytes as a byte string, which is
+            // unwrapped here and written out verbatim
+            let mut serializer = Serializer::new();
+            value.serialize(&mut serializer)?;
+            let encoded = serializer.into_bytes()?;
+            let raw = Decoder::new(&encoded)
+                .next_object()?
+                .ok_or(StructureError::UnexpectedEof)
+                .map_err(decoding::Error::from)?
+                .try_into_bytes()?;
+            self.encoder.emit_raw(raw)?;
+            return Ok(());
+        }
+
         value.serialize(self)
     }
 
@@ -2,14 +2,14 @@ use crate::serde::common::*;
 
 /// Bencode sub-serializer for maps.
 pub struct MapSerializer<'outer> {
-    pub(crate) outer: &'outer mut Encoder,
+    pub(crate) outer: &'outer mut Encoder<'static>,
     encoder: UnsortedDictEncoder,
     key: Option<Vec<u8>>,
 }
 
 impl<'outer> MapSerializer<'outer> {
     pub(crate) fn new(
-        outer: &'outer mut Encoder,
+        outer: &'outer mut Encoder<'static>,
         encoder: UnsortedDictEncoder,
     ) -> MapSerializer<'outer> {
         MapSerializer {
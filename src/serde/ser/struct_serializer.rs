@@ -2,13 +2,13 @@ use crate::serde::common::*;
 
 /// Bencode sub-serializer for structs.
 pub struct StructSerializer<'outer> {
-    pub(crate) outer: &'outer mut Encoder,
+    pub(crate) outer: &'outer mut Encoder<'static>,
     encoder: UnsortedDictEncoder,
 }
 
 impl<'outer> StructSerializer<'outer> {
     pub(crate) fn new(
-        outer: &'outer mut Encoder,
+        outer: &'outer mut Encoder<'static>,
         encoder: UnsortedDictEncoder,
     ) -> StructSerializer<'outer> {
         StructSerializer { encoder, outer }
@@ -1,7 +1,10 @@
+mod key;
 mod stack;
 mod state;
 mod structure_error;
 mod token;
 
 pub use self::token::Token;
-pub(crate) use self::{stack::Stack, state::StateTracker, structure_error::StructureError};
+pub(crate) use self::{
+    key::Key, stack::Stack, state::StateTracker, structure_error::StructureError,
+};
@@ -0,0 +1,51 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The number of bytes of a key which are stored inline by [`Key`]
+const INLINE_KEY_LEN: usize = 23;
+
+/// An owned copy of a dict key, kept inline if it's short, as most keys are,
+/// to avoid allocating for every key that's encoded
+#[derive(Clone, Debug)]
+pub enum Key {
+    Inline(u8, [u8; INLINE_KEY_LEN]),
+    Heap(Vec<u8>),
+}
+
+impl<'a> From<&'a [u8]> for Key {
+    fn from(key: &'a [u8]) -> Self {
+        if key.len() <= INLINE_KEY_LEN {
+            let mut bytes = [0; INLINE_KEY_LEN];
+            bytes[..key.len()].copy_from_slice(key);
+            Key::Inline(key.len() as u8, bytes)
+        } else {
+            Key::Heap(key.to_vec())
+        }
+    }
+}
+
+impl AsRef<[u8]> for Key {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Key::Inline(len, bytes) => &bytes[..usize::from(*len)],
+            Key::Heap(bytes) => bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_keys_are_kept_on_the_heap() {
+        let short = Key::from(&b"announce"[..]);
+        assert!(matches!(short, Key::Inline(8, _)));
+        assert_eq!(short.as_ref(), b"announce");
+
+        let long = [b'a'; INLINE_KEY_LEN + 1];
+        let key = Key::from(&long[..]);
+        assert!(matches!(key, Key::Heap(_)));
+        assert_eq!(key.as_ref(), &long[..]);
+    }
+}
@@ -1,36 +1,94 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
-pub trait Stack<T> {
-    fn peek_mut(&mut self) -> Option<&mut T>;
+/// The number of levels kept inline by [`Stack`], enough for the nesting of
+/// typical documents such as torrents
+const INLINE_DEPTH: usize = 16;
 
-    fn peek(&self) -> Option<&T>;
+/// A stack which keeps its first [`INLINE_DEPTH`] items inline, so that it
+/// only allocates for deeply nested documents
+#[derive(Debug)]
+pub struct Stack<T> {
+    inline: [Option<T>; INLINE_DEPTH],
+    /// The items above the inline ones
+    spilled: Vec<T>,
+    len: usize,
+}
 
-    fn replace_top(&mut self, new_value: T);
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Stack {
+            inline: Default::default(),
+            spilled: Vec::new(),
+            len: 0,
+        }
+    }
 }
 
-impl<T> Stack<T> for Vec<T> {
-    fn peek_mut(&mut self) -> Option<&mut T> {
-        let len = self.len();
-        if len == 0 {
-            None
+impl<T> Stack<T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.len < INLINE_DEPTH {
+            self.inline[self.len] = Some(value);
         } else {
-            Some(&mut self[len - 1])
+            self.spilled.push(value);
+        }
+        self.len += 1;
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        match self.len {
+            0 => None,
+            len if len <= INLINE_DEPTH => self.inline[len - 1].as_ref(),
+            _ => self.spilled.last(),
         }
     }
 
-    fn peek(&self) -> Option<&T> {
-        let len = self.len();
-        if len == 0 {
-            None
+    /// Remove all items, keeping the allocation of the spilled ones
+    #[cfg(feature = "std")]
+    pub fn clear(&mut self) {
+        for item in &mut self.inline[..self.len.min(INLINE_DEPTH)] {
+            *item = None;
+        }
+        self.spilled.clear();
+        self.len = 0;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        if self.len < INLINE_DEPTH {
+            self.inline[self.len].take()
         } else {
-            Some(&self[len - 1])
+            self.spilled.pop()
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn replace_top(&mut self, new_value: T) {
-        self.peek_mut()
-            .map(|top| *top = new_value)
-            .expect("Shouldn't replace_top with nothing on the stack");
+    #[test]
+    fn deep_stacks_spill() {
+        let mut stack = Stack::default();
+        for i in 0..INLINE_DEPTH * 2 {
+            stack.push(i);
+        }
+        assert_eq!(stack.len(), INLINE_DEPTH * 2);
+        for i in (0..INLINE_DEPTH * 2).rev() {
+            assert_eq!(stack.pop(), Some(i));
+        }
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop(), None);
     }
 }
@@ -1,33 +1,49 @@
-#[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{format, string::ToString};
 
-use crate::state_tracker::{Stack, StructureError, Token};
+use crate::{
+    decoding::{EmptyPolicy, Warnings},
+    state_tracker::{Stack, StructureError, Token},
+};
 
 /// The state of current level of the decoder
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
-enum State<S: AsRef<[u8]>, E> {
+enum State<S: AsRef<[u8]>> {
     /// An inner list. Allows any token
     Seq,
     /// Inside a map, expecting a key. Contains the last key read, so sorting can be validated
     MapKey(Option<S>),
     /// Inside a map, expecting a value. Contains the last key read, so sorting can be validated
     MapValue(S),
-    /// Received an error while decoding
-    Failed(E),
 }
 
 /// Used to validate that a structure is valid
 #[derive(Debug)]
 pub struct StateTracker<S: AsRef<[u8]>, E = StructureError> {
-    state: Vec<State<S, E>>,
+    state: Stack<State<S>>,
+    /// The first error received, which is returned for every later token
+    error: Option<E>,
     max_depth: usize,
+    require_sorted_keys: bool,
+    /// Whether a key was accepted that wasn't greater than the one before it
+    saw_unsorted_keys: bool,
+    max_key_len: usize,
+    empty_keys: EmptyPolicy,
+    empty_strings: EmptyPolicy,
+    warnings: Warnings,
 }
 
 impl<S: AsRef<[u8]>, E> Default for StateTracker<S, E> {
     fn default() -> Self {
         StateTracker {
-            state: Vec::new(),
+            state: Stack::default(),
+            error: None,
             max_depth: 2048,
+            require_sorted_keys: true,
+            saw_unsorted_keys: false,
+            max_key_len: usize::MAX,
+            empty_keys: EmptyPolicy::Accept,
+            empty_strings: EmptyPolicy::Accept,
+            warnings: Warnings::default(),
         }
     }
 }
@@ -45,10 +61,97 @@ where
         self.max_depth = new_max_depth
     }
 
+    pub fn set_require_sorted_keys(&mut self, require_sorted_keys: bool) {
+        self.require_sorted_keys = require_sorted_keys
+    }
+
+    pub fn set_max_key_len(&mut self, max_key_len: usize) {
+        self.max_key_len = max_key_len
+    }
+
+    #[cfg(feature = "std")]
+    pub fn max_key_len(&self) -> usize {
+        self.max_key_len
+    }
+
+    pub fn set_empty_keys(&mut self, policy: EmptyPolicy) {
+        self.empty_keys = policy
+    }
+
+    pub fn set_empty_strings(&mut self, policy: EmptyPolicy) {
+        self.empty_strings = policy
+    }
+
+    /// The inputs accepted with a warning so far
+    pub fn warnings(&self) -> Warnings {
+        self.warnings
+    }
+
+    /// Apply the policy for an empty byte string, which is a key if one is
+    /// expected
+    fn observe_empty(&mut self) -> Result<(), E> {
+        let is_key = matches!(self.state.peek(), Some(State::MapKey(_)));
+        let policy = if is_key {
+            self.empty_keys
+        } else {
+            self.empty_strings
+        };
+
+        match (policy, is_key) {
+            (EmptyPolicy::Accept, _) => Ok(()),
+            (EmptyPolicy::Warn, true) => {
+                self.warnings.empty_keys += 1;
+                #[cfg(feature = "tracing")]
+                tracing::warn!(depth = self.state.len(), "accepted an empty key");
+                Ok(())
+            },
+            (EmptyPolicy::Warn, false) => {
+                self.warnings.empty_strings += 1;
+                #[cfg(feature = "tracing")]
+                tracing::warn!(depth = self.state.len(), "accepted an empty byte string");
+                Ok(())
+            },
+            (EmptyPolicy::Reject, true) => self.latch_err(Err(E::from(
+                StructureError::InvalidKey("empty keys aren't allowed".to_string()),
+            ))),
+            (EmptyPolicy::Reject, false) => {
+                self.latch_err(Err(E::from(StructureError::EmptyString)))
+            },
+        }
+    }
+
+    /// Whether an unsorted or duplicate key was accepted, since sorting isn't
+    /// required
+    pub fn saw_unsorted_keys(&self) -> bool {
+        self.saw_unsorted_keys
+    }
+
     pub fn remaining_depth(&self) -> usize {
         self.max_depth - self.state.len()
     }
 
+    /// The number of lists and dicts the next token is nested in
+    pub fn depth(&self) -> usize {
+        self.state.len()
+    }
+
+    /// Whether the next token is a dict key
+    #[cfg(feature = "std")]
+    pub fn expects_key(&self) -> bool {
+        matches!(self.state.peek(), Some(State::MapKey(_)))
+    }
+
+    /// Take over the stack of `other` to reuse its allocation, starting from an
+    /// empty stack and no error but keeping the settings of `self`
+    #[cfg(feature = "std")]
+    pub fn recycle(&mut self, other: Self) {
+        self.state = other.state;
+        self.state.clear();
+        self.error = None;
+        self.saw_unsorted_keys = false;
+        self.warnings = Warnings::default();
+    }
+
     /// Observe that an EOF was seen. This function is idempotent.
     pub fn observe_eof(&mut self) -> Result<(), E> {
         self.check_error()?;
@@ -60,8 +163,30 @@ where
         }
     }
 
-    #[allow(clippy::match_same_arms)]
     pub fn observe_token<'a>(&mut self, token: &Token<'a>) -> Result<(), E>
+    where
+        S: From<&'a [u8]>,
+    {
+        if let Token::String(b"") = *token {
+            self.observe_empty()?;
+        }
+        self.observe_structure(token)
+    }
+
+    /// Observe a byte string whose contents are read later, such as a
+    /// [`StreamToken::LargeBytes`](crate::decoding::StreamToken::LargeBytes).
+    /// It is never a key and never empty, so the empty string policy doesn't
+    /// apply to it.
+    #[cfg(feature = "std")]
+    pub fn observe_large_string<'a>(&mut self) -> Result<(), E>
+    where
+        S: From<&'a [u8]>,
+    {
+        self.observe_structure(&Token::String(&[]))
+    }
+
+    #[allow(clippy::match_same_arms)]
+    fn observe_structure<'a>(&mut self, token: &Token<'a>) -> Result<(), E>
     where
         S: From<&'a [u8]>,
     {
@@ -75,12 +200,25 @@ where
             },
             (Some(Seq), End) => {},
             (Some(MapKey(_)), End) => {},
+            (Some(oldstate @ MapKey(_)), String(label)) if label.len() > self.max_key_len => {
+                self.state.push(oldstate);
+                return self.latch_err(Err(E::from(StructureError::InvalidKey(format!(
+                    "{} bytes long, more than the limit of {}",
+                    label.len(),
+                    self.max_key_len
+                )))));
+            },
             (Some(MapKey(None)), String(label)) => {
                 self.state.push(MapValue(S::from(label)));
             },
             (Some(MapKey(Some(oldlabel))), String(label)) => {
                 if oldlabel.as_ref() >= label {
-                    return self.latch_err(Err(E::from(StructureError::UnsortedKeys)));
+                    if self.require_sorted_keys {
+                        return self.latch_err(Err(E::from(StructureError::UnsortedKeys)));
+                    }
+                    self.saw_unsorted_keys = true;
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(depth = self.state.len() + 1, "accepted an unsorted key");
                 }
                 self.state.push(MapValue(S::from(label)));
             },
@@ -144,13 +282,18 @@ where
     pub fn latch_err<T>(&mut self, result: Result<T, E>) -> Result<T, E> {
         self.check_error()?;
         if let Err(ref err) = result {
-            self.state.push(State::Failed(err.clone()))
+            self.error = Some(err.clone());
         }
         result
     }
 
+    /// Replace the error latched by the last call, such as to add details
+    pub fn replace_err(&mut self, error: E) {
+        self.error = Some(error);
+    }
+
     pub fn check_error(&self) -> Result<(), E> {
-        if let Some(&State::Failed(ref error)) = self.state.peek() {
+        if let Some(ref error) = self.error {
             Err(error.clone())
         } else {
             Ok(())
@@ -3,33 +3,52 @@ use alloc::{
     format,
     string::{String, ToString},
 };
-#[cfg(not(feature = "std"))]
-use core::fmt::Display;
-#[cfg(feature = "std")]
-use std::fmt::Display;
-
-use failure::Fail;
+use core::fmt::{self, Display, Formatter};
 
 /// An encoding or decoding error
-#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Fail)]
+///
+/// More kinds may be added without a major release, so matches on this enum
+/// need a wildcard arm.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+#[non_exhaustive]
 pub enum StructureError {
-    #[fail(display = "Saw the wrong type of token: {}", _0)]
     /// Wrong type of token detected.
     InvalidState(String),
-    #[fail(display = "Keys were not sorted")]
     /// Keys were not sorted.
     UnsortedKeys,
-    #[fail(display = "Reached EOF in the middle of a message")]
     /// EOF reached to early.
     UnexpectedEof,
-    #[fail(display = "Malformed number of unexpected character: {}", _0)]
     /// Unexpected characters detected.
     SyntaxError(String),
-    #[fail(display = "Maximum nesting depth exceeded")]
     /// Exceeded the recursion limit.
     NestingTooDeep,
+    /// A dict key was rejected by the key limits of the decoder.
+    InvalidKey(String),
+    /// An empty byte string was rejected by the policy of the decoder.
+    EmptyString,
+}
+
+impl Display for StructureError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            StructureError::InvalidState(state) => {
+                write!(f, "Saw the wrong type of token: {}", state)
+            },
+            StructureError::UnsortedKeys => write!(f, "Keys were not sorted"),
+            StructureError::UnexpectedEof => write!(f, "Reached EOF in the middle of a message"),
+            StructureError::SyntaxError(message) => {
+                write!(f, "Malformed number of unexpected character: {}", message)
+            },
+            StructureError::NestingTooDeep => write!(f, "Maximum nesting depth exceeded"),
+            StructureError::InvalidKey(problem) => write!(f, "Invalid dict key: {}", problem),
+            StructureError::EmptyString => write!(f, "Empty byte strings aren't allowed"),
+        }
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for StructureError {}
+
 impl StructureError {
     pub fn unexpected(expected: impl Display, got: char, offset: usize) -> Self {
         StructureError::SyntaxError(format!(
@@ -38,6 +57,19 @@ impl StructureError {
         ))
     }
 
+    /// The stable code of this error, which starts with `E01`
+    pub fn code(&self) -> &'static str {
+        match self {
+            StructureError::UnsortedKeys => "E0101",
+            StructureError::UnexpectedEof => "E0102",
+            StructureError::SyntaxError(_) => "E0103",
+            StructureError::InvalidState(_) => "E0104",
+            StructureError::NestingTooDeep => "E0105",
+            StructureError::InvalidKey(_) => "E0107",
+            StructureError::EmptyString => "E0108",
+        }
+    }
+
     pub fn invalid_state(expected: impl Display) -> Self {
         StructureError::InvalidState(expected.to_string())
     }
@@ -0,0 +1,57 @@
+//! Helpers for testing types and backends against the bencode specification.
+//!
+//! Every type implementing both [`ToBencode`](crate::encoding::ToBencode) and
+//! [`FromBencode`](crate::decoding::FromBencode) should decode its own
+//! encoding back into an equal value, and encode it into canonical bencode.
+//! [`assert_roundtrip`] checks both, so conformance tests for a type take one
+//! line per value:
+//!
+//! ```
+//! use bendy::testing::{assert_canonical, assert_roundtrip};
+//!
+//! assert_eq!(assert_roundtrip(&vec![1, 2, 3]), b"li1ei2ei3ee");
+//! assert_roundtrip(&String::from("spam"));
+//!
+//! assert_canonical(b"d3:cow3:moo4:spam4:eggse");
+//! ```
+//!
+//! Decoders and encoders which don't use this crate's traits, such as
+//! alternative backends or wrappers around other libraries, can be checked
+//! against a fixed set of [test vectors](vectors) instead: inputs every
+//! conforming decoder must accept and re-encode unchanged, and inputs it must
+//! reject.
+//!
+//! ```
+//! use bendy::{
+//!     decoding::{Decoder, Error, FromBencode},
+//!     encoding::ToBencode,
+//!     testing::check_codec,
+//!     value::Value,
+//! };
+//!
+//! // A decoder which, unlike `Value::from_bencode`, rejects trailing data
+//! fn decode(input: &'static [u8]) -> Result<Value<'static>, Error> {
+//!     let mut decoder = Decoder::new(input);
+//!     let object = decoder
+//!         .next_object()?
+//!         .ok_or_else(|| Error::unexpected_token("a value", "the end"))?;
+//!     let value = Value::decode_bencode_object(object)?;
+//!     match decoder.remaining() {
+//!         b"" => Ok(value),
+//!         _ => Err(Error::trailing_data()),
+//!     }
+//! }
+//!
+//! check_codec(decode, |value: &Value| value.to_bencode()).assert_conforming();
+//! ```
+
+mod roundtrip;
+mod vectors;
+
+pub use self::{
+    roundtrip::{assert_canonical, assert_roundtrip},
+    vectors::{
+        check_codec, check_decoder, vectors, Category, Expected, Failure, Problem, Report,
+        TestVector,
+    },
+};
@@ -0,0 +1,161 @@
+//! Assertions that types round-trip through their encoding.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::{Debug, Display};
+
+use crate::{
+    decoding::{self, FromBencode, Validity},
+    encoding::ToBencode,
+    value::Value,
+};
+
+/// Encode `value`, and panic unless the encoding is canonical, decodes into
+/// an equal value, and encodes again into the same bytes.
+///
+/// Returns the encoding, so that tests can also compare it with the
+/// expected bytes.
+#[track_caller]
+pub fn assert_roundtrip<T>(value: &T) -> Vec<u8>
+where
+    T: ToBencode + FromBencode + PartialEq + Debug,
+{
+    let encoded = expect(value.to_bencode(), || {
+        format!("{:?} can't be encoded", value)
+    });
+    check_canonical(&encoded, T::EXPECTED_RECURSION_DEPTH);
+
+    let decoded = expect(T::from_bencode(&encoded), || {
+        format!("encoding of {:?} can't be decoded", value)
+    });
+    assert_eq!(
+        &decoded,
+        value,
+        "decoding {} changed the value",
+        String::from_utf8_lossy(&encoded)
+    );
+
+    let reencoded = expect(decoded.to_bencode(), || {
+        format!("decoded {:?} can't be encoded", decoded)
+    });
+    assert_eq!(
+        String::from_utf8_lossy(&reencoded),
+        String::from_utf8_lossy(&encoded),
+        "encoding {:?} again changed the bytes",
+        decoded
+    );
+
+    encoded
+}
+
+/// Panic unless `bytes` is a single bencoded value in canonical form: no
+/// trailing data, and every dict with its keys unique and sorted.
+///
+/// If the value fits a [`Value`], this also checks that it encodes back into
+/// `bytes`.
+#[track_caller]
+pub fn assert_canonical(bytes: &[u8]) {
+    let max_depth = <Value as FromBencode>::EXPECTED_RECURSION_DEPTH;
+    check_canonical(bytes, max_depth);
+
+    // Integers beyond `i64` are canonical, but not `Value`s
+    if let Ok(value) = Value::from_bencode(bytes) {
+        let encoded = expect(value.to_bencode(), || {
+            format!("{:?} can't be encoded", value)
+        });
+        assert_eq!(
+            String::from_utf8_lossy(&encoded),
+            String::from_utf8_lossy(bytes),
+            "encoding the decoded {:?} changed the bytes",
+            value
+        );
+    }
+}
+
+#[track_caller]
+fn check_canonical(bytes: &[u8], max_depth: usize) {
+    let validity = expect(decoding::validate(bytes, max_depth), || {
+        format!("{} isn't bencode", String::from_utf8_lossy(bytes))
+    });
+    assert_eq!(
+        validity,
+        Validity::Canonical,
+        "{} isn't canonical",
+        String::from_utf8_lossy(bytes)
+    );
+}
+
+#[track_caller]
+fn expect<T, E: Display>(result: Result<T, E>, message: impl FnOnce() -> String) -> T {
+    match result {
+        Ok(value) => value,
+        Err(error) => panic!("{}: {}", message(), error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    use super::*;
+
+    use crate::{
+        decoding::{Error as DecodingError, Object},
+        encoding::{Error as EncodingError, SingleItemEncoder},
+    };
+
+    /// A type which drops its second field when decoding
+    #[derive(Debug, PartialEq)]
+    struct Lossy(i64, i64);
+
+    impl ToBencode for Lossy {
+        const MAX_DEPTH: usize = 1;
+
+        fn encode(&self, encoder: SingleItemEncoder) -> Result<(), EncodingError> {
+            encoder.emit_list(|e| {
+                e.emit_int(self.0)?;
+                e.emit_int(self.1)
+            })
+        }
+    }
+
+    impl FromBencode for Lossy {
+        fn decode_bencode_object(object: Object) -> Result<Self, DecodingError> {
+            let mut list = object.try_into_list()?;
+            let first = list.next_object()?.unwrap().try_into_integer()?;
+            while list.next_object()?.is_some() {}
+            Ok(Lossy(first.parse()?, 0))
+        }
+    }
+
+    #[test]
+    fn roundtrips_return_the_encoding() {
+        assert_eq!(assert_roundtrip(&42_i64), b"i42e");
+        assert_eq!(assert_roundtrip(&Lossy(1, 0)), b"li1ei0ee");
+        assert_roundtrip(&Value::List(vec![Value::Bytes(b"spam".to_vec().into())]));
+    }
+
+    #[test]
+    #[should_panic(expected = "decoding li1ei2ee changed the value")]
+    fn lossy_types_fail_roundtrips() {
+        assert_roundtrip(&Lossy(1, 2));
+    }
+
+    #[test]
+    fn canonical_documents_pass() {
+        assert_canonical(b"d1:ai1e1:bli2ei3eee");
+        assert_canonical(b"i99999999999999999999e");
+    }
+
+    #[test]
+    #[should_panic(expected = "d1:bi1e1:ai2ee isn't canonical")]
+    fn unsorted_keys_fail() {
+        assert_canonical(b"d1:bi1e1:ai2ee");
+    }
+
+    #[test]
+    #[should_panic(expected = "i-0e isn't bencode")]
+    fn invalid_documents_fail() {
+        assert_canonical(b"i-0e");
+    }
+}
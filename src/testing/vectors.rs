@@ -0,0 +1,473 @@
+//! Test vectors for decoders and encoders of any backend.
+
+use alloc::{string::String, string::ToString, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+
+/// What the specification requires of a decoder for a [`TestVector`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Expected {
+    /// The input is canonical bencode, which must be decoded, and encoding
+    /// the decoded value must return the input
+    Valid,
+    /// The input isn't canonical bencode, and must be rejected
+    Invalid,
+}
+
+/// The part of the specification a [`TestVector`] checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Category {
+    /// Integers, including the limits of `i64`, leading zeros and signs
+    Integer,
+    /// Byte strings, including empty and binary strings and bad lengths
+    ByteString,
+    /// Lists and their terminators
+    List,
+    /// Dicts, their terminators and the types of their keys
+    Dict,
+    /// Dict keys which are out of order or appear twice
+    KeyOrder,
+    /// Deeply nested lists and dicts
+    Nesting,
+    /// Empty inputs, and data before or after the value
+    Framing,
+}
+
+/// An input, and whether a decoder must accept or reject it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TestVector {
+    name: &'static str,
+    category: Category,
+    expected: Expected,
+    input: &'static [u8],
+}
+
+const fn valid(name: &'static str, category: Category, input: &'static [u8]) -> TestVector {
+    TestVector {
+        name,
+        category,
+        expected: Expected::Valid,
+        input,
+    }
+}
+
+const fn invalid(name: &'static str, category: Category, input: &'static [u8]) -> TestVector {
+    TestVector {
+        name,
+        category,
+        expected: Expected::Invalid,
+        input,
+    }
+}
+
+/// Integers are kept within `i64`: bencode doesn't limit their size, but
+/// most decoders do. Nothing is nested more than 32 deep.
+static VECTORS: &[TestVector] = {
+    use self::Category::*;
+
+    &[
+        valid("zero", Integer, b"i0e"),
+        valid("positive integer", Integer, b"i42e"),
+        valid("negative integer", Integer, b"i-42e"),
+        valid("largest i64", Integer, b"i9223372036854775807e"),
+        valid("smallest i64", Integer, b"i-9223372036854775808e"),
+        invalid("empty integer", Integer, b"ie"),
+        invalid("lone minus sign", Integer, b"i-e"),
+        invalid("negative zero", Integer, b"i-0e"),
+        invalid("leading zero", Integer, b"i03e"),
+        invalid("negative leading zero", Integer, b"i-03e"),
+        invalid("double zero", Integer, b"i00e"),
+        invalid("plus sign", Integer, b"i+1e"),
+        invalid("decimal point", Integer, b"i1.5e"),
+        invalid("exponent", Integer, b"i1e3e"),
+        invalid("whitespace in integer", Integer, b"i 1e"),
+        invalid("letters in integer", Integer, b"iae"),
+        invalid("unterminated integer", Integer, b"i42"),
+        valid("empty string", ByteString, b"0:"),
+        valid("string", ByteString, b"4:spam"),
+        valid("string with spaces", ByteString, b"11:hello world"),
+        valid("binary string", ByteString, b"4:\x00\xff\x80e"),
+        valid("string of digits", ByteString, b"3:i1e"),
+        invalid("string shorter than its length", ByteString, b"5:spam"),
+        invalid("length without bytes", ByteString, b"1:"),
+        invalid("negative length", ByteString, b"-1:a"),
+        invalid("length with leading zero", ByteString, b"04:spam"),
+        invalid("length with plus sign", ByteString, b"+4:spam"),
+        invalid("missing colon", ByteString, b"4spam"),
+        invalid("missing length", ByteString, b":spam"),
+        valid("empty list", List, b"le"),
+        valid("list of mixed items", List, b"li1e4:spame"),
+        valid("list of lists", List, b"lli1eeli2eee"),
+        valid("list of empty lists", List, b"llelelee"),
+        invalid("unterminated list", List, b"l"),
+        invalid("unterminated list with items", List, b"li1e4:spam"),
+        invalid("unknown item type", List, b"lxe"),
+        valid("empty dict", Dict, b"de"),
+        valid("dict of strings", Dict, b"d3:cow3:moo4:spam4:eggse"),
+        valid("dict of lists", Dict, b"d4:spaml1:a1:bee"),
+        valid("dict of dicts", Dict, b"d1:ad1:bdeee"),
+        valid("empty key", Dict, b"d0:i1ee"),
+        valid("binary key", Dict, b"d1:\x00i1e1:\xffi2ee"),
+        invalid("unterminated dict", Dict, b"d"),
+        invalid("key without value", Dict, b"d1:ae"),
+        invalid("integer key", Dict, b"di1ei2ee"),
+        invalid("list key", Dict, b"dli1eei2ee"),
+        invalid("dict key", Dict, b"ddei2ee"),
+        valid("keys sorted by bytes", KeyOrder, b"d1:Ai1e1:ai2ee"),
+        valid("prefix sorted first", KeyOrder, b"d1:ai1e2:aai2ee"),
+        valid("keys sorted unsigned", KeyOrder, b"d1:zi1e1:\x80i2ee"),
+        invalid("unsorted keys", KeyOrder, b"d1:bi1e1:ai2ee"),
+        invalid("duplicate keys", KeyOrder, b"d1:ai1e1:ai2ee"),
+        invalid("keys sorted as text", KeyOrder, b"d1:ai1e1:Ai2ee"),
+        invalid("unsorted nested keys", KeyOrder, b"d1:ad1:ci1e1:bi2eee"),
+        valid(
+            "32 nested lists",
+            Nesting,
+            b"lllllllllllllllllllllllllllllllleeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee",
+        ),
+        valid(
+            "nested dicts and lists",
+            Nesting,
+            b"d1:ald1:ald1:ald1:aleeeeeeee",
+        ),
+        invalid(
+            "nested lists missing one terminator",
+            Nesting,
+            b"llllllllllllllllllllllllllllllllleeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee",
+        ),
+        invalid("empty input", Framing, b""),
+        invalid("two values", Framing, b"i1ei2e"),
+        invalid("trailing byte", Framing, b"4:spamx"),
+        invalid("trailing terminator", Framing, b"lee"),
+        invalid("trailing newline", Framing, b"i1e\n"),
+        invalid("leading whitespace", Framing, b" i1e"),
+        invalid("lone terminator", Framing, b"e"),
+    ]
+};
+
+/// The test vectors: canonical bencode which every decoder must accept and
+/// re-encode unchanged, and inputs it must reject.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::testing::{vectors, Category, Expected};
+///
+/// let integers = vectors().iter().filter(|v| v.category() == Category::Integer);
+/// for vector in integers {
+///     let valid = bendy::decoding::is_valid_bencode(vector.input());
+///     assert_eq!(valid, vector.expected() == Expected::Valid, "{}", vector.name());
+/// }
+/// ```
+pub fn vectors() -> &'static [TestVector] {
+    VECTORS
+}
+
+impl TestVector {
+    /// A short description of the input, unique among the vectors
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The part of the specification this checks
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    /// Whether a decoder must accept or reject the input
+    pub fn expected(&self) -> Expected {
+        self.expected
+    }
+
+    /// The input to decode
+    pub fn input(&self) -> &'static [u8] {
+        self.input
+    }
+
+    /// Check that `decode` accepts or rejects the input as expected
+    pub fn check_decoder<V, E>(
+        &self,
+        mut decode: impl FnMut(&'static [u8]) -> Result<V, E>,
+    ) -> Result<(), Problem>
+    where
+        E: Display,
+    {
+        self.check(&mut decode, None::<&mut fn(&V) -> Result<Vec<u8>, E>>)
+    }
+
+    /// Check that `decode` accepts or rejects the input as expected, and that
+    /// `encode` turns valid inputs back into the same bytes
+    pub fn check_codec<V, E, F>(
+        &self,
+        mut decode: impl FnMut(&'static [u8]) -> Result<V, E>,
+        mut encode: impl FnMut(&V) -> Result<Vec<u8>, F>,
+    ) -> Result<(), Problem>
+    where
+        E: Display,
+        F: Display,
+    {
+        self.check(&mut decode, Some(&mut encode))
+    }
+
+    fn check<V, E, F>(
+        &self,
+        decode: &mut impl FnMut(&'static [u8]) -> Result<V, E>,
+        encode: Option<&mut impl FnMut(&V) -> Result<Vec<u8>, F>>,
+    ) -> Result<(), Problem>
+    where
+        E: Display,
+        F: Display,
+    {
+        match (self.expected, decode(self.input)) {
+            (Expected::Invalid, Ok(_)) => Err(Problem::Accepted),
+            (Expected::Invalid, Err(_)) => Ok(()),
+            (Expected::Valid, Err(error)) => Err(Problem::Rejected(error.to_string())),
+            (Expected::Valid, Ok(value)) => match encode.map(|encode| encode(&value)) {
+                None => Ok(()),
+                Some(Err(error)) => Err(Problem::NotEncoded(error.to_string())),
+                Some(Ok(encoded)) if encoded == self.input => Ok(()),
+                Some(Ok(encoded)) => Err(Problem::Reencoded(encoded)),
+            },
+        }
+    }
+}
+
+/// How a decoder or encoder failed a [`TestVector`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Problem {
+    /// A valid input failed to decode, with this error
+    Rejected(String),
+    /// An invalid input was decoded
+    Accepted,
+    /// The value decoded from a valid input failed to encode, with this error
+    NotEncoded(String),
+    /// The value decoded from a valid input was encoded into these bytes
+    /// instead of the input
+    Reencoded(Vec<u8>),
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Problem::Rejected(error) => write!(f, "rejected valid input: {}", error),
+            Problem::Accepted => write!(f, "accepted invalid input"),
+            Problem::NotEncoded(error) => write!(f, "failed to encode: {}", error),
+            Problem::Reencoded(encoded) => {
+                write!(f, "encoded as {:?}", String::from_utf8_lossy(encoded))
+            },
+        }
+    }
+}
+
+/// A [`TestVector`] which a decoder or encoder failed, and how
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    vector: &'static TestVector,
+    problem: Problem,
+}
+
+impl Failure {
+    /// The failed vector
+    pub fn vector(&self) -> &'static TestVector {
+        self.vector
+    }
+
+    /// How the vector failed
+    pub fn problem(&self) -> &Problem {
+        &self.problem
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:?}): {}",
+            self.vector.name,
+            String::from_utf8_lossy(self.vector.input),
+            self.problem
+        )
+    }
+}
+
+/// The results of checking a decoder or encoder against every [`TestVector`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Report {
+    checked: usize,
+    failures: Vec<Failure>,
+}
+
+impl Report {
+    /// How many vectors were checked
+    pub fn checked(&self) -> usize {
+        self.checked
+    }
+
+    /// The vectors which failed, in order
+    pub fn failures(&self) -> &[Failure] {
+        &self.failures
+    }
+
+    /// Whether every vector passed
+    pub fn is_conforming(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Panic with every failure unless every vector passed
+    #[track_caller]
+    pub fn assert_conforming(&self) {
+        if !self.is_conforming() {
+            panic!("{}", self);
+        }
+    }
+
+    fn record(&mut self, vector: &'static TestVector, result: Result<(), Problem>) {
+        self.checked += 1;
+        if let Err(problem) = result {
+            self.failures.push(Failure { vector, problem });
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} test vectors failed",
+            self.failures.len(),
+            self.checked
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check `decode` against every [`TestVector`]: it must accept the valid
+/// inputs and reject the invalid ones.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{decoding::is_valid_bencode, testing::check_decoder};
+///
+/// let report = check_decoder(|input| match is_valid_bencode(input) {
+///     true => Ok(()),
+///     false => Err("invalid"),
+/// });
+/// // Validation allows unsorted keys
+/// assert_eq!(report.failures().len(), 4);
+/// ```
+pub fn check_decoder<V, E>(mut decode: impl FnMut(&'static [u8]) -> Result<V, E>) -> Report
+where
+    E: Display,
+{
+    let mut report = Report::default();
+    for vector in VECTORS {
+        report.record(vector, vector.check_decoder(&mut decode));
+    }
+    report
+}
+
+/// Check `decode` and `encode` against every [`TestVector`]: the decoder must
+/// accept the valid inputs and reject the invalid ones, and the encoder must
+/// turn the values decoded from valid inputs back into the same bytes.
+pub fn check_codec<V, E, F>(
+    mut decode: impl FnMut(&'static [u8]) -> Result<V, E>,
+    mut encode: impl FnMut(&V) -> Result<Vec<u8>, F>,
+) -> Report
+where
+    E: Display,
+    F: Display,
+{
+    let mut report = Report::default();
+    for vector in VECTORS {
+        report.record(vector, vector.check_codec(&mut decode, &mut encode));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        decoding::{self, Decoder, FromBencode, Validity},
+        encoding::ToBencode,
+        value::Value,
+    };
+
+    #[test]
+    fn names_are_unique() {
+        let mut names: Vec<_> = VECTORS.iter().map(TestVector::name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), VECTORS.len());
+    }
+
+    /// Decode a value, which `Value::from_bencode` does without checking
+    /// for trailing data
+    fn decode_value(input: &'static [u8]) -> Result<Value<'static>, decoding::Error> {
+        let mut decoder = Decoder::new(input);
+        let object = decoder
+            .next_object()?
+            .ok_or_else(|| decoding::Error::unexpected_token("a value", "the end"))?;
+        let value = Value::decode_bencode_object(object)?;
+        match decoder.remaining().is_empty() {
+            true => Ok(value),
+            false => Err(decoding::Error::trailing_data()),
+        }
+    }
+
+    #[test]
+    fn values_conform() {
+        check_codec(decode_value, |value: &Value| value.to_bencode()).assert_conforming();
+    }
+
+    #[test]
+    fn from_bencode_ignores_trailing_data() {
+        let report = check_decoder(Value::from_bencode);
+        let failed: Vec<_> = report
+            .failures()
+            .iter()
+            .map(|f| f.vector().name())
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                "exponent",
+                "two values",
+                "trailing byte",
+                "trailing terminator",
+                "trailing newline"
+            ]
+        );
+    }
+
+    #[test]
+    fn validation_conforms() {
+        check_decoder(|input| match decoding::validate(input, 64)? {
+            Validity::Canonical => Ok(()),
+            Validity::UnsortedKeys => Err(decoding::Error::unexpected_field("unsorted")),
+        })
+        .assert_conforming();
+    }
+
+    #[test]
+    fn failures_are_reported() {
+        let report = check_codec(Value::from_bencode, |_: &Value| {
+            Ok::<_, &str>(b"0:".to_vec())
+        });
+        assert!(!report.is_conforming());
+        assert_eq!(report.checked(), VECTORS.len());
+
+        let failure = &report.failures()[0];
+        assert_eq!(failure.vector().name(), "zero");
+        assert_eq!(failure.problem(), &Problem::Reencoded(b"0:".to_vec()));
+        assert!(report
+            .to_string()
+            .contains("\n  zero (\"i0e\"): encoded as \"0:\""));
+    }
+}
@@ -0,0 +1,74 @@
+//! Typed models of BitTorrent metainfo (`.torrent`) files, as specified in
+//! [BEP 3](http://bittorrent.org/beps/bep_0003.html).
+//!
+//! Decoding a [`MetaInfo`] keeps every field it doesn't model in the `extra` map
+//! of the enclosing struct, so encoding it again reproduces the original
+//! structure. [`TorrentBuilder`] creates v1, v2 and hybrid torrents from files on
+//! disk.
+
+pub mod krpc;
+
+mod bitfield;
+mod builder;
+mod compact;
+mod dedup;
+mod dht_state;
+mod edit;
+mod extension;
+mod hybrid;
+mod info_hash;
+mod lint;
+mod magnet;
+mod merkle;
+mod metadata;
+mod metainfo;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+mod path;
+mod pex;
+mod pieces;
+mod query;
+mod rehash;
+mod resume;
+mod root_hash;
+mod scrape;
+mod sha1;
+mod sha256;
+mod signature;
+mod tiers;
+mod tracker;
+mod utorrent;
+mod v2;
+mod validate;
+
+pub use self::{
+    bitfield::Bitfield,
+    builder::{BuildError, Progress, TorrentBuilder, TorrentVersion, DEFAULT_PIECE_LENGTH},
+    compact::{
+        decode_compact_peers, decode_compact_peers6, encode_compact_peers, encode_compact_peers6,
+        COMPACT_PEER6_LEN, COMPACT_PEER_LEN,
+    },
+    dht_state::DhtState,
+    edit::{rewrite_trackers, EditError, TorrentEditor, TrackerChange},
+    extension::ExtendedHandshake,
+    hybrid::HybridError,
+    info_hash::{info_hash, info_hash_from_reader, info_hash_v2, info_hashes, raw_info, InfoHash},
+    lint::ClientLimits,
+    magnet::{MagnetError, MagnetLink},
+    metadata::{MetadataMessage, METADATA_PIECE_LEN},
+    metainfo::{DhtNode, FileEntry, Info, Layout, MetaInfo, UrlList},
+    path::{check_component, safe_path, PathError},
+    pex::{PexFlags, PexMessage},
+    pieces::{PieceHashes, PiecesError, PIECE_HASH_LEN},
+    query::{decode_info_hash, encode_info_hash, QueryError},
+    rehash::InfoHashChange,
+    resume::{ResumeData, RESUME_FILE_FORMAT},
+    root_hash::merkle_root,
+    scrape::{ScrapeResponse, ScrapeStats},
+    signature::{signed_data, Signature},
+    tiers::TrackerTiers,
+    tracker::{Peer, Peers, TrackerError, TrackerResponse},
+    utorrent::{UtorrentResume, UtorrentSettings, UtorrentTorrent},
+    v2::{FileTree, FileTreeEntry, Sha256Hash, TreeFile},
+    validate::{Problem, ProblemKind, Severity},
+};
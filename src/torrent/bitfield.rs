@@ -0,0 +1,178 @@
+//! Bitfields of pieces, as sent in `bitfield` messages and saved by clients in
+//! their resume data.
+
+use std::iter::FromIterator;
+
+use crate::{
+    decoding::{self, FromBencode, Object},
+    encoding::{self, SingleItemEncoder, ToBencode},
+};
+
+/// One bit per piece, with the first piece in the most significant bit of the
+/// first byte, encoded as a byte string.
+///
+/// A bitfield decoded from bytes has 8 bits per byte, since the number of
+/// pieces isn't part of the encoding. Spare bits at the end are expected to be
+/// unset.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{decoding::FromBencode, encoding::ToBencode, torrent::Bitfield};
+///
+/// let mut bitfield = Bitfield::new(10);
+/// bitfield.set(0, true);
+/// bitfield.set(9, true);
+///
+/// assert_eq!(bitfield.as_bytes(), &[0x80, 0x40]);
+/// assert_eq!(bitfield.count_ones(), 2);
+/// assert_eq!(bitfield.ones().collect::<Vec<_>>(), [0, 9]);
+///
+/// let encoded = bitfield.to_bencode().unwrap();
+/// assert_eq!(encoded, b"2:\x80\x40");
+/// assert_eq!(Bitfield::from_bencode(&encoded).unwrap().len(), 16);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bitfield {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bitfield {
+    /// Create a bitfield of `len` unset bits.
+    pub fn new(len: usize) -> Self {
+        Bitfield {
+            bytes: vec![0; (len + 7) / 8],
+            len,
+        }
+    }
+
+    /// Wrap the bytes of a bitfield, which has 8 bits per byte.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Bitfield {
+            len: bytes.len() * 8,
+            bytes,
+        }
+    }
+
+    /// The bytes of the bitfield, with spare bits at the end unset.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Unwrap the bytes of the bitfield.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// The number of bits.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no bits.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the bit `index` is set. Bits past the end are unset.
+    pub fn get(&self, index: usize) -> bool {
+        self.bytes
+            .get(index / 8)
+            .map_or(false, |byte| byte & mask(index) != 0)
+    }
+
+    /// Set or unset the bit `index`, growing the bitfield if needed.
+    pub fn set(&mut self, index: usize, value: bool) {
+        if index >= self.len {
+            self.len = index + 1;
+            self.bytes.resize((self.len + 7) / 8, 0);
+        }
+        if value {
+            self.bytes[index / 8] |= mask(index);
+        } else {
+            self.bytes[index / 8] &= !mask(index);
+        }
+    }
+
+    /// The number of set bits.
+    pub fn count_ones(&self) -> usize {
+        self.bytes
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    /// Whether all bits are set, as when a torrent is complete.
+    pub fn all(&self) -> bool {
+        (0..self.len).all(|index| self.get(index))
+    }
+
+    /// The indices of the set bits, in order.
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(move |&index| self.get(index))
+    }
+
+    /// Every bit, in order.
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(move |index| self.get(index))
+    }
+}
+
+fn mask(index: usize) -> u8 {
+    0x80 >> (index % 8)
+}
+
+impl FromIterator<bool> for Bitfield {
+    fn from_iter<I: IntoIterator<Item = bool>>(bits: I) -> Self {
+        let mut bitfield = Bitfield::default();
+        for (index, bit) in bits.into_iter().enumerate() {
+            bitfield.set(index, bit);
+        }
+        bitfield
+    }
+}
+
+impl FromBencode for Bitfield {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        object
+            .try_into_bytes()
+            .map(|bytes| Bitfield::from_bytes(bytes.to_vec()))
+    }
+}
+
+impl ToBencode for Bitfield {
+    const MAX_DEPTH: usize = 0;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_bytes(&self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_are_set_and_cleared() {
+        let mut bitfield: Bitfield = [true, false, true].iter().copied().collect();
+        assert_eq!(bitfield.len(), 3);
+        assert_eq!(bitfield.as_bytes(), &[0xa0]);
+        assert!(!bitfield.all());
+
+        bitfield.set(1, true);
+        assert!(bitfield.all());
+        bitfield.set(0, false);
+        assert_eq!(bitfield.iter().collect::<Vec<_>>(), [false, true, true]);
+        assert!(!bitfield.get(100));
+
+        assert!(Bitfield::new(0).all());
+        assert_eq!(Bitfield::new(9).as_bytes(), &[0, 0]);
+    }
+
+    #[test]
+    fn only_byte_strings_are_decoded() {
+        assert!(Bitfield::from_bencode(b"i1e").is_err());
+        assert_eq!(Bitfield::from_bencode(b"0:").unwrap(), Bitfield::default());
+    }
+}
@@ -0,0 +1,1272 @@
+//! Creating torrents from files on disk.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::{self, Read},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "rayon")]
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+
+#[cfg(feature = "rayon")]
+use crate::torrent::sha1::sha1;
+
+use crate::{
+    encoding::{self, ToBencode},
+    torrent::{
+        merkle::{FileHasher, RootAndLayer},
+        merkle_root,
+        sha1::Sha1,
+        DhtNode, FileEntry, FileTree, FileTreeEntry, Info, Layout, MetaInfo, TreeFile,
+        PIECE_HASH_LEN,
+    },
+};
+
+/// The piece length used unless [`TorrentBuilder::with_piece_length`] is called.
+pub const DEFAULT_PIECE_LENGTH: u64 = 256 * 1024;
+
+/// How far a [`TorrentBuilder`] has got hashing its input, as reported to the
+/// callback of [`TorrentBuilder::build_with_progress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of bytes of the files hashed so far.
+    pub bytes: u64,
+    /// The total length of all files in bytes.
+    pub total_bytes: u64,
+    /// The number of pieces hashed so far. Each file starts a new piece in v2
+    /// torrents, so these are the pieces of the v1 metadata if there is any.
+    pub pieces: u64,
+    /// The total number of pieces.
+    pub total_pieces: u64,
+}
+
+/// The metadata versions a [`TorrentBuilder`] creates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TorrentVersion {
+    /// Only v1 metadata: `pieces` and the file layout.
+    V1,
+    /// Only v2 metadata: the `file tree` and `piece layers`, as specified in
+    /// [BEP 52](http://bittorrent.org/beps/bep_0052.html).
+    V2,
+    /// Both v1 and v2 metadata describing the same files. Padding files are
+    /// inserted into the v1 file list so every file starts at a piece boundary,
+    /// as required for the pieces of both versions to line up.
+    Hybrid,
+}
+
+impl TorrentVersion {
+    fn has_v1(self) -> bool {
+        self != TorrentVersion::V2
+    }
+
+    fn has_v2(self) -> bool {
+        self != TorrentVersion::V1
+    }
+}
+
+/// An error encountered while building a torrent.
+#[derive(Debug)]
+pub enum BuildError {
+    /// Reading a file or directory failed.
+    Io(PathBuf, io::Error),
+    /// A path contains a component which isn't valid UTF-8.
+    NonUtf8Path(PathBuf),
+    /// The input is a directory which contains no files.
+    NoFiles(PathBuf),
+    /// A symlink in the input directory leads back to a directory containing
+    /// it, which would make the torrent infinitely large.
+    SymlinkLoop(PathBuf),
+    /// A file is shorter or longer than when its length was recorded, so it
+    /// was changed while the torrent was built.
+    FileChanged(PathBuf),
+    /// The piece length isn't a power of two of at least 16 KiB.
+    InvalidPieceLength(u64),
+    /// Encoding the torrent failed.
+    Encoding(encoding::Error),
+    /// The progress callback of [`TorrentBuilder::build_with_progress`]
+    /// cancelled the build.
+    Cancelled,
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            BuildError::Io(path, error) => {
+                write!(f, "reading {} failed: {}", path.display(), error)
+            },
+            BuildError::NonUtf8Path(path) => {
+                write!(f, "path is not valid UTF-8: {}", path.display())
+            },
+            BuildError::NoFiles(path) => write!(f, "no files found in {}", path.display()),
+            BuildError::SymlinkLoop(path) => {
+                write!(f, "symlink loop found at {}", path.display())
+            },
+            BuildError::FileChanged(path) => {
+                write!(f, "file changed while building: {}", path.display())
+            },
+            BuildError::InvalidPieceLength(length) => write!(
+                f,
+                "piece length must be a power of two of at least 16384, got {}",
+                length
+            ),
+            BuildError::Encoding(error) => write!(f, "encoding the torrent failed: {}", error),
+            BuildError::Cancelled => write!(f, "building the torrent was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::Io(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Builds a [`MetaInfo`] for a file or a directory, hashing its contents.
+///
+/// A directory becomes a multi-file torrent containing every file below it, in
+/// the byte order of their paths, so the same directory always produces the same
+/// torrent. Symlinks are followed, but a symlink to a directory containing it
+/// fails the build with [`BuildError::SymlinkLoop`].
+/// [`with_version`](TorrentBuilder::with_version) selects whether v1,
+/// v2 or hybrid metadata is created.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bendy::torrent::TorrentBuilder;
+///
+/// let torrent = TorrentBuilder::new("debian.iso")
+///     .with_announce("http://bttracker.debian.org:6969/announce")
+///     .with_comment("Debian CD")
+///     .with_creation_date(1_520_682_848)
+///     .build_bencode()
+///     .unwrap();
+///
+/// std::fs::write("debian.iso.torrent", torrent).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct TorrentBuilder {
+    path: PathBuf,
+    name: Option<String>,
+    piece_length: u64,
+    announce: Option<String>,
+    comment: Option<String>,
+    created_by: Option<String>,
+    creation_date: Option<i64>,
+    version: TorrentVersion,
+    pad_files: bool,
+    merkle: bool,
+    #[cfg(feature = "rayon")]
+    threads: usize,
+    mmap: bool,
+    private: bool,
+    source: Option<String>,
+    nodes: Vec<DhtNode>,
+}
+
+impl TorrentBuilder {
+    /// Create a builder for the torrent of the file or directory at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        TorrentBuilder {
+            path: path.into(),
+            name: None,
+            piece_length: DEFAULT_PIECE_LENGTH,
+            announce: None,
+            comment: None,
+            created_by: None,
+            creation_date: None,
+            version: TorrentVersion::V1,
+            pad_files: false,
+            merkle: false,
+            #[cfg(feature = "rayon")]
+            threads: 1,
+            mmap: false,
+            private: false,
+            source: None,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Set the name of the torrent. Defaults to the last component of the path.
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the number of bytes per piece, a power of two of at least 16 KiB.
+    #[must_use]
+    pub fn with_piece_length(mut self, piece_length: u64) -> Self {
+        self.piece_length = piece_length;
+        self
+    }
+
+    /// Set the URL of the tracker.
+    #[must_use]
+    pub fn with_announce(mut self, announce: impl Into<String>) -> Self {
+        self.announce = Some(announce.into());
+        self
+    }
+
+    /// Add a DHT node to bootstrap from, for trackerless torrents.
+    #[must_use]
+    pub fn with_node(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.nodes.push(DhtNode::new(host, port));
+        self
+    }
+
+    /// Set the free-form comment.
+    #[must_use]
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Set the name of the program creating the torrent.
+    #[must_use]
+    pub fn with_created_by(mut self, created_by: impl Into<String>) -> Self {
+        self.created_by = Some(created_by.into());
+        self
+    }
+
+    /// Set the creation date, in seconds since the Unix epoch.
+    #[must_use]
+    pub fn with_creation_date(mut self, creation_date: i64) -> Self {
+        self.creation_date = Some(creation_date);
+        self
+    }
+
+    /// Set the metadata versions to create. Defaults to [`TorrentVersion::V1`].
+    #[must_use]
+    pub fn with_version(mut self, version: TorrentVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Mark the torrent as private, so that clients only get peers from its
+    /// trackers.
+    #[must_use]
+    pub fn with_private(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+
+    /// Set the `source` of the torrent, giving it a distinct infohash.
+    #[must_use]
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Insert padding files so that every file of a multi-file torrent starts
+    /// at a piece boundary. Hybrid torrents are always padded.
+    #[must_use]
+    pub fn with_padding(mut self, pad_files: bool) -> Self {
+        self.pad_files = pad_files;
+        self
+    }
+
+    /// Create a Merkle torrent as specified in
+    /// [BEP 30](http://bittorrent.org/beps/bep_0030.html), whose info dict has
+    /// the [`merkle_root`] of the piece hashes instead of the hashes
+    /// themselves. Only v1 torrents can be Merkle torrents, so this is ignored
+    /// for other versions.
+    #[must_use]
+    pub fn with_merkle(mut self, merkle: bool) -> Self {
+        self.merkle = merkle;
+        self
+    }
+
+    /// Hash the v1 pieces on a `rayon` thread pool with `threads` threads.
+    /// Defaults to 1, hashing on the calling thread, and 0 uses one thread per
+    /// CPU, like rayon's global pool.
+    ///
+    /// The pieces are the same for any number of threads. The blocks of v2
+    /// metadata are always hashed on the calling thread.
+    #[cfg(feature = "rayon")]
+    #[must_use]
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Memory-map the input files instead of reading them into a buffer, which
+    /// saves copying large files.
+    ///
+    /// This needs the `mmap` feature and a Unix platform. Otherwise the files
+    /// are read as usual, and so are empty files and files that can't be
+    /// mapped, such as pipes and other special files.
+    ///
+    /// # Safety
+    ///
+    /// The hashers read the mapped files as ordinary byte slices, so the
+    /// caller must ensure that no process or thread changes or truncates any
+    /// input file until [`build`](TorrentBuilder::build) returns. Changing a
+    /// mapped file is undefined behaviour, and truncating it raises `SIGBUS`
+    /// on reading the pages past its new end, which kills the whole process
+    /// unless it handles the signal.
+    #[must_use]
+    pub unsafe fn with_mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// The hasher for the v1 pieces, on a thread pool if more than one thread
+    /// was asked for and the pool can be created
+    fn piece_hasher(&self) -> Pieces {
+        #[cfg(feature = "rayon")]
+        {
+            if self.threads != 1 && self.version.has_v1() {
+                if let Some(hasher) = ParallelPieceHasher::new(self.piece_length, self.threads) {
+                    return Pieces::Parallel(hasher);
+                }
+            }
+        }
+        Pieces::Sequential(PieceHasher::new(self.piece_length))
+    }
+
+    /// Read and hash the input and return the torrent.
+    pub fn build(&self) -> Result<MetaInfo, BuildError> {
+        self.build_with_progress(|_| ControlFlow::Continue(()))
+    }
+
+    /// Like [`build`](TorrentBuilder::build), but call `progress` every time
+    /// another part of the input was hashed, and once more when all of it was.
+    /// Returning [`ControlFlow::Break`] from the callback cancels the build with
+    /// [`BuildError::Cancelled`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::ops::ControlFlow;
+    ///
+    /// use bendy::torrent::TorrentBuilder;
+    ///
+    /// let torrent = TorrentBuilder::new("debian.iso")
+    ///     .build_with_progress(|progress| {
+    ///         println!("{} of {} pieces", progress.pieces, progress.total_pieces);
+    ///         ControlFlow::Continue(())
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn build_with_progress(
+        &self,
+        mut progress: impl FnMut(Progress) -> ControlFlow<()>,
+    ) -> Result<MetaInfo, BuildError> {
+        if self.piece_length < 16 * 1024 || !self.piece_length.is_power_of_two() {
+            return Err(BuildError::InvalidPieceLength(self.piece_length));
+        }
+
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => utf8(&self.path, self.path.file_name())?,
+        };
+
+        let metadata =
+            fs::metadata(&self.path).map_err(|e| BuildError::Io(self.path.clone(), e))?;
+        let (sources, files) = if metadata.is_dir() {
+            let mut sources = Vec::new();
+            collect_files(&self.path, &mut Vec::new(), &mut sources)?;
+            if sources.is_empty() {
+                return Err(BuildError::NoFiles(self.path.clone()));
+            }
+            sources.sort();
+
+            let files = sources
+                .iter()
+                .map(|source| {
+                    let relative = source
+                        .strip_prefix(&self.path)
+                        .expect("collected files are below the input directory");
+                    Ok(FileEntry {
+                        length: file_length(source)?,
+                        path: relative
+                            .iter()
+                            .map(|component| utf8(source, Some(component)))
+                            .collect::<Result<_, _>>()?,
+                        attr: None,
+                        extra: BTreeMap::new(),
+                    })
+                })
+                .collect::<Result<Vec<_>, BuildError>>()?;
+            (sources, Some(files))
+        } else {
+            (vec![self.path.clone()], None)
+        };
+        let lengths: Vec<u64> = match &files {
+            Some(files) => files.iter().map(|file| file.length).collect(),
+            None => vec![metadata.len()],
+        };
+
+        // Pad every file but the last to a piece boundary in padded v1 and in
+        // hybrid torrents
+        let pad_files = match self.version {
+            TorrentVersion::V1 => self.pad_files,
+            TorrentVersion::V2 => false,
+            TorrentVersion::Hybrid => true,
+        };
+        let pad_to_pieces = |pad_files: bool| -> Vec<u64> {
+            lengths
+                .iter()
+                .enumerate()
+                .map(|(i, length)| {
+                    if pad_files && i + 1 < lengths.len() {
+                        (self.piece_length - length % self.piece_length) % self.piece_length
+                    } else {
+                        0
+                    }
+                })
+                .collect()
+        };
+        let padding = pad_to_pieces(pad_files);
+        // Count the pieces of v2 torrents as though the files were padded, so
+        // every file starts a new piece
+        let mut tracker = ProgressTracker::new(
+            &mut progress,
+            &lengths,
+            pad_to_pieces(pad_files || !self.version.has_v1()),
+            self.piece_length,
+        );
+        let (pieces, roots) = hash_files(
+            &sources,
+            &lengths,
+            &padding,
+            self.piece_length,
+            self.version,
+            self.mmap,
+            self.piece_hasher(),
+            &mut tracker,
+        )?;
+
+        let mut file_tree = FileTree::default();
+        let mut piece_layers = BTreeMap::new();
+        for (i, (root, length)) in roots.into_iter().zip(&lengths).enumerate() {
+            let mut file = TreeFile {
+                length: *length,
+                pieces_root: None,
+                extra: BTreeMap::new(),
+            };
+            if let Some((root, layer)) = root {
+                file.pieces_root = Some(root);
+                if !layer.is_empty() {
+                    piece_layers.insert(root, layer);
+                }
+            }
+            let path = match &files {
+                Some(files) => &files[i].path[..],
+                None => std::slice::from_ref(&name),
+            };
+            insert_file(&mut file_tree, path, file);
+        }
+
+        let layout = match files {
+            Some(mut files) => {
+                for (i, length) in padding.iter().enumerate().rev() {
+                    if *length > 0 {
+                        files.insert(i + 1, padding_file(*length));
+                    }
+                }
+                Layout::MultiFile { files }
+            },
+            None => Layout::SingleFile { length: lengths[0] },
+        };
+
+        let (has_v1, has_v2) = (self.version.has_v1(), self.version.has_v2());
+        let root_hash = if self.merkle && self.version == TorrentVersion::V1 {
+            let hashes: Vec<[u8; PIECE_HASH_LEN]> = pieces
+                .chunks_exact(PIECE_HASH_LEN)
+                .map(|hash| hash.try_into().expect("pieces are whole hashes"))
+                .collect();
+            Some(merkle_root(&hashes))
+        } else {
+            None
+        };
+        Ok(MetaInfo {
+            announce: self.announce.clone(),
+            announce_list: None,
+            comment: self.comment.clone().map(String::into_bytes),
+            created_by: self.created_by.clone().map(String::into_bytes),
+            creation_date: self.creation_date,
+            encoding: None,
+            httpseeds: None,
+            nodes: if self.nodes.is_empty() {
+                None
+            } else {
+                Some(self.nodes.clone())
+            },
+            info: Info {
+                name,
+                piece_length: self.piece_length,
+                pieces: if has_v1 && root_hash.is_none() {
+                    Some(pieces)
+                } else {
+                    None
+                },
+                root_hash,
+                layout: if has_v1 { Some(layout) } else { None },
+                meta_version: if has_v2 { Some(2) } else { None },
+                file_tree: if has_v2 { Some(file_tree) } else { None },
+                private: if self.private { Some(true) } else { None },
+                source: self.source.clone(),
+                extra: BTreeMap::new(),
+            },
+            piece_layers: if has_v2 { Some(piece_layers) } else { None },
+            signatures: None,
+            url_list: None,
+            extra: BTreeMap::new(),
+        })
+    }
+
+    /// Like [`build`](TorrentBuilder::build), but return the encoded torrent.
+    pub fn build_bencode(&self) -> Result<Vec<u8>, BuildError> {
+        self.build()?.to_bencode().map_err(BuildError::Encoding)
+    }
+}
+
+fn utf8(path: &Path, component: Option<&std::ffi::OsStr>) -> Result<String, BuildError> {
+    component
+        .and_then(|component| component.to_str())
+        .map(String::from)
+        .ok_or_else(|| BuildError::NonUtf8Path(path.to_path_buf()))
+}
+
+fn file_length(path: &Path) -> Result<u64, BuildError> {
+    fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .map_err(|e| BuildError::Io(path.to_path_buf(), e))
+}
+
+/// Add the files below `directory` to `files`, following symlinks unless they
+/// lead back to one of the `ancestors` of `directory`
+fn collect_files(
+    directory: &Path,
+    ancestors: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), BuildError> {
+    let io_error = |e| BuildError::Io(directory.to_path_buf(), e);
+
+    let canonical = fs::canonicalize(directory).map_err(io_error)?;
+    if ancestors.contains(&canonical) {
+        return Err(BuildError::SymlinkLoop(directory.to_path_buf()));
+    }
+    ancestors.push(canonical);
+
+    for entry in fs::read_dir(directory).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        let metadata = fs::metadata(&path).map_err(|e| BuildError::Io(path.clone(), e))?;
+        if metadata.is_dir() {
+            collect_files(&path, ancestors, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    ancestors.pop();
+    Ok(())
+}
+
+/// A padding file of `length` bytes, marked with the `p` attribute of
+/// [BEP 47](http://bittorrent.org/beps/bep_0047.html).
+fn padding_file(length: u64) -> FileEntry {
+    FileEntry {
+        length,
+        path: vec![".pad".to_string(), length.to_string()],
+        attr: Some("p".to_string()),
+        extra: BTreeMap::new(),
+    }
+}
+
+fn insert_file(tree: &mut FileTree, path: &[String], file: TreeFile) {
+    match path {
+        [name] => {
+            tree.entries.insert(name.clone(), FileTreeEntry::File(file));
+        },
+        [directory, rest @ ..] => {
+            let entry = tree
+                .entries
+                .entry(directory.clone())
+                .or_insert_with(|| FileTreeEntry::Directory(FileTree::default()));
+            if let FileTreeEntry::Directory(subtree) = entry {
+                insert_file(subtree, rest, file);
+            }
+        },
+        [] => {},
+    }
+}
+
+/// Hashes concatenated data with SHA-1 in pieces of a fixed length.
+struct PieceHasher {
+    hasher: Sha1,
+    piece_length: u64,
+    piece_filled: u64,
+    pieces: Vec<u8>,
+}
+
+impl PieceHasher {
+    fn new(piece_length: u64) -> Self {
+        PieceHasher {
+            hasher: Sha1::new(),
+            piece_length,
+            piece_filled: 0,
+            pieces: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = data
+                .len()
+                .min((self.piece_length - self.piece_filled) as usize);
+            self.hasher.update(&data[..take]);
+            self.piece_filled += take as u64;
+            data = &data[take..];
+
+            if self.piece_filled == self.piece_length {
+                let hasher = std::mem::replace(&mut self.hasher, Sha1::new());
+                self.pieces.extend_from_slice(&hasher.finish());
+                self.piece_filled = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.piece_filled > 0 {
+            self.pieces.extend_from_slice(&self.hasher.finish());
+        }
+        self.pieces
+    }
+}
+
+/// Hashes concatenated data with SHA-1 in pieces of a fixed length on a
+/// thread pool. Complete pieces are collected into batches of a few pieces per
+/// thread, whose pieces are hashed in parallel and kept in order.
+#[cfg(feature = "rayon")]
+struct ParallelPieceHasher {
+    pool: ThreadPool,
+    piece_length: usize,
+    batch: Vec<u8>,
+    batch_len: usize,
+    pieces: Vec<u8>,
+}
+
+#[cfg(feature = "rayon")]
+impl ParallelPieceHasher {
+    /// Start a pool of `threads` threads, or one per CPU for 0, or return
+    /// `None` if the threads can't be started
+    fn new(piece_length: u64, threads: usize) -> Option<Self> {
+        let pool = ThreadPoolBuilder::new().num_threads(threads).build().ok()?;
+        let piece_length = piece_length as usize;
+        // Bound the input waiting to be hashed, so reading doesn't fill the
+        // memory
+        let batch_len = 2 * pool.current_num_threads() * piece_length;
+        Some(ParallelPieceHasher {
+            pool,
+            piece_length,
+            batch: Vec::with_capacity(batch_len),
+            batch_len,
+            pieces: Vec::new(),
+        })
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = data.len().min(self.batch_len - self.batch.len());
+            self.batch.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            if self.batch.len() == self.batch_len {
+                self.hash_batch();
+            }
+        }
+    }
+
+    fn hash_batch(&mut self) {
+        let (batch, piece_length) = (&self.batch, self.piece_length);
+        let hashes: Vec<[u8; 20]> = self
+            .pool
+            .install(|| batch.par_chunks(piece_length).map(sha1).collect());
+        for hash in &hashes {
+            self.pieces.extend_from_slice(hash);
+        }
+        self.batch.clear();
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if !self.batch.is_empty() {
+            self.hash_batch();
+        }
+        self.pieces
+    }
+}
+
+/// The v1 piece hasher used by [`hash_files`].
+enum Pieces {
+    Sequential(PieceHasher),
+    #[cfg(feature = "rayon")]
+    Parallel(ParallelPieceHasher),
+}
+
+impl Pieces {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Pieces::Sequential(hasher) => hasher.update(data),
+            #[cfg(feature = "rayon")]
+            Pieces::Parallel(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Pieces::Sequential(hasher) => hasher.finish(),
+            #[cfg(feature = "rayon")]
+            Pieces::Parallel(hasher) => hasher.finish(),
+        }
+    }
+}
+
+/// Hash the contents of `files` in a single pass, each of the recorded length
+/// and followed by the given number of padding zeros. Return the v1 pieces of the concatenated contents
+/// and the v2 root and piece layer of each file, as far as `version` needs them.
+fn hash_files(
+    files: &[PathBuf],
+    lengths: &[u64],
+    padding: &[u64],
+    piece_length: u64,
+    version: TorrentVersion,
+    mmap: bool,
+    mut pieces: Pieces,
+    progress: &mut ProgressTracker,
+) -> Result<(Vec<u8>, Vec<Option<RootAndLayer>>), BuildError> {
+    let mut roots = Vec::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    for ((path, length), padding) in files.iter().zip(lengths).zip(padding) {
+        let mut file_hasher = FileHasher::new();
+        let file = File::open(path).map_err(|e| BuildError::Io(path.clone(), e))?;
+        read_file(path, file, *length, mmap, &mut buffer, |data| {
+            if version.has_v1() {
+                pieces.update(data);
+            }
+            if version.has_v2() {
+                file_hasher.update(data);
+            }
+            progress.advance(data.len() as u64)
+        })?;
+        progress.end_file();
+
+        let zeros = [0; 1024];
+        let mut padding = *padding;
+        while padding > 0 {
+            let take = padding.min(zeros.len() as u64);
+            pieces.update(&zeros[..take as usize]);
+            padding -= take;
+        }
+        roots.push(file_hasher.finish(piece_length));
+    }
+
+    let pieces = pieces.finish();
+    progress.finish()?;
+    Ok((pieces, roots))
+}
+
+/// Tracks the [`Progress`] of [`hash_files`] and reports it to a callback.
+struct ProgressTracker<'a> {
+    callback: &'a mut dyn FnMut(Progress) -> ControlFlow<()>,
+    progress: Progress,
+    piece_length: u64,
+    /// The number of bytes after each file which belong to the same piece.
+    alignment: Vec<u64>,
+    file: usize,
+    /// The position in the files and their alignment.
+    offset: u64,
+}
+
+impl<'a> ProgressTracker<'a> {
+    fn new(
+        callback: &'a mut dyn FnMut(Progress) -> ControlFlow<()>,
+        lengths: &[u64],
+        alignment: Vec<u64>,
+        piece_length: u64,
+    ) -> Self {
+        let total_bytes = lengths.iter().sum();
+        let aligned = total_bytes + alignment.iter().sum::<u64>();
+        ProgressTracker {
+            callback,
+            progress: Progress {
+                bytes: 0,
+                total_bytes,
+                pieces: 0,
+                total_pieces: (aligned + piece_length - 1) / piece_length,
+            },
+            piece_length,
+            alignment,
+            file: 0,
+            offset: 0,
+        }
+    }
+
+    fn report(&mut self) -> Result<(), BuildError> {
+        match (self.callback)(self.progress) {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(()) => Err(BuildError::Cancelled),
+        }
+    }
+
+    fn advance(&mut self, bytes: u64) -> Result<(), BuildError> {
+        self.progress.bytes += bytes;
+        self.offset += bytes;
+        self.progress.pieces = self.offset / self.piece_length;
+        self.report()
+    }
+
+    fn end_file(&mut self) {
+        self.offset += self.alignment[self.file];
+        self.file += 1;
+    }
+
+    fn finish(&mut self) -> Result<(), BuildError> {
+        self.progress.pieces = self.progress.total_pieces;
+        self.report()
+    }
+}
+
+/// The size of the parts of a memory-mapped file passed to the hashers at once,
+/// between which progress is reported.
+#[cfg(all(feature = "mmap", unix))]
+const MAPPED_CHUNK: usize = 4 * 1024 * 1024;
+
+/// Pass the contents of `file` to `update`, in chunks the size of `buffer`
+/// unless it was mapped into memory, stopping at the first error. The file must
+/// still be `length` bytes long.
+fn read_file(
+    path: &Path,
+    mut file: File,
+    length: u64,
+    mmap: bool,
+    buffer: &mut [u8],
+    mut update: impl FnMut(&[u8]) -> Result<(), BuildError>,
+) -> Result<(), BuildError> {
+    #[cfg(all(feature = "mmap", unix))]
+    {
+        if mmap {
+            // Safety: `mmap` is only set by `TorrentBuilder::with_mmap`, whose
+            // caller ensures that the input files don't change while building
+            if let Ok(map) = unsafe { crate::torrent::mmap::Mmap::map(&file) } {
+                if map.as_slice().len() as u64 != length {
+                    return Err(BuildError::FileChanged(path.to_path_buf()));
+                }
+                return map.as_slice().chunks(MAPPED_CHUNK).try_for_each(update);
+            }
+        }
+    }
+    #[cfg(not(all(feature = "mmap", unix)))]
+    let _ = mmap;
+
+    // Read one byte past the end, to find out if the file has grown
+    let mut remaining = length;
+    loop {
+        let take = remaining.min(buffer.len() as u64).max(1) as usize;
+        let read = match file.read(&mut buffer[..take]) {
+            Ok(0) if remaining == 0 => return Ok(()),
+            Ok(read) if read as u64 > remaining || read == 0 => {
+                return Err(BuildError::FileChanged(path.to_path_buf()));
+            },
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(BuildError::Io(path.to_path_buf(), e)),
+        };
+        remaining -= read as u64;
+        update(&buffer[..read])?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        decoding::FromBencode,
+        torrent::{info_hash, info_hash_v2, sha1::sha1},
+    };
+
+    /// A scratch directory which is removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("bendy-builder-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn single_file_pieces_are_hashed() {
+        let dir = TempDir::new("single");
+        let path = dir.0.join("data.bin");
+        let data: Vec<u8> = (0..40_000u32).map(|i| i as u8).collect();
+        fs::write(&path, &data).unwrap();
+
+        let torrent = TorrentBuilder::new(&path)
+            .with_piece_length(16 * 1024)
+            .with_announce("http://tracker/")
+            .with_creation_date(1)
+            .build()
+            .unwrap();
+
+        assert_eq!(torrent.info.name, "data.bin");
+        assert_eq!(
+            torrent.info.layout,
+            Some(Layout::SingleFile { length: 40_000 })
+        );
+        let expected: Vec<u8> = data.chunks(16 * 1024).flat_map(sha1).collect();
+        assert_eq!(torrent.info.pieces, Some(expected));
+
+        let encoded = TorrentBuilder::new(&path)
+            .with_piece_length(16 * 1024)
+            .with_announce("http://tracker/")
+            .with_creation_date(1)
+            .build_bencode()
+            .unwrap();
+        assert_eq!(MetaInfo::from_bencode(&encoded).unwrap(), torrent);
+    }
+
+    #[test]
+    fn merkle_torrents_have_a_root_hash() {
+        let dir = TempDir::new("merkle");
+        let path = dir.0.join("data.bin");
+        let data = vec![3; 40_000];
+        fs::write(&path, &data).unwrap();
+
+        let builder = TorrentBuilder::new(&path)
+            .with_piece_length(16 * 1024)
+            .with_merkle(true);
+        let torrent = builder.build().unwrap();
+        let hashes: Vec<_> = data.chunks(16 * 1024).map(sha1).collect();
+        assert_eq!(torrent.info.pieces, None);
+        assert_eq!(torrent.info.root_hash, Some(merkle_root(&hashes)));
+        assert!(torrent.info.verify_root_hash(&hashes));
+
+        let hybrid = builder
+            .with_version(TorrentVersion::Hybrid)
+            .build()
+            .unwrap();
+        assert_eq!(hybrid.info.root_hash, None);
+        assert!(hybrid.info.pieces.is_some());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn threads_produce_the_same_pieces() {
+        let dir = TempDir::new("threads");
+        fs::write(dir.0.join("a"), vec![4; 50_000]).unwrap();
+        fs::write(
+            dir.0.join("b"),
+            (0..70_000u32).map(|i| i as u8).collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        let builder = TorrentBuilder::new(&dir.0)
+            .with_piece_length(16 * 1024)
+            .with_padding(true);
+        let expected = builder.build().unwrap();
+        for threads in &[0, 2, 7] {
+            let torrent = builder.clone().with_threads(*threads).build().unwrap();
+            assert_eq!(torrent, expected);
+        }
+
+        let hybrid = builder.with_version(TorrentVersion::Hybrid);
+        assert_eq!(
+            hybrid.clone().with_threads(3).build().unwrap(),
+            hybrid.build().unwrap()
+        );
+    }
+
+    #[test]
+    fn progress_is_reported() {
+        let dir = TempDir::new("progress");
+        fs::write(dir.0.join("a"), vec![7; 100_000]).unwrap();
+        fs::write(dir.0.join("b"), vec![8; 20_000]).unwrap();
+
+        for (version, total_pieces) in &[
+            (TorrentVersion::V1, 8),
+            (TorrentVersion::V2, 9),
+            (TorrentVersion::Hybrid, 9),
+        ] {
+            let mut reports = Vec::new();
+            TorrentBuilder::new(&dir.0)
+                .with_piece_length(16 * 1024)
+                .with_version(*version)
+                .build_with_progress(|progress| {
+                    reports.push(progress);
+                    ControlFlow::Continue(())
+                })
+                .unwrap();
+
+            let last = reports.last().unwrap();
+            assert_eq!(
+                *last,
+                Progress {
+                    bytes: 120_000,
+                    total_bytes: 120_000,
+                    pieces: *total_pieces,
+                    total_pieces: *total_pieces,
+                }
+            );
+            assert!(reports.windows(2).all(|pair| {
+                pair[0].bytes <= pair[1].bytes && pair[0].pieces <= pair[1].pieces
+            }));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_refused() {
+        let dir = TempDir::new("symlinks");
+        fs::create_dir(dir.0.join("sub")).unwrap();
+        fs::write(dir.0.join("sub").join("data.bin"), vec![1; 100]).unwrap();
+        std::os::unix::fs::symlink(&dir.0, dir.0.join("sub").join("parent")).unwrap();
+
+        let error = TorrentBuilder::new(&dir.0).build().unwrap_err();
+        assert!(
+            matches!(&error, BuildError::SymlinkLoop(path) if path.ends_with("sub/parent")),
+            "{}",
+            error
+        );
+
+        // Symlinks elsewhere are followed
+        fs::remove_file(dir.0.join("sub").join("parent")).unwrap();
+        std::os::unix::fs::symlink(dir.0.join("sub"), dir.0.join("copy")).unwrap();
+        let torrent = TorrentBuilder::new(&dir.0).build().unwrap();
+        let files = match &torrent.info.layout {
+            Some(Layout::MultiFile { files }) => files,
+            other => panic!("expected multiple files, got {:?}", other),
+        };
+        let paths: Vec<_> = files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![vec!["copy", "data.bin"], vec!["sub", "data.bin"]]
+        );
+    }
+
+    #[test]
+    fn progress_callbacks_cancel() {
+        let dir = TempDir::new("cancel");
+        let path = dir.0.join("data.bin");
+        fs::write(&path, vec![9; 200_000]).unwrap();
+
+        let mut calls = 0;
+        let result = TorrentBuilder::new(&path)
+            .with_piece_length(16 * 1024)
+            .build_with_progress(|progress| {
+                calls += 1;
+                if progress.bytes > 0 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            });
+        assert!(matches!(result, Err(BuildError::Cancelled)));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn mapped_files_produce_the_same_pieces() {
+        let dir = TempDir::new("mmap");
+        fs::write(dir.0.join("a"), vec![5; 40_000]).unwrap();
+        fs::write(dir.0.join("b"), b"").unwrap();
+        fs::write(dir.0.join("c"), vec![6; 1000]).unwrap();
+
+        let builder = TorrentBuilder::new(&dir.0)
+            .with_piece_length(16 * 1024)
+            .with_version(TorrentVersion::Hybrid);
+        // Safety: nothing else writes the files in the temporary directory
+        let mapped = unsafe { builder.clone().with_mmap(true) };
+        assert_eq!(mapped.build().unwrap(), builder.build().unwrap());
+    }
+
+    #[test]
+    fn files_of_another_length_are_rejected() {
+        let dir = TempDir::new("changed");
+        let path = dir.0.join("data.bin");
+        fs::write(&path, vec![7; 3000]).unwrap();
+
+        for &mmap in &[false, true] {
+            for &length in &[0, 2999, 3001, 5000] {
+                let file = File::open(&path).unwrap();
+                let result = read_file(&path, file, length, mmap, &mut [0; 1024], |_| Ok(()));
+                assert!(
+                    matches!(&result, Err(BuildError::FileChanged(changed)) if *changed == path),
+                    "{} bytes recorded, mmap {}: {:?}",
+                    length,
+                    mmap,
+                    result
+                );
+            }
+
+            let mut read = Vec::new();
+            let file = File::open(&path).unwrap();
+            read_file(&path, file, 3000, mmap, &mut [0; 1024], |data| {
+                read.extend_from_slice(data);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(read, vec![7; 3000]);
+        }
+    }
+
+    #[test]
+    fn directories_become_multi_file_torrents() {
+        let dir = TempDir::new("multi");
+        fs::create_dir_all(dir.0.join("b")).unwrap();
+        fs::write(dir.0.join("b").join("c"), vec![1; 20_000]).unwrap();
+        fs::write(dir.0.join("a"), vec![2; 1000]).unwrap();
+
+        let torrent = TorrentBuilder::new(&dir.0)
+            .with_name("dir")
+            .with_piece_length(16 * 1024)
+            .build()
+            .unwrap();
+
+        let files = match &torrent.info.layout {
+            Some(Layout::MultiFile { files }) => files,
+            other => panic!("expected multiple files, got {:?}", other),
+        };
+        let paths: Vec<_> = files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(paths, vec![vec!["a"], vec!["b", "c"]]);
+
+        let mut data = vec![2; 1000];
+        data.extend_from_slice(&[1; 20_000]);
+        let expected: Vec<u8> = data.chunks(16 * 1024).flat_map(sha1).collect();
+        assert_eq!(torrent.info.pieces, Some(expected));
+    }
+
+    #[test]
+    fn padding_aligns_files_to_pieces() {
+        let dir = TempDir::new("padded");
+        fs::write(dir.0.join("a"), vec![2; 1000]).unwrap();
+        fs::write(dir.0.join("b"), vec![1; 20_000]).unwrap();
+
+        let torrent = TorrentBuilder::new(&dir.0)
+            .with_piece_length(16 * 1024)
+            .with_padding(true)
+            .build()
+            .unwrap();
+
+        let files = match &torrent.info.layout {
+            Some(Layout::MultiFile { files }) => files,
+            other => panic!("expected multiple files, got {:?}", other),
+        };
+        let padding: Vec<_> = files.iter().map(FileEntry::is_padding).collect();
+        assert_eq!(padding, vec![false, true, false]);
+        assert_eq!(files[1].length, 16 * 1024 - 1000);
+        assert_eq!(files[1].path, vec![".pad", "15384"]);
+
+        let mut data = vec![2; 1000];
+        data.resize(16 * 1024, 0);
+        data.extend_from_slice(&[1; 20_000]);
+        let expected: Vec<u8> = data.chunks(16 * 1024).flat_map(sha1).collect();
+        assert_eq!(torrent.info.pieces, Some(expected));
+    }
+
+    #[test]
+    fn hybrid_torrents_are_consistent() {
+        let dir = TempDir::new("hybrid");
+        fs::create_dir_all(dir.0.join("b")).unwrap();
+        fs::write(dir.0.join("b").join("c"), vec![1; 40_000]).unwrap();
+        fs::write(dir.0.join("a"), vec![2; 1000]).unwrap();
+
+        let torrent = TorrentBuilder::new(&dir.0)
+            .with_name("dir")
+            .with_piece_length(16 * 1024)
+            .with_version(TorrentVersion::Hybrid)
+            .build()
+            .unwrap();
+        assert_eq!(torrent.check_hybrid(), Ok(()));
+
+        let files = match &torrent.info.layout {
+            Some(Layout::MultiFile { files }) => files,
+            other => panic!("expected multiple files, got {:?}", other),
+        };
+        let paths: Vec<_> = files.iter().map(|file| file.path.join("/")).collect();
+        assert_eq!(paths, vec!["a", ".pad/15384", "b/c"]);
+
+        let mut data = vec![2; 1000];
+        data.extend_from_slice(&[0; 15_384]);
+        data.extend_from_slice(&[1; 40_000]);
+        let expected: Vec<u8> = data.chunks(16 * 1024).flat_map(sha1).collect();
+        assert_eq!(torrent.info.pieces, Some(expected));
+
+        // Only the file larger than a piece has a piece layer
+        let layers = torrent.piece_layers.as_ref().unwrap();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers.values().next().unwrap().len(), 3 * 32);
+
+        let encoded = torrent.to_bencode().unwrap();
+        assert_eq!(MetaInfo::from_bencode(&encoded).unwrap(), torrent);
+        assert_ne!(
+            info_hash_v2(&encoded).unwrap()[..20],
+            info_hash(&encoded).unwrap()
+        );
+    }
+
+    #[test]
+    fn v2_torrents_have_no_v1_metadata() {
+        let dir = TempDir::new("v2");
+        let path = dir.0.join("data.bin");
+        fs::write(&path, vec![3; 20_000]).unwrap();
+
+        let torrent = TorrentBuilder::new(&path)
+            .with_piece_length(16 * 1024)
+            .with_version(TorrentVersion::V2)
+            .build()
+            .unwrap();
+
+        assert!(!torrent.info.is_v1());
+        assert!(torrent.info.is_v2());
+        let files: Vec<_> = torrent
+            .info
+            .file_tree
+            .as_ref()
+            .unwrap()
+            .files()
+            .map(|(path, file)| (path, file.length))
+            .collect();
+        assert_eq!(files, vec![(vec!["data.bin"], 20_000)]);
+    }
+
+    #[test]
+    fn invalid_input_is_rejected() {
+        let dir = TempDir::new("invalid");
+        assert!(matches!(
+            TorrentBuilder::new(&dir.0).build(),
+            Err(BuildError::NoFiles(_))
+        ));
+        assert!(matches!(
+            TorrentBuilder::new(&dir.0)
+                .with_piece_length(20_000)
+                .build(),
+            Err(BuildError::InvalidPieceLength(20_000))
+        ));
+        assert!(matches!(
+            TorrentBuilder::new(dir.0.join("missing")).build(),
+            Err(BuildError::Io(..))
+        ));
+    }
+}
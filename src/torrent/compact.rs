@@ -0,0 +1,118 @@
+//! The compact peer lists of [BEP 23](http://bittorrent.org/beps/bep_0023.html)
+//! and their IPv6 form from [BEP 7](http://bittorrent.org/beps/bep_0007.html).
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+/// The number of bytes of a compact IPv4 peer: 4 bytes of address and 2 bytes
+/// of port, both in network byte order.
+pub const COMPACT_PEER_LEN: usize = 6;
+
+/// The number of bytes of a compact IPv6 peer: 16 bytes of address and 2 bytes
+/// of port, both in network byte order.
+pub const COMPACT_PEER6_LEN: usize = 18;
+
+/// Decode a compact `peers` string.
+///
+/// Trailing bytes which don't form a whole peer, as sent by some trackers, are
+/// ignored rather than failing the whole list.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::decode_compact_peers;
+///
+/// let peers = decode_compact_peers(b"\x0a\x00\x00\x01\x1a\xe1\x0a");
+/// assert_eq!(peers, vec!["10.0.0.1:6881".parse().unwrap()]);
+/// ```
+pub fn decode_compact_peers(bytes: &[u8]) -> Vec<SocketAddrV4> {
+    bytes
+        .chunks_exact(COMPACT_PEER_LEN)
+        .map(|peer| {
+            SocketAddrV4::new(
+                Ipv4Addr::new(peer[0], peer[1], peer[2], peer[3]),
+                u16::from_be_bytes([peer[4], peer[5]]),
+            )
+        })
+        .collect()
+}
+
+/// Encode peers as a compact `peers` string.
+pub fn encode_compact_peers(peers: &[SocketAddrV4]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(peers.len() * COMPACT_PEER_LEN);
+    for peer in peers {
+        bytes.extend_from_slice(&peer.ip().octets());
+        bytes.extend_from_slice(&peer.port().to_be_bytes());
+    }
+    bytes
+}
+
+/// Decode a compact `peers6` string, ignoring trailing bytes like
+/// [`decode_compact_peers`].
+pub fn decode_compact_peers6(bytes: &[u8]) -> Vec<SocketAddrV6> {
+    bytes
+        .chunks_exact(COMPACT_PEER6_LEN)
+        .map(|peer| {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&peer[..16]);
+            SocketAddrV6::new(
+                Ipv6Addr::from(octets),
+                u16::from_be_bytes([peer[16], peer[17]]),
+                0,
+                0,
+            )
+        })
+        .collect()
+}
+
+/// Encode peers as a compact `peers6` string. Flow info and scope ids are not
+/// part of the compact form.
+pub fn encode_compact_peers6(peers: &[SocketAddrV6]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(peers.len() * COMPACT_PEER6_LEN);
+    for peer in peers {
+        bytes.extend_from_slice(&peer.ip().octets());
+        bytes.extend_from_slice(&peer.port().to_be_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_peers_round_trip() {
+        let peers: Vec<SocketAddrV4> = vec![
+            "127.0.0.1:80".parse().unwrap(),
+            "255.1.2.3:65535".parse().unwrap(),
+        ];
+        let bytes = encode_compact_peers(&peers);
+        assert_eq!(bytes, b"\x7f\x00\x00\x01\x00\x50\xff\x01\x02\x03\xff\xff");
+        assert_eq!(decode_compact_peers(&bytes), peers);
+    }
+
+    #[test]
+    fn compact_peers6_round_trip() {
+        let peers: Vec<SocketAddrV6> = vec![
+            "[::1]:6881".parse().unwrap(),
+            "[2001:db8::ff00:42:8329]:80".parse().unwrap(),
+        ];
+        let bytes = encode_compact_peers6(&peers);
+        assert_eq!(bytes.len(), 2 * COMPACT_PEER6_LEN);
+        assert_eq!(bytes[..18], *b"\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x01\x1a\xe1");
+        assert_eq!(decode_compact_peers6(&bytes), peers);
+
+        // Truncated peers are ignored
+        assert_eq!(decode_compact_peers6(&bytes[..17]), vec![]);
+        assert_eq!(decode_compact_peers6(&bytes[..35]).len(), 1);
+    }
+
+    #[test]
+    fn truncated_peers_are_ignored() {
+        assert_eq!(decode_compact_peers(b""), vec![]);
+        assert_eq!(decode_compact_peers(b"\x7f\x00\x00"), vec![]);
+        assert_eq!(
+            decode_compact_peers(b"\x7f\x00\x00\x01\x00\x50\x01\x02\x03\x04\x05"),
+            vec!["127.0.0.1:80".parse().unwrap()]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Finding duplicate torrents, either the same torrent or torrents of the same
+//! files.
+
+use crate::{
+    encoding::{self, ToBencode},
+    torrent::{metainfo::Extra, sha1::sha1, Info, InfoHash, MetaInfo},
+};
+
+impl InfoHash {
+    /// Whether both infohashes identify the same torrent, because they share
+    /// their v1 or their v2 infohash.
+    ///
+    /// A hybrid torrent matches the v1 and the v2 infohash it contains.
+    pub fn matches(&self, other: &InfoHash) -> bool {
+        matches!((self.v1(), other.v1()), (Some(a), Some(b)) if a == b)
+            || matches!((self.v2(), other.v2()), (Some(a), Some(b)) if a == b)
+    }
+}
+
+impl Info {
+    /// The fields describing the content: the name, the files and the pieces.
+    fn content(&self) -> Info {
+        Info {
+            private: None,
+            source: None,
+            extra: Extra::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Whether both info dicts describe the same files with the same pieces.
+    ///
+    /// Unlike comparing infohashes, this ignores the `private` flag, the
+    /// `source` and unknown fields, which trackers set to tell cross-posted
+    /// torrents apart, and the way the dicts were encoded.
+    pub fn same_content(&self, other: &Info) -> bool {
+        self.content() == other.content()
+    }
+
+    /// A SHA-1 hash of the fields compared by
+    /// [`same_content`](Info::same_content), to find duplicates among many
+    /// torrents.
+    pub fn fingerprint(&self) -> Result<[u8; 20], encoding::Error> {
+        self.content().to_bencode().map(|encoded| sha1(&encoded))
+    }
+}
+
+impl MetaInfo {
+    /// Whether both torrents are the same torrent, with matching infohashes of
+    /// their info dicts as encoded by bendy. Fields outside the info dict, such
+    /// as trackers and comments, are ignored.
+    pub fn same_torrent(&self, other: &MetaInfo) -> Result<bool, encoding::Error> {
+        Ok(self.info.info_hashes()?.matches(&other.info.info_hashes()?))
+    }
+
+    /// Whether both torrents describe the same files with the same pieces, as
+    /// described for [`Info::same_content`].
+    pub fn same_content(&self, other: &MetaInfo) -> bool {
+        self.info.same_content(&other.info)
+    }
+
+    /// The [`Info::fingerprint`] of the torrent's content.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::{decoding::FromBencode, torrent::MetaInfo};
+    ///
+    /// let a = MetaInfo::from_bencode(
+    ///     b"d8:announce8:http://a4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e\
+    ///       6:pieces0:7:privatei1e6:source1:Aee",
+    /// )
+    /// .unwrap();
+    /// let b = MetaInfo::from_bencode(
+    ///     b"d7:comment3:abc4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e\
+    ///       6:pieces0:ee",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert!(!a.same_torrent(&b).unwrap());
+    /// assert!(a.same_content(&b));
+    /// assert_eq!(a.fingerprint().unwrap(), b.fingerprint().unwrap());
+    /// ```
+    pub fn fingerprint(&self) -> Result<[u8; 20], encoding::Error> {
+        self.info.fingerprint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::decoding::FromBencode;
+
+    #[test]
+    fn hybrid_torrents_match_both_versions() {
+        let hybrid = InfoHash::Hybrid([1; 20], [2; 32]);
+        assert!(hybrid.matches(&InfoHash::V1([1; 20])));
+        assert!(InfoHash::V2([2; 32]).matches(&hybrid));
+        assert!(!hybrid.matches(&InfoHash::Hybrid([3; 20], [4; 32])));
+        assert!(!InfoHash::V1([1; 20]).matches(&InfoHash::V2([1; 32])));
+    }
+
+    #[test]
+    fn different_files_differ() {
+        let decode = |torrent: &[u8]| MetaInfo::from_bencode(torrent).unwrap();
+        let a = decode(b"d4:infod6:lengthi5e4:name1:a12:piece lengthi1e6:pieces0:ee");
+        let longer = decode(b"d4:infod6:lengthi6e4:name1:a12:piece lengthi1e6:pieces0:ee");
+        let renamed = decode(b"d4:infod6:lengthi5e4:name1:b12:piece lengthi1e6:pieces0:ee");
+
+        assert!(a.same_torrent(&a.clone()).unwrap());
+        for other in &[longer, renamed] {
+            assert!(!a.same_torrent(other).unwrap());
+            assert!(!a.same_content(other));
+            assert_ne!(a.fingerprint().unwrap(), other.fingerprint().unwrap());
+        }
+    }
+}
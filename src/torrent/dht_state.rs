@@ -0,0 +1,230 @@
+//! Snapshots of the state of a DHT node, in the format libtorrent saves as its
+//! `dht state`.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+use crate::{
+    decoding::{self, FromBencode, Object, ResultExt},
+    encoding::{self, AsString, SingleItemEncoder, ToBencode},
+    torrent::{
+        compact::{
+            decode_compact_peers, decode_compact_peers6, encode_compact_peers,
+            encode_compact_peers6, COMPACT_PEER6_LEN, COMPACT_PEER_LEN,
+        },
+        krpc::NodeId,
+        metainfo::{decode_extra, Extra},
+    },
+    value::Value,
+};
+
+/// The state a DHT node persists to restart with its id and routing table.
+///
+/// Every `node-id` entry is the 20-byte id of the local node followed by the
+/// IPv4 or IPv6 address of the interface it's used on. Older versions of
+/// libtorrent save a single id without an address instead. The `nodes` and
+/// `nodes6` lists contain the addresses of known nodes in the compact form of
+/// [BEP 5](http://bittorrent.org/beps/bep_0005.html), without their ids, which
+/// are learnt again when the nodes are contacted. Entries of the wrong length
+/// are skipped when decoding.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{decoding::FromBencode, encoding::ToBencode, torrent::DhtState};
+///
+/// let mut state = DhtState::default();
+/// state.node_ids.push(([7; 20], Some("10.0.0.1".parse().unwrap())));
+/// state.nodes.push("1.2.3.4:6881".parse().unwrap());
+///
+/// let encoded = state.to_bencode().unwrap();
+/// assert_eq!(DhtState::from_bencode(&encoded).unwrap(), state);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DhtState {
+    /// The ids of the local node, each with the address of the interface it's
+    /// used on, if known.
+    pub node_ids: Vec<(NodeId, Option<IpAddr>)>,
+    /// The addresses of known IPv4 nodes.
+    pub nodes: Vec<SocketAddrV4>,
+    /// The addresses of known IPv6 nodes.
+    pub nodes6: Vec<SocketAddrV6>,
+    /// All other fields of the state dict.
+    pub extra: Extra,
+}
+
+fn decode_node_id(bytes: &[u8]) -> Result<(NodeId, Option<IpAddr>), decoding::Error> {
+    let mut id = [0; 20];
+    let addr = match bytes.len() {
+        20 => None,
+        24 => {
+            let octets = [bytes[20], bytes[21], bytes[22], bytes[23]];
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        },
+        36 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&bytes[20..]);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        },
+        length => {
+            return Err(decoding::Error::unexpected_token(
+                "20, 24 or 36 bytes",
+                format!("{} bytes", length),
+            ))
+        },
+    };
+    id.copy_from_slice(&bytes[..20]);
+    Ok((id, addr))
+}
+
+fn encode_node_id(id: &NodeId, addr: &Option<IpAddr>) -> Vec<u8> {
+    let mut bytes = id.to_vec();
+    match addr {
+        Some(IpAddr::V4(addr)) => bytes.extend_from_slice(&addr.octets()),
+        Some(IpAddr::V6(addr)) => bytes.extend_from_slice(&addr.octets()),
+        None => {},
+    }
+    bytes
+}
+
+/// Read a list of compact addresses, passing those of `length` bytes to
+/// `decode`.
+fn decode_nodes<T>(
+    object: Object,
+    length: usize,
+    decode: fn(&[u8]) -> Vec<T>,
+) -> Result<Vec<T>, decoding::Error> {
+    let mut nodes = Vec::new();
+    let mut list = object.try_into_list()?;
+    while let Some(node) = list.next_object()? {
+        let node = node.try_into_bytes()?;
+        if node.len() == length {
+            nodes.extend(decode(node));
+        }
+    }
+    Ok(nodes)
+}
+
+impl FromBencode for DhtState {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut state = DhtState::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"node-id", Object::Bytes(id)) => {
+                    state.node_ids = vec![decode_node_id(id).context("node-id")?];
+                },
+                (b"node-id", value) => {
+                    let mut list = value.try_into_list().context("node-id")?;
+                    state.node_ids.clear();
+                    while let Some(id) = list.next_object()? {
+                        let id = id
+                            .try_into_bytes()
+                            .and_then(decode_node_id)
+                            .context("node-id")?;
+                        state.node_ids.push(id);
+                    }
+                },
+                (b"nodes", value) => {
+                    state.nodes = decode_nodes(value, COMPACT_PEER_LEN, decode_compact_peers)
+                        .context("nodes")?;
+                },
+                (b"nodes6", value) => {
+                    state.nodes6 = decode_nodes(value, COMPACT_PEER6_LEN, decode_compact_peers6)
+                        .context("nodes6")?;
+                },
+                (key, value) => decode_extra(&mut state.extra, key, value)?,
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+impl ToBencode for DhtState {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            e.emit_pair_with(b"node-id", |e| {
+                e.emit_list(|e| {
+                    for (id, addr) in &self.node_ids {
+                        e.emit(AsString(encode_node_id(id, addr)))?;
+                    }
+                    Ok(())
+                })
+            })?;
+            if !self.nodes.is_empty() {
+                e.emit_pair_with(b"nodes", |e| {
+                    e.emit_list(|e| {
+                        for node in &self.nodes {
+                            e.emit(AsString(encode_compact_peers(&[*node])))?;
+                        }
+                        Ok(())
+                    })
+                })?;
+            }
+            if !self.nodes6.is_empty() {
+                e.emit_pair_with(b"nodes6", |e| {
+                    e.emit_list(|e| {
+                        for node in &self.nodes6 {
+                            e.emit(AsString(encode_compact_peers6(&[*node])))?;
+                        }
+                        Ok(())
+                    })
+                })?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn libtorrent_states_round_trip() {
+        let mut encoded = b"d7:node-idl24:".to_vec();
+        encoded.extend_from_slice(&[0x11; 20]);
+        encoded.extend_from_slice(&[192, 168, 0, 1]);
+        encoded.extend_from_slice(b"36:");
+        encoded.extend_from_slice(&[0x22; 20]);
+        encoded.extend_from_slice(&[0xfe; 16]);
+        encoded.extend_from_slice(b"e5:nodesl6:\x01\x02\x03\x04\x1a\xe1e6:nodes6l18:");
+        encoded.extend_from_slice(&[0; 15]);
+        encoded.extend_from_slice(b"\x01\x1a\xe1ee");
+
+        let state = DhtState::from_bencode(&encoded).unwrap();
+        assert_eq!(state.node_ids.len(), 2);
+        assert_eq!(
+            state.node_ids[0],
+            ([0x11; 20], Some("192.168.0.1".parse().unwrap()))
+        );
+        assert!(matches!(state.node_ids[1].1, Some(IpAddr::V6(_))));
+        assert_eq!(state.nodes, vec!["1.2.3.4:6881".parse().unwrap()]);
+        assert_eq!(state.nodes6, vec!["[::1]:6881".parse().unwrap()]);
+        assert_eq!(state.to_bencode().unwrap(), encoded);
+    }
+
+    #[test]
+    fn old_states_are_decoded() {
+        let mut encoded = b"d7:node-id20:".to_vec();
+        encoded.extend_from_slice(&[0x33; 20]);
+        encoded.extend_from_slice(
+            b"5:nodesl6:\x01\x02\x03\x04\x1a\xe13:bad\
+                                   6:\x05\x06\x07\x08\x00\x50ee",
+        );
+
+        let state = DhtState::from_bencode(&encoded).unwrap();
+        assert_eq!(state.node_ids, vec![([0x33; 20], None)]);
+        assert_eq!(state.nodes.len(), 2);
+        assert!(state.nodes6.is_empty());
+
+        assert!(DhtState::from_bencode(b"d7:node-id3:abce").is_err());
+        assert!(DhtState::from_bencode(b"d7:node-idi1ee").is_err());
+    }
+}
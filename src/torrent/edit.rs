@@ -0,0 +1,419 @@
+//! Editing the fields of encoded torrents outside their `info` dict.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    decoding::{self, FromBencode},
+    encoding::{self, Encoder, ToBencode},
+    torrent::{
+        info_hash::{hash_info, raw_info},
+        metainfo::Extra,
+        sha1::sha1,
+        signature::{signed_data, Signature},
+        InfoHash, MetaInfo, TrackerTiers, UrlList,
+    },
+};
+
+/// A tracker URL changed by [`TorrentEditor::rewrite_trackers`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackerChange {
+    /// The URL before the change.
+    pub old: String,
+    /// The URL it was replaced with, or `None` if it was removed.
+    pub new: Option<String>,
+}
+
+/// An error encountered while editing an encoded torrent.
+#[derive(Clone, Debug)]
+pub enum EditError {
+    /// The torrent couldn't be decoded.
+    Decoding(decoding::Error),
+    /// The edited torrent couldn't be encoded.
+    Encoding(encoding::Error),
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            EditError::Decoding(error) => write!(f, "invalid torrent: {}", error),
+            EditError::Encoding(error) => write!(f, "encoding the torrent failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+impl From<decoding::Error> for EditError {
+    fn from(error: decoding::Error) -> Self {
+        EditError::Decoding(error)
+    }
+}
+
+impl From<encoding::Error> for EditError {
+    fn from(error: encoding::Error) -> Self {
+        EditError::Encoding(error)
+    }
+}
+
+/// Rewrite the tracker URLs of an encoded torrent, returning the edited
+/// torrent and the URLs which changed.
+///
+/// This is [`TorrentEditor::rewrite_trackers`] for a single edit, so the
+/// `info` dict and the infohash stay the same.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::{info_hash, rewrite_trackers};
+///
+/// let torrent = b"d8:announce12:http://old/a13:announce-listll12:http://old/a\
+///                 12:http://dead/ee4:infod6:lengthi5e4:name5:a.txt\
+///                 12:piece lengthi16384e6:pieces0:ee";
+///
+/// let (edited, changes) = rewrite_trackers(torrent, |url| match url {
+///     "http://dead/" => None,
+///     url => Some(url.replace("http://old/", "https://new/")),
+/// })
+/// .unwrap();
+///
+/// assert_eq!(changes.len(), 2);
+/// assert_eq!(changes[0].new.as_deref(), Some("https://new/a"));
+/// assert_eq!(changes[1].new, None);
+/// assert_eq!(info_hash(&edited).unwrap(), info_hash(torrent).unwrap());
+/// ```
+pub fn rewrite_trackers(
+    torrent: &[u8],
+    rewrite: impl FnMut(&str) -> Option<String>,
+) -> Result<(Vec<u8>, Vec<TrackerChange>), EditError> {
+    let mut editor = TorrentEditor::new(torrent)?;
+    let changes = editor.rewrite_trackers(rewrite);
+    Ok((editor.to_bencode()?, changes))
+}
+
+/// Changes the fields of an encoded torrent outside its `info` dict, without
+/// changing its infohash.
+///
+/// The `info` dict is copied verbatim from the original torrent rather than
+/// re-encoded, so its hash is guaranteed to stay the same.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::{info_hash, TorrentEditor};
+///
+/// let torrent = b"d8:announce6:http:/4:infod6:lengthi5e4:name5:a.txt\
+///                 12:piece lengthi16384e6:pieces0:ee";
+///
+/// let mut editor = TorrentEditor::new(torrent).unwrap();
+/// editor.set_announce(Some("http://tracker/".to_string()));
+/// editor.set_comment(Some("edited".to_string()));
+/// let edited = editor.to_bencode().unwrap();
+///
+/// assert_eq!(info_hash(&edited).unwrap(), info_hash(torrent).unwrap());
+/// ```
+#[derive(Clone, Debug)]
+pub struct TorrentEditor<'a> {
+    raw_info: &'a [u8],
+    torrent: MetaInfo,
+}
+
+impl<'a> TorrentEditor<'a> {
+    /// Decode `torrent` for editing.
+    pub fn new(torrent: &'a [u8]) -> Result<Self, decoding::Error> {
+        Ok(TorrentEditor {
+            raw_info: raw_info(torrent)?,
+            torrent: MetaInfo::from_bencode(torrent)?,
+        })
+    }
+
+    /// The torrent with the edits made so far.
+    pub fn metainfo(&self) -> &MetaInfo {
+        &self.torrent
+    }
+
+    /// The v1 infohash, which is the same before and after editing.
+    pub fn info_hash(&self) -> [u8; 20] {
+        sha1(self.raw_info)
+    }
+
+    /// The infohashes, which are the same before and after editing.
+    pub fn info_hashes(&self) -> InfoHash {
+        hash_info(&self.torrent.info, self.raw_info)
+    }
+
+    /// Set or remove the URL of the tracker.
+    pub fn set_announce(&mut self, announce: Option<String>) {
+        self.torrent.announce = announce;
+    }
+
+    /// Set or remove the tiers of tracker URLs.
+    pub fn set_announce_list(&mut self, announce_list: Option<Vec<Vec<String>>>) {
+        self.torrent.announce_list = announce_list;
+    }
+
+    /// Replace all trackers, setting both `announce` and `announce-list` as
+    /// described for [`TrackerTiers::apply_to`].
+    pub fn set_trackers(&mut self, tiers: &TrackerTiers) {
+        tiers.apply_to(&mut self.torrent);
+    }
+
+    /// Replace each tracker URL in `announce` and `announce-list` with the URL
+    /// `rewrite` returns for it, or remove it if `rewrite` returns `None`,
+    /// returning the URLs which changed in the order they were found.
+    ///
+    /// `rewrite` is called once for every distinct URL. Tiers left without
+    /// URLs are removed, as is `announce-list` if no tiers are left.
+    pub fn rewrite_trackers(
+        &mut self,
+        mut rewrite: impl FnMut(&str) -> Option<String>,
+    ) -> Vec<TrackerChange> {
+        let mut rewritten: BTreeMap<String, Option<String>> = BTreeMap::new();
+        let mut changes = Vec::new();
+        let mut map = |url: String| -> Option<String> {
+            if let Some(new) = rewritten.get(&url) {
+                return new.clone();
+            }
+            let new = rewrite(&url);
+            if new.as_ref() != Some(&url) {
+                changes.push(TrackerChange {
+                    old: url.clone(),
+                    new: new.clone(),
+                });
+            }
+            rewritten.insert(url, new.clone());
+            new
+        };
+
+        let torrent = &mut self.torrent;
+        torrent.announce = torrent.announce.take().and_then(&mut map);
+        torrent.announce_list = torrent
+            .announce_list
+            .take()
+            .map(|tiers| {
+                tiers
+                    .into_iter()
+                    .map(|tier| tier.into_iter().filter_map(&mut map).collect::<Vec<_>>())
+                    .filter(|tier| !tier.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|tiers| !tiers.is_empty());
+        changes
+    }
+
+    /// Set or remove the free-form comment.
+    pub fn set_comment(&mut self, comment: Option<String>) {
+        self.torrent.comment = comment.map(String::into_bytes);
+    }
+
+    /// Set or remove the name of the program that created the torrent.
+    pub fn set_created_by(&mut self, created_by: Option<String>) {
+        self.torrent.created_by = created_by.map(String::into_bytes);
+    }
+
+    /// Set or remove the creation date, in seconds since the Unix epoch.
+    pub fn set_creation_date(&mut self, creation_date: Option<i64>) {
+        self.torrent.creation_date = creation_date;
+    }
+
+    /// Set or remove the web seeds.
+    pub fn set_url_list(&mut self, url_list: Option<UrlList>) {
+        self.torrent.url_list = url_list;
+    }
+
+    /// Sign the `info` dict and `info` as `identity`, replacing any signature
+    /// by the same identity.
+    ///
+    /// `sign` is called with the [`signed_data`] and returns its signature.
+    /// Signing doesn't change the infohash.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::torrent::TorrentEditor;
+    ///
+    /// let torrent = b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces0:ee";
+    /// let checksum = |data: &[u8]| data.iter().fold(0u8, |sum, byte| sum ^ byte);
+    ///
+    /// let mut editor = TorrentEditor::new(torrent).unwrap();
+    /// editor
+    ///     .sign("me", None, None, |data| vec![checksum(data)])
+    ///     .unwrap();
+    ///
+    /// let signed = editor.to_bencode().unwrap();
+    /// let editor = TorrentEditor::new(&signed).unwrap();
+    /// assert!(editor.verify("me", |data, signature| signature.signature == [checksum(data)]));
+    /// assert!(!editor.verify("you", |_, _| true));
+    /// ```
+    pub fn sign(
+        &mut self,
+        identity: impl Into<String>,
+        info: Option<Extra>,
+        certificate: Option<Vec<u8>>,
+        sign: impl FnOnce(&[u8]) -> Vec<u8>,
+    ) -> Result<(), encoding::Error> {
+        let signature = sign(&signed_data(self.raw_info, info.as_ref())?);
+        self.torrent
+            .signatures
+            .get_or_insert_with(Default::default)
+            .insert(
+                identity.into(),
+                Signature {
+                    certificate,
+                    info,
+                    signature,
+                    extra: Extra::new(),
+                },
+            );
+        Ok(())
+    }
+
+    /// Whether the torrent has a valid signature by `identity`.
+    ///
+    /// `verify` is called with the [`signed_data`] and the signature, and
+    /// checks the signature against the key, or the certificate, of
+    /// `identity`.
+    pub fn verify(&self, identity: &str, verify: impl FnOnce(&[u8], &Signature) -> bool) -> bool {
+        let signature = match self
+            .torrent
+            .signatures
+            .as_ref()
+            .and_then(|signatures| signatures.get(identity))
+        {
+            Some(signature) => signature,
+            None => return false,
+        };
+        match signed_data(self.raw_info, signature.info.as_ref()) {
+            Ok(data) => verify(&data, signature),
+            Err(_) => false,
+        }
+    }
+
+    /// Remove all signatures.
+    pub fn clear_signatures(&mut self) {
+        self.torrent.signatures = None;
+    }
+
+    /// Encode the edited torrent, containing the original `info` dict.
+    pub fn to_bencode(&self) -> Result<Vec<u8>, encoding::Error> {
+        let mut encoder = Encoder::new().with_max_depth(MetaInfo::MAX_DEPTH);
+        encoder.emit_with(|e| {
+            self.torrent
+                .encode_with_info(e, |e| e.emit_raw(self.raw_info))
+        })?;
+        encoder.get_output()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{torrent::info_hash, value::Value};
+
+    #[test]
+    fn info_is_copied_verbatim() {
+        let torrent = b"d7:comment3:old4:infod6:lengthi1e4:name1:a12:piece lengthi1e\
+                        6:pieces0:1:zi1eee";
+        let mut editor = TorrentEditor::new(torrent).unwrap();
+        editor.set_comment(None);
+        editor.set_creation_date(Some(1));
+        editor.set_trackers(&TrackerTiers::from(vec![vec!["a".to_string()]]));
+
+        let edited = editor.to_bencode().unwrap();
+        assert_eq!(
+            edited,
+            &b"d8:announce1:a13:creation datei1e4:infod6:lengthi1e4:name1:a\
+               12:piece lengthi1e6:pieces0:1:zi1eee"[..]
+        );
+        assert_eq!(editor.info_hash(), info_hash(torrent).unwrap());
+        assert_eq!(editor.info_hashes(), InfoHash::V1(editor.info_hash()));
+        assert_eq!(info_hash(&edited).unwrap(), info_hash(torrent).unwrap());
+        assert_eq!(editor.metainfo().comment, None);
+    }
+
+    #[test]
+    fn signatures_cover_both_info_dicts() {
+        let torrent = b"d4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:ee";
+        let mut editor = TorrentEditor::new(torrent).unwrap();
+        let mut info = Extra::new();
+        info.insert(b"expires".to_vec(), Value::Integer(9));
+        editor
+            .sign("a", Some(info), Some(b"cert".to_vec()), |data| {
+                data.to_vec()
+            })
+            .unwrap();
+
+        let signed = editor.to_bencode().unwrap();
+        assert_eq!(
+            signed,
+            &b"d4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:e10:signatures\
+               d1:ad11:certificate4:cert4:infod7:expiresi9ee9:signature\
+               64:d6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:ed7:expiresi9eeeee"[..]
+        );
+        assert_eq!(info_hash(&signed).unwrap(), info_hash(torrent).unwrap());
+
+        let editor = TorrentEditor::new(&signed).unwrap();
+        assert!(editor.verify("a", |data, signature| {
+            signature.certificate.as_deref() == Some(&b"cert"[..]) && signature.signature == data
+        }));
+        assert!(!editor.verify("a", |_, _| false));
+
+        assert!(TorrentEditor::new(
+            b"d4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:e10:signaturesd1:adeee"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn trackers_are_rewritten_once() {
+        let torrent = b"d8:announce1:a13:announce-listll1:ael1:b1:cee\
+                        4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:ee";
+        let mut editor = TorrentEditor::new(torrent).unwrap();
+        let mut calls = 0;
+        let changes = editor.rewrite_trackers(|url| {
+            calls += 1;
+            match url {
+                "a" => Some("x".to_string()),
+                "b" => None,
+                url => Some(url.to_string()),
+            }
+        });
+
+        assert_eq!(calls, 3);
+        assert_eq!(
+            changes,
+            vec![
+                TrackerChange {
+                    old: "a".to_string(),
+                    new: Some("x".to_string())
+                },
+                TrackerChange {
+                    old: "b".to_string(),
+                    new: None
+                },
+            ]
+        );
+        assert_eq!(editor.metainfo().announce.as_deref(), Some("x"));
+        assert_eq!(
+            editor.metainfo().announce_list,
+            Some(vec![vec!["x".to_string()], vec!["c".to_string()]])
+        );
+
+        assert_eq!(editor.rewrite_trackers(|_| None).len(), 2);
+        assert_eq!(editor.metainfo().announce, None);
+        assert_eq!(editor.metainfo().announce_list, None);
+    }
+
+    #[test]
+    fn invalid_torrents_are_rejected() {
+        assert!(TorrentEditor::new(b"de").is_err());
+        assert!(TorrentEditor::new(b"d4:infodee").is_err());
+        assert!(matches!(
+            rewrite_trackers(b"de", |url| Some(url.to_string())),
+            Err(EditError::Decoding(_))
+        ));
+    }
+}
@@ -0,0 +1,206 @@
+//! The handshake of the extension protocol, as specified in
+//! [BEP 10](http://bittorrent.org/beps/bep_0010.html).
+
+use std::collections::BTreeMap;
+
+use crate::{
+    decoding::{self, FromBencode, Object, ResultExt},
+    encoding::{self, SingleItemEncoder, ToBencode},
+    torrent::metainfo::{decode_extra, Extra},
+    value::Value,
+};
+
+/// The extended handshake, the dict sent as extended message 0 after the
+/// BitTorrent handshake.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{
+///     decoding::FromBencode,
+///     encoding::ToBencode,
+///     torrent::ExtendedHandshake,
+/// };
+///
+/// let handshake = ExtendedHandshake::new()
+///     .with_extension("ut_metadata", 2)
+///     .with_extension("ut_pex", 1)
+///     .with_metadata_size(31_235);
+///
+/// let encoded = handshake.to_bencode().unwrap();
+/// assert_eq!(
+///     encoded,
+///     &b"d1:md11:ut_metadatai2e6:ut_pexi1ee13:metadata_sizei31235ee"[..]
+/// );
+///
+/// let decoded = ExtendedHandshake::from_bencode(&encoded).unwrap();
+/// assert_eq!(decoded.message_id("ut_metadata"), Some(2));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtendedHandshake {
+    /// The extended message id of each supported extension, keyed by its name.
+    /// An id of 0 disables an extension announced in an earlier handshake.
+    pub extensions: BTreeMap<String, u8>,
+    /// The local TCP listen port of the sender, given as `p`.
+    pub port: Option<u16>,
+    /// The client name and version of the sender, given as `v`.
+    pub version: Option<String>,
+    /// The number of outstanding request messages the sender supports.
+    pub reqq: Option<u64>,
+    /// The size of the info dict in bytes, for the metadata exchange of
+    /// [BEP 9](http://bittorrent.org/beps/bep_0009.html).
+    pub metadata_size: Option<u64>,
+    /// All other fields of the handshake dict, such as `yourip`.
+    pub extra: Extra,
+}
+
+impl ExtendedHandshake {
+    /// Create a handshake without any extensions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Announce support for the extension `name` with the message id `id`.
+    #[must_use]
+    pub fn with_extension(mut self, name: impl Into<String>, id: u8) -> Self {
+        self.extensions.insert(name.into(), id);
+        self
+    }
+
+    /// Set the local TCP listen port.
+    #[must_use]
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Set the client name and version.
+    #[must_use]
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Set the number of outstanding request messages supported.
+    #[must_use]
+    pub fn with_reqq(mut self, reqq: u64) -> Self {
+        self.reqq = Some(reqq);
+        self
+    }
+
+    /// Set the size of the info dict.
+    #[must_use]
+    pub fn with_metadata_size(mut self, metadata_size: u64) -> Self {
+        self.metadata_size = Some(metadata_size);
+        self
+    }
+
+    /// The message id for the extension `name`, unless it isn't supported or
+    /// has been disabled.
+    pub fn message_id(&self, name: &str) -> Option<u8> {
+        self.extensions.get(name).copied().filter(|&id| id != 0)
+    }
+}
+
+impl FromBencode for ExtendedHandshake {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut handshake = ExtendedHandshake::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"m", value) => {
+                    let mut extensions = value.try_into_dictionary().context("m")?;
+                    while let Some((name, id)) = extensions.next_pair()? {
+                        let name = String::from_utf8_lossy(name).into_owned();
+                        let id = u8::decode_bencode_object(id).context(&name).context("m")?;
+                        handshake.extensions.insert(name, id);
+                    }
+                },
+                (b"metadata_size", value) => {
+                    handshake.metadata_size = u64::decode_bencode_object(value)
+                        .context("metadata_size")
+                        .map(Some)?;
+                },
+                (b"p", value) => {
+                    handshake.port = u16::decode_bencode_object(value).context("p").map(Some)?;
+                },
+                (b"reqq", value) => {
+                    handshake.reqq = u64::decode_bencode_object(value)
+                        .context("reqq")
+                        .map(Some)?;
+                },
+                (b"v", value) => {
+                    handshake.version = value
+                        .try_into_bytes()
+                        .context("v")
+                        .map(|version| Some(String::from_utf8_lossy(version).into_owned()))?;
+                },
+                (key, value) => decode_extra(&mut handshake.extra, key, value)?,
+            }
+        }
+
+        Ok(handshake)
+    }
+}
+
+impl ToBencode for ExtendedHandshake {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            e.emit_pair_with(b"m", |e| {
+                e.emit_dict(|mut e| {
+                    for (name, id) in &self.extensions {
+                        e.emit_pair(name.as_bytes(), id)?;
+                    }
+                    Ok(())
+                })
+            })?;
+            if let Some(metadata_size) = self.metadata_size {
+                e.emit_pair(b"metadata_size", metadata_size)?;
+            }
+            if let Some(port) = self.port {
+                e.emit_pair(b"p", port)?;
+            }
+            if let Some(reqq) = self.reqq {
+                e.emit_pair(b"reqq", reqq)?;
+            }
+            if let Some(version) = &self.version {
+                e.emit_pair(b"v", version)?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshakes_round_trip() {
+        let encoded = b"d1:ei0e1:md11:LT_metadatai1e6:ut_pexi0ee1:pi6881e4:reqqi500e\
+                        1:v13:\xc2\xb5Torrent 1.26:yourip4:\x7f\x00\x00\x01e";
+
+        let handshake = ExtendedHandshake::from_bencode(encoded).unwrap();
+        assert_eq!(handshake.message_id("LT_metadata"), Some(1));
+        assert_eq!(handshake.message_id("ut_pex"), None);
+        assert_eq!(handshake.message_id("ut_metadata"), None);
+        assert_eq!(handshake.port, Some(6881));
+        assert_eq!(handshake.reqq, Some(500));
+        assert_eq!(handshake.version.as_deref(), Some("\u{b5}Torrent 1.2"));
+        assert!(handshake.extra.contains_key(&b"yourip"[..]));
+        assert_eq!(handshake.to_bencode().unwrap(), &encoded[..]);
+    }
+
+    #[test]
+    fn invalid_handshakes_are_rejected() {
+        assert!(ExtendedHandshake::from_bencode(b"d1:md6:ut_pexi256eee").is_err());
+        assert!(ExtendedHandshake::from_bencode(b"d1:pi70000ee").is_err());
+        assert!(ExtendedHandshake::from_bencode(b"d1:mi1ee").is_err());
+    }
+}
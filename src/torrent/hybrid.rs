@@ -0,0 +1,209 @@
+//! Checking that the v1 and v2 metadata of hybrid torrents agree.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::torrent::{Layout, MetaInfo};
+
+/// An inconsistency between the v1 and v2 metadata of a hybrid torrent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HybridError {
+    reason: &'static str,
+    path: String,
+}
+
+impl HybridError {
+    fn new(reason: &'static str, path: impl Into<String>) -> Self {
+        HybridError {
+            reason,
+            path: path.into(),
+        }
+    }
+
+    /// A description of the inconsistency.
+    pub fn reason(&self) -> &str {
+        self.reason
+    }
+
+    /// The `/`-separated path of the file the inconsistency was found at, or an
+    /// empty string if it concerns the whole torrent.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Display for HybridError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "inconsistent hybrid torrent: {}", self.reason)
+        } else {
+            write!(
+                f,
+                "inconsistent hybrid torrent: {} at {}",
+                self.reason, self.path
+            )
+        }
+    }
+}
+
+impl std::error::Error for HybridError {}
+
+impl MetaInfo {
+    /// Check that the torrent contains both v1 and v2 metadata and that they
+    /// describe the same files.
+    ///
+    /// The v1 files, ignoring padding files, must match the files of the
+    /// `file tree` in order, path and length, and must each start at a piece
+    /// boundary. The number of v1 pieces and the size of every piece layer must
+    /// match the lengths of the files. The hashes themselves can only be checked
+    /// against the files' contents.
+    pub fn check_hybrid(&self) -> Result<(), HybridError> {
+        let info = &self.info;
+        let (pieces, layout, tree) = match (&info.pieces, &info.layout, &info.file_tree) {
+            (Some(pieces), Some(layout), Some(tree)) => (pieces, layout, tree),
+            _ => return Err(HybridError::new("missing v1 or v2 metadata", "")),
+        };
+
+        let mut v1_files = Vec::new();
+        let mut offset = 0;
+        match layout {
+            Layout::SingleFile { length } => {
+                v1_files.push((info.name.clone(), *length));
+                offset = *length;
+            },
+            Layout::MultiFile { files } => {
+                for file in files {
+                    let path = file.path.join("/");
+                    if file.is_padding() {
+                        offset += file.length;
+                        continue;
+                    }
+                    if file.length > 0 && offset % info.piece_length != 0 {
+                        return Err(HybridError::new("file is not aligned to a piece", path));
+                    }
+                    offset += file.length;
+                    v1_files.push((path, file.length));
+                }
+            },
+        }
+
+        let pieces_needed = (offset + info.piece_length - 1) / info.piece_length;
+        if pieces.len() as u64 != pieces_needed * 20 {
+            return Err(HybridError::new("wrong number of v1 pieces", ""));
+        }
+
+        let mut v2_files = tree.files();
+        for (path, length) in v1_files {
+            let (v2_path, file) = match v2_files.next() {
+                Some(file) => file,
+                None => return Err(HybridError::new("file is missing from the file tree", path)),
+            };
+            if v2_path.join("/") != path || file.length != length {
+                return Err(HybridError::new("file differs from the file tree", path));
+            }
+
+            let root = match (&file.pieces_root, file.length) {
+                (_, 0) => continue,
+                (Some(root), _) => root,
+                (None, _) => return Err(HybridError::new("file has no pieces root", path)),
+            };
+            if file.length <= info.piece_length {
+                continue;
+            }
+            let layer = self
+                .piece_layers
+                .as_ref()
+                .and_then(|layers| layers.get(root));
+            let layer_pieces = (file.length + info.piece_length - 1) / info.piece_length;
+            match layer {
+                Some(layer) if layer.len() as u64 == layer_pieces * 32 => {},
+                Some(_) => return Err(HybridError::new("piece layer has the wrong size", path)),
+                None => return Err(HybridError::new("file has no piece layer", path)),
+            }
+        }
+        if let Some((path, _)) = v2_files.next() {
+            return Err(HybridError::new(
+                "file is missing from the v1 files",
+                path.join("/"),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        decoding::FromBencode,
+        torrent::{FileEntry, FileTree},
+    };
+
+    fn torrent(info: &str, piece_layers: &str) -> MetaInfo {
+        let encoded = format!("d4:infod{}e12:piece layersd{}ee", info, piece_layers);
+        MetaInfo::from_bencode(encoded.as_bytes()).unwrap()
+    }
+
+    fn single_file_info(length: u64, tree_length: u64) -> String {
+        format!(
+            "9:file treed1:ad0:d6:lengthi{}e11:pieces root32:{}eee\
+             6:lengthi{}e12:meta versioni2e4:name1:a12:piece lengthi16384e6:pieces20:{}",
+            tree_length,
+            "r".repeat(32),
+            length,
+            "p".repeat(20),
+        )
+    }
+
+    #[test]
+    fn consistent_torrents_pass() {
+        assert_eq!(torrent(&single_file_info(5, 5), "").check_hybrid(), Ok(()));
+    }
+
+    #[test]
+    fn inconsistencies_are_found() {
+        let error = torrent(&single_file_info(5, 6), "")
+            .check_hybrid()
+            .unwrap_err();
+        assert_eq!(error.reason(), "file differs from the file tree");
+        assert_eq!(error.path(), "a");
+
+        let error = torrent(&single_file_info(20_000, 20_000), "")
+            .check_hybrid()
+            .unwrap_err();
+        assert_eq!(error.reason(), "wrong number of v1 pieces");
+
+        let mut v1_only = torrent(&single_file_info(5, 5), "");
+        v1_only.info.file_tree = None;
+        assert!(v1_only.check_hybrid().is_err());
+    }
+
+    #[test]
+    fn padding_files_are_skipped() {
+        let mut hybrid = torrent(&single_file_info(5, 5), "");
+        hybrid.info.file_tree = Some(
+            FileTree::from_bencode(
+                format!(
+                    "d1:ad0:d6:lengthi5e11:pieces root32:{0}ee1:bd0:d6:lengthi1e11:pieces root32:{0}eeee",
+                    "r".repeat(32)
+                )
+                .as_bytes(),
+            )
+            .unwrap(),
+        );
+        hybrid.info.pieces = Some(vec![b'p'; 40]);
+        let files = b"ld6:lengthi5e4:pathl1:aeed4:attr1:p6:lengthi16379e4:pathl4:.pad5:16379eed6:lengthi1e4:pathl1:beee";
+        let files = Vec::<FileEntry>::from_bencode(files).unwrap();
+        hybrid.info.layout = Some(Layout::MultiFile { files });
+        assert_eq!(hybrid.check_hybrid(), Ok(()));
+
+        if let Some(Layout::MultiFile { files }) = &mut hybrid.info.layout {
+            files.remove(1);
+        }
+        hybrid.info.pieces = Some(vec![b'p'; 20]);
+        let error = hybrid.check_hybrid().unwrap_err();
+        assert_eq!(error.reason(), "file is not aligned to a piece");
+        assert_eq!(error.path(), "b");
+    }
+}
@@ -0,0 +1,281 @@
+//! Computing infohashes from the exact bytes of the `info` dict.
+
+use std::io::BufRead;
+
+use crate::{
+    decoding::{Decoder, Error, FromBencode, ResultExt, StreamDecoder},
+    state_tracker::StructureError,
+    torrent::{
+        sha1::{sha1, Sha1},
+        sha256::sha256,
+        Info, Sha256Hash,
+    },
+};
+
+/// The infohashes of a torrent, which depend on the versions of the protocol
+/// its `info` dict supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InfoHash {
+    /// The SHA-1 infohash of a v1 torrent.
+    V1([u8; 20]),
+    /// The SHA-256 infohash of a v2 torrent.
+    V2(Sha256Hash),
+    /// Both infohashes of a hybrid torrent.
+    Hybrid([u8; 20], Sha256Hash),
+}
+
+impl InfoHash {
+    /// Combine the hashes of a torrent, or return `None` if there are none.
+    pub fn from_hashes(v1: Option<[u8; 20]>, v2: Option<Sha256Hash>) -> Option<Self> {
+        match (v1, v2) {
+            (Some(v1), None) => Some(InfoHash::V1(v1)),
+            (None, Some(v2)) => Some(InfoHash::V2(v2)),
+            (Some(v1), Some(v2)) => Some(InfoHash::Hybrid(v1, v2)),
+            (None, None) => None,
+        }
+    }
+
+    /// The v1 infohash, if the torrent has one.
+    pub fn v1(&self) -> Option<[u8; 20]> {
+        match self {
+            InfoHash::V1(v1) | InfoHash::Hybrid(v1, _) => Some(*v1),
+            InfoHash::V2(_) => None,
+        }
+    }
+
+    /// The v2 infohash, if the torrent has one.
+    pub fn v2(&self) -> Option<Sha256Hash> {
+        match self {
+            InfoHash::V2(v2) | InfoHash::Hybrid(_, v2) => Some(*v2),
+            InfoHash::V1(_) => None,
+        }
+    }
+
+    /// The v2 infohash truncated to 20 bytes, which v2 peers use in place of a
+    /// v1 infohash to announce to trackers and the DHT.
+    pub fn truncated_v2(&self) -> Option<[u8; 20]> {
+        self.v2().map(|v2| {
+            let mut truncated = [0; 20];
+            truncated.copy_from_slice(&v2[..20]);
+            truncated
+        })
+    }
+
+    /// The 20-byte hash to announce the torrent with: the v1 infohash, or the
+    /// truncated v2 infohash if there is none.
+    ///
+    /// Hybrid torrents have a second swarm, of v2 peers, announced with the
+    /// [`truncated_v2`](InfoHash::truncated_v2) infohash.
+    pub fn announce_hash(&self) -> [u8; 20] {
+        match self {
+            InfoHash::V1(v1) | InfoHash::Hybrid(v1, _) => *v1,
+            InfoHash::V2(_) => self.truncated_v2().unwrap(),
+        }
+    }
+}
+
+/// Find the encoded `info` dict of an encoded torrent.
+///
+/// The returned bytes are exactly those of the input, so they are suitable for
+/// hashing even if the torrent contains unknown fields or wasn't produced by
+/// bendy. The rest of the torrent is validated as well.
+pub fn raw_info(torrent: &[u8]) -> Result<&[u8], Error> {
+    let mut decoder = Decoder::new(torrent);
+    let mut info = None;
+    match decoder.next_object()? {
+        Some(object) => {
+            let mut dict = object.try_into_dictionary()?;
+            while let Some((key, value)) = dict.next_pair()? {
+                if key == b"info" {
+                    info = Some(value.try_into_dictionary().context("info")?.into_raw()?);
+                }
+            }
+        },
+        None => return Err(Error::from(StructureError::UnexpectedEof)),
+    }
+    if decoder.next_object()?.is_some() {
+        return Err(Error::trailing_data());
+    }
+
+    info.ok_or_else(|| Error::missing_field("info"))
+}
+
+/// Compute the v1 infohash of an encoded torrent, the SHA-1 hash of its `info`
+/// dict.
+///
+/// The hash is computed over the `info` dict exactly as it appears in
+/// `torrent`, as found by [`raw_info`]. Decoding a [`MetaInfo`] and hashing its
+/// re-encoded `info` instead changes the hash if the original encoding wasn't
+/// reproduced exactly.
+///
+/// [`MetaInfo`]: crate::torrent::MetaInfo
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::info_hash;
+///
+/// let torrent = b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces0:ee";
+/// let hash = info_hash(torrent).unwrap();
+///
+/// assert_eq!(hash[..4], [0xe7, 0x08, 0x21, 0x09]);
+/// ```
+pub fn info_hash(torrent: &[u8]) -> Result<[u8; 20], Error> {
+    raw_info(torrent).map(sha1)
+}
+
+/// Compute the v1 infohash of a torrent read from `reader`, hashing the `info`
+/// dict while it's read.
+///
+/// Unlike [`info_hash`], this doesn't need the torrent in memory: the
+/// [`StreamDecoder`] only keeps short byte strings, and long ones like
+/// `pieces` are hashed as they pass by.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::{info_hash, info_hash_from_reader};
+///
+/// let torrent = b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces0:ee";
+///
+/// assert_eq!(
+///     info_hash_from_reader(&torrent[..]).unwrap(),
+///     info_hash(torrent).unwrap()
+/// );
+/// ```
+pub fn info_hash_from_reader<R: BufRead>(reader: R) -> Result<[u8; 20], Error> {
+    let mut decoder = StreamDecoder::new(reader).with_digest(b"info", Sha1::new());
+    while decoder.next_token()?.is_some() {}
+    decoder
+        .into_digest()
+        .map(Sha1::finish)
+        .ok_or_else(|| Error::missing_field("info"))
+}
+
+/// Compute the v2 infohash of an encoded torrent, the SHA-256 hash of its
+/// `info` dict.
+///
+/// Hybrid torrents have both a v1 and a v2 infohash, computed over the same
+/// bytes. Like [`info_hash`], this hashes the `info` dict exactly as it appears
+/// in `torrent`.
+pub fn info_hash_v2(torrent: &[u8]) -> Result<Sha256Hash, Error> {
+    raw_info(torrent).map(sha256)
+}
+
+/// Compute the infohashes of an encoded torrent, as many as its `info` dict
+/// has metadata for.
+///
+/// Like [`info_hash`], this hashes the `info` dict exactly as it appears in
+/// `torrent`. An `info` dict without v2 metadata has a v1 infohash, even if it
+/// doesn't have `pieces` either.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::{info_hash, info_hashes, InfoHash};
+///
+/// let torrent = b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces0:ee";
+///
+/// assert_eq!(
+///     info_hashes(torrent).unwrap(),
+///     InfoHash::V1(info_hash(torrent).unwrap())
+/// );
+/// ```
+pub fn info_hashes(torrent: &[u8]) -> Result<InfoHash, Error> {
+    let raw_info = raw_info(torrent)?;
+    let info = Info::from_bencode(raw_info).context("info")?;
+    Ok(hash_info(&info, raw_info))
+}
+
+/// Hash `encoded`, the encoding of `info`, with the versions `info` supports.
+pub(super) fn hash_info(info: &Info, encoded: &[u8]) -> InfoHash {
+    if !info.is_v2() {
+        InfoHash::V1(sha1(encoded))
+    } else if info.is_v1() {
+        InfoHash::Hybrid(sha1(encoded), sha256(encoded))
+    } else {
+        InfoHash::V2(sha256(encoded))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static DEBIAN: &[u8] =
+        include_bytes!("../../examples/torrent_files/debian-9.4.0-amd64-netinst.iso.torrent");
+
+    #[test]
+    fn debian_info_hash_matches() {
+        let hash: String = info_hash(DEBIAN)
+            .unwrap()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        assert_eq!(hash, "7431a969b347e14bba641b3517c024f7b40dfb7f");
+    }
+
+    #[test]
+    fn streamed_info_hash_matches() {
+        let expected = info_hash(DEBIAN).unwrap();
+        assert_eq!(info_hash_from_reader(DEBIAN).unwrap(), expected);
+
+        let tiny = std::io::BufReader::with_capacity(7, DEBIAN);
+        assert_eq!(info_hash_from_reader(tiny).unwrap(), expected);
+
+        assert!(info_hash_from_reader(&b"d4:name1:ae"[..]).is_err());
+        assert!(info_hash_from_reader(&DEBIAN[..DEBIAN.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn v2_info_hash_hashes_the_same_bytes() {
+        assert_eq!(
+            info_hash_v2(DEBIAN).unwrap(),
+            sha256(raw_info(DEBIAN).unwrap())
+        );
+    }
+
+    #[test]
+    fn info_hashes_follow_the_versions() {
+        let v2 = b"d4:infod9:file treede12:meta versioni2e4:name1:a\
+                   12:piece lengthi16384eee";
+        let hybrid = b"d4:infod9:file treede6:lengthi1e12:meta versioni2e4:name1:a\
+                       12:piece lengthi16384e6:pieces0:ee";
+
+        let hash = info_hashes(DEBIAN).unwrap();
+        assert_eq!(hash, InfoHash::V1(info_hash(DEBIAN).unwrap()));
+        assert_eq!(hash.announce_hash(), info_hash(DEBIAN).unwrap());
+
+        let hash = info_hashes(v2).unwrap();
+        assert_eq!(hash, InfoHash::V2(info_hash_v2(v2).unwrap()));
+        assert_eq!(hash.v1(), None);
+        assert_eq!(hash.announce_hash()[..], info_hash_v2(v2).unwrap()[..20]);
+
+        let hash = info_hashes(hybrid).unwrap();
+        assert_eq!(
+            InfoHash::from_hashes(hash.v1(), hash.v2()),
+            Some(InfoHash::Hybrid(
+                info_hash(hybrid).unwrap(),
+                info_hash_v2(hybrid).unwrap()
+            ))
+        );
+        assert_eq!(hash.announce_hash(), info_hash(hybrid).unwrap());
+        assert_eq!(InfoHash::from_hashes(None, None), None);
+    }
+
+    #[test]
+    fn raw_info_is_a_subslice() {
+        let torrent = b"d1:ai1e4:infod1:xi1ee1:zi2ee";
+        assert_eq!(raw_info(torrent).unwrap(), b"d1:xi1ee");
+    }
+
+    #[test]
+    fn invalid_torrents_are_rejected() {
+        assert!(raw_info(b"").is_err());
+        assert!(raw_info(b"de").is_err());
+        assert!(raw_info(b"d4:infoi1ee").is_err());
+        assert!(raw_info(b"d4:infodee1:ai1ee").is_err());
+        assert!(raw_info(b"d4:infode1:ai1ee").is_err());
+        assert!(raw_info(b"le").is_err());
+    }
+}
@@ -0,0 +1,890 @@
+//! The KRPC messages of the mainline DHT, as specified in
+//! [BEP 5](http://bittorrent.org/beps/bep_0005.html).
+//!
+//! Every [`Message`] is a query, a response or an error, tied together by a
+//! transaction id chosen by the querying node. Responses don't say which query
+//! they answer, so all of their fields are optional and have to be interpreted
+//! based on the outstanding query with the same transaction id.
+
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    net::{IpAddr, SocketAddr},
+};
+
+use crate::{
+    decoding::{self, Decoder, FromBencode, Object, ResultExt},
+    encoding::{self, AsString, SingleItemEncoder, ToBencode},
+    state_tracker::StructureError,
+    torrent::{
+        compact::{
+            decode_compact_peers, decode_compact_peers6, encode_compact_peers,
+            encode_compact_peers6, COMPACT_PEER6_LEN, COMPACT_PEER_LEN,
+        },
+        metainfo::{decode_extra, Extra},
+    },
+    value::Value,
+};
+
+/// The 160-bit id of a DHT node.
+pub type NodeId = [u8; 20];
+
+/// The number of bytes of a node in the compact `nodes` form: a node id followed
+/// by a compact IPv4 address.
+pub const COMPACT_NODE_LEN: usize = 26;
+
+/// The number of bytes of a node in the compact `nodes6` form of
+/// [BEP 32](http://bittorrent.org/beps/bep_0032.html): a node id followed by a
+/// compact IPv6 address.
+pub const COMPACT_NODE6_LEN: usize = 38;
+
+/// The contact information of a DHT node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeInfo {
+    /// The id of the node.
+    pub id: NodeId,
+    /// The address of the node.
+    pub addr: SocketAddr,
+}
+
+impl NodeInfo {
+    /// Decode the contact information of a single node, in either the IPv4 or
+    /// the IPv6 compact form depending on the length of `bytes`.
+    pub fn from_compact(bytes: &[u8]) -> Option<Self> {
+        let addr = match bytes.len() {
+            COMPACT_NODE_LEN => SocketAddr::V4(decode_compact_peers(&bytes[20..])[0]),
+            COMPACT_NODE6_LEN => SocketAddr::V6(decode_compact_peers6(&bytes[20..])[0]),
+            _ => return None,
+        };
+        let mut id = [0; 20];
+        id.copy_from_slice(&bytes[..20]);
+        Some(NodeInfo { id, addr })
+    }
+
+    /// Encode the contact information in the compact form matching the family
+    /// of its address.
+    pub fn to_compact(&self) -> Vec<u8> {
+        let mut bytes = self.id.to_vec();
+        match self.addr {
+            SocketAddr::V4(addr) => bytes.extend(encode_compact_peers(&[addr])),
+            SocketAddr::V6(addr) => bytes.extend(encode_compact_peers6(&[addr])),
+        }
+        bytes
+    }
+}
+
+/// Decode a compact `nodes` string of IPv4 nodes.
+///
+/// Trailing bytes which don't form a whole node are ignored. Messages decoded in
+/// [`DecodeMode::Strict`] reject them instead.
+pub fn decode_compact_nodes(bytes: &[u8]) -> Vec<NodeInfo> {
+    bytes
+        .chunks_exact(COMPACT_NODE_LEN)
+        .filter_map(NodeInfo::from_compact)
+        .collect()
+}
+
+/// Decode a compact `nodes6` string of IPv6 nodes, ignoring trailing bytes like
+/// [`decode_compact_nodes`].
+pub fn decode_compact_nodes6(bytes: &[u8]) -> Vec<NodeInfo> {
+    bytes
+        .chunks_exact(COMPACT_NODE6_LEN)
+        .filter_map(NodeInfo::from_compact)
+        .collect()
+}
+
+/// Encode the IPv4 nodes of `nodes` as a compact `nodes` string. Nodes with an
+/// IPv6 address belong in `nodes6` and are skipped.
+pub fn encode_compact_nodes(nodes: &[NodeInfo]) -> Vec<u8> {
+    nodes
+        .iter()
+        .filter(|node| node.addr.is_ipv4())
+        .flat_map(NodeInfo::to_compact)
+        .collect()
+}
+
+/// Encode the IPv6 nodes of `nodes` as a compact `nodes6` string. Nodes with an
+/// IPv4 address belong in `nodes` and are skipped.
+pub fn encode_compact_nodes6(nodes: &[NodeInfo]) -> Vec<u8> {
+    nodes
+        .iter()
+        .filter(|node| node.addr.is_ipv6())
+        .flat_map(NodeInfo::to_compact)
+        .collect()
+}
+
+/// How strictly to treat malformed fields while decoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Reject messages with unsorted keys, trailing bytes, truncated `nodes` or
+    /// `nodes6`, or `values` entries of the wrong size.
+    Strict,
+    /// Accept unsorted keys and trailing bytes, drop incomplete nodes at the end
+    /// of `nodes` and `nodes6` and skip `values` entries of the wrong size, as sent by some
+    /// nodes on the network.
+    Lenient,
+}
+
+/// A KRPC message.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{
+///     encoding::ToBencode,
+///     torrent::krpc::{Body, Message, Method, Query},
+/// };
+///
+/// let ping = Message::query(
+///     b"aa".to_vec(),
+///     Query::new(*b"abcdefghij0123456789", Method::Ping),
+/// );
+///
+/// assert_eq!(
+///     ping.to_bencode().unwrap(),
+///     &b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe"[..]
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    /// The transaction id, echoed in the response to a query.
+    pub transaction_id: Vec<u8>,
+    /// The client version of the sender, given as `v`.
+    pub version: Option<Vec<u8>>,
+    /// The query, response or error.
+    pub body: Body,
+    /// All other fields of the message dict.
+    pub extra: Extra,
+}
+
+/// The contents of a [`Message`], depending on its type `y`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Body {
+    /// A query, `y` = `q`.
+    Query(Query),
+    /// A response, `y` = `r`.
+    Response(Response),
+    /// An error, `y` = `e`.
+    Error(Error),
+}
+
+/// A query and its arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Query {
+    /// The id of the querying node.
+    pub id: NodeId,
+    /// The query method and its specific arguments.
+    pub method: Method,
+    /// All other arguments.
+    pub extra: Extra,
+}
+
+/// The method of a [`Query`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// `ping`: check that a node is reachable.
+    Ping,
+    /// `find_node`: find the closest nodes to a target id.
+    FindNode {
+        /// The id to find nodes close to.
+        target: NodeId,
+    },
+    /// `get_peers`: find peers of a torrent.
+    GetPeers {
+        /// The infohash of the torrent.
+        info_hash: [u8; 20],
+    },
+    /// `announce_peer`: announce that the querying node is a peer of a torrent.
+    AnnouncePeer {
+        /// The infohash of the torrent.
+        info_hash: [u8; 20],
+        /// The port the peer listens on.
+        port: u16,
+        /// Whether to use the source port of the query instead of `port`.
+        implied_port: bool,
+        /// The token received from an earlier `get_peers` response.
+        token: Vec<u8>,
+    },
+    /// Any other method. Its arguments are in the `extra` map of the query.
+    Other(String),
+}
+
+/// A response to a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    /// The id of the responding node.
+    pub id: NodeId,
+    /// IPv4 nodes close to the target, as returned by `find_node` and
+    /// `get_peers`.
+    pub nodes: Option<Vec<NodeInfo>>,
+    /// IPv6 nodes close to the target, as specified in
+    /// [BEP 32](http://bittorrent.org/beps/bep_0032.html).
+    pub nodes6: Option<Vec<NodeInfo>>,
+    /// Peers of the torrent, as returned by `get_peers`.
+    pub values: Option<Vec<SocketAddr>>,
+    /// The token to send with a later `announce_peer`, as returned by
+    /// `get_peers`.
+    pub token: Option<Vec<u8>>,
+    /// All other fields of the response.
+    pub extra: Extra,
+}
+
+/// An error returned in place of a response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    /// The error code, such as [`Error::GENERIC`].
+    pub code: i64,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl Error {
+    /// A generic error.
+    pub const GENERIC: i64 = 201;
+    /// An error of the responding node.
+    pub const SERVER: i64 = 202;
+    /// A malformed packet, invalid arguments or a bad token.
+    pub const PROTOCOL: i64 = 203;
+    /// The query method isn't known.
+    pub const METHOD_UNKNOWN: i64 = 204;
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "KRPC error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Generates transaction ids for outgoing queries.
+///
+/// The ids are two bytes long, as in most clients, and repeat only after
+/// 65536 queries.
+#[derive(Clone, Debug, Default)]
+pub struct TransactionIds {
+    next: u16,
+}
+
+impl TransactionIds {
+    /// Create a generator starting at `start`, such as a random number.
+    pub fn new(start: u16) -> Self {
+        TransactionIds { next: start }
+    }
+
+    /// Return the next transaction id.
+    pub fn next_id(&mut self) -> Vec<u8> {
+        let id = self.next;
+        self.next = self.next.wrapping_add(1);
+        id.to_be_bytes().to_vec()
+    }
+}
+
+impl Query {
+    /// Create a query without additional arguments.
+    pub fn new(id: NodeId, method: Method) -> Self {
+        Query {
+            id,
+            method,
+            extra: Extra::new(),
+        }
+    }
+
+    /// The name of the query method, given as `q`.
+    pub fn method_name(&self) -> &str {
+        match &self.method {
+            Method::Ping => "ping",
+            Method::FindNode { .. } => "find_node",
+            Method::GetPeers { .. } => "get_peers",
+            Method::AnnouncePeer { .. } => "announce_peer",
+            Method::Other(name) => name,
+        }
+    }
+}
+
+impl Response {
+    /// Create a response without any optional fields.
+    pub fn new(id: NodeId) -> Self {
+        Response {
+            id,
+            nodes: None,
+            nodes6: None,
+            values: None,
+            token: None,
+            extra: Extra::new(),
+        }
+    }
+}
+
+impl Message {
+    /// Create a query message.
+    pub fn query(transaction_id: Vec<u8>, query: Query) -> Self {
+        Message::new(transaction_id, Body::Query(query))
+    }
+
+    /// Create a response message, with the transaction id of `query`.
+    pub fn response(query: &Message, response: Response) -> Self {
+        Message::new(query.transaction_id.clone(), Body::Response(response))
+    }
+
+    /// Create an error message, with the transaction id of `query`.
+    pub fn error(query: &Message, error: Error) -> Self {
+        Message::new(query.transaction_id.clone(), Body::Error(error))
+    }
+
+    fn new(transaction_id: Vec<u8>, body: Body) -> Self {
+        Message {
+            transaction_id,
+            version: None,
+            body,
+            extra: Extra::new(),
+        }
+    }
+
+    /// Whether this message is a response or error for the query `query`.
+    pub fn answers(&self, query: &Message) -> bool {
+        matches!(query.body, Body::Query(_))
+            && !matches!(self.body, Body::Query(_))
+            && self.transaction_id == query.transaction_id
+    }
+
+    /// Decode a message received from the network.
+    ///
+    /// [`FromBencode`] decodes messages in [`DecodeMode::Strict`].
+    pub fn decode(bytes: &[u8], mode: DecodeMode) -> Result<Self, decoding::Error> {
+        let mut decoder = Decoder::new(bytes).with_sorted_keys(mode == DecodeMode::Strict);
+        let message = match decoder.next_object()? {
+            Some(object) => decode_message(object, mode)?,
+            None => return Err(decoding::Error::from(StructureError::UnexpectedEof)),
+        };
+        if mode == DecodeMode::Strict && decoder.next_object()?.is_some() {
+            return Err(decoding::Error::trailing_data());
+        }
+        Ok(message)
+    }
+}
+
+fn decode_id(bytes: &[u8]) -> Result<NodeId, decoding::Error> {
+    NodeId::try_from(bytes).map_err(|_| {
+        decoding::Error::unexpected_token("20-byte id", format!("{} bytes", bytes.len()))
+    })
+}
+
+/// Take a 20-byte id or infohash from the extra arguments of a query.
+fn take_id(args: &mut Extra, key: &str) -> Result<NodeId, decoding::Error> {
+    match args.remove(key.as_bytes()) {
+        Some(Value::Bytes(bytes)) => decode_id(&bytes).context(key),
+        Some(_) => Err(decoding::Error::unexpected_token("String", "other").context(key)),
+        None => Err(decoding::Error::missing_field(key)),
+    }
+}
+
+fn take_integer(args: &mut Extra, key: &str) -> Result<Option<i64>, decoding::Error> {
+    match args.remove(key.as_bytes()) {
+        Some(Value::Integer(integer)) => Ok(Some(integer)),
+        Some(_) => Err(decoding::Error::unexpected_token("Num", "other").context(key)),
+        None => Ok(None),
+    }
+}
+
+fn decode_method(name: &[u8], args: &mut Extra) -> Result<Method, decoding::Error> {
+    Ok(match name {
+        b"ping" => Method::Ping,
+        b"find_node" => Method::FindNode {
+            target: take_id(args, "target")?,
+        },
+        b"get_peers" => Method::GetPeers {
+            info_hash: take_id(args, "info_hash")?,
+        },
+        b"announce_peer" => {
+            let port = take_integer(args, "port")?
+                .ok_or_else(|| decoding::Error::missing_field("port"))?;
+            let token = match args.remove(&b"token"[..]) {
+                Some(Value::Bytes(token)) => token.into_vec(),
+                Some(_) => {
+                    return Err(
+                        decoding::Error::unexpected_token("String", "other").context("token")
+                    )
+                },
+                None => return Err(decoding::Error::missing_field("token")),
+            };
+            Method::AnnouncePeer {
+                info_hash: take_id(args, "info_hash")?,
+                port: u16::try_from(port)
+                    .map_err(decoding::Error::malformed_content)
+                    .context("port")?,
+                implied_port: take_integer(args, "implied_port")?.unwrap_or(0) != 0,
+                token,
+            }
+        },
+        other => Method::Other(String::from_utf8(other.to_vec())?),
+    })
+}
+
+fn decode_values(object: Object, mode: DecodeMode) -> Result<Vec<SocketAddr>, decoding::Error> {
+    let mut values = Vec::new();
+    let mut list = object.try_into_list()?;
+    while let Some(value) = list.next_object()? {
+        let bytes = value.try_into_bytes()?;
+        match bytes.len() {
+            COMPACT_PEER_LEN => {
+                values.extend(decode_compact_peers(bytes).into_iter().map(SocketAddr::V4))
+            },
+            COMPACT_PEER6_LEN => {
+                values.extend(decode_compact_peers6(bytes).into_iter().map(SocketAddr::V6))
+            },
+            _len if mode == DecodeMode::Lenient => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(len = _len, "skipped a peer address of the wrong length");
+            },
+            len => {
+                return Err(decoding::Error::unexpected_token(
+                    "6 or 18 bytes",
+                    format!("{} bytes", len),
+                ))
+            },
+        }
+    }
+    Ok(values)
+}
+
+fn decode_nodes(
+    object: Object,
+    node_len: usize,
+    mode: DecodeMode,
+) -> Result<Vec<NodeInfo>, decoding::Error> {
+    let bytes = object.try_into_bytes()?;
+    if mode == DecodeMode::Strict && bytes.len() % node_len != 0 {
+        return Err(decoding::Error::unexpected_token(
+            format!("a multiple of {} bytes", node_len),
+            format!("{} bytes", bytes.len()),
+        ));
+    }
+    Ok(bytes
+        .chunks_exact(node_len)
+        .filter_map(NodeInfo::from_compact)
+        .collect())
+}
+
+fn decode_response(object: Object, mode: DecodeMode) -> Result<Response, decoding::Error> {
+    let mut id = None;
+    let mut response = Response::new([0; 20]);
+
+    let mut dict = object.try_into_dictionary()?;
+    while let Some(pair) = dict.next_pair()? {
+        match pair {
+            (b"id", value) => {
+                id = value
+                    .try_into_bytes()
+                    .and_then(decode_id)
+                    .context("id")
+                    .map(Some)?
+            },
+            (b"nodes", value) => {
+                response.nodes = decode_nodes(value, COMPACT_NODE_LEN, mode)
+                    .context("nodes")
+                    .map(Some)?;
+            },
+            (b"nodes6", value) => {
+                response.nodes6 = decode_nodes(value, COMPACT_NODE6_LEN, mode)
+                    .context("nodes6")
+                    .map(Some)?;
+            },
+            (b"token", value) => {
+                response.token = value
+                    .try_into_bytes()
+                    .context("token")
+                    .map(|token| Some(token.to_vec()))?;
+            },
+            (b"values", value) => {
+                response.values = decode_values(value, mode).context("values").map(Some)?
+            },
+            (key, value) => decode_extra(&mut response.extra, key, value)?,
+        }
+    }
+
+    response.id = id.ok_or_else(|| decoding::Error::missing_field("id"))?;
+    Ok(response)
+}
+
+fn decode_error(object: Object) -> Result<Error, decoding::Error> {
+    let mut list = object.try_into_list()?;
+    let code = match list.next_object()? {
+        Some(code) => i64::decode_bencode_object(code)?,
+        None => return Err(decoding::Error::missing_field("code")),
+    };
+    let message = match list.next_object()? {
+        Some(message) => String::from_utf8_lossy(message.try_into_bytes()?).into_owned(),
+        None => String::new(),
+    };
+    Ok(Error { code, message })
+}
+
+fn decode_message(object: Object, mode: DecodeMode) -> Result<Message, decoding::Error> {
+    let mut transaction_id = None;
+    let mut version = None;
+    let mut message_type = None;
+    let mut method = None;
+    let mut args = None;
+    let mut response = None;
+    let mut error = None;
+    let mut extra = Extra::new();
+
+    let mut dict = object.try_into_dictionary()?;
+    while let Some(pair) = dict.next_pair()? {
+        match pair {
+            (b"a", value) => {
+                let mut arguments = Extra::new();
+                let mut dict = value.try_into_dictionary().context("a")?;
+                while let Some((key, value)) = dict.next_pair()? {
+                    decode_extra(&mut arguments, key, value).context("a")?;
+                }
+                args = Some(arguments);
+            },
+            (b"e", value) => error = decode_error(value).context("e").map(Some)?,
+            (b"q", value) => method = value.try_into_bytes().context("q").map(Some)?,
+            (b"r", value) => response = decode_response(value, mode).context("r").map(Some)?,
+            (b"t", value) => transaction_id = value.try_into_bytes().context("t").map(Some)?,
+            (b"v", value) => {
+                version = value
+                    .try_into_bytes()
+                    .context("v")
+                    .map(|v| Some(v.to_vec()))?
+            },
+            (b"y", value) => message_type = value.try_into_bytes().context("y").map(Some)?,
+            (key, value) => decode_extra(&mut extra, key, value)?,
+        }
+    }
+
+    let body = match message_type {
+        Some(b"q") => {
+            let mut args = args.ok_or_else(|| decoding::Error::missing_field("a"))?;
+            let name = method.ok_or_else(|| decoding::Error::missing_field("q"))?;
+            let id = take_id(&mut args, "id").context("a")?;
+            let method = decode_method(name, &mut args).context("a")?;
+            Body::Query(Query {
+                id,
+                method,
+                extra: args,
+            })
+        },
+        Some(b"r") => Body::Response(response.ok_or_else(|| decoding::Error::missing_field("r"))?),
+        Some(b"e") => Body::Error(error.ok_or_else(|| decoding::Error::missing_field("e"))?),
+        Some(other) => {
+            return Err(decoding::Error::unexpected_token(
+                "q, r or e",
+                String::from_utf8_lossy(other),
+            )
+            .context("y"))
+        },
+        None => return Err(decoding::Error::missing_field("y")),
+    };
+
+    Ok(Message {
+        transaction_id: transaction_id
+            .ok_or_else(|| decoding::Error::missing_field("t"))?
+            .to_vec(),
+        version,
+        body,
+        extra,
+    })
+}
+
+impl FromBencode for Message {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        decode_message(object, DecodeMode::Strict)
+    }
+}
+
+impl ToBencode for Query {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            e.emit_pair(b"id", AsString(&self.id[..]))?;
+            match &self.method {
+                Method::Ping | Method::Other(_) => {},
+                Method::FindNode { target } => e.emit_pair(b"target", AsString(&target[..]))?,
+                Method::GetPeers { info_hash } => {
+                    e.emit_pair(b"info_hash", AsString(&info_hash[..]))?
+                },
+                Method::AnnouncePeer {
+                    info_hash,
+                    port,
+                    implied_port,
+                    token,
+                } => {
+                    e.emit_pair(b"implied_port", i64::from(*implied_port))?;
+                    e.emit_pair(b"info_hash", AsString(&info_hash[..]))?;
+                    e.emit_pair(b"port", port)?;
+                    e.emit_pair(b"token", AsString(token))?;
+                },
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ToBencode for Response {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            e.emit_pair(b"id", AsString(&self.id[..]))?;
+            if let Some(nodes) = &self.nodes {
+                e.emit_pair(b"nodes", AsString(&encode_compact_nodes(nodes)))?;
+            }
+            if let Some(nodes) = &self.nodes6 {
+                e.emit_pair(b"nodes6", AsString(&encode_compact_nodes6(nodes)))?;
+            }
+            if let Some(token) = &self.token {
+                e.emit_pair(b"token", AsString(token))?;
+            }
+            if let Some(values) = &self.values {
+                e.emit_pair_with(b"values", |e| {
+                    e.emit_list(|e| {
+                        for value in values {
+                            let mut bytes = match value.ip() {
+                                IpAddr::V4(ip) => ip.octets().to_vec(),
+                                IpAddr::V6(ip) => ip.octets().to_vec(),
+                            };
+                            bytes.extend_from_slice(&value.port().to_be_bytes());
+                            e.emit(AsString(&bytes))?;
+                        }
+                        Ok(())
+                    })
+                })?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ToBencode for Error {
+    const MAX_DEPTH: usize = 1;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_list(|e| {
+            e.emit_int(self.code)?;
+            e.emit_str(&self.message)
+        })
+    }
+}
+
+impl ToBencode for Message {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            match &self.body {
+                Body::Query(query) => {
+                    e.emit_pair(b"a", query)?;
+                    e.emit_pair(b"q", query.method_name())?;
+                    e.emit_pair(b"y", "q")?;
+                },
+                Body::Response(response) => {
+                    e.emit_pair(b"r", response)?;
+                    e.emit_pair(b"y", "r")?;
+                },
+                Body::Error(error) => {
+                    e.emit_pair(b"e", error)?;
+                    e.emit_pair(b"y", "e")?;
+                },
+            }
+            e.emit_pair(b"t", AsString(&self.transaction_id))?;
+            if let Some(version) = &self.version {
+                e.emit_pair(b"v", AsString(version))?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(encoded: &[u8]) -> Message {
+        let message = Message::from_bencode(encoded).unwrap();
+        assert_eq!(message.to_bencode().unwrap(), encoded);
+        message
+    }
+
+    #[test]
+    fn bep5_examples_round_trip() {
+        let ping = round_trip(b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe");
+        assert_eq!(
+            ping.body,
+            Body::Query(Query::new(*b"abcdefghij0123456789", Method::Ping))
+        );
+
+        let find_node = round_trip(
+            b"d1:ad2:id20:abcdefghij01234567896:target20:mnopqrstuvwxyz123456e\
+              1:q9:find_node1:t2:aa1:y1:qe",
+        );
+        assert_eq!(
+            find_node.body,
+            Body::Query(Query::new(
+                *b"abcdefghij0123456789",
+                Method::FindNode {
+                    target: *b"mnopqrstuvwxyz123456"
+                }
+            ))
+        );
+
+        let announce = round_trip(
+            b"d1:ad2:id20:abcdefghij012345678912:implied_porti1e9:info_hash20:\
+              mnopqrstuvwxyz1234564:porti6881e5:token8:aoeusnthe1:q13:announce_peer\
+              1:t2:aa1:y1:qe",
+        );
+        match announce.body {
+            Body::Query(Query {
+                method:
+                    Method::AnnouncePeer {
+                        port, implied_port, ..
+                    },
+                ..
+            }) => assert_eq!((port, implied_port), (6881, true)),
+            other => panic!("expected announce_peer, got {:?}", other),
+        }
+
+        let values = round_trip(
+            b"d1:rd2:id20:abcdefghij01234567895:token8:aoeusnth6:valuesl6:axje.u6:idhtnmee\
+              1:t2:aa1:y1:re",
+        );
+        match &values.body {
+            Body::Response(response) => {
+                assert_eq!(response.token.as_deref(), Some(&b"aoeusnth"[..]));
+                assert_eq!(response.values.as_ref().map(Vec::len), Some(2));
+            },
+            other => panic!("expected a response, got {:?}", other),
+        }
+
+        let error = round_trip(b"d1:eli201e23:A Generic Error Ocurrede1:t2:aa1:y1:ee");
+        assert_eq!(
+            error.body,
+            Body::Error(Error {
+                code: Error::GENERIC,
+                message: "A Generic Error Ocurred".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn responses_answer_queries() {
+        let mut ids = TransactionIds::new(0xfffe);
+        let query = Message::query(
+            ids.next_id(),
+            Query::new([1; 20], Method::GetPeers { info_hash: [2; 20] }),
+        );
+        assert_eq!(query.transaction_id, vec![0xff, 0xfe]);
+        assert_eq!(ids.next_id(), vec![0xff, 0xff]);
+        assert_eq!(ids.next_id(), vec![0, 0]);
+
+        let response = Message::response(&query, Response::new([3; 20]));
+        assert!(response.answers(&query));
+        assert!(!query.answers(&query));
+        assert!(!Message::error(
+            &response,
+            Error {
+                code: Error::PROTOCOL,
+                message: String::new(),
+            }
+        )
+        .answers(&Message::query(vec![0], Query::new([1; 20], Method::Ping))));
+    }
+
+    #[test]
+    fn malformed_nodes_depend_on_mode() {
+        let mut encoded = b"d1:rd2:id20:abcdefghij01234567895:nodes30:".to_vec();
+        encoded.extend_from_slice(&[7; 30]);
+        encoded.extend_from_slice(b"6:valuesl6:axje.u3:badee1:t2:aa1:y1:re");
+
+        assert!(Message::decode(&encoded, DecodeMode::Strict).is_err());
+        let message = Message::decode(&encoded, DecodeMode::Lenient).unwrap();
+        match message.body {
+            Body::Response(response) => {
+                let node = NodeInfo {
+                    id: [7; 20],
+                    addr: "7.7.7.7:1799".parse().unwrap(),
+                };
+                assert_eq!(response.nodes, Some(vec![node]));
+                assert_eq!(response.values.map(|values| values.len()), Some(1));
+            },
+            other => panic!("expected a response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn compact_nodes_round_trip() {
+        let nodes = vec![
+            NodeInfo {
+                id: [1; 20],
+                addr: "10.0.0.1:6881".parse().unwrap(),
+            },
+            NodeInfo {
+                id: [2; 20],
+                addr: "[::1]:6882".parse().unwrap(),
+            },
+        ];
+        let compact = encode_compact_nodes(&nodes);
+        assert_eq!(compact.len(), COMPACT_NODE_LEN);
+        assert_eq!(compact[20..], *b"\x0a\x00\x00\x01\x1a\xe1");
+        let compact6 = encode_compact_nodes6(&nodes);
+        assert_eq!(compact6.len(), COMPACT_NODE6_LEN);
+        assert_eq!(decode_compact_nodes(&compact), nodes[..1]);
+        assert_eq!(decode_compact_nodes6(&compact6), nodes[1..]);
+        assert_eq!(decode_compact_nodes(&compact[..25]), vec![]);
+
+        let mut response = Response::new([3; 20]);
+        response.nodes = Some(nodes[..1].to_vec());
+        response.nodes6 = Some(nodes[1..].to_vec());
+        let message = Message::response(
+            &Message::query(b"aa".to_vec(), Query::new([4; 20], Method::Ping)),
+            response,
+        );
+        let encoded = message.to_bencode().unwrap();
+        assert_eq!(Message::from_bencode(&encoded).unwrap(), message);
+    }
+
+    #[test]
+    fn invalid_messages_are_rejected() {
+        // Unknown type
+        assert!(Message::from_bencode(b"d1:t2:aa1:y1:xe").is_err());
+        // Short id
+        assert!(Message::from_bencode(b"d1:ad2:id2:abe1:q4:ping1:t2:aa1:y1:qe").is_err());
+        // Missing target
+        assert!(Message::from_bencode(
+            b"d1:ad2:id20:abcdefghij0123456789e1:q9:find_node1:t2:aa1:y1:qe"
+        )
+        .is_err());
+        // Trailing bytes are only accepted leniently
+        let trailing = b"d1:eli201e0:e1:t2:aa1:y1:eei1e";
+        assert!(Message::decode(trailing, DecodeMode::Strict).is_err());
+        assert!(Message::decode(trailing, DecodeMode::Lenient).is_ok());
+    }
+
+    #[test]
+    fn unknown_methods_keep_their_arguments() {
+        let message = round_trip(
+            b"d1:ad2:id20:abcdefghij01234567896:target20:mnopqrstuvwxyz123456e\
+              1:q17:sample_infohashes1:t2:aa1:y1:qe",
+        );
+        match message.body {
+            Body::Query(query) => {
+                assert_eq!(query.method, Method::Other("sample_infohashes".to_string()));
+                assert!(query.extra.contains_key(&b"target"[..]));
+            },
+            other => panic!("expected a query, got {:?}", other),
+        }
+    }
+}
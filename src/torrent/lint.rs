@@ -0,0 +1,218 @@
+//! Checking torrents for things which are valid but known to break popular
+//! clients.
+
+use crate::torrent::{
+    validate::{Problem, ProblemKind, Problems, Severity},
+    Layout, MetaInfo,
+};
+
+/// The limits checked by [`MetaInfo::lint_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientLimits {
+    /// The number of files clients can be expected to handle. Clients keep
+    /// state for each file, and some become unusably slow or run out of memory
+    /// for torrents with hundreds of thousands of files.
+    pub max_files: usize,
+    /// The number of characters of a path, including the name of the torrent
+    /// and separators. Windows limits paths to 260 characters unless long
+    /// paths are enabled, and clients add the download directory on top.
+    pub max_path_length: usize,
+    /// The number of bytes of a name or path component, 255 on most file
+    /// systems.
+    pub max_component_length: usize,
+}
+
+impl Default for ClientLimits {
+    fn default() -> Self {
+        ClientLimits {
+            max_files: 100_000,
+            max_path_length: 260,
+            max_component_length: 255,
+        }
+    }
+}
+
+impl MetaInfo {
+    /// Check the torrent for things which popular clients handle badly, with
+    /// the default [`ClientLimits`].
+    ///
+    /// Unlike [`validate`](MetaInfo::validate), this flags torrents which are
+    /// valid but likely to fail in some clients or on some platforms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::{
+    ///     decoding::FromBencode,
+    ///     torrent::{MetaInfo, ProblemKind, Severity},
+    /// };
+    ///
+    /// let torrent = MetaInfo::from_bencode(
+    ///     b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi10000e6:pieces20:aaaaaaaaaaaaaaaaaaaaee",
+    /// )
+    /// .unwrap();
+    ///
+    /// let problems = torrent.lint();
+    /// assert_eq!(problems.len(), 1);
+    /// assert_eq!(problems[0].severity, Severity::Warning);
+    /// assert_eq!(problems[0].kind, ProblemKind::PieceLengthNotPowerOfTwo(10000));
+    /// ```
+    pub fn lint(&self) -> Vec<Problem> {
+        self.lint_with(&ClientLimits::default())
+    }
+
+    /// Check the torrent like [`lint`](MetaInfo::lint) against `limits`.
+    pub fn lint_with(&self, limits: &ClientLimits) -> Vec<Problem> {
+        let info = &self.info;
+        let mut problems = Problems(Vec::new());
+
+        problems.check_piece_length(info);
+        if info.name.is_empty() {
+            problems.push(Severity::Error, "info.name", ProblemKind::EmptyName);
+        }
+
+        // Hybrid torrents list the same files twice, so only the v1 files are
+        // checked for them
+        let (location, paths): (_, Vec<Vec<&str>>) = match (&info.layout, &info.file_tree) {
+            (Some(Layout::MultiFile { files }), _) => (
+                "info.files",
+                files
+                    .iter()
+                    .map(|file| file.path.iter().map(String::as_str).collect())
+                    .collect(),
+            ),
+            (Some(Layout::SingleFile { .. }), _) => ("info.length", vec![Vec::new()]),
+            (None, Some(tree)) => (
+                "info.file tree",
+                tree.files().map(|(path, _)| path).collect(),
+            ),
+            (None, None) => ("info", Vec::new()),
+        };
+
+        if paths.len() > limits.max_files {
+            problems.push(
+                Severity::Warning,
+                location,
+                ProblemKind::TooManyFiles(paths.len()),
+            );
+        }
+
+        if info.name.len() > limits.max_component_length {
+            problems.push(
+                Severity::Error,
+                "info.name",
+                ProblemKind::ComponentTooLong(info.name.clone()),
+            );
+        }
+        for path in &paths {
+            let location = if path.is_empty() {
+                "info.name".to_string()
+            } else {
+                path.join("/")
+            };
+            for component in path {
+                if component.len() > limits.max_component_length {
+                    problems.push(
+                        Severity::Error,
+                        location.as_str(),
+                        ProblemKind::ComponentTooLong(component.to_string()),
+                    );
+                }
+            }
+            let length = path
+                .iter()
+                .map(|component| component.chars().count() + 1)
+                .sum::<usize>()
+                + info.name.chars().count();
+            if length > limits.max_path_length {
+                problems.push(
+                    Severity::Warning,
+                    location,
+                    ProblemKind::PathTooLong(length),
+                );
+            }
+        }
+
+        problems.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::decoding::FromBencode;
+
+    fn lint(encoded: &[u8], limits: &ClientLimits) -> Vec<(Severity, String, ProblemKind)> {
+        MetaInfo::from_bencode(encoded)
+            .unwrap()
+            .lint_with(limits)
+            .into_iter()
+            .map(|problem| (problem.severity, problem.location, problem.kind))
+            .collect()
+    }
+
+    #[test]
+    fn valid_torrents_pass() {
+        static DEBIAN: &[u8] =
+            include_bytes!("../../examples/torrent_files/debian-9.4.0-amd64-netinst.iso.torrent");
+        assert_eq!(lint(DEBIAN, &ClientLimits::default()), vec![]);
+    }
+
+    #[test]
+    fn limits_are_checked() {
+        let torrent = b"d4:infod5:filesld6:lengthi1e4:pathl3:abc1:deed6:lengthi1e4:pathl1:eeee\
+                        4:name0:12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee";
+        let limits = ClientLimits {
+            max_files: 1,
+            max_path_length: 5,
+            max_component_length: 2,
+        };
+        assert_eq!(
+            lint(torrent, &limits),
+            vec![
+                (
+                    Severity::Error,
+                    "info.name".to_string(),
+                    ProblemKind::EmptyName
+                ),
+                (
+                    Severity::Warning,
+                    "info.files".to_string(),
+                    ProblemKind::TooManyFiles(2)
+                ),
+                (
+                    Severity::Error,
+                    "abc/d".to_string(),
+                    ProblemKind::ComponentTooLong("abc".to_string())
+                ),
+                (
+                    Severity::Warning,
+                    "abc/d".to_string(),
+                    ProblemKind::PathTooLong(6)
+                ),
+            ]
+        );
+        assert_eq!(lint(torrent, &ClientLimits::default()).len(), 1);
+    }
+
+    #[test]
+    fn single_file_names_are_checked() {
+        let torrent = format!(
+            "d4:infod6:lengthi1e4:name300:{}12:piece lengthi16384e6:pieces20:{}ee",
+            "a".repeat(300),
+            "a".repeat(20)
+        );
+        let kinds: Vec<_> = lint(torrent.as_bytes(), &ClientLimits::default())
+            .into_iter()
+            .map(|(_, _, kind)| kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ProblemKind::ComponentTooLong("a".repeat(300)),
+                ProblemKind::PathTooLong(300),
+            ]
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! Magnet URIs, as specified in [BEP 9](http://bittorrent.org/beps/bep_0009.html)
+//! and, for v2 infohashes, [BEP 52](http://bittorrent.org/beps/bep_0052.html).
+
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{
+    decoding::{self, FromBencode},
+    torrent::{
+        info_hashes,
+        query::{percent_decode_bytes, percent_encode_bytes},
+        DhtNode, InfoHash, MetaInfo, Sha256Hash,
+    },
+};
+
+/// The parameters of a magnet URI.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::MagnetLink;
+///
+/// let torrent = b"d8:announce15:http://tracker/4:infod6:lengthi5e4:name5:a.txt\
+///                 12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee";
+/// let magnet = MagnetLink::from_torrent(torrent).unwrap();
+///
+/// assert_eq!(
+///     magnet.to_string(),
+///     "magnet:?xt=urn:btih:7faf75b2447f88700c68f1eceda713cd90a0127a\
+///      &dn=a.txt&tr=http%3A%2F%2Ftracker%2F"
+/// );
+/// assert_eq!(magnet.to_string().parse::<MagnetLink>().unwrap(), magnet);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MagnetLink {
+    /// The infohashes, given as `xt=urn:btih:` for v1 and `xt=urn:btmh:` with
+    /// a SHA-256 multihash prefix for v2.
+    pub info_hash: InfoHash,
+    /// The name to display before the metadata is known, given as `dn`.
+    pub display_name: Option<String>,
+    /// Tracker URLs, given as `tr`.
+    pub trackers: Vec<String>,
+    /// Addresses of peers, as `host:port`, given as `x.pe`.
+    pub peers: Vec<String>,
+    /// Addresses of DHT nodes to bootstrap from, as `host:port`, given as `dht`
+    /// like libtorrent does.
+    pub nodes: Vec<String>,
+}
+
+/// An error encountered while parsing a magnet URI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MagnetError {
+    /// The URI doesn't start with `magnet:?`.
+    NotMagnet,
+    /// An `xt` parameter isn't a valid BitTorrent infohash.
+    InvalidInfoHash(String),
+    /// A parameter contains an invalid percent-encoding or isn't UTF-8.
+    InvalidEncoding(String),
+    /// The URI doesn't contain an infohash.
+    MissingInfoHash,
+}
+
+impl Display for MagnetError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            MagnetError::NotMagnet => write!(f, "not a magnet URI"),
+            MagnetError::InvalidInfoHash(xt) => write!(f, "invalid infohash: {}", xt),
+            MagnetError::InvalidEncoding(value) => {
+                write!(f, "invalid percent-encoding: {}", value)
+            },
+            MagnetError::MissingInfoHash => write!(f, "magnet URI has no infohash"),
+        }
+    }
+}
+
+impl std::error::Error for MagnetError {}
+
+/// The multihash prefix of a SHA-256 hash: the hash function code and length.
+const SHA256_MULTIHASH: &str = "1220";
+
+impl MagnetLink {
+    /// Create a magnet URI with only an infohash.
+    pub fn new(info_hash: InfoHash) -> Self {
+        MagnetLink {
+            info_hash,
+            display_name: None,
+            trackers: Vec::new(),
+            peers: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Create the magnet URI of an encoded torrent, with its infohashes, name
+    /// and trackers.
+    pub fn from_torrent(torrent: &[u8]) -> Result<Self, decoding::Error> {
+        let metainfo = MetaInfo::from_bencode(torrent)?;
+
+        let mut trackers: Vec<String> = metainfo.announce.into_iter().collect();
+        for tracker in metainfo.announce_list.into_iter().flatten().flatten() {
+            if !trackers.contains(&tracker) {
+                trackers.push(tracker);
+            }
+        }
+
+        Ok(MagnetLink {
+            info_hash: info_hashes(torrent)?,
+            display_name: Some(metainfo.info.name),
+            trackers,
+            peers: Vec::new(),
+            nodes: metainfo
+                .nodes
+                .iter()
+                .flatten()
+                .map(DhtNode::to_string)
+                .collect(),
+        })
+    }
+}
+
+impl Display for MagnetLink {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut separator = "?";
+        let mut param = |f: &mut Formatter, key: &str, value: &str| {
+            let result = write!(f, "{}{}={}", separator, key, value);
+            separator = "&";
+            result
+        };
+
+        write!(f, "magnet:")?;
+        if let Some(hash) = &self.info_hash.v1() {
+            param(f, "xt", &format!("urn:btih:{}", hex(hash)))?;
+        }
+        if let Some(hash) = &self.info_hash.v2() {
+            param(
+                f,
+                "xt",
+                &format!("urn:btmh:{}{}", SHA256_MULTIHASH, hex(hash)),
+            )?;
+        }
+        if let Some(name) = &self.display_name {
+            param(f, "dn", &percent_encode(name))?;
+        }
+        for tracker in &self.trackers {
+            param(f, "tr", &percent_encode(tracker))?;
+        }
+        for peer in &self.peers {
+            param(f, "x.pe", &percent_encode(peer))?;
+        }
+        for node in &self.nodes {
+            param(f, "dht", &percent_encode(node))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MagnetLink {
+    type Err = MagnetError;
+
+    /// Parse a magnet URI. Parameters other than `xt`, `dn`, `tr`, `x.pe` and
+    /// `dht` are ignored, as are `xt` parameters which aren't BitTorrent infohashes.
+    fn from_str(uri: &str) -> Result<Self, MagnetError> {
+        let query = match uri.get(..8) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("magnet:?") => &uri[8..],
+            _ => return Err(MagnetError::NotMagnet),
+        };
+
+        // The infohash is replaced once all `xt` parameters are known
+        let mut hashes = (None, None);
+        let mut magnet = MagnetLink::new(InfoHash::V1([0; 20]));
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = match param.find('=') {
+                Some(index) => (&param[..index], &param[index + 1..]),
+                None => (param, ""),
+            };
+            let value = percent_decode(value)?;
+
+            match key {
+                "xt" => parse_exact_topic(&mut hashes, &value)?,
+                "dn" => magnet.display_name = Some(value),
+                "tr" => magnet.trackers.push(value),
+                "x.pe" => magnet.peers.push(value),
+                "dht" => magnet.nodes.push(value),
+                _ => {},
+            }
+        }
+
+        magnet.info_hash =
+            InfoHash::from_hashes(hashes.0, hashes.1).ok_or(MagnetError::MissingInfoHash)?;
+        Ok(magnet)
+    }
+}
+
+fn parse_exact_topic(
+    hashes: &mut (Option<[u8; 20]>, Option<Sha256Hash>),
+    xt: &str,
+) -> Result<(), MagnetError> {
+    let invalid = || MagnetError::InvalidInfoHash(xt.to_string());
+
+    if let Some(hash) = strip_prefix_ignore_case(xt, "urn:btih:") {
+        let bytes = match hash.len() {
+            40 => decode_hex(hash),
+            32 => decode_base32(hash),
+            _ => None,
+        };
+        let bytes = bytes.ok_or_else(invalid)?;
+        hashes.0 = Some(<[u8; 20]>::try_from(&bytes[..]).map_err(|_| invalid())?);
+    } else if let Some(multihash) = strip_prefix_ignore_case(xt, "urn:btmh:") {
+        let bytes = strip_prefix_ignore_case(multihash, SHA256_MULTIHASH)
+            .filter(|hash| hash.len() == 64)
+            .and_then(decode_hex)
+            .ok_or_else(invalid)?;
+        hashes.1 = Some(Sha256Hash::try_from(&bytes[..]).map_err(|_| invalid())?);
+    }
+    Ok(())
+}
+
+fn strip_prefix_ignore_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    match value.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&value[prefix.len()..]),
+        _ => None,
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Decode the RFC 4648 base32 encoding which older clients use for v1
+/// infohashes.
+fn decode_base32(base32: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in base32.bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | u32::from(value);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+fn percent_encode(value: &str) -> String {
+    percent_encode_bytes(value.as_bytes())
+}
+
+fn percent_decode(value: &str) -> Result<String, MagnetError> {
+    percent_decode_bytes(value)
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| MagnetError::InvalidEncoding(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static DEBIAN: &[u8] =
+        include_bytes!("../../examples/torrent_files/debian-9.4.0-amd64-netinst.iso.torrent");
+
+    #[test]
+    fn debian_magnet_link_is_created() {
+        let magnet = MagnetLink::from_torrent(DEBIAN).unwrap();
+        assert_eq!(
+            magnet.to_string(),
+            "magnet:?xt=urn:btih:7431a969b347e14bba641b3517c024f7b40dfb7f\
+             &dn=debian-9.4.0-amd64-netinst.iso\
+             &tr=http%3A%2F%2Fbttracker.debian.org%3A6969%2Fannounce"
+        );
+    }
+
+    #[test]
+    fn trackerless_torrents_list_dht_nodes() {
+        let torrent = b"d4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:e\
+                        5:nodesll4:hosti6881eeee";
+        let magnet = MagnetLink::from_torrent(torrent).unwrap();
+        assert_eq!(magnet.nodes, ["host:6881"]);
+        assert!(magnet.to_string().ends_with("&dht=host%3A6881"));
+    }
+
+    #[test]
+    fn magnet_links_round_trip() {
+        let magnet = MagnetLink {
+            info_hash: InfoHash::Hybrid([0xab; 20], [0x01; 32]),
+            display_name: Some("a b&c/ä".to_string()),
+            trackers: vec!["udp://tracker:80".to_string(), "http://t/a?b=c".to_string()],
+            peers: vec!["10.0.0.1:6881".to_string()],
+            nodes: vec!["[::1]:6881".to_string()],
+        };
+        assert_eq!(magnet.to_string().parse::<MagnetLink>(), Ok(magnet));
+    }
+
+    #[test]
+    fn other_encodings_are_parsed() {
+        let magnet: MagnetLink = "MAGNET:?dn=a+b&xt=urn:btih:VOV2XK5LVOV2XK5LVOV2XK5LVOV2XK5L\
+                                  &xt=urn:ed2k:31D6CFE0D16AE931B73C59D7E0C089C0&so=1"
+            .parse()
+            .unwrap();
+        assert_eq!(magnet.info_hash, InfoHash::V1([0xab; 20]));
+        assert_eq!(magnet.display_name.as_deref(), Some("a b"));
+    }
+
+    #[test]
+    fn invalid_magnet_links_are_rejected() {
+        let parse = |uri: &str| uri.parse::<MagnetLink>().unwrap_err();
+
+        assert_eq!(parse("http://example.com/"), MagnetError::NotMagnet);
+        assert_eq!(parse("magnet:?dn=a"), MagnetError::MissingInfoHash);
+        assert_eq!(
+            parse("magnet:?xt=urn:btih:abcd"),
+            MagnetError::InvalidInfoHash("urn:btih:abcd".to_string())
+        );
+        assert_eq!(
+            parse("magnet:?xt=urn:btmh:1114abcd"),
+            MagnetError::InvalidInfoHash("urn:btmh:1114abcd".to_string())
+        );
+        assert_eq!(
+            parse("magnet:?dn=%zz"),
+            MagnetError::InvalidEncoding("%zz".to_string())
+        );
+    }
+}
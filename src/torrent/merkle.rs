@@ -0,0 +1,126 @@
+//! The per-file Merkle trees of v2 torrents.
+
+use crate::torrent::{
+    sha256::{sha256, Sha256},
+    Sha256Hash,
+};
+
+/// The size of the blocks whose hashes are the leaves of a file's Merkle tree.
+pub(crate) const BLOCK_SIZE: usize = 16 * 1024;
+
+/// The `pieces root` of a file and its piece layer.
+pub(crate) type RootAndLayer = (Sha256Hash, Vec<u8>);
+
+/// Hashes a file into the leaves of its Merkle tree.
+pub(crate) struct FileHasher {
+    block: Sha256,
+    block_filled: usize,
+    leaves: Vec<Sha256Hash>,
+}
+
+impl FileHasher {
+    pub(crate) fn new() -> Self {
+        FileHasher {
+            block: Sha256::new(),
+            block_filled: 0,
+            leaves: Vec::new(),
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = data.len().min(BLOCK_SIZE - self.block_filled);
+            self.block.update(&data[..take]);
+            self.block_filled += take;
+            data = &data[take..];
+
+            if self.block_filled == BLOCK_SIZE {
+                let block = std::mem::replace(&mut self.block, Sha256::new());
+                self.leaves.push(block.finish());
+                self.block_filled = 0;
+            }
+        }
+    }
+
+    /// Return the `pieces root` of the file and its piece layer, the
+    /// concatenated hashes of its pieces. The piece layer is empty for files no
+    /// larger than a piece, and there is no root for empty files.
+    pub(crate) fn finish(mut self, piece_length: u64) -> Option<RootAndLayer> {
+        if self.block_filled > 0 {
+            self.leaves.push(self.block.finish());
+        }
+        if self.leaves.is_empty() {
+            return None;
+        }
+        Some(root_and_layer(self.leaves, piece_length))
+    }
+}
+
+/// Build the Merkle tree over `leaves`, padded with zero hashes to a power of
+/// two, and return its root and the layer whose nodes cover one piece each.
+pub(crate) fn root_and_layer(mut level: Vec<Sha256Hash>, piece_length: u64) -> RootAndLayer {
+    let blocks_per_piece = (piece_length / BLOCK_SIZE as u64) as usize;
+    let pieces = (level.len() + blocks_per_piece - 1) / blocks_per_piece;
+    level.resize(level.len().next_power_of_two(), [0; 32]);
+
+    let mut layer = Vec::new();
+    let mut covered = 1;
+    loop {
+        if covered == blocks_per_piece && pieces > 1 {
+            layer = level[..pieces].concat();
+        }
+        if level.len() == 1 {
+            return (level[0], layer);
+        }
+        level = level
+            .chunks_exact(2)
+            .map(|pair| sha256(&pair.concat()))
+            .collect();
+        covered *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_file(data: &[u8], piece_length: u64) -> Option<RootAndLayer> {
+        let mut hasher = FileHasher::new();
+        for chunk in data.chunks(5000) {
+            hasher.update(chunk);
+        }
+        hasher.finish(piece_length)
+    }
+
+    #[test]
+    fn small_files_have_no_layer() {
+        assert_eq!(hash_file(b"", 16384), None);
+        assert_eq!(hash_file(b"abc", 16384), Some((sha256(b"abc"), Vec::new())));
+
+        let data = vec![1; BLOCK_SIZE + 1];
+        let leaves = [sha256(&data[..BLOCK_SIZE]), sha256(&data[BLOCK_SIZE..])];
+        assert_eq!(
+            hash_file(&data, 32768),
+            Some((sha256(&leaves.concat()), Vec::new()))
+        );
+    }
+
+    #[test]
+    fn layers_cover_one_piece_per_hash() {
+        // Three blocks with one block per piece: the leaves are the piece layer,
+        // padded with a zero hash to build the tree
+        let data = vec![2; 2 * BLOCK_SIZE + 10];
+        let leaves: Vec<_> = data.chunks(BLOCK_SIZE).map(sha256).collect();
+        let left = sha256(&[leaves[0], leaves[1]].concat());
+        let right = sha256(&[leaves[2], [0; 32]].concat());
+
+        let (root, layer) = hash_file(&data, 16384).unwrap();
+        assert_eq!(root, sha256(&[left, right].concat()));
+        assert_eq!(layer, leaves.concat());
+
+        // With two blocks per piece, the layer is one level up
+        let (root2, layer) = hash_file(&data, 32768).unwrap();
+        assert_eq!(root2, root);
+        assert_eq!(layer, [left, right].concat());
+    }
+}
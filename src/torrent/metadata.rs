@@ -0,0 +1,228 @@
+//! The messages of the metadata exchange, as specified in
+//! [BEP 9](http://bittorrent.org/beps/bep_0009.html).
+//!
+//! Peers exchange the info dict of a torrent in pieces of
+//! [`METADATA_PIECE_LEN`] bytes. A data message is a bencoded dict directly
+//! followed by the raw bytes of the piece, so it can't be decoded with
+//! [`FromBencode`](crate::decoding::FromBencode) alone.
+
+use crate::{
+    decoding::{self, Decoder, FromBencode, Object, ResultExt},
+    encoding::{self, Encoder},
+    state_tracker::StructureError,
+};
+
+/// The size of each metadata piece but the last.
+pub const METADATA_PIECE_LEN: usize = 16 * 1024;
+
+/// A message of the `ut_metadata` extension.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::MetadataMessage;
+///
+/// let message = MetadataMessage::Data {
+///     piece: 0,
+///     total_size: 5,
+///     data: b"d1:ae".to_vec(),
+/// };
+///
+/// let encoded = message.to_bytes().unwrap();
+/// assert_eq!(encoded, &b"d8:msg_typei1e5:piecei0e10:total_sizei5eed1:ae"[..]);
+/// assert_eq!(MetadataMessage::from_bytes(&encoded).unwrap(), message);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetadataMessage {
+    /// A request for the piece `piece`, with `msg_type` 0.
+    Request { piece: u32 },
+    /// The piece `piece` of the metadata, with `msg_type` 1.
+    Data {
+        piece: u32,
+        /// The size of the whole info dict in bytes.
+        total_size: u64,
+        /// The bytes of the piece, following the bencoded dict.
+        data: Vec<u8>,
+    },
+    /// A refusal to send the piece `piece`, with `msg_type` 2.
+    Reject { piece: u32 },
+}
+
+impl MetadataMessage {
+    const REQUEST: u8 = 0;
+    const DATA: u8 = 1;
+    const REJECT: u8 = 2;
+
+    /// The index of the piece this message is about.
+    pub fn piece(&self) -> u32 {
+        match *self {
+            MetadataMessage::Request { piece }
+            | MetadataMessage::Data { piece, .. }
+            | MetadataMessage::Reject { piece } => piece,
+        }
+    }
+
+    /// Decode the payload of a `ut_metadata` extended message.
+    ///
+    /// Unknown fields of the dict are ignored. Bytes after the dict are only
+    /// allowed in data messages.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, decoding::Error> {
+        let mut decoder = Decoder::new(bytes);
+        let (msg_type, piece, total_size) = match decoder.next_object()? {
+            Some(object) => decode_header(object)?,
+            None => return Err(decoding::Error::from(StructureError::UnexpectedEof)),
+        };
+        let piece = piece.ok_or_else(|| decoding::Error::missing_field("piece"))?;
+
+        let message = match msg_type {
+            Self::REQUEST => MetadataMessage::Request { piece },
+            Self::DATA => MetadataMessage::Data {
+                piece,
+                total_size: total_size
+                    .ok_or_else(|| decoding::Error::missing_field("total_size"))?,
+                data: decoder.remaining().to_vec(),
+            },
+            Self::REJECT => MetadataMessage::Reject { piece },
+            other => {
+                return Err(
+                    decoding::Error::unexpected_token("msg_type 0, 1 or 2", other)
+                        .context("msg_type"),
+                )
+            },
+        };
+        if msg_type != Self::DATA && !decoder.remaining().is_empty() {
+            return Err(decoding::Error::trailing_data());
+        }
+        Ok(message)
+    }
+
+    /// Encode the message as the payload of a `ut_metadata` extended message.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, encoding::Error> {
+        let mut encoder = Encoder::new();
+        encoder.emit_dict(|mut e| match self {
+            MetadataMessage::Request { piece } => {
+                e.emit_pair(b"msg_type", Self::REQUEST)?;
+                e.emit_pair(b"piece", piece)
+            },
+            MetadataMessage::Data {
+                piece, total_size, ..
+            } => {
+                e.emit_pair(b"msg_type", Self::DATA)?;
+                e.emit_pair(b"piece", piece)?;
+                e.emit_pair(b"total_size", total_size)
+            },
+            MetadataMessage::Reject { piece } => {
+                e.emit_pair(b"msg_type", Self::REJECT)?;
+                e.emit_pair(b"piece", piece)
+            },
+        })?;
+
+        let mut bytes = encoder.get_output()?;
+        if let MetadataMessage::Data { data, .. } = self {
+            bytes.extend_from_slice(data);
+        }
+        Ok(bytes)
+    }
+}
+
+/// Read `msg_type`, `piece` and `total_size` from the dict of a message.
+fn decode_header(object: Object) -> Result<(u8, Option<u32>, Option<u64>), decoding::Error> {
+    let mut msg_type = None;
+    let mut piece = None;
+    let mut total_size = None;
+
+    let mut dict = object.try_into_dictionary()?;
+    while let Some(pair) = dict.next_pair()? {
+        match pair {
+            (b"msg_type", value) => {
+                msg_type = Some(u8::decode_bencode_object(value).context("msg_type")?)
+            },
+            (b"piece", value) => piece = Some(u32::decode_bencode_object(value).context("piece")?),
+            (b"total_size", value) => {
+                total_size = Some(u64::decode_bencode_object(value).context("total_size")?)
+            },
+            _ => {},
+        }
+    }
+
+    let msg_type = msg_type.ok_or_else(|| decoding::Error::missing_field("msg_type"))?;
+    Ok((msg_type, piece, total_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            (
+                MetadataMessage::Request { piece: 3 },
+                &b"d8:msg_typei0e5:piecei3ee"[..],
+            ),
+            (
+                MetadataMessage::Reject { piece: 3 },
+                &b"d8:msg_typei2e5:piecei3ee"[..],
+            ),
+            (
+                MetadataMessage::Data {
+                    piece: 1,
+                    total_size: 16_390,
+                    data: vec![b'e'; 6],
+                },
+                &b"d8:msg_typei1e5:piecei1e10:total_sizei16390eeeeeeee"[..],
+            ),
+        ];
+        for (message, encoded) in &messages {
+            assert_eq!(message.to_bytes().unwrap(), *encoded);
+            assert_eq!(MetadataMessage::from_bytes(encoded).unwrap(), *message);
+        }
+    }
+
+    #[test]
+    fn payloads_may_look_like_bencode() {
+        let message =
+            MetadataMessage::from_bytes(b"d8:msg_typei1e5:piecei0e10:total_sizei3ee1:x").unwrap();
+        assert_eq!(
+            message,
+            MetadataMessage::Data {
+                piece: 0,
+                total_size: 3,
+                data: b"1:x".to_vec(),
+            }
+        );
+
+        let message =
+            MetadataMessage::from_bytes(b"d8:msg_typei1e5:piecei0e10:total_sizei0ee").unwrap();
+        assert_eq!(message.piece(), 0);
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let message =
+            MetadataMessage::from_bytes(b"d1:ai1e8:msg_typei0e5:piecei2e10:total_sizei9ee")
+                .unwrap();
+        assert_eq!(message, MetadataMessage::Request { piece: 2 });
+    }
+
+    #[test]
+    fn invalid_messages_are_rejected() {
+        for encoded in &[
+            &b""[..],
+            b"d5:piecei0ee",
+            b"d8:msg_typei0ee",
+            b"d8:msg_typei3e5:piecei0ee",
+            b"d8:msg_typei1e5:piecei0ee",
+            b"d8:msg_typei0e5:piecei-1ee",
+            b"d8:msg_typei0e5:piecei0eee",
+            b"d8:msg_typei2e5:piecei0ee1:x",
+            b"d8:msg_typei1e5:piecei0e10:total_sizei3e",
+        ] {
+            assert!(
+                MetadataMessage::from_bytes(encoded).is_err(),
+                "{:?}",
+                encoded
+            );
+        }
+    }
+}
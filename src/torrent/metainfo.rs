@@ -0,0 +1,957 @@
+//! The metainfo dict and its `info` dict.
+
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    decoding::{self, FromBencode, Object, ResultExt},
+    encoding::{self, AsString, SingleItemEncoder, ToBencode},
+    torrent::{
+        signature::Signature,
+        v2::{decode_piece_layers, FileTree, Sha256Hash},
+    },
+    value::Value,
+};
+
+/// Fields without a typed representation, keyed by their dict key.
+pub(super) type Extra = BTreeMap<Vec<u8>, Value<'static>>;
+
+/// The contents of a `.torrent` file.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{decoding::FromBencode, encoding::ToBencode, torrent::MetaInfo};
+///
+/// let encoded = b"d8:announce15:http://tracker/4:infod6:lengthi5e4:name5:a.txt\
+///                 12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee";
+/// let torrent = MetaInfo::from_bencode(encoded).unwrap();
+///
+/// assert_eq!(torrent.announce.as_deref(), Some("http://tracker/"));
+/// assert_eq!(torrent.info.name, "a.txt");
+/// assert_eq!(torrent.info.total_length(), 5);
+/// assert_eq!(torrent.to_bencode().unwrap(), &encoded[..]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetaInfo {
+    /// The URL of the tracker. Trackerless torrents don't have one.
+    pub announce: Option<String>,
+    /// Tiers of tracker URLs, as specified in
+    /// [BEP 12](http://bittorrent.org/beps/bep_0012.html).
+    pub announce_list: Option<Vec<Vec<String>>>,
+    /// A free-form comment, in the character set given by `encoding`. See
+    /// [`MetaInfo::comment_text`].
+    pub comment: Option<Vec<u8>>,
+    /// The program that created the torrent, in the character set given by
+    /// `encoding`.
+    pub created_by: Option<Vec<u8>>,
+    /// When the torrent was created, in seconds since the Unix epoch.
+    pub creation_date: Option<i64>,
+    /// The character set of the strings in the torrent, such as `UTF-8`.
+    pub encoding: Option<Vec<u8>>,
+    /// The description of the files, whose hash identifies the torrent.
+    pub info: Info,
+    /// DHT nodes to bootstrap from, for trackerless torrents as specified in
+    /// [BEP 5](http://bittorrent.org/beps/bep_0005.html).
+    pub nodes: Option<Vec<DhtNode>>,
+    /// The URLs of HTTP seeds, as specified in
+    /// [BEP 17](http://bittorrent.org/beps/bep_0017.html).
+    pub httpseeds: Option<Vec<String>>,
+    /// The hashes of the pieces of each file of a v2 torrent that is larger
+    /// than a piece, keyed by the file's `pieces root`.
+    pub piece_layers: Option<BTreeMap<Sha256Hash, Vec<u8>>>,
+    /// Signatures of the `info` dict, keyed by the identity of the signer, as
+    /// specified in [BEP 35](http://bittorrent.org/beps/bep_0035.html).
+    pub signatures: Option<BTreeMap<String, Signature>>,
+    /// The URLs of web seeds, as specified in
+    /// [BEP 19](http://bittorrent.org/beps/bep_0019.html).
+    pub url_list: Option<UrlList>,
+    /// All other fields of the metainfo dict.
+    pub extra: Extra,
+}
+
+/// A DHT node in the `nodes` field of a torrent, encoded as a list of its host
+/// and port.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DhtNode {
+    /// The host name or IP address of the node.
+    pub host: String,
+    /// The UDP port of the node.
+    pub port: u16,
+}
+
+impl DhtNode {
+    /// Create a node from its host and port.
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        DhtNode {
+            host: host.into(),
+            port,
+        }
+    }
+}
+
+impl Display for DhtNode {
+    /// Write the node as `host:port`, enclosing IPv6 addresses in brackets.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// The `url-list` field, which is either a single URL or a list of URLs.
+///
+/// Both forms are kept apart so that re-encoding a torrent doesn't change it.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{decoding::FromBencode, torrent::UrlList};
+///
+/// let single = UrlList::from_bencode(b"12:http://seed/").unwrap();
+/// let list = UrlList::from_bencode(b"l12:http://seed/e").unwrap();
+///
+/// assert_eq!(single.urls(), list.urls());
+/// assert_ne!(single, list);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UrlList {
+    /// A single URL given as a string.
+    Single(String),
+    /// Any number of URLs given as a list.
+    List(Vec<String>),
+}
+
+/// The `info` dict of a torrent, describing its files and pieces.
+///
+/// A v1 torrent has `pieces` and a `layout`, a v2 torrent has a `meta_version`
+/// and a `file_tree`, as specified in
+/// [BEP 52](http://bittorrent.org/beps/bep_0052.html). A Merkle torrent is a v1
+/// torrent with a `root_hash` instead of `pieces`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Info {
+    /// The name of the file, or of the directory containing the files.
+    pub name: String,
+    /// The number of bytes in each piece, except possibly the last one.
+    pub piece_length: u64,
+    /// The concatenated SHA-1 hashes of all pieces of a v1 torrent, split by
+    /// [`Info::piece_hashes`].
+    pub pieces: Option<Vec<u8>>,
+    /// The root of the SHA-1 Merkle tree over the piece hashes of a Merkle
+    /// torrent, which replaces `pieces`, as specified in
+    /// [BEP 30](http://bittorrent.org/beps/bep_0030.html).
+    pub root_hash: Option<[u8; 20]>,
+    /// Whether a v1 torrent contains a single file or a directory of files.
+    pub layout: Option<Layout>,
+    /// The version of a v2 torrent's metadata, always 2.
+    pub meta_version: Option<u64>,
+    /// The files of a v2 torrent.
+    pub file_tree: Option<FileTree>,
+    /// Whether peers may only be obtained from the torrent's trackers, as
+    /// specified in [BEP 27](http://bittorrent.org/beps/bep_0027.html). Only
+    /// the values 0 and 1 are typed, others are kept in `extra`.
+    ///
+    /// Like every field of the info dict, this is part of the infohash, so
+    /// changing it creates a different torrent. See
+    /// [`MetaInfo::set_private`](crate::torrent::MetaInfo::set_private).
+    pub private: Option<bool>,
+    /// The tracker or community the torrent was made for, which trackers use to
+    /// give cross-posted torrents distinct infohashes.
+    pub source: Option<String>,
+    /// All other fields of the info dict.
+    pub extra: Extra,
+}
+
+/// The files of a torrent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// A single file named after the torrent, encoded as a `length` field.
+    SingleFile {
+        /// The length of the file in bytes.
+        length: u64,
+    },
+    /// A directory named after the torrent, encoded as a `files` list.
+    MultiFile {
+        /// The files in the order in which their contents are concatenated into
+        /// pieces.
+        files: Vec<FileEntry>,
+    },
+}
+
+/// A file of a multi-file torrent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileEntry {
+    /// The length of the file in bytes.
+    pub length: u64,
+    /// The path of the file relative to the torrent's directory, one element
+    /// per path component.
+    pub path: Vec<String>,
+    /// The attributes of the file as specified in
+    /// [BEP 47](http://bittorrent.org/beps/bep_0047.html), one letter each:
+    /// `p` for padding, `x` for executable, `h` for hidden and `l` for symlink.
+    pub attr: Option<String>,
+    /// All other fields of the file dict.
+    pub extra: Extra,
+}
+
+impl FileEntry {
+    /// Whether the file has the attribute `attribute`.
+    pub fn has_attr(&self, attribute: char) -> bool {
+        self.attr
+            .as_ref()
+            .map_or(false, |attr| attr.contains(attribute))
+    }
+
+    /// Whether the file is a padding file, which only contains zeros to align
+    /// the next file to a piece boundary and isn't written to disk.
+    pub fn is_padding(&self) -> bool {
+        self.has_attr('p')
+    }
+}
+
+impl MetaInfo {
+    /// The comment as text, decoded as described for [`MetaInfo::decode_text`].
+    pub fn comment_text(&self) -> Option<Cow<'_, str>> {
+        self.comment
+            .as_deref()
+            .map(|comment| self.decode_text(comment))
+    }
+
+    /// The name of the program that created the torrent as text, decoded as
+    /// described for [`MetaInfo::decode_text`].
+    pub fn created_by_text(&self) -> Option<Cow<'_, str>> {
+        self.created_by
+            .as_deref()
+            .map(|created_by| self.decode_text(created_by))
+    }
+
+    /// Convert a string of the torrent to text, respecting the declared
+    /// `encoding`.
+    ///
+    /// Strings are decoded as Latin-1 if the torrent declares `ISO-8859-1` or
+    /// an alias of it, and as UTF-8 otherwise, replacing invalid sequences with
+    /// U+FFFD.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::{decoding::FromBencode, torrent::MetaInfo};
+    ///
+    /// let torrent = MetaInfo::from_bencode(
+    ///     b"d7:comment4:caf\xe98:encoding10:ISO-8859-14:infod6:lengthi5e\
+    ///       4:name5:a.txt12:piece lengthi16384e6:pieces0:ee",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(torrent.comment_text().unwrap(), "caf\u{e9}");
+    /// ```
+    pub fn decode_text<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        let latin1 = self.encoding.as_deref().map_or(false, |encoding| {
+            ["iso-8859-1", "iso8859-1", "latin1", "latin-1", "l1"]
+                .iter()
+                .any(|name| name.as_bytes().eq_ignore_ascii_case(encoding))
+        });
+        if latin1 {
+            Cow::Owned(bytes.iter().map(|&byte| char::from(byte)).collect())
+        } else {
+            String::from_utf8_lossy(bytes)
+        }
+    }
+}
+
+impl UrlList {
+    /// The URLs, regardless of the form they were given in. An empty single URL,
+    /// which some tools write instead of leaving the field out, is no URL.
+    pub fn urls(&self) -> &[String] {
+        match self {
+            UrlList::Single(url) if url.is_empty() => &[],
+            UrlList::Single(url) => std::slice::from_ref(url),
+            UrlList::List(urls) => urls,
+        }
+    }
+}
+
+impl From<Vec<String>> for UrlList {
+    fn from(urls: Vec<String>) -> Self {
+        UrlList::List(urls)
+    }
+}
+
+impl Info {
+    /// The total length of all files in bytes.
+    pub fn total_length(&self) -> u64 {
+        match (&self.layout, &self.file_tree) {
+            (Some(Layout::SingleFile { length }), _) => *length,
+            (Some(Layout::MultiFile { files }), _) => files.iter().map(|file| file.length).sum(),
+            (None, Some(tree)) => tree.total_length(),
+            (None, None) => 0,
+        }
+    }
+
+    /// Whether the torrent describes a directory of files.
+    pub fn is_multi_file(&self) -> bool {
+        match (&self.layout, &self.file_tree) {
+            (Some(layout), _) => matches!(layout, Layout::MultiFile { .. }),
+            (None, Some(tree)) => tree.files().any(|(path, _)| path.len() > 1),
+            (None, None) => false,
+        }
+    }
+
+    /// Whether the info dict contains v1 metadata, either `pieces` or a
+    /// `root_hash`.
+    pub fn is_v1(&self) -> bool {
+        self.pieces.is_some() || self.root_hash.is_some()
+    }
+
+    /// Whether the torrent is a Merkle torrent, identifying its pieces by a
+    /// `root_hash`.
+    pub fn is_merkle(&self) -> bool {
+        self.root_hash.is_some()
+    }
+
+    /// Whether the info dict contains v2 metadata.
+    pub fn is_v2(&self) -> bool {
+        self.file_tree.is_some()
+    }
+
+    /// Whether the torrent is private.
+    pub fn is_private(&self) -> bool {
+        self.private == Some(true)
+    }
+}
+
+pub(super) fn decode_extra(
+    extra: &mut Extra,
+    key: &[u8],
+    value: Object,
+) -> Result<(), decoding::Error> {
+    let value =
+        Value::decode_bencode_object(value).context(String::from_utf8_lossy(key).into_owned())?;
+    extra.insert(key.to_vec(), value);
+    Ok(())
+}
+
+impl FromBencode for MetaInfo {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut announce = None;
+        let mut announce_list = None;
+        let mut comment = None;
+        let mut created_by = None;
+        let mut creation_date = None;
+        let mut encoding = None;
+        let mut httpseeds = None;
+        let mut info = None;
+        let mut nodes = None;
+        let mut piece_layers = None;
+        let mut signatures = None;
+        let mut url_list = None;
+        let mut extra = Extra::new();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"announce", value) => {
+                    announce = String::decode_bencode_object(value)
+                        .context("announce")
+                        .map(Some)?;
+                },
+                (b"announce-list", value) => {
+                    announce_list = Vec::decode_bencode_object(value)
+                        .context("announce-list")
+                        .map(Some)?;
+                },
+                (b"comment", value) => {
+                    comment = AsString::decode_bencode_object(value)
+                        .context("comment")
+                        .map(|bytes| Some(bytes.0))?;
+                },
+                (b"created by", value) => {
+                    created_by = AsString::decode_bencode_object(value)
+                        .context("created by")
+                        .map(|bytes| Some(bytes.0))?;
+                },
+                (b"creation date", value) => {
+                    creation_date = i64::decode_bencode_object(value)
+                        .context("creation date")
+                        .map(Some)?;
+                },
+                (b"encoding", value) => {
+                    encoding = AsString::decode_bencode_object(value)
+                        .context("encoding")
+                        .map(|bytes| Some(bytes.0))?;
+                },
+                (b"httpseeds", value) => {
+                    httpseeds = Vec::decode_bencode_object(value)
+                        .context("httpseeds")
+                        .map(Some)?;
+                },
+                (b"info", value) => {
+                    info = Info::decode_bencode_object(value)
+                        .context("info")
+                        .map(Some)?;
+                },
+                (b"nodes", value) => {
+                    nodes = Vec::decode_bencode_object(value)
+                        .context("nodes")
+                        .map(Some)?;
+                },
+                (b"piece layers", value) => {
+                    piece_layers = decode_piece_layers(value)
+                        .context("piece layers")
+                        .map(Some)?;
+                },
+                (b"signatures", value) => {
+                    signatures = BTreeMap::decode_bencode_object(value)
+                        .context("signatures")
+                        .map(Some)?;
+                },
+                (b"url-list", value) => {
+                    url_list = UrlList::decode_bencode_object(value)
+                        .context("url-list")
+                        .map(Some)?;
+                },
+                (key, value) => decode_extra(&mut extra, key, value)?,
+            }
+        }
+
+        Ok(MetaInfo {
+            announce,
+            announce_list,
+            comment,
+            created_by,
+            creation_date,
+            encoding,
+            httpseeds,
+            info: info.ok_or_else(|| decoding::Error::missing_field("info"))?,
+            nodes,
+            piece_layers,
+            signatures,
+            url_list,
+            extra,
+        })
+    }
+}
+
+impl FromBencode for DhtNode {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut list = object.try_into_list()?;
+        let host = match list.next_object()? {
+            Some(host) => String::decode_bencode_object(host).context("host")?,
+            None => return Err(decoding::Error::missing_field("host")),
+        };
+        let port = match list.next_object()? {
+            Some(port) => u16::decode_bencode_object(port).context("port")?,
+            None => return Err(decoding::Error::missing_field("port")),
+        };
+        if list.next_object()?.is_some() {
+            return Err(decoding::Error::unexpected_token("End", "more items"));
+        }
+        Ok(DhtNode { host, port })
+    }
+}
+
+impl FromBencode for UrlList {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        match object {
+            Object::List(_) => Vec::decode_bencode_object(object).map(UrlList::List),
+            _ => String::decode_bencode_object(object).map(UrlList::Single),
+        }
+    }
+}
+
+impl FromBencode for Info {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut name = None;
+        let mut piece_length = None;
+        let mut pieces = None;
+        let mut root_hash = None;
+        let mut length = None;
+        let mut files = None;
+        let mut meta_version = None;
+        let mut file_tree = None;
+        let mut private = None;
+        let mut source = None;
+        let mut extra = Extra::new();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"file tree", value) => {
+                    file_tree = FileTree::decode_bencode_object(value)
+                        .context("file tree")
+                        .map(Some)?;
+                },
+                (b"files", value) => {
+                    files = Vec::decode_bencode_object(value)
+                        .context("files")
+                        .map(Some)?;
+                },
+                (b"length", value) => {
+                    length = u64::decode_bencode_object(value)
+                        .context("length")
+                        .map(Some)?;
+                },
+                (b"meta version", value) => {
+                    meta_version = u64::decode_bencode_object(value)
+                        .context("meta version")
+                        .map(Some)?;
+                },
+                (b"name", value) => {
+                    name = String::decode_bencode_object(value)
+                        .context("name")
+                        .map(Some)?;
+                },
+                (b"piece length", value) => {
+                    piece_length = u64::decode_bencode_object(value)
+                        .context("piece length")
+                        .map(Some)?;
+                },
+                (b"pieces", value) => {
+                    pieces = AsString::decode_bencode_object(value)
+                        .context("pieces")
+                        .map(|bytes| Some(bytes.0))?;
+                },
+                (b"private", Object::Integer("0")) => private = Some(false),
+                (b"private", Object::Integer("1")) => private = Some(true),
+                (b"root hash", value) => {
+                    let bytes = value.try_into_bytes().context("root hash")?;
+                    root_hash = <[u8; 20]>::try_from(bytes)
+                        .map_err(|_| {
+                            decoding::Error::unexpected_token(
+                                "20-byte hash",
+                                format!("{} bytes", bytes.len()),
+                            )
+                        })
+                        .context("root hash")
+                        .map(Some)?;
+                },
+                (b"source", value) => {
+                    source = String::decode_bencode_object(value)
+                        .context("source")
+                        .map(Some)?;
+                },
+                (key, value) => decode_extra(&mut extra, key, value)?,
+            }
+        }
+
+        let layout = match (length, files) {
+            (Some(length), None) => Some(Layout::SingleFile { length }),
+            (None, Some(files)) => Some(Layout::MultiFile { files }),
+            (Some(_), Some(_)) => return Err(decoding::Error::unexpected_field("files")),
+            (None, None) => None,
+        };
+        match (&pieces, &root_hash, &layout) {
+            (Some(_), Some(_), _) => return Err(decoding::Error::unexpected_field("root hash")),
+            (Some(_), None, None) | (None, Some(_), None) => {
+                return Err(decoding::Error::missing_field("length"))
+            },
+            (None, None, Some(_)) => return Err(decoding::Error::missing_field("pieces")),
+            _ => {},
+        }
+        match (meta_version, &file_tree) {
+            (Some(2), Some(_)) | (None, None) => {},
+            (Some(2), None) => return Err(decoding::Error::missing_field("file tree")),
+            (None, Some(_)) => return Err(decoding::Error::missing_field("meta version")),
+            (Some(version), _) => {
+                return Err(decoding::Error::unexpected_token("meta version 2", version))
+            },
+        }
+        if pieces.is_none() && root_hash.is_none() && file_tree.is_none() {
+            return Err(decoding::Error::missing_field("pieces"));
+        }
+
+        Ok(Info {
+            name: name.ok_or_else(|| decoding::Error::missing_field("name"))?,
+            piece_length: piece_length
+                .ok_or_else(|| decoding::Error::missing_field("piece length"))?,
+            pieces,
+            root_hash,
+            layout,
+            meta_version,
+            file_tree,
+            private,
+            source,
+            extra,
+        })
+    }
+}
+
+impl FromBencode for FileEntry {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut attr = None;
+        let mut length = None;
+        let mut path = None;
+        let mut extra = Extra::new();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"attr", value) => {
+                    attr = String::decode_bencode_object(value)
+                        .context("attr")
+                        .map(Some)?;
+                },
+                (b"length", value) => {
+                    length = u64::decode_bencode_object(value)
+                        .context("length")
+                        .map(Some)?;
+                },
+                (b"path", value) => {
+                    path = Vec::decode_bencode_object(value)
+                        .context("path")
+                        .map(Some)?;
+                },
+                (key, value) => decode_extra(&mut extra, key, value)?,
+            }
+        }
+
+        Ok(FileEntry {
+            length: length.ok_or_else(|| decoding::Error::missing_field("length"))?,
+            path: path.ok_or_else(|| decoding::Error::missing_field("path"))?,
+            attr,
+            extra,
+        })
+    }
+}
+
+// The `extra` maps can contain keys which sort between the typed fields, so the
+// dicts are emitted unsorted and sorted by the encoder.
+
+impl ToBencode for MetaInfo {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        self.encode_with_info(encoder, |e| e.emit(&self.info))
+    }
+}
+
+impl MetaInfo {
+    /// Encode the metainfo dict, with `emit_info` emitting the `info` dict.
+    pub(super) fn encode_with_info<F>(
+        &self,
+        encoder: SingleItemEncoder,
+        emit_info: F,
+    ) -> Result<(), encoding::Error>
+    where
+        F: FnOnce(SingleItemEncoder) -> Result<(), encoding::Error>,
+    {
+        encoder.emit_unsorted_dict(|e| {
+            if let Some(announce) = &self.announce {
+                e.emit_pair(b"announce", announce)?;
+            }
+            if let Some(announce_list) = &self.announce_list {
+                e.emit_pair(b"announce-list", announce_list)?;
+            }
+            if let Some(comment) = &self.comment {
+                e.emit_pair(b"comment", AsString(comment))?;
+            }
+            if let Some(created_by) = &self.created_by {
+                e.emit_pair(b"created by", AsString(created_by))?;
+            }
+            if let Some(creation_date) = self.creation_date {
+                e.emit_pair(b"creation date", creation_date)?;
+            }
+            if let Some(encoding) = &self.encoding {
+                e.emit_pair(b"encoding", AsString(encoding))?;
+            }
+            if let Some(httpseeds) = &self.httpseeds {
+                e.emit_pair(b"httpseeds", httpseeds)?;
+            }
+            e.emit_pair_with(b"info", emit_info)?;
+            if let Some(nodes) = &self.nodes {
+                e.emit_pair(b"nodes", nodes)?;
+            }
+            if let Some(piece_layers) = &self.piece_layers {
+                e.emit_pair_with(b"piece layers", |e| {
+                    e.emit_dict(|mut e| {
+                        for (root, hashes) in piece_layers {
+                            e.emit_pair(root, AsString(hashes))?;
+                        }
+                        Ok(())
+                    })
+                })?;
+            }
+            if let Some(signatures) = &self.signatures {
+                e.emit_pair(b"signatures", signatures)?;
+            }
+            if let Some(url_list) = &self.url_list {
+                e.emit_pair(b"url-list", url_list)?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ToBencode for Info {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            match &self.layout {
+                Some(Layout::SingleFile { length }) => e.emit_pair(b"length", length)?,
+                Some(Layout::MultiFile { files }) => e.emit_pair(b"files", files)?,
+                None => {},
+            }
+            if let Some(file_tree) = &self.file_tree {
+                e.emit_pair(b"file tree", file_tree)?;
+            }
+            if let Some(meta_version) = self.meta_version {
+                e.emit_pair(b"meta version", meta_version)?;
+            }
+            e.emit_pair(b"name", &self.name)?;
+            e.emit_pair(b"piece length", self.piece_length)?;
+            if let Some(pieces) = &self.pieces {
+                e.emit_pair(b"pieces", AsString(pieces))?;
+            }
+            if let Some(private) = self.private {
+                e.emit_pair(b"private", i64::from(private))?;
+            }
+            if let Some(root_hash) = &self.root_hash {
+                e.emit_pair(b"root hash", AsString(root_hash))?;
+            }
+            if let Some(source) = &self.source {
+                e.emit_pair(b"source", source)?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ToBencode for DhtNode {
+    const MAX_DEPTH: usize = 1;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_list(|e| {
+            e.emit_str(&self.host)?;
+            e.emit_int(self.port)
+        })
+    }
+}
+
+impl ToBencode for UrlList {
+    const MAX_DEPTH: usize = 1;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        match self {
+            UrlList::Single(url) => encoder.emit(url),
+            UrlList::List(urls) => encoder.emit(urls),
+        }
+    }
+}
+
+impl ToBencode for FileEntry {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            if let Some(attr) = &self.attr {
+                e.emit_pair(b"attr", attr)?;
+            }
+            e.emit_pair(b"length", self.length)?;
+            e.emit_pair(b"path", &self.path)?;
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static DEBIAN: &[u8] =
+        include_bytes!("../../examples/torrent_files/debian-9.4.0-amd64-netinst.iso.torrent");
+
+    #[test]
+    fn single_file_torrent_round_trips() {
+        let torrent = MetaInfo::from_bencode(DEBIAN).unwrap();
+
+        assert_eq!(
+            torrent.announce.as_deref(),
+            Some("http://bttracker.debian.org:6969/announce")
+        );
+        assert_eq!(torrent.info.name, "debian-9.4.0-amd64-netinst.iso");
+        assert_eq!(torrent.info.piece_length, 262_144);
+        assert_eq!(torrent.info.total_length(), 305_135_616);
+        assert!(!torrent.info.is_multi_file());
+        assert_eq!(torrent.httpseeds.as_ref().map(Vec::len), Some(2));
+        assert_eq!(torrent.to_bencode().unwrap(), DEBIAN);
+    }
+
+    #[test]
+    fn multi_file_torrent_round_trips() {
+        let encoded =
+            b"d4:infod5:filesld6:lengthi3e6:md5sum0:4:pathl1:a1:beed4:attr2:xh6:lengthi4e4:pathl1:ceee\
+                        4:name3:dir12:piece lengthi16384e6:pieces0:7:privatei1eee";
+        let torrent = MetaInfo::from_bencode(encoded).unwrap();
+
+        match &torrent.info.layout {
+            Some(Layout::MultiFile { files }) => {
+                assert_eq!(files.len(), 2);
+                assert_eq!(files[0].path, vec!["a", "b"]);
+                assert!(files[0].extra.contains_key(&b"md5sum"[..]));
+                assert_eq!(files[0].attr, None);
+                assert!(files[1].has_attr('x') && files[1].has_attr('h'));
+                assert!(!files[1].is_padding());
+            },
+            other => panic!("expected multiple files, got {:?}", other),
+        }
+        assert_eq!(torrent.info.total_length(), 7);
+        assert!(torrent.info.is_private());
+        assert_eq!(torrent.to_bencode().unwrap(), &encoded[..]);
+    }
+
+    #[test]
+    fn v2_torrent_round_trips() {
+        let root = [3; 32];
+        let mut encoded =
+            b"d4:infod9:file treed4:a.txd0:d6:lengthi20000e11:pieces root32:".to_vec();
+        encoded.extend_from_slice(&root);
+        encoded.extend_from_slice(
+            b"eee12:meta versioni2e4:name4:a.tx12:piece lengthi16384ee12:piece layersd32:",
+        );
+        encoded.extend_from_slice(&root);
+        encoded.extend_from_slice(b"64:");
+        encoded.extend_from_slice(&[9; 64]);
+        encoded.extend_from_slice(b"ee");
+
+        let torrent = MetaInfo::from_bencode(&encoded).unwrap();
+        assert!(torrent.info.is_v2());
+        assert!(!torrent.info.is_v1());
+        assert!(!torrent.info.is_multi_file());
+        assert_eq!(torrent.info.total_length(), 20_000);
+        assert_eq!(torrent.piece_layers.as_ref().unwrap()[&root], vec![9; 64]);
+        assert_eq!(torrent.to_bencode().unwrap(), encoded);
+    }
+
+    #[test]
+    fn inconsistent_versions_are_rejected() {
+        let tree = "9:file treed1:ad0:d6:lengthi1eeee";
+        for info in &[
+            format!("d{}4:name1:a12:piece lengthi16384ee", tree),
+            format!(
+                "d{}12:meta versioni3e4:name1:a12:piece lengthi16384ee",
+                tree
+            ),
+            "d12:meta versioni2e4:name1:a12:piece lengthi16384ee".to_string(),
+            "d6:lengthi1e4:name1:a12:piece lengthi16384ee".to_string(),
+        ] {
+            let torrent = format!("d4:info{}e", info);
+            assert!(
+                MetaInfo::from_bencode(torrent.as_bytes()).is_err(),
+                "{}",
+                info
+            );
+        }
+        assert!(MetaInfo::from_bencode(
+            b"d4:infod4:name1:a12:piece lengthi1e12:piece layersd1:a0:eee"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn text_fields_need_not_be_utf8() {
+        let info = "4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:e";
+        let mut encoded = b"d7:comment2:\xe9!".to_vec();
+        encoded.extend_from_slice(info.as_bytes());
+        encoded.push(b'e');
+        let torrent = MetaInfo::from_bencode(&encoded).unwrap();
+        assert_eq!(torrent.comment.as_deref(), Some(&b"\xe9!"[..]));
+        assert_eq!(torrent.comment_text().unwrap(), "\u{fffd}!");
+        assert_eq!(torrent.created_by_text(), None);
+        assert_eq!(torrent.to_bencode().unwrap(), encoded);
+
+        let encoded = format!("d10:created by2:\u{e9}8:encoding6:latin1{}e", info);
+        let torrent = MetaInfo::from_bencode(encoded.as_bytes()).unwrap();
+        assert_eq!(torrent.created_by_text().unwrap(), "\u{c3}\u{a9}");
+        assert_eq!(torrent.decode_text(b"\xff"), "\u{ff}");
+    }
+
+    #[test]
+    fn private_and_source_round_trip() {
+        let info = |fields: &str| {
+            let encoded = format!(
+                "d4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:{}ee",
+                fields
+            );
+            let torrent = MetaInfo::from_bencode(encoded.as_bytes()).unwrap();
+            assert_eq!(torrent.to_bencode().unwrap(), encoded.as_bytes());
+            torrent.info
+        };
+        let typed = info("7:privatei1e6:source3:abc");
+        assert!(typed.is_private());
+        assert_eq!(typed.source.as_deref(), Some("abc"));
+        assert_eq!(info("7:privatei0e").private, Some(false));
+
+        let unusual = info("7:privatei2e");
+        assert_eq!(unusual.private, None);
+        assert_eq!(unusual.extra[&b"private"[..]], Value::Integer(2));
+    }
+
+    #[test]
+    fn web_seeds_round_trip() {
+        let info = "4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:e";
+        for url_list in &["8:http://s", "l8:http://s8:http://te", "0:"] {
+            let encoded = format!("d9:httpseedsl8:http://he{}8:url-list{}e", info, url_list);
+            let torrent = MetaInfo::from_bencode(encoded.as_bytes()).unwrap();
+            assert_eq!(torrent.httpseeds, Some(vec!["http://h".to_string()]));
+            assert_eq!(torrent.to_bencode().unwrap(), encoded.as_bytes());
+        }
+
+        let torrent = |url_list: &str| {
+            let encoded = format!("d{}8:url-list{}e", info, url_list);
+            MetaInfo::from_bencode(encoded.as_bytes()).map(|torrent| torrent.url_list)
+        };
+        let urls = torrent("8:http://s").unwrap().unwrap();
+        assert_eq!(urls.urls(), ["http://s"]);
+        assert!(torrent("0:").unwrap().unwrap().urls().is_empty());
+        assert!(torrent("i1e").is_err());
+        assert!(torrent("li1ee").is_err());
+    }
+
+    #[test]
+    fn dht_nodes_round_trip() {
+        let encoded = b"d4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:e\
+                        5:nodesll9:127.0.0.1i6881eel3:::1i80eeee";
+        let torrent = MetaInfo::from_bencode(encoded).unwrap();
+        let nodes = torrent.nodes.as_ref().unwrap();
+        assert_eq!(
+            nodes,
+            &[DhtNode::new("127.0.0.1", 6881), DhtNode::new("::1", 80)]
+        );
+        assert_eq!(nodes[0].to_string(), "127.0.0.1:6881");
+        assert_eq!(nodes[1].to_string(), "[::1]:80");
+        assert_eq!(torrent.to_bencode().unwrap(), &encoded[..]);
+
+        let node = |encoded: &[u8]| DhtNode::from_bencode(encoded);
+        assert!(node(b"l1:ae").is_err());
+        assert!(node(b"l1:ai70000ee").is_err());
+        assert!(node(b"l1:ai1ei2ee").is_err());
+    }
+
+    #[test]
+    fn invalid_layouts_are_rejected() {
+        assert!(
+            MetaInfo::from_bencode(b"d4:infod4:name1:a12:piece lengthi1e6:pieces0:ee").is_err()
+        );
+        assert!(MetaInfo::from_bencode(
+            b"d4:infod5:filesle6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:ee"
+        )
+        .is_err());
+        assert!(MetaInfo::from_bencode(b"d8:announce1:ae").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! Read-only memory maps of the files hashed by the torrent builder.
+
+use std::{fs::File, io, os::unix::io::AsRawFd, ptr, slice};
+
+/// A read-only memory map of a whole file, unmapped when dropped.
+pub(crate) struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Map all of `file`, which fails for empty files and for files that can't
+    /// be mapped, like pipes.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be changed or truncated while the map exists, since
+    /// [`as_slice`](Mmap::as_slice) hands out its contents as a plain slice.
+    pub(crate) unsafe fn map(file: &File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        if len == 0 || len > usize::MAX as u64 {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+        let len = len as usize;
+
+        // Safety: a new shared read-only mapping doesn't alias any memory, and
+        // the file descriptor stays valid for the duration of the call
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // Safety: the range is the mapping created above. The advice is only a
+        // hint, so its result doesn't matter
+        libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        Ok(Mmap { ptr, len })
+    }
+
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        // Safety: the mapping is readable and `len` bytes long until dropped,
+        // and its contents don't change, as `map` requires
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // Safety: the mapping was created by `map` and isn't used afterwards
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
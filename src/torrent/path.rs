@@ -0,0 +1,161 @@
+//! Turning the paths of files in torrents into paths which are safe to create.
+//!
+//! Paths in torrents are lists of components chosen by whoever created the
+//! torrent. Joining them naively lets a torrent write outside the download
+//! directory with components like `..` or `/etc`, or fail on Windows with
+//! names like `CON`.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
+
+use crate::torrent::FileEntry;
+
+/// Why a path from a torrent is unsafe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathError {
+    /// The path has no components.
+    Empty,
+    /// A component is empty, `.` or `..`.
+    Relative(String),
+    /// A component contains a path separator, a drive prefix or a NUL byte, so
+    /// it could name an absolute path or a different directory.
+    Separator(String),
+    /// A component is a device name reserved on Windows, such as `NUL` or
+    /// `com1.txt`.
+    Reserved(String),
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            PathError::Empty => write!(f, "path has no components"),
+            PathError::Relative(component) => {
+                write!(f, "path component {:?} is relative", component)
+            },
+            PathError::Separator(component) => {
+                write!(f, "path component {:?} contains a separator", component)
+            },
+            PathError::Reserved(component) => {
+                write!(f, "path component {:?} is a reserved name", component)
+            },
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// The device names Windows reserves in every directory, with any extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Check that `component` names a file or directory within its parent on
+/// every platform.
+pub fn check_component(component: &str) -> Result<(), PathError> {
+    if component.is_empty() || component == "." || component == ".." {
+        return Err(PathError::Relative(component.to_string()));
+    }
+    if component.contains(['/', '\\', ':', '\0']) {
+        return Err(PathError::Separator(component.to_string()));
+    }
+    let stem = component.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(stem))
+    {
+        return Err(PathError::Reserved(component.to_string()));
+    }
+    Ok(())
+}
+
+/// Join the components of a path from a torrent into a relative path, checking
+/// each with [`check_component`].
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+///
+/// use bendy::torrent::{safe_path, PathError};
+///
+/// assert_eq!(safe_path(&["dir", "file.txt"]), Ok(PathBuf::from("dir/file.txt")));
+/// assert_eq!(
+///     safe_path(&["..", "passwd"]),
+///     Err(PathError::Relative("..".to_string()))
+/// );
+/// assert!(safe_path(&["/etc"]).is_err());
+/// assert!(safe_path(&["aux.c"]).is_err());
+/// ```
+pub fn safe_path<S: AsRef<str>>(components: &[S]) -> Result<PathBuf, PathError> {
+    if components.is_empty() {
+        return Err(PathError::Empty);
+    }
+    let mut path = PathBuf::new();
+    for component in components {
+        check_component(component.as_ref())?;
+        path.push(component.as_ref());
+    }
+    Ok(path)
+}
+
+impl FileEntry {
+    /// The path of the file relative to the torrent's directory, if it's safe
+    /// as described for [`safe_path`].
+    pub fn safe_path(&self) -> Result<PathBuf, PathError> {
+        safe_path(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_components_are_joined() {
+        let path = safe_path(&["a", "b.c", "con-fig", "..d", "NULL"]).unwrap();
+        assert_eq!(
+            path,
+            ["a", "b.c", "con-fig", "..d", "NULL"]
+                .iter()
+                .collect::<PathBuf>()
+        );
+        assert!(path.is_relative());
+    }
+
+    #[test]
+    fn unsafe_components_are_rejected() {
+        assert_eq!(safe_path::<&str>(&[]), Err(PathError::Empty));
+        for component in &["", ".", ".."] {
+            assert_eq!(
+                safe_path(&["a", component]),
+                Err(PathError::Relative(component.to_string()))
+            );
+        }
+        for component in &["/", "/etc", "a/..", "..\\b", "C:", "C:\\x", "a\0"] {
+            assert_eq!(
+                check_component(component),
+                Err(PathError::Separator(component.to_string()))
+            );
+        }
+        for component in &["CON", "nul", "Com1.txt", "lpt9.tar.gz", "aux ."] {
+            assert_eq!(
+                check_component(component),
+                Err(PathError::Reserved(component.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn file_entries_have_safe_paths() {
+        let file = FileEntry {
+            length: 0,
+            path: vec!["x".to_string(), "..".to_string()],
+            attr: None,
+            extra: Default::default(),
+        };
+        assert!(file.safe_path().is_err());
+    }
+}
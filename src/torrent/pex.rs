@@ -0,0 +1,238 @@
+//! The messages of the peer exchange extension `ut_pex`, as specified in
+//! [BEP 11](http://bittorrent.org/beps/bep_0011.html).
+
+use std::{
+    fmt::{self, Debug, Formatter},
+    net::{SocketAddrV4, SocketAddrV6},
+};
+
+use crate::{
+    decoding::{self, FromBencode, Object, ResultExt},
+    encoding::{self, AsString, SingleItemEncoder, ToBencode},
+    torrent::{
+        compact::{
+            decode_compact_peers, decode_compact_peers6, encode_compact_peers,
+            encode_compact_peers6,
+        },
+        metainfo::{decode_extra, Extra},
+    },
+    value::Value,
+};
+
+/// The flags describing a peer added in a [`PexMessage`], one byte per peer in
+/// the `added.f` and `added6.f` fields.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PexFlags(pub u8);
+
+impl PexFlags {
+    /// The peer prefers encrypted connections.
+    pub const ENCRYPTION: PexFlags = PexFlags(0x01);
+    /// The peer is a seed or partial seed.
+    pub const SEED: PexFlags = PexFlags(0x02);
+    /// The peer supports uTP.
+    pub const UTP: PexFlags = PexFlags(0x04);
+    /// The peer supports the holepunch extension.
+    pub const HOLEPUNCH: PexFlags = PexFlags(0x08);
+    /// The peer is reachable, as the sender connected to it.
+    pub const REACHABLE: PexFlags = PexFlags(0x10);
+
+    /// Whether all flags set in `flags` are also set in `self`.
+    pub fn contains(self, flags: PexFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+}
+
+impl std::ops::BitOr for PexFlags {
+    type Output = PexFlags;
+
+    fn bitor(self, other: PexFlags) -> PexFlags {
+        PexFlags(self.0 | other.0)
+    }
+}
+
+impl Debug for PexFlags {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "PexFlags({:#04x})", self.0)
+    }
+}
+
+/// A `ut_pex` message, listing the peers connected and disconnected since the
+/// previous message.
+///
+/// Peers are stored together with their flags. Flags missing from a received
+/// message, as some clients omit `added.f`, are empty.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{
+///     decoding::FromBencode,
+///     encoding::ToBencode,
+///     torrent::{PexFlags, PexMessage},
+/// };
+///
+/// let mut message = PexMessage::default();
+/// message.added.push(("10.0.0.1:6881".parse().unwrap(), PexFlags::SEED));
+///
+/// let encoded = message.to_bencode().unwrap();
+/// assert_eq!(
+///     encoded,
+///     &b"d5:added6:\x0a\x00\x00\x01\x1a\xe17:added.f1:\x026:added60:8:added6.f0:\
+///        7:dropped0:8:dropped60:e"[..]
+/// );
+/// assert_eq!(PexMessage::from_bencode(&encoded).unwrap(), message);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PexMessage {
+    /// The IPv4 peers which were connected, from `added` and `added.f`.
+    pub added: Vec<(SocketAddrV4, PexFlags)>,
+    /// The IPv6 peers which were connected, from `added6` and `added6.f`.
+    pub added6: Vec<(SocketAddrV6, PexFlags)>,
+    /// The IPv4 peers which were disconnected.
+    pub dropped: Vec<SocketAddrV4>,
+    /// The IPv6 peers which were disconnected.
+    pub dropped6: Vec<SocketAddrV6>,
+    /// All other fields of the message dict.
+    pub extra: Extra,
+}
+
+impl PexMessage {
+    /// Whether the message neither adds nor drops any peers.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.added6.is_empty()
+            && self.dropped.is_empty()
+            && self.dropped6.is_empty()
+    }
+}
+
+/// Pair each peer with its flag byte.
+fn with_flags<A>(peers: Vec<A>, flags: &[u8]) -> Vec<(A, PexFlags)> {
+    peers
+        .into_iter()
+        .enumerate()
+        .map(|(i, peer)| (peer, PexFlags(flags.get(i).copied().unwrap_or_default())))
+        .collect()
+}
+
+fn flag_bytes<A>(peers: &[(A, PexFlags)]) -> Vec<u8> {
+    peers.iter().map(|(_, flags)| flags.0).collect()
+}
+
+fn addrs<A: Copy>(peers: &[(A, PexFlags)]) -> Vec<A> {
+    peers.iter().map(|&(addr, _)| addr).collect()
+}
+
+impl FromBencode for PexMessage {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut message = PexMessage::default();
+        let mut added = Vec::new();
+        let mut added_flags: &[u8] = &[];
+        let mut added6 = Vec::new();
+        let mut added6_flags: &[u8] = &[];
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"added", value) => {
+                    added = decode_compact_peers(value.try_into_bytes().context("added")?);
+                },
+                (b"added.f", value) => added_flags = value.try_into_bytes().context("added.f")?,
+                (b"added6", value) => {
+                    added6 = decode_compact_peers6(value.try_into_bytes().context("added6")?);
+                },
+                (b"added6.f", value) => {
+                    added6_flags = value.try_into_bytes().context("added6.f")?;
+                },
+                (b"dropped", value) => {
+                    message.dropped =
+                        decode_compact_peers(value.try_into_bytes().context("dropped")?);
+                },
+                (b"dropped6", value) => {
+                    message.dropped6 =
+                        decode_compact_peers6(value.try_into_bytes().context("dropped6")?);
+                },
+                (key, value) => decode_extra(&mut message.extra, key, value)?,
+            }
+        }
+
+        message.added = with_flags(added, added_flags);
+        message.added6 = with_flags(added6, added6_flags);
+        Ok(message)
+    }
+}
+
+impl ToBencode for PexMessage {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            e.emit_pair(
+                b"added",
+                AsString(&encode_compact_peers(&addrs(&self.added))),
+            )?;
+            e.emit_pair(b"added.f", AsString(&flag_bytes(&self.added)))?;
+            e.emit_pair(
+                b"added6",
+                AsString(&encode_compact_peers6(&addrs(&self.added6))),
+            )?;
+            e.emit_pair(b"added6.f", AsString(&flag_bytes(&self.added6)))?;
+            e.emit_pair(b"dropped", AsString(&encode_compact_peers(&self.dropped)))?;
+            e.emit_pair(
+                b"dropped6",
+                AsString(&encode_compact_peers6(&self.dropped6)),
+            )?;
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let message = PexMessage {
+            added: vec![
+                (
+                    "1.2.3.4:80".parse().unwrap(),
+                    PexFlags::ENCRYPTION | PexFlags::UTP,
+                ),
+                ("5.6.7.8:443".parse().unwrap(), PexFlags::default()),
+            ],
+            added6: vec![("[::1]:6881".parse().unwrap(), PexFlags::REACHABLE)],
+            dropped: vec!["9.9.9.9:1".parse().unwrap()],
+            dropped6: vec!["[2001:db8::1]:2".parse().unwrap()],
+            extra: Extra::new(),
+        };
+        let encoded = message.to_bencode().unwrap();
+        let decoded = PexMessage::from_bencode(&encoded).unwrap();
+        assert_eq!(decoded, message);
+        assert!(decoded.added[0].1.contains(PexFlags::UTP));
+        assert!(!decoded.added[0].1.contains(PexFlags::SEED));
+    }
+
+    #[test]
+    fn missing_flags_are_empty() {
+        let message = PexMessage::from_bencode(
+            b"d5:added12:\x01\x02\x03\x04\x00\x50\x05\x06\x07\x08\x01\xbb7:added.f1:\x02e",
+        )
+        .unwrap();
+        assert_eq!(message.added.len(), 2);
+        assert_eq!(message.added[0].1, PexFlags::SEED);
+        assert_eq!(message.added[1].1, PexFlags::default());
+        assert!(message.added6.is_empty());
+        assert!(!message.is_empty());
+        assert!(PexMessage::from_bencode(b"de").unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_messages_are_rejected() {
+        assert!(PexMessage::from_bencode(b"d5:addedi1ee").is_err());
+        assert!(PexMessage::from_bencode(b"d8:dropped6lee").is_err());
+    }
+}
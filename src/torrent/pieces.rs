@@ -0,0 +1,153 @@
+//! Access to the SHA-1 hashes of the pieces of v1 torrents.
+
+use std::{
+    convert::TryInto,
+    fmt::{self, Display, Formatter},
+    slice::ChunksExact,
+};
+
+use crate::torrent::Info;
+
+/// The length of the SHA-1 hash of a piece in `pieces`.
+pub const PIECE_HASH_LEN: usize = 20;
+
+/// Why the `pieces` of a torrent can't be split into hashes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PiecesError {
+    /// The torrent has no `pieces`, because it only contains v2 metadata or is
+    /// a Merkle torrent.
+    Missing,
+    /// The length of `pieces`, given here, isn't a multiple of 20 bytes.
+    Truncated(usize),
+}
+
+impl Display for PiecesError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            PiecesError::Missing => write!(f, "torrent has no v1 pieces"),
+            PiecesError::Truncated(length) => {
+                write!(f, "{} bytes of hashes are not a multiple of 20", length)
+            },
+        }
+    }
+}
+
+impl std::error::Error for PiecesError {}
+
+/// An iterator over the SHA-1 hashes of the pieces of a v1 torrent, returned
+/// by [`Info::piece_hashes`].
+#[derive(Clone, Debug)]
+pub struct PieceHashes<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+/// View a chunk of `pieces` as a hash
+fn to_hash(chunk: &[u8]) -> &[u8; PIECE_HASH_LEN] {
+    chunk.try_into().expect("chunks are as long as a hash")
+}
+
+impl<'a> Iterator for PieceHashes<'a> {
+    type Item = &'a [u8; PIECE_HASH_LEN];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks.next().map(to_hash)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.chunks.nth(n).map(to_hash)
+    }
+}
+
+impl<'a> DoubleEndedIterator for PieceHashes<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.chunks.next_back().map(to_hash)
+    }
+}
+
+impl<'a> ExactSizeIterator for PieceHashes<'a> {}
+
+impl Info {
+    /// The SHA-1 hashes of the pieces of a v1 torrent, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::{
+    ///     decoding::FromBencode,
+    ///     torrent::{MetaInfo, PiecesError},
+    /// };
+    ///
+    /// let torrent = MetaInfo::from_bencode(
+    ///     b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e\
+    ///       6:pieces20:aaaaaaaaaaaaaaaaaaaaee",
+    /// )
+    /// .unwrap();
+    /// let hashes: Vec<_> = torrent.info.piece_hashes().unwrap().collect();
+    /// assert_eq!(hashes, [&[b'a'; 20]]);
+    ///
+    /// let truncated = MetaInfo::from_bencode(
+    ///     b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces3:aaaee",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     truncated.info.piece_hashes().unwrap_err(),
+    ///     PiecesError::Truncated(3)
+    /// );
+    /// ```
+    pub fn piece_hashes(&self) -> Result<PieceHashes<'_>, PiecesError> {
+        let pieces = self.pieces.as_ref().ok_or(PiecesError::Missing)?;
+        let chunks = pieces.chunks_exact(PIECE_HASH_LEN);
+        if chunks.remainder().is_empty() {
+            Ok(PieceHashes { chunks })
+        } else {
+            Err(PiecesError::Truncated(pieces.len()))
+        }
+    }
+
+    /// The SHA-1 hash of the piece at `index`, if the torrent has v1 metadata
+    /// and enough pieces.
+    pub fn piece_hash(&self, index: usize) -> Option<&[u8; PIECE_HASH_LEN]> {
+        self.piece_hashes().ok()?.nth(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{decoding::FromBencode, torrent::MetaInfo};
+
+    static DEBIAN: &[u8] =
+        include_bytes!("../../examples/torrent_files/debian-9.4.0-amd64-netinst.iso.torrent");
+
+    #[test]
+    fn pieces_are_split_into_hashes() {
+        let torrent = MetaInfo::from_bencode(DEBIAN).unwrap();
+        let pieces = torrent.info.pieces.as_ref().unwrap();
+        let hashes: Vec<_> = torrent.info.piece_hashes().unwrap().copied().collect();
+        assert_eq!(hashes.len() * PIECE_HASH_LEN, pieces.len());
+        assert_eq!(hashes.concat(), *pieces);
+        assert_eq!(torrent.info.piece_hash(1), Some(&hashes[1]));
+        assert_eq!(torrent.info.piece_hash(hashes.len()), None);
+        assert_eq!(
+            torrent.info.piece_hashes().unwrap().next_back(),
+            hashes.last()
+        );
+    }
+
+    #[test]
+    fn missing_and_truncated_pieces_are_rejected() {
+        let mut info = MetaInfo::from_bencode(DEBIAN).unwrap().info;
+        info.pieces = Some(vec![0; 41]);
+        assert_eq!(info.piece_hashes().unwrap_err(), PiecesError::Truncated(41));
+        assert_eq!(info.piece_hash(0), None);
+        info.pieces = None;
+        assert_eq!(info.piece_hashes().unwrap_err(), PiecesError::Missing);
+        info.pieces = Some(Vec::new());
+        assert_eq!(info.piece_hashes().unwrap().len(), 0);
+    }
+}
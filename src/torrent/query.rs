@@ -0,0 +1,142 @@
+//! Percent-encoding of the binary values in the query strings of HTTP tracker
+//! requests.
+//!
+//! The `info_hash` and `peer_id` parameters of announce and scrape requests
+//! are raw bytes rather than text, so URL encoders which expect UTF-8 strings
+//! can't encode them.
+
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+};
+
+/// An error decoding a binary value from a query string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryError {
+    /// The value contains a `%` not followed by two hex digits.
+    InvalidEncoding(String),
+    /// The value decodes to this number of bytes rather than 20.
+    WrongLength(usize),
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            QueryError::InvalidEncoding(value) => {
+                write!(f, "invalid percent-encoding: {}", value)
+            },
+            QueryError::WrongLength(length) => {
+                write!(f, "expected 20 bytes, got {}", length)
+            },
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Percent-encode an infohash or peer id for the query string of a tracker
+/// request.
+///
+/// Only the unreserved characters of RFC 3986 are kept, so the result can be
+/// inserted into a URL as is.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::{decode_info_hash, encode_info_hash};
+///
+/// let mut info_hash = [b'a'; 20];
+/// info_hash[0] = 0x12;
+/// info_hash[1] = b' ';
+///
+/// let encoded = encode_info_hash(&info_hash);
+/// assert_eq!(encoded, "%12%20aaaaaaaaaaaaaaaaaa");
+/// assert_eq!(decode_info_hash(&encoded), Ok(info_hash));
+/// ```
+pub fn encode_info_hash(info_hash: &[u8; 20]) -> String {
+    percent_encode_bytes(info_hash)
+}
+
+/// Decode an infohash or peer id from the query string of a tracker request.
+///
+/// `+` decodes to a space, as in HTML forms.
+pub fn decode_info_hash(encoded: &str) -> Result<[u8; 20], QueryError> {
+    let bytes = percent_decode_bytes(encoded)
+        .ok_or_else(|| QueryError::InvalidEncoding(encoded.to_string()))?;
+    <[u8; 20]>::try_from(&bytes[..]).map_err(|_| QueryError::WrongLength(bytes.len()))
+}
+
+pub(super) fn percent_encode_bytes(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len() * 3);
+    for &byte in bytes {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            },
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+pub(super) fn percent_decode_bytes(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'%' => {
+                let digits = rest.get(..2)?;
+                if !digits.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                let digits = std::str::from_utf8(digits).ok()?;
+                bytes.push(u8::from_str_radix(digits, 16).ok()?);
+                rest = &rest[2..];
+            },
+            b'+' => bytes.push(b' '),
+            _ => bytes.push(byte),
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_byte_round_trips() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for chunk in bytes.chunks_exact(20) {
+            let info_hash = <[u8; 20]>::try_from(chunk).unwrap();
+            let encoded = encode_info_hash(&info_hash);
+            assert!(encoded
+                .bytes()
+                .all(|c| c.is_ascii_alphanumeric() || b"%-._~".contains(&c)));
+            assert_eq!(decode_info_hash(&encoded), Ok(info_hash));
+        }
+    }
+
+    #[test]
+    fn other_encodings_are_decoded() {
+        // Lowercase hex digits, `+` and characters which didn't need encoding
+        let decoded = decode_info_hash("%ab%CD+!*aaaaaaaaaaaaaaa").unwrap();
+        assert_eq!(&decoded[..5], &[0xab, 0xcd, b' ', b'!', b'*']);
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        for encoded in &["%", "%1", "%1g", "%+1aaaaaaaaaaaaaaaaaaa"] {
+            assert_eq!(
+                decode_info_hash(encoded),
+                Err(QueryError::InvalidEncoding(encoded.to_string()))
+            );
+        }
+        assert_eq!(decode_info_hash("abc"), Err(QueryError::WrongLength(3)));
+        assert_eq!(
+            decode_info_hash(&"%00".repeat(21)),
+            Err(QueryError::WrongLength(21))
+        );
+    }
+}
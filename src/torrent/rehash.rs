@@ -0,0 +1,150 @@
+//! Changing fields of the `info` dict of existing torrents, which changes their
+//! infohash.
+
+use crate::{
+    encoding::{self, ToBencode},
+    torrent::{
+        info_hash::hash_info, sha1::sha1, sha256::sha256, Info, InfoHash, MetaInfo, Sha256Hash,
+    },
+};
+
+/// The infohashes of a torrent before and after a change to its `info` dict.
+///
+/// Both are computed over the `info` dict as encoded by bendy, which matches
+/// [`info_hash`](crate::torrent::info_hash) of the original file unless that
+/// wasn't encoded canonically. The v2 hashes are only present for torrents
+/// with v2 metadata.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InfoHashChange {
+    /// The v1 infohash before the change.
+    pub old: [u8; 20],
+    /// The v1 infohash after the change.
+    pub new: [u8; 20],
+    /// The v2 infohash before the change.
+    pub old_v2: Option<Sha256Hash>,
+    /// The v2 infohash after the change.
+    pub new_v2: Option<Sha256Hash>,
+}
+
+impl InfoHashChange {
+    /// Whether the change left the infohashes as they were, because the field
+    /// already had the new value.
+    pub fn is_unchanged(&self) -> bool {
+        self.old == self.new && self.old_v2 == self.new_v2
+    }
+}
+
+impl Info {
+    /// The v1 infohash of the info dict as encoded by bendy.
+    pub fn info_hash(&self) -> Result<[u8; 20], encoding::Error> {
+        self.to_bencode().map(|encoded| sha1(&encoded))
+    }
+
+    /// The v2 infohash of the info dict as encoded by bendy, if it contains v2
+    /// metadata.
+    pub fn info_hash_v2(&self) -> Result<Option<Sha256Hash>, encoding::Error> {
+        if !self.is_v2() {
+            return Ok(None);
+        }
+        self.to_bencode().map(|encoded| Some(sha256(&encoded)))
+    }
+
+    /// The infohashes of the info dict as encoded by bendy, as many as it has
+    /// metadata for.
+    pub fn info_hashes(&self) -> Result<InfoHash, encoding::Error> {
+        self.to_bencode().map(|encoded| hash_info(self, &encoded))
+    }
+}
+
+impl MetaInfo {
+    /// Set or clear the `private` flag, returning the infohashes before and
+    /// after.
+    ///
+    /// Clearing removes the field, rather than setting it to 0. Peers of the
+    /// original torrent won't find the changed one, so it needs to be
+    /// distributed anew.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::{decoding::FromBencode, torrent::MetaInfo};
+    ///
+    /// let mut torrent = MetaInfo::from_bencode(
+    ///     b"d4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces0:ee",
+    /// )
+    /// .unwrap();
+    ///
+    /// let change = torrent.set_private(true).unwrap();
+    /// assert!(torrent.info.is_private());
+    /// assert_ne!(change.old, change.new);
+    /// ```
+    pub fn set_private(&mut self, private: bool) -> Result<InfoHashChange, encoding::Error> {
+        self.change_info(|info| info.private = if private { Some(true) } else { None })
+    }
+
+    /// Set or remove the `source` field, returning the infohashes before and
+    /// after.
+    pub fn set_source(
+        &mut self,
+        source: Option<String>,
+    ) -> Result<InfoHashChange, encoding::Error> {
+        self.change_info(|info| info.source = source)
+    }
+
+    fn change_info(
+        &mut self,
+        change: impl FnOnce(&mut Info),
+    ) -> Result<InfoHashChange, encoding::Error> {
+        let old = self.info.info_hash()?;
+        let old_v2 = self.info.info_hash_v2()?;
+        let mut info = self.info.clone();
+        change(&mut info);
+        let new = info.info_hash()?;
+        let new_v2 = info.info_hash_v2()?;
+        self.info = info;
+        Ok(InfoHashChange {
+            old,
+            new,
+            old_v2,
+            new_v2,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{decoding::FromBencode, torrent::info_hash};
+
+    static DEBIAN: &[u8] =
+        include_bytes!("../../examples/torrent_files/debian-9.4.0-amd64-netinst.iso.torrent");
+
+    #[test]
+    fn hashes_match_the_original_encoding() {
+        let torrent = MetaInfo::from_bencode(DEBIAN).unwrap();
+        assert_eq!(
+            torrent.info.info_hash().unwrap(),
+            info_hash(DEBIAN).unwrap()
+        );
+        assert_eq!(torrent.info.info_hash_v2().unwrap(), None);
+    }
+
+    #[test]
+    fn changes_report_both_hashes() {
+        let mut torrent = MetaInfo::from_bencode(DEBIAN).unwrap();
+        let original = torrent.info.info_hash().unwrap();
+
+        let change = torrent.set_source(Some("tracker".to_string())).unwrap();
+        assert_eq!(change.old, original);
+        assert_eq!(change.new, torrent.info.info_hash().unwrap());
+        assert!(!change.is_unchanged());
+        assert_eq!(torrent.info.source.as_deref(), Some("tracker"));
+
+        let change = torrent.set_private(false).unwrap();
+        assert!(change.is_unchanged());
+
+        torrent.set_source(None).unwrap();
+        assert_eq!(torrent.info.info_hash().unwrap(), original);
+    }
+}
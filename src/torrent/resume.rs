@@ -0,0 +1,334 @@
+//! The fast-resume data libtorrent saves for each torrent, as read and written
+//! by `read_resume_data` and `write_resume_data`.
+
+use std::convert::TryFrom;
+
+use crate::{
+    decoding::{self, FromBencode, Object, ResultExt},
+    encoding::{self, AsString, SingleItemEncoder, ToBencode},
+    torrent::{
+        metainfo::{decode_extra, Extra},
+        v2::{decode_hash, Sha256Hash},
+        Bitfield,
+    },
+    value::Value,
+};
+
+/// The value of `file-format` in resume data written by libtorrent.
+pub const RESUME_FILE_FORMAT: &str = "libtorrent resume file";
+
+/// The resume data of a torrent in libtorrent's format.
+///
+/// Only the commonly used fields are typed. All others, such as the statistics
+/// fields, the `peers` or an embedded `info` dict, are kept in `extra` so that
+/// re-encoding doesn't lose them.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{decoding::FromBencode, encoding::ToBencode, torrent::ResumeData};
+///
+/// let encoded = b"d11:file-format22:libtorrent resume file13:file_priorityli1ei0ee\
+///                 6:pausedi0e6:pieces3:\x01\x00\x019:save_path9:/downloade";
+/// let resume = ResumeData::from_bencode(encoded).unwrap();
+///
+/// assert_eq!(resume.save_path.as_deref(), Some("/download"));
+/// assert_eq!(resume.file_priority, Some(vec![1, 0]));
+/// assert_eq!(resume.paused, Some(false));
+/// assert!(resume.has_piece(2));
+/// assert_eq!(resume.pieces_have(), 2);
+/// assert_eq!(resume.to_bencode().unwrap(), &encoded[..]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResumeData {
+    /// The format marker, [`RESUME_FILE_FORMAT`] for libtorrent.
+    pub file_format: Option<String>,
+    /// The version of the format.
+    pub file_version: Option<i64>,
+    /// The v1 infohash of the torrent.
+    pub info_hash: Option<[u8; 20]>,
+    /// The v2 infohash of the torrent, given as `info-hash2`.
+    pub info_hash_v2: Option<Sha256Hash>,
+    /// The name of the torrent.
+    pub name: Option<String>,
+    /// The directory the files are saved in.
+    pub save_path: Option<String>,
+    /// The download priority of each file, from 0 (don't download) to 7.
+    pub file_priority: Option<Vec<u8>>,
+    /// The download priority of each piece, one byte per piece.
+    pub piece_priority: Option<Vec<u8>>,
+    /// The state of each piece, one byte per piece. The lowest bit is set for
+    /// pieces which have been downloaded.
+    pub pieces: Option<Vec<u8>>,
+    /// The tiers of tracker URLs, structured like `announce-list`.
+    pub trackers: Option<Vec<Vec<String>>>,
+    /// The URLs of web seeds.
+    pub url_list: Option<Vec<String>>,
+    /// When the torrent was added, in seconds since the Unix epoch.
+    pub added_time: Option<i64>,
+    /// When the torrent was completed, in seconds since the Unix epoch.
+    pub completed_time: Option<i64>,
+    /// Whether the torrent is paused.
+    pub paused: Option<bool>,
+    /// Whether the torrent is started and stopped by the session's queue.
+    pub auto_managed: Option<bool>,
+    /// All other fields of the resume data dict.
+    pub extra: Extra,
+}
+
+impl ResumeData {
+    /// Whether the piece `index` has been downloaded according to `pieces`.
+    pub fn has_piece(&self, index: usize) -> bool {
+        self.pieces
+            .as_ref()
+            .and_then(|pieces| pieces.get(index))
+            .map_or(false, |state| state & 1 != 0)
+    }
+
+    /// The number of pieces which have been downloaded.
+    pub fn pieces_have(&self) -> usize {
+        self.pieces
+            .iter()
+            .flatten()
+            .filter(|&state| state & 1 != 0)
+            .count()
+    }
+
+    /// Mark the piece `index` as downloaded or not, growing `pieces` if needed.
+    pub fn set_piece(&mut self, index: usize, have: bool) {
+        let pieces = self.pieces.get_or_insert_with(Vec::new);
+        if pieces.len() <= index {
+            pieces.resize(index + 1, 0);
+        }
+        if have {
+            pieces[index] |= 1;
+        } else {
+            pieces[index] &= !1;
+        }
+    }
+
+    /// The downloaded pieces according to `pieces`, as a bitfield.
+    pub fn bitfield(&self) -> Bitfield {
+        self.pieces
+            .iter()
+            .flatten()
+            .map(|state| state & 1 != 0)
+            .collect()
+    }
+
+    /// Set `pieces` to the downloaded pieces in `bitfield`, one byte per bit.
+    pub fn set_bitfield(&mut self, bitfield: &Bitfield) {
+        self.pieces = Some(bitfield.iter().map(u8::from).collect());
+    }
+}
+
+/// Read one of the integer flags libtorrent stores for booleans.
+fn decode_flag(object: Object) -> Result<bool, decoding::Error> {
+    Ok(i64::decode_bencode_object(object)? != 0)
+}
+
+impl FromBencode for ResumeData {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut resume = ResumeData::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"added_time", value) => {
+                    resume.added_time = i64::decode_bencode_object(value)
+                        .context("added_time")
+                        .map(Some)?;
+                },
+                (b"auto_managed", value) => {
+                    resume.auto_managed = decode_flag(value).context("auto_managed").map(Some)?;
+                },
+                (b"completed_time", value) => {
+                    resume.completed_time = i64::decode_bencode_object(value)
+                        .context("completed_time")
+                        .map(Some)?;
+                },
+                (b"file-format", value) => {
+                    resume.file_format = String::decode_bencode_object(value)
+                        .context("file-format")
+                        .map(Some)?;
+                },
+                (b"file-version", value) => {
+                    resume.file_version = i64::decode_bencode_object(value)
+                        .context("file-version")
+                        .map(Some)?;
+                },
+                (b"file_priority", value) => {
+                    resume.file_priority = Vec::decode_bencode_object(value)
+                        .context("file_priority")
+                        .map(Some)?;
+                },
+                (b"info-hash", value) => {
+                    let bytes = value.try_into_bytes().context("info-hash")?;
+                    let hash = <[u8; 20]>::try_from(bytes).map_err(|_| {
+                        decoding::Error::unexpected_token(
+                            "20-byte hash",
+                            format!("{} bytes", bytes.len()),
+                        )
+                        .context("info-hash")
+                    })?;
+                    resume.info_hash = Some(hash);
+                },
+                (b"info-hash2", value) => {
+                    resume.info_hash_v2 = value
+                        .try_into_bytes()
+                        .and_then(decode_hash)
+                        .context("info-hash2")
+                        .map(Some)?;
+                },
+                (b"name", value) => {
+                    resume.name = String::decode_bencode_object(value)
+                        .context("name")
+                        .map(Some)?;
+                },
+                (b"paused", value) => {
+                    resume.paused = decode_flag(value).context("paused").map(Some)?;
+                },
+                (b"piece_priority", value) => {
+                    resume.piece_priority = value
+                        .try_into_bytes()
+                        .context("piece_priority")
+                        .map(|bytes| Some(bytes.to_vec()))?;
+                },
+                (b"pieces", value) => {
+                    resume.pieces = value
+                        .try_into_bytes()
+                        .context("pieces")
+                        .map(|bytes| Some(bytes.to_vec()))?;
+                },
+                (b"save_path", value) => {
+                    resume.save_path = String::decode_bencode_object(value)
+                        .context("save_path")
+                        .map(Some)?;
+                },
+                (b"trackers", value) => {
+                    resume.trackers = Vec::decode_bencode_object(value)
+                        .context("trackers")
+                        .map(Some)?;
+                },
+                (b"url-list", value) => {
+                    resume.url_list = Vec::decode_bencode_object(value)
+                        .context("url-list")
+                        .map(Some)?;
+                },
+                (key, value) => decode_extra(&mut resume.extra, key, value)?,
+            }
+        }
+
+        Ok(resume)
+    }
+}
+
+impl ToBencode for ResumeData {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            if let Some(added_time) = self.added_time {
+                e.emit_pair(b"added_time", added_time)?;
+            }
+            if let Some(auto_managed) = self.auto_managed {
+                e.emit_pair(b"auto_managed", i64::from(auto_managed))?;
+            }
+            if let Some(completed_time) = self.completed_time {
+                e.emit_pair(b"completed_time", completed_time)?;
+            }
+            if let Some(file_format) = &self.file_format {
+                e.emit_pair(b"file-format", file_format)?;
+            }
+            if let Some(file_version) = self.file_version {
+                e.emit_pair(b"file-version", file_version)?;
+            }
+            if let Some(file_priority) = &self.file_priority {
+                e.emit_pair(b"file_priority", file_priority)?;
+            }
+            if let Some(info_hash) = &self.info_hash {
+                e.emit_pair(b"info-hash", AsString(&info_hash[..]))?;
+            }
+            if let Some(info_hash_v2) = &self.info_hash_v2 {
+                e.emit_pair(b"info-hash2", AsString(&info_hash_v2[..]))?;
+            }
+            if let Some(name) = &self.name {
+                e.emit_pair(b"name", name)?;
+            }
+            if let Some(paused) = self.paused {
+                e.emit_pair(b"paused", i64::from(paused))?;
+            }
+            if let Some(piece_priority) = &self.piece_priority {
+                e.emit_pair(b"piece_priority", AsString(piece_priority))?;
+            }
+            if let Some(pieces) = &self.pieces {
+                e.emit_pair(b"pieces", AsString(pieces))?;
+            }
+            if let Some(save_path) = &self.save_path {
+                e.emit_pair(b"save_path", save_path)?;
+            }
+            if let Some(trackers) = &self.trackers {
+                e.emit_pair(b"trackers", trackers)?;
+            }
+            if let Some(url_list) = &self.url_list {
+                e.emit_pair(b"url-list", url_list)?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_data_round_trips() {
+        let mut encoded = b"d10:added_timei1600000000e12:auto_managedi1e11:file-format\
+                            22:libtorrent resume file12:file-versioni1e9:info-hash20:"
+            .to_vec();
+        encoded.extend_from_slice(&[0xab; 20]);
+        encoded.extend_from_slice(
+            b"4:name4:test14:piece_priority2:\x04\x0114:total_uploadedi1024e8:trackers\
+              ll15:udp://tracker/a15:udp://tracker/bel15:udp://tracker/cee8:url-listl\
+              11:http://seedee",
+        );
+
+        let resume = ResumeData::from_bencode(&encoded).unwrap();
+        assert_eq!(resume.file_format.as_deref(), Some(RESUME_FILE_FORMAT));
+        assert_eq!(resume.info_hash, Some([0xab; 20]));
+        assert_eq!(resume.auto_managed, Some(true));
+        assert_eq!(resume.piece_priority, Some(vec![4, 1]));
+        assert_eq!(resume.trackers.as_ref().map(Vec::len), Some(2));
+        assert_eq!(resume.url_list, Some(vec!["http://seed".to_string()]));
+        assert_eq!(resume.extra[&b"total_uploaded"[..]], Value::Integer(1024));
+        assert_eq!(resume.to_bencode().unwrap(), encoded);
+    }
+
+    #[test]
+    fn pieces_can_be_updated() {
+        let mut resume = ResumeData::default();
+        assert!(!resume.has_piece(0));
+        resume.set_piece(3, true);
+        resume.set_piece(1, true);
+        resume.set_piece(1, false);
+        assert_eq!(resume.pieces, Some(vec![0, 0, 0, 1]));
+        assert!(resume.has_piece(3));
+        assert_eq!(resume.pieces_have(), 1);
+
+        let bitfield = resume.bitfield();
+        assert_eq!(bitfield.ones().collect::<Vec<_>>(), [3]);
+        resume.set_bitfield(&Bitfield::from_bytes(vec![0x41]));
+        assert_eq!(resume.pieces, Some(vec![0, 1, 0, 0, 0, 0, 0, 1]));
+    }
+
+    #[test]
+    fn invalid_resume_data_is_rejected() {
+        assert!(ResumeData::from_bencode(b"d9:info-hash3:abce").is_err());
+        assert!(ResumeData::from_bencode(b"d13:file_priorityi1ee").is_err());
+        assert!(ResumeData::from_bencode(b"d6:paused1:1e").is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! The SHA-1 hash trees of Merkle torrents, as specified in
+//! [BEP 30](http://bittorrent.org/beps/bep_0030.html).
+
+use crate::torrent::{pieces::PIECE_HASH_LEN, sha1::sha1, Info};
+
+/// Compute the `root hash` of a Merkle torrent from the SHA-1 hashes of its
+/// pieces.
+///
+/// The hashes are the leaves of a binary tree, padded with zero hashes to a
+/// power of two, and every other node is the hash of its two children. The
+/// root of a torrent with a single piece is the hash of that piece.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::merkle_root;
+///
+/// assert_eq!(merkle_root(&[[1; 20]]), [1; 20]);
+/// assert_ne!(merkle_root(&[[1; 20], [2; 20]]), merkle_root(&[[2; 20], [1; 20]]));
+/// ```
+pub fn merkle_root(piece_hashes: &[[u8; PIECE_HASH_LEN]]) -> [u8; PIECE_HASH_LEN] {
+    let mut level = piece_hashes.to_vec();
+    level.resize(level.len().next_power_of_two(), [0; PIECE_HASH_LEN]);
+    while level.len() > 1 {
+        level = level
+            .chunks_exact(2)
+            .map(|pair| sha1(&pair.concat()))
+            .collect();
+    }
+    level[0]
+}
+
+impl Info {
+    /// Whether `piece_hashes` are the hashes of the pieces of this Merkle
+    /// torrent: there must be one for every piece of the files, and their
+    /// [`merkle_root`] must be the torrent's `root_hash`.
+    ///
+    /// Clients receive the piece hashes from peers rather than from the
+    /// torrent, so they need to be checked before pieces are verified against
+    /// them. Torrents without a `root_hash` never match.
+    pub fn verify_root_hash(&self, piece_hashes: &[[u8; PIECE_HASH_LEN]]) -> bool {
+        let root_hash = match &self.root_hash {
+            Some(root_hash) if self.piece_length > 0 => root_hash,
+            _ => return false,
+        };
+        let pieces = (self.total_length() + self.piece_length - 1) / self.piece_length;
+        piece_hashes.len() as u64 == pieces && merkle_root(piece_hashes) == *root_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        decoding::FromBencode,
+        encoding::ToBencode,
+        torrent::{sha1::sha1, MetaInfo},
+    };
+
+    #[test]
+    fn trees_are_padded_with_zero_hashes() {
+        let hashes = [[1; 20], [2; 20], [3; 20]];
+        let left = sha1(&[hashes[0], hashes[1]].concat());
+        let right = sha1(&[hashes[2], [0; 20]].concat());
+        assert_eq!(merkle_root(&hashes), sha1(&[left, right].concat()));
+        assert_eq!(merkle_root(&[]), [0; 20]);
+    }
+
+    #[test]
+    fn merkle_torrents_round_trip() {
+        let hashes = [[1; 20], [2; 20]];
+        let mut encoded =
+            b"d4:infod6:lengthi20000e4:name1:a12:piece lengthi16384e9:root hash20:".to_vec();
+        encoded.extend_from_slice(&merkle_root(&hashes));
+        encoded.extend_from_slice(b"ee");
+
+        let torrent = MetaInfo::from_bencode(&encoded).unwrap();
+        assert!(torrent.info.is_merkle());
+        assert!(torrent.info.is_v1());
+        assert_eq!(torrent.to_bencode().unwrap(), encoded);
+
+        assert!(torrent.info.verify_root_hash(&hashes));
+        assert!(!torrent.info.verify_root_hash(&hashes[..1]));
+        assert!(!torrent.info.verify_root_hash(&[[2; 20], [1; 20]]));
+        assert!(!torrent
+            .info
+            .verify_root_hash(&[hashes[0], hashes[1], [0; 20]]));
+    }
+
+    #[test]
+    fn invalid_root_hashes_are_rejected() {
+        let info = |fields: &str| {
+            MetaInfo::from_bencode(
+                format!("d4:infod6:lengthi1e4:name1:a12:piece lengthi1e{}ee", fields).as_bytes(),
+            )
+        };
+        assert!(info("9:root hash20:aaaaaaaaaaaaaaaaaaaa").is_ok());
+        assert!(info("9:root hash3:aaa").is_err());
+        assert!(info("6:pieces0:9:root hash20:aaaaaaaaaaaaaaaaaaaa").is_err());
+        assert!(MetaInfo::from_bencode(
+            b"d4:infod4:name1:a12:piece lengthi1e9:root hash20:aaaaaaaaaaaaaaaaaaaaee"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Responses of HTTP trackers to scrape requests, as specified in
+//! [BEP 48](http://bittorrent.org/beps/bep_0048.html).
+
+use std::{collections::BTreeMap, convert::TryFrom};
+
+use crate::{
+    decoding::{self, FromBencode, Object},
+    torrent::{
+        metainfo::{decode_extra, Extra},
+        tracker::{decode_lenient, lenient_integer, lenient_string, TrackerError},
+        InfoHash,
+    },
+};
+
+/// The response of a tracker to a scrape request.
+///
+/// The `files` dict is keyed by the raw 20-byte infohashes, which are usually
+/// not valid UTF-8. Decoding is as lenient as for
+/// [`TrackerResponse`](crate::torrent::TrackerResponse): keys given as 40 hex
+/// digits are accepted, files with keys of any other form are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::ScrapeResponse;
+///
+/// let mut encoded = b"d5:filesd20:".to_vec();
+/// encoded.extend_from_slice(&[0xff; 20]);
+/// encoded.extend_from_slice(b"d8:completei5e10:downloadedi50e10:incompletei10eeee");
+///
+/// let response = ScrapeResponse::decode_lenient(&encoded).unwrap();
+/// let stats = &response.files[&[0xff; 20]];
+/// assert_eq!(stats.complete, Some(5));
+/// assert_eq!(stats.downloaded, Some(50));
+/// assert_eq!(stats.incomplete, Some(10));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScrapeResponse {
+    /// Why the request failed.
+    pub failure_reason: Option<String>,
+    /// The statistics of each torrent, keyed by its infohash.
+    pub files: BTreeMap<[u8; 20], ScrapeStats>,
+    /// All other fields of the response dict.
+    pub extra: Extra,
+}
+
+/// The statistics of one torrent in a [`ScrapeResponse`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScrapeStats {
+    /// The number of seeders.
+    pub complete: Option<u64>,
+    /// The number of completed downloads.
+    pub downloaded: Option<u64>,
+    /// The number of leechers.
+    pub incomplete: Option<u64>,
+    /// The name of the torrent, which some trackers include.
+    pub name: Option<String>,
+    /// All other fields of the file dict.
+    pub extra: Extra,
+}
+
+impl ScrapeResponse {
+    /// Decode a scrape response, also accepting dict keys which aren't sorted or
+    /// unique, and bytes after the response. For repeated keys the last value
+    /// wins.
+    pub fn decode_lenient(bytes: &[u8]) -> Result<Self, decoding::Error> {
+        decode_lenient(bytes)
+    }
+
+    /// Decode a response like [`decode_lenient`](ScrapeResponse::decode_lenient)
+    /// and fail if the tracker rejected the request.
+    pub fn parse(bytes: &[u8]) -> Result<Self, TrackerError> {
+        Self::decode_lenient(bytes)?.into_result()
+    }
+
+    /// Return the response unless it has a `failure_reason`.
+    pub fn into_result(self) -> Result<Self, TrackerError> {
+        match self.failure_reason {
+            Some(reason) => Err(TrackerError::Failure(reason)),
+            None => Ok(self),
+        }
+    }
+
+    /// The statistics of a torrent, looked up by its
+    /// [`announce_hash`](InfoHash::announce_hash) or, for hybrid torrents, by
+    /// its truncated v2 infohash if the tracker only knows the v2 swarm.
+    pub fn stats(&self, info_hash: &InfoHash) -> Option<&ScrapeStats> {
+        self.files.get(&info_hash.announce_hash()).or_else(|| {
+            info_hash
+                .truncated_v2()
+                .and_then(|truncated| self.files.get(&truncated))
+        })
+    }
+}
+
+/// Read an infohash key, either raw or as hex digits.
+fn decode_info_hash(key: &[u8]) -> Option<[u8; 20]> {
+    if let Ok(hash) = <[u8; 20]>::try_from(key) {
+        return Some(hash);
+    }
+    if key.len() != 40 || !key.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let mut hash = [0; 20];
+    for (byte, digits) in hash.iter_mut().zip(key.chunks_exact(2)) {
+        let digits = std::str::from_utf8(digits).ok()?;
+        *byte = u8::from_str_radix(digits, 16).ok()?;
+    }
+    Some(hash)
+}
+
+impl FromBencode for ScrapeResponse {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut response = ScrapeResponse::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"failure reason", value) => response.failure_reason = lenient_string(value),
+                (b"files", Object::Dict(mut files)) => {
+                    while let Some((key, value)) = files.next_pair()? {
+                        if let (Some(hash), Object::Dict(_)) = (decode_info_hash(key), &value) {
+                            response
+                                .files
+                                .insert(hash, ScrapeStats::decode_bencode_object(value)?);
+                        }
+                    }
+                },
+                (b"files", _) => {},
+                (key, value) => decode_extra(&mut response.extra, key, value)?,
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+impl FromBencode for ScrapeStats {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut stats = ScrapeStats::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"complete", value) => stats.complete = lenient_integer(value),
+                (b"downloaded", value) => stats.downloaded = lenient_integer(value),
+                (b"incomplete", value) => stats.incomplete = lenient_integer(value),
+                (b"name", value) => stats.name = lenient_string(value),
+                (key, value) => decode_extra(&mut stats.extra, key, value)?,
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_and_hex_keys_are_decoded() {
+        let mut encoded = b"d5:filesd40:".to_vec();
+        encoded.extend_from_slice(&[b'A'; 40]);
+        encoded.extend_from_slice(b"d10:incomplete1:2e3:badd8:completei9ee20:");
+        encoded.extend_from_slice(&[0x80; 20]);
+        encoded.extend_from_slice(b"d8:completei1e4:name3:abcee5:flagsi1ee");
+
+        let response = ScrapeResponse::from_bencode(&encoded).unwrap();
+        assert_eq!(response.files.len(), 2);
+        assert_eq!(
+            response.files[&[0x80; 20]],
+            ScrapeStats {
+                complete: Some(1),
+                name: Some("abc".to_string()),
+                ..ScrapeStats::default()
+            }
+        );
+        assert_eq!(response.files[&[0xaa; 20]].incomplete, Some(2));
+        assert!(response.extra.contains_key(&b"flags"[..]));
+    }
+
+    #[test]
+    fn stats_are_found_for_each_version() {
+        let mut encoded = b"d5:filesd20:".to_vec();
+        encoded.extend_from_slice(&[1; 20]);
+        encoded.extend_from_slice(b"d8:completei1ee20:");
+        encoded.extend_from_slice(&[2; 20]);
+        encoded.extend_from_slice(b"d8:completei2eeee");
+        let response = ScrapeResponse::decode_lenient(&encoded).unwrap();
+        let complete = |hash| response.stats(&hash).and_then(|stats| stats.complete);
+
+        assert_eq!(complete(InfoHash::V1([1; 20])), Some(1));
+        assert_eq!(complete(InfoHash::V2([2; 32])), Some(2));
+        assert_eq!(complete(InfoHash::Hybrid([1; 20], [2; 32])), Some(1));
+        assert_eq!(complete(InfoHash::Hybrid([3; 20], [2; 32])), Some(2));
+        assert_eq!(complete(InfoHash::V1([3; 20])), None);
+    }
+
+    #[test]
+    fn failures_are_decoded() {
+        let response =
+            ScrapeResponse::decode_lenient(b"d14:failure reason4:nope5:filesi0ee").unwrap();
+        assert_eq!(response.failure_reason.as_deref(), Some("nope"));
+        assert!(response.files.is_empty());
+        assert!(matches!(
+            response.into_result(),
+            Err(TrackerError::Failure(reason)) if reason == "nope"
+        ));
+        assert!(ScrapeResponse::parse(b"d5:filesdee").is_ok());
+    }
+}
@@ -0,0 +1,103 @@
+//! Signed torrents, as specified in
+//! [BEP 35](http://bittorrent.org/beps/bep_0035.html).
+//!
+//! No signature algorithm is prescribed here: signing and verifying take
+//! closures, so any cryptography library can be used.
+
+use crate::{
+    decoding::{self, FromBencode, Object, ResultExt},
+    encoding::{self, AsString, SingleItemEncoder, ToBencode},
+    torrent::metainfo::{decode_extra, Extra},
+    value::Value,
+};
+
+/// A signature in the `signatures` dict of a torrent.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Signature {
+    /// The DER-encoded X.509 certificate of the signer, if it isn't known to
+    /// the verifier by other means.
+    pub certificate: Option<Vec<u8>>,
+    /// Additional data covered by the signature.
+    pub info: Option<Extra>,
+    /// The signature of the [`signed_data`].
+    pub signature: Vec<u8>,
+    /// All other fields of the signature dict.
+    pub extra: Extra,
+}
+
+/// The data signed by a signature with the given `info` dict: the `info` dict
+/// of the torrent, exactly as encoded in it, followed by the encoded `info`
+/// dict of the signature, if any.
+///
+/// Use [`raw_info`](crate::torrent::raw_info) to get the `info` dict of a
+/// torrent without re-encoding it.
+pub fn signed_data(raw_info: &[u8], info: Option<&Extra>) -> Result<Vec<u8>, encoding::Error> {
+    let mut data = raw_info.to_vec();
+    if let Some(info) = info {
+        data.extend_from_slice(&info.to_bencode()?);
+    }
+    Ok(data)
+}
+
+impl FromBencode for Signature {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut certificate = None;
+        let mut info = None;
+        let mut signature = None;
+        let mut extra = Extra::new();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"certificate", value) => {
+                    certificate = value
+                        .try_into_bytes()
+                        .context("certificate")
+                        .map(|bytes| Some(bytes.to_vec()))?;
+                },
+                (b"info", value) => {
+                    let mut fields = Extra::new();
+                    let mut dict = value.try_into_dictionary().context("info")?;
+                    while let Some((key, value)) = dict.next_pair().context("info")? {
+                        decode_extra(&mut fields, key, value).context("info")?;
+                    }
+                    info = Some(fields);
+                },
+                (b"signature", value) => {
+                    signature = value
+                        .try_into_bytes()
+                        .context("signature")
+                        .map(|bytes| Some(bytes.to_vec()))?;
+                },
+                (key, value) => decode_extra(&mut extra, key, value)?,
+            }
+        }
+
+        Ok(Signature {
+            certificate,
+            info,
+            signature: signature.ok_or_else(|| decoding::Error::missing_field("signature"))?,
+            extra,
+        })
+    }
+}
+
+impl ToBencode for Signature {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            if let Some(certificate) = &self.certificate {
+                e.emit_pair(b"certificate", AsString(certificate))?;
+            }
+            if let Some(info) = &self.info {
+                e.emit_pair(b"info", info)?;
+            }
+            e.emit_pair(b"signature", AsString(&self.signature))?;
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,241 @@
+//! Tiers of trackers, as specified in
+//! [BEP 12](http://bittorrent.org/beps/bep_0012.html).
+
+use crate::{
+    decoding::{self, FromBencode, Object},
+    encoding::{self, SingleItemEncoder, ToBencode},
+    torrent::metainfo::MetaInfo,
+};
+
+/// The trackers of a torrent, grouped into tiers which are tried in order.
+///
+/// A torrent with an `announce-list` uses only its tiers and ignores
+/// `announce`, which is kept for clients that don't understand the list.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::TrackerTiers;
+///
+/// let mut tiers = TrackerTiers::from_fields(
+///     Some("http://a/"),
+///     Some(&[vec!["http://b/".to_string(), "http://b/".to_string()]]),
+/// );
+/// tiers.merge(&TrackerTiers::from_fields(Some("http://a/"), None));
+/// tiers.dedup();
+///
+/// assert_eq!(tiers.urls().collect::<Vec<_>>(), ["http://b/", "http://a/"]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrackerTiers {
+    tiers: Vec<Vec<String>>,
+}
+
+impl TrackerTiers {
+    /// Create an empty list of tiers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The tiers given by the `announce` and `announce-list` fields of a
+    /// torrent: the list if it contains any URLs, else `announce` alone.
+    pub fn from_fields(announce: Option<&str>, announce_list: Option<&[Vec<String>]>) -> Self {
+        let tiers = TrackerTiers::from(announce_list.unwrap_or_default().to_vec());
+        if !tiers.is_empty() {
+            return tiers;
+        }
+        announce
+            .map(|url| TrackerTiers::from(vec![vec![url.to_string()]]))
+            .unwrap_or_default()
+    }
+
+    /// The tiers of the torrent `torrent`, like [`TrackerTiers::from_fields`].
+    pub fn from_torrent(torrent: &MetaInfo) -> Self {
+        Self::from_fields(
+            torrent.announce.as_deref(),
+            torrent.announce_list.as_deref(),
+        )
+    }
+
+    /// Store the tiers in the `announce` and `announce-list` fields of a
+    /// torrent. `announce` becomes the first URL, and `announce-list` is only
+    /// written if there is more than one URL.
+    pub fn apply_to(&self, torrent: &mut MetaInfo) {
+        torrent.announce = self.urls().next().map(str::to_string);
+        torrent.announce_list = if self.urls().nth(1).is_some() {
+            Some(self.tiers.clone())
+        } else {
+            None
+        };
+    }
+
+    /// The tiers, in order.
+    pub fn tiers(&self) -> &[Vec<String>] {
+        &self.tiers
+    }
+
+    /// Add a tier after all others. Empty tiers are ignored.
+    pub fn push_tier(&mut self, tier: Vec<String>) {
+        if !tier.is_empty() {
+            self.tiers.push(tier);
+        }
+    }
+
+    /// All URLs in the order they are tried, flattening the tiers.
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.tiers.iter().flatten().map(String::as_str)
+    }
+
+    /// The number of URLs in all tiers.
+    pub fn len(&self) -> usize {
+        self.tiers.iter().map(Vec::len).sum()
+    }
+
+    /// Whether there are no URLs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove each URL which already occurs in the same or an earlier tier, and
+    /// tiers left empty.
+    pub fn dedup(&mut self) {
+        let mut seen = std::collections::BTreeSet::new();
+        for tier in &mut self.tiers {
+            tier.retain(|url| seen.insert(url.clone()));
+        }
+        self.tiers.retain(|tier| !tier.is_empty());
+    }
+
+    /// Append the tiers of `other` after the existing ones, skipping URLs which
+    /// are already present.
+    pub fn merge(&mut self, other: &TrackerTiers) {
+        for tier in &other.tiers {
+            let tier = tier
+                .iter()
+                .filter(|url| !self.urls().any(|existing| existing == url.as_str()))
+                .cloned()
+                .collect();
+            self.push_tier(tier);
+        }
+    }
+
+    /// Move the URL `url` to the front of its tier, as a client does after
+    /// successfully announcing to it. Returns whether the URL was found.
+    pub fn promote(&mut self, url: &str) -> bool {
+        for tier in &mut self.tiers {
+            if let Some(index) = tier.iter().position(|existing| existing == url) {
+                let url = tier.remove(index);
+                tier.insert(0, url);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl From<Vec<Vec<String>>> for TrackerTiers {
+    /// Create tiers from an `announce-list`, dropping empty tiers.
+    fn from(tiers: Vec<Vec<String>>) -> Self {
+        let mut result = TrackerTiers::new();
+        for tier in tiers {
+            result.push_tier(tier);
+        }
+        result
+    }
+}
+
+impl From<TrackerTiers> for Vec<Vec<String>> {
+    fn from(tiers: TrackerTiers) -> Self {
+        tiers.tiers
+    }
+}
+
+impl FromBencode for TrackerTiers {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        Vec::decode_bencode_object(object).map(TrackerTiers::from)
+    }
+}
+
+impl ToBencode for TrackerTiers {
+    const MAX_DEPTH: usize = 2;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit(&self.tiers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiers(tiers: &[&[&str]]) -> TrackerTiers {
+        TrackerTiers::from(
+            tiers
+                .iter()
+                .map(|tier| tier.iter().map(|url| url.to_string()).collect())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn announce_list_takes_precedence() {
+        let list = vec![vec!["b".to_string()]];
+        assert_eq!(
+            TrackerTiers::from_fields(Some("a"), Some(&list)),
+            tiers(&[&["b"]])
+        );
+        assert_eq!(
+            TrackerTiers::from_fields(Some("a"), Some(&[vec![]])),
+            tiers(&[&["a"]])
+        );
+        assert_eq!(TrackerTiers::from_fields(None, None), TrackerTiers::new());
+    }
+
+    #[test]
+    fn tiers_are_deduplicated_and_merged() {
+        let mut first = tiers(&[&["a", "b", "a"], &["b"], &["c"]]);
+        first.dedup();
+        assert_eq!(first, tiers(&[&["a", "b"], &["c"]]));
+
+        first.merge(&tiers(&[&["c", "d"], &["a"]]));
+        assert_eq!(first, tiers(&[&["a", "b"], &["c"], &["d"]]));
+        assert_eq!(first.len(), 4);
+    }
+
+    #[test]
+    fn promoted_urls_move_to_the_front() {
+        let mut tiers = tiers(&[&["a"], &["b", "c"]]);
+        assert!(tiers.promote("c"));
+        assert!(!tiers.promote("d"));
+        assert_eq!(tiers.urls().collect::<Vec<_>>(), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn tiers_are_applied_to_torrents() {
+        let mut torrent = MetaInfo::from_bencode(
+            b"d8:announce1:x4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:ee",
+        )
+        .unwrap();
+        assert_eq!(TrackerTiers::from_torrent(&torrent), tiers(&[&["x"]]));
+
+        tiers(&[&["a"], &["b"]]).apply_to(&mut torrent);
+        assert_eq!(torrent.announce.as_deref(), Some("a"));
+        assert_eq!(
+            TrackerTiers::from_torrent(&torrent),
+            tiers(&[&["a"], &["b"]])
+        );
+
+        tiers(&[&["c"]]).apply_to(&mut torrent);
+        assert_eq!(torrent.announce.as_deref(), Some("c"));
+        assert_eq!(torrent.announce_list, None);
+    }
+
+    #[test]
+    fn tiers_round_trip() {
+        let encoded = b"ll1:a1:bel1:cee";
+        let decoded = TrackerTiers::from_bencode(encoded).unwrap();
+        assert_eq!(decoded, tiers(&[&["a", "b"], &["c"]]));
+        assert_eq!(decoded.to_bencode().unwrap(), &encoded[..]);
+        assert!(TrackerTiers::from_bencode(b"lli1eee").is_err());
+    }
+}
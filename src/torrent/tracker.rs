@@ -0,0 +1,469 @@
+//! Responses of HTTP trackers to announce requests, as specified in
+//! [BEP 3](http://bittorrent.org/beps/bep_0003.html).
+
+use std::{
+    fmt::{self, Display, Formatter},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str,
+};
+
+use crate::{
+    decoding::{self, Decoder, FromBencode, Object},
+    state_tracker::StructureError,
+    torrent::{
+        compact::{decode_compact_peers, decode_compact_peers6},
+        metainfo::{decode_extra, Extra},
+    },
+};
+
+/// The response of a tracker to an announce request.
+///
+/// Trackers in the wild deviate from the specification in many small ways, so
+/// decoding is lenient: integers may be given as strings, fields with values of
+/// the wrong type or out of range are ignored, strings which aren't UTF-8 are
+/// converted lossily and malformed peers are skipped. Use
+/// [`decode_lenient`](TrackerResponse::decode_lenient) to also accept unsorted
+/// keys and trailing bytes.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::torrent::TrackerResponse;
+///
+/// let response = TrackerResponse::decode_lenient(
+///     b"d8:intervali1800e5:peers6:\x0a\x00\x00\x01\x1a\xe1e",
+/// )
+/// .unwrap();
+///
+/// assert_eq!(response.interval, Some(1800));
+/// assert_eq!(
+///     response.peers.socket_addrs(),
+///     vec!["10.0.0.1:6881".parse().unwrap()]
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrackerResponse {
+    /// Why the request failed. If present, no other fields are required.
+    pub failure_reason: Option<String>,
+    /// A warning to be shown to the user, while the request succeeded.
+    pub warning_message: Option<String>,
+    /// The number of seconds to wait before the next regular announce.
+    pub interval: Option<u64>,
+    /// The number of seconds to wait at least before announcing again.
+    pub min_interval: Option<u64>,
+    /// An opaque id to send with the next announce.
+    pub tracker_id: Option<String>,
+    /// The number of seeders.
+    pub complete: Option<u64>,
+    /// The number of leechers.
+    pub incomplete: Option<u64>,
+    /// The peers, from the `peers` and `peers6` fields.
+    pub peers: Peers,
+    /// All other fields of the response dict.
+    pub extra: Extra,
+}
+
+/// Why a request to a tracker failed.
+#[derive(Clone, Debug)]
+pub enum TrackerError {
+    /// The response couldn't be decoded.
+    Decoding(decoding::Error),
+    /// The tracker rejected the request, giving this `failure reason`.
+    Failure(String),
+}
+
+impl Display for TrackerError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            TrackerError::Decoding(error) => write!(f, "invalid tracker response: {}", error),
+            TrackerError::Failure(reason) => write!(f, "tracker request failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for TrackerError {}
+
+impl From<decoding::Error> for TrackerError {
+    fn from(error: decoding::Error) -> Self {
+        TrackerError::Decoding(error)
+    }
+}
+
+/// A peer returned by a tracker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Peer {
+    /// The id of the peer. Compact peer lists don't include it.
+    pub peer_id: Option<Vec<u8>>,
+    /// The IP address or DNS name of the peer.
+    pub ip: String,
+    /// The port of the peer.
+    pub port: u16,
+}
+
+/// The peers of a tracker response, from whichever representations it
+/// contains.
+///
+/// A response lists IPv4 peers, and possibly peers with DNS names or IPv6
+/// addresses, in its `peers` field, either as a list of dicts or in the compact
+/// form of [BEP 23](http://bittorrent.org/beps/bep_0023.html). IPv6 peers may be
+/// listed in the compact form in a separate `peers6` field, as specified in
+/// [BEP 7](http://bittorrent.org/beps/bep_0007.html). Decoding a `Peers` from a
+/// response dict reads both and ignores all other fields.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Peers {
+    /// The peers from the `peers` field.
+    pub peers: Vec<Peer>,
+    /// The peers from the `peers6` field.
+    pub peers6: Vec<Peer>,
+}
+
+impl Peers {
+    /// All peers, those from `peers` first.
+    pub fn iter(&self) -> impl Iterator<Item = &Peer> {
+        self.peers.iter().chain(&self.peers6)
+    }
+
+    /// The number of peers.
+    pub fn len(&self) -> usize {
+        self.peers.len() + self.peers6.len()
+    }
+
+    /// Whether there are no peers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The socket addresses of all peers which have an IP address rather than a
+    /// DNS name.
+    pub fn socket_addrs(&self) -> Vec<SocketAddr> {
+        self.iter().filter_map(Peer::socket_addr).collect()
+    }
+
+    /// Read the value of the `peers` field, or of the `peers6` field if `ipv6`
+    /// is set.
+    fn decode_field(&mut self, ipv6: bool, value: Object) -> Result<(), decoding::Error> {
+        let peers = if ipv6 {
+            &mut self.peers6
+        } else {
+            &mut self.peers
+        };
+        match value {
+            Object::Bytes(compact) if ipv6 => {
+                *peers = decode_compact_peers6(compact)
+                    .into_iter()
+                    .map(|addr| Peer::compact(SocketAddr::V6(addr)))
+                    .collect();
+            },
+            Object::Bytes(compact) => {
+                *peers = decode_compact_peers(compact)
+                    .into_iter()
+                    .map(|addr| Peer::compact(SocketAddr::V4(addr)))
+                    .collect();
+            },
+            Object::List(mut list) => {
+                peers.clear();
+                while let Some(peer) = list.next_object()? {
+                    peers.extend(decode_peer(peer)?);
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+}
+
+impl Peer {
+    fn compact(addr: SocketAddr) -> Self {
+        Peer {
+            peer_id: None,
+            ip: addr.ip().to_string(),
+            port: addr.port(),
+        }
+    }
+
+    /// The socket address of the peer, if its `ip` is an IP address rather than
+    /// a DNS name.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        self.ip
+            .parse::<IpAddr>()
+            .ok()
+            .map(|ip| SocketAddr::new(ip, self.port))
+    }
+}
+
+impl TrackerResponse {
+    /// Decode a tracker response like [`from_bencode`](FromBencode::from_bencode),
+    /// but also accept dict keys which aren't sorted or unique, and bytes after
+    /// the response. For repeated keys the last value wins.
+    pub fn decode_lenient(bytes: &[u8]) -> Result<Self, decoding::Error> {
+        decode_lenient(bytes)
+    }
+
+    /// Decode a response like [`decode_lenient`](TrackerResponse::decode_lenient)
+    /// and fail if the tracker rejected the request, so that a failure can't be
+    /// mistaken for an empty list of peers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::torrent::{TrackerError, TrackerResponse};
+    ///
+    /// match TrackerResponse::parse(b"d14:failure reason12:unregisterede") {
+    ///     Err(TrackerError::Failure(reason)) => assert_eq!(reason, "unregistered"),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn parse(bytes: &[u8]) -> Result<Self, TrackerError> {
+        Self::decode_lenient(bytes)?.into_result()
+    }
+
+    /// Return the response unless it has a `failure_reason`, in which case the
+    /// request failed and the other fields are meaningless.
+    pub fn into_result(self) -> Result<Self, TrackerError> {
+        match self.failure_reason {
+            Some(reason) => Err(TrackerError::Failure(reason)),
+            None => Ok(self),
+        }
+    }
+}
+
+/// Decode the first object of `bytes`, accepting unsorted and repeated keys.
+pub(super) fn decode_lenient<T: FromBencode>(bytes: &[u8]) -> Result<T, decoding::Error> {
+    let mut decoder = Decoder::new(bytes).with_sorted_keys(false);
+    let result = match decoder.next_object()? {
+        Some(object) => T::decode_bencode_object(object),
+        None => Err(decoding::Error::from(StructureError::UnexpectedEof)),
+    };
+    result
+}
+
+/// Read an integer, or a string containing one, ignoring anything else.
+pub(super) fn lenient_integer<T: str::FromStr>(object: Object) -> Option<T> {
+    match object {
+        Object::Integer(integer) => integer.parse().ok(),
+        Object::Bytes(bytes) => str::from_utf8(bytes).ok()?.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Read a string, converting it lossily to UTF-8, or an integer as a string.
+pub(super) fn lenient_string(object: Object) -> Option<String> {
+    match object {
+        Object::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        Object::Integer(integer) => Some(integer.to_string()),
+        _ => None,
+    }
+}
+
+/// Read a peer dict, returning `None` if it lacks a usable address.
+fn decode_peer(object: Object) -> Result<Option<Peer>, decoding::Error> {
+    let mut peer_id = None;
+    let mut ip = None;
+    let mut port = None;
+
+    let mut dict = match object {
+        Object::Dict(dict) => dict,
+        _ => return Ok(None),
+    };
+    while let Some(pair) = dict.next_pair()? {
+        match pair {
+            (b"peer id", Object::Bytes(id)) => peer_id = Some(id.to_vec()),
+            (b"ip", Object::Bytes(address)) => {
+                // Some trackers send the address in binary form
+                let text = address.iter().all(u8::is_ascii_graphic);
+                ip = match (text, address.len()) {
+                    (true, _) => Some(String::from_utf8_lossy(address).into_owned()),
+                    (false, 4) => Some(
+                        Ipv4Addr::from([address[0], address[1], address[2], address[3]])
+                            .to_string(),
+                    ),
+                    (false, 16) => {
+                        let mut octets = [0; 16];
+                        octets.copy_from_slice(address);
+                        Some(Ipv6Addr::from(octets).to_string())
+                    },
+                    (false, _) => None,
+                };
+            },
+            (b"port", value) => port = lenient_integer(value),
+            _ => {},
+        }
+    }
+
+    Ok(match (ip, port) {
+        (Some(ip), Some(port)) => Some(Peer { peer_id, ip, port }),
+        _ => None,
+    })
+}
+
+impl FromBencode for Peers {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut peers = Peers::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"peers", value) => peers.decode_field(false, value)?,
+                (b"peers6", value) => peers.decode_field(true, value)?,
+                _ => {},
+            }
+        }
+
+        Ok(peers)
+    }
+}
+
+impl FromBencode for TrackerResponse {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut response = TrackerResponse::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"failure reason", value) => response.failure_reason = lenient_string(value),
+                (b"warning message", value) => response.warning_message = lenient_string(value),
+                (b"interval", value) => response.interval = lenient_integer(value),
+                (b"min interval", value) => response.min_interval = lenient_integer(value),
+                (b"tracker id", value) => response.tracker_id = lenient_string(value),
+                (b"complete", value) => response.complete = lenient_integer(value),
+                (b"incomplete", value) => response.incomplete = lenient_integer(value),
+                (b"peers", value) => response.peers.decode_field(false, value)?,
+                (b"peers6", value) => response.peers.decode_field(true, value)?,
+                (key, value) => decode_extra(&mut response.extra, key, value)?,
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dict_peers_are_decoded() {
+        let response = TrackerResponse::from_bencode(
+            b"d8:completei5e10:incompletei3e8:intervali900e12:min intervali60e\
+              5:peersld2:ip8:10.0.0.17:peer id20:aaaaaaaaaaaaaaaaaaaa4:porti6881eed2:ip\
+              11:example.com4:porti80eee10:tracker id3:abc7:warning4:caree",
+        )
+        .unwrap();
+
+        assert_eq!(response.complete, Some(5));
+        assert_eq!(response.incomplete, Some(3));
+        assert_eq!(response.interval, Some(900));
+        assert_eq!(response.min_interval, Some(60));
+        assert_eq!(response.tracker_id.as_deref(), Some("abc"));
+        assert_eq!(
+            response.peers.peers,
+            vec![
+                Peer {
+                    peer_id: Some(vec![b'a'; 20]),
+                    ip: "10.0.0.1".to_string(),
+                    port: 6881,
+                },
+                Peer {
+                    peer_id: None,
+                    ip: "example.com".to_string(),
+                    port: 80,
+                },
+            ]
+        );
+        assert_eq!(
+            response.peers.peers[0].socket_addr(),
+            "10.0.0.1:6881".parse().ok()
+        );
+        assert_eq!(response.peers.peers[1].socket_addr(), None);
+        assert!(response.extra.contains_key(&b"warning"[..]));
+    }
+
+    #[test]
+    fn failures_become_errors() {
+        let response = TrackerResponse::parse(b"d5:peers0:15:warning message4:slowe").unwrap();
+        assert_eq!(response.warning_message.as_deref(), Some("slow"));
+        assert!(response.peers.is_empty());
+
+        let error = TrackerResponse::parse(b"d5:peers0:14:failure reason4:gonee").unwrap_err();
+        assert!(matches!(&error, TrackerError::Failure(reason) if reason == "gone"));
+        assert_eq!(error.to_string(), "tracker request failed: gone");
+        assert!(matches!(
+            TrackerResponse::parse(b"le"),
+            Err(TrackerError::Decoding(_))
+        ));
+    }
+
+    #[test]
+    fn failures_are_decoded() {
+        let response = TrackerResponse::from_bencode(b"d14:failure reason9:not founde").unwrap();
+        assert_eq!(response.failure_reason.as_deref(), Some("not found"));
+        assert_eq!(response.interval, None);
+        assert!(response.peers.is_empty());
+    }
+
+    #[test]
+    fn quirks_are_tolerated() {
+        let response = TrackerResponse::decode_lenient(
+            b"d8:interval4:18005:peers8:\x7f\x00\x00\x01\x00\x50\x01\x02\
+              8:completei-1e14:failure reason2:\xff!5:peersld2:ip4:\x01\x02\x03\x04\
+              4:porti1eei3ed4:porti2eeee\n",
+        )
+        .unwrap();
+
+        assert_eq!(response.interval, Some(1800));
+        assert_eq!(response.complete, None);
+        assert_eq!(response.failure_reason.as_deref(), Some("\u{fffd}!"));
+        assert_eq!(
+            response.peers.peers,
+            vec![Peer {
+                peer_id: None,
+                ip: "1.2.3.4".to_string(),
+                port: 1,
+            }]
+        );
+
+        // Compact peers with trailing bytes
+        let response =
+            TrackerResponse::decode_lenient(b"d5:peers8:\x7f\x00\x00\x01\x00\x50\x01\x02e")
+                .unwrap();
+        assert_eq!(response.peers.len(), 1);
+        assert_eq!(
+            response.peers.socket_addrs(),
+            vec!["127.0.0.1:80".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn both_peer_lists_are_decoded() {
+        let mut encoded = b"d8:intervali10e5:peers6:\x0a\x00\x00\x01\x1a\xe16:peers618:".to_vec();
+        encoded.extend_from_slice(&[0; 15]);
+        encoded.extend_from_slice(b"\x01\x00\x50e");
+
+        let response = TrackerResponse::from_bencode(&encoded).unwrap();
+        assert_eq!(response.peers.len(), 2);
+        assert_eq!(
+            response.peers.socket_addrs(),
+            vec![
+                "10.0.0.1:6881".parse().unwrap(),
+                "[::1]:80".parse().unwrap(),
+            ]
+        );
+        assert_eq!(Peers::from_bencode(&encoded).unwrap(), response.peers);
+
+        // Dict lists in peers6 are accepted as well
+        let response = TrackerResponse::from_bencode(b"d6:peers6ld2:ip3:::14:porti1eeee").unwrap();
+        assert_eq!(response.peers.peers6[0].ip, "::1");
+    }
+
+    #[test]
+    fn unsorted_keys_need_lenient_decoding() {
+        let unsorted = b"d8:intervali1e5:aaaaai1ee";
+        assert!(TrackerResponse::from_bencode(unsorted).is_err());
+        assert_eq!(
+            TrackerResponse::decode_lenient(unsorted).unwrap().interval,
+            Some(1)
+        );
+        assert!(TrackerResponse::decode_lenient(b"").is_err());
+        assert!(TrackerResponse::decode_lenient(b"i1e").is_err());
+    }
+}
@@ -0,0 +1,431 @@
+//! The state files of uTorrent and BitTorrent Stable, `resume.dat` and
+//! `settings.dat`.
+//!
+//! Both are bencoded dicts with a `.fileguard` entry, a checksum uTorrent uses
+//! to detect corrupted files. Its algorithm isn't documented, so it's kept as
+//! is. Remove it from an edited file, as uTorrent skips the check for files
+//! without one.
+
+use std::{collections::BTreeMap, convert::TryFrom};
+
+use crate::{
+    decoding::{self, FromBencode, Object, ResultExt},
+    encoding::{self, AsString, SingleItemEncoder, ToBencode},
+    torrent::{
+        metainfo::{decode_extra, Extra},
+        Bitfield,
+    },
+    value::Value,
+};
+
+/// The key of the checksum in uTorrent's state files.
+const FILEGUARD: &[u8] = b".fileguard";
+
+/// The `resume.dat` file of uTorrent, describing every torrent it manages.
+///
+/// The file maps the name of each torrent's `.torrent` file, relative to
+/// uTorrent's settings directory, to its state. Entries whose keys don't end in
+/// `.torrent`, such as `rec`, are kept in `extra`.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{decoding::FromBencode, torrent::UtorrentResume};
+///
+/// let encoded = b"d10:.fileguard4:ABCD9:a.torrentd8:added_oni1600000000e\
+///                 7:caption1:a4:path9:C:\\Data\\a7:startedi1eee";
+/// let resume = UtorrentResume::from_bencode(encoded).unwrap();
+///
+/// let torrent = &resume.torrents[&b"a.torrent"[..]];
+/// assert_eq!(torrent.path.as_deref(), Some("C:\\Data\\a"));
+/// assert_eq!(torrent.added_on, Some(1_600_000_000));
+/// assert_eq!(resume.fileguard.as_deref(), Some("ABCD"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UtorrentResume {
+    /// The checksum of the file, given as `.fileguard`.
+    pub fileguard: Option<String>,
+    /// The state of each torrent, keyed by the name of its `.torrent` file.
+    pub torrents: BTreeMap<Vec<u8>, UtorrentTorrent>,
+    /// All other entries of the file.
+    pub extra: Extra,
+}
+
+/// The state of a torrent in uTorrent's `resume.dat`.
+///
+/// Only the fields migration tools commonly need are typed. All others, such
+/// as the statistics, file moves in `targets` or the peer cache, are kept in
+/// `extra`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UtorrentTorrent {
+    /// The name shown for the torrent.
+    pub caption: Option<String>,
+    /// The file of a single-file torrent, or the directory of a multi-file one.
+    pub path: Option<String>,
+    /// The v1 infohash of the torrent, given as `info`.
+    pub info_hash: Option<[u8; 20]>,
+    /// When the torrent was added, in seconds since the Unix epoch.
+    pub added_on: Option<i64>,
+    /// When the torrent was completed, in seconds since the Unix epoch, or 0.
+    pub completed_on: Option<i64>,
+    /// The number of bytes downloaded.
+    pub downloaded: Option<u64>,
+    /// The number of bytes uploaded.
+    pub uploaded: Option<u64>,
+    /// The pieces which have been downloaded.
+    pub have: Option<Bitfield>,
+    /// The download priority of each file, one byte per file, where 0 means
+    /// the file is skipped.
+    pub prio: Option<Vec<u8>>,
+    /// The URLs of the torrent's trackers, with empty strings separating tiers.
+    pub trackers: Option<Vec<String>>,
+    /// The label of the torrent.
+    pub label: Option<String>,
+    /// The labels of the torrent, in versions which support several.
+    pub labels: Option<Vec<String>>,
+    /// Whether the torrent is stopped (0), started (1) or paused (2).
+    pub started: Option<i64>,
+    /// All other fields of the torrent's dict.
+    pub extra: Extra,
+}
+
+impl UtorrentTorrent {
+    /// Whether the piece `index` has been downloaded according to `have`.
+    pub fn has_piece(&self, index: usize) -> bool {
+        self.have.as_ref().map_or(false, |have| have.get(index))
+    }
+
+    /// The tiers of tracker URLs, splitting `trackers` at empty strings.
+    pub fn tracker_tiers(&self) -> Vec<Vec<String>> {
+        self.trackers
+            .iter()
+            .flatten()
+            .fold(vec![Vec::new()], |mut tiers, url| {
+                if url.is_empty() {
+                    tiers.push(Vec::new());
+                } else if let Some(tier) = tiers.last_mut() {
+                    tier.push(url.clone());
+                }
+                tiers
+            })
+            .into_iter()
+            .filter(|tier| !tier.is_empty())
+            .collect()
+    }
+}
+
+/// The `settings.dat` file of uTorrent.
+///
+/// The settings are an open-ended set of mostly integer and string values, so
+/// they're kept as [`Value`]s.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UtorrentSettings {
+    /// The checksum of the file, given as `.fileguard`.
+    pub fileguard: Option<String>,
+    /// The settings, keyed by their names.
+    pub settings: Extra,
+}
+
+impl UtorrentSettings {
+    /// The value of the setting `name`.
+    pub fn get(&self, name: &str) -> Option<&Value<'static>> {
+        self.settings.get(name.as_bytes())
+    }
+
+    /// The value of the integer setting `name`.
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        match self.get(name)? {
+            Value::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of the string setting `name`, if it's valid UTF-8.
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            Value::Bytes(value) => std::str::from_utf8(value).ok(),
+            _ => None,
+        }
+    }
+
+    /// Set the setting `name` to `value`.
+    pub fn set(&mut self, name: &str, value: impl Into<Value<'static>>) {
+        self.settings.insert(name.as_bytes().to_vec(), value.into());
+    }
+}
+
+fn decode_fileguard(object: Object) -> Result<String, decoding::Error> {
+    String::decode_bencode_object(object).context(".fileguard")
+}
+
+impl FromBencode for UtorrentResume {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut resume = UtorrentResume::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (FILEGUARD, value) => resume.fileguard = Some(decode_fileguard(value)?),
+                (key, value @ Object::Dict(_)) if key.ends_with(b".torrent") => {
+                    let torrent = UtorrentTorrent::decode_bencode_object(value)
+                        .context(String::from_utf8_lossy(key))?;
+                    resume.torrents.insert(key.to_vec(), torrent);
+                },
+                (key, value) => decode_extra(&mut resume.extra, key, value)?,
+            }
+        }
+
+        Ok(resume)
+    }
+}
+
+impl FromBencode for UtorrentTorrent {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut torrent = UtorrentTorrent::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"added_on", value) => {
+                    torrent.added_on = i64::decode_bencode_object(value)
+                        .context("added_on")
+                        .map(Some)?;
+                },
+                (b"caption", value) => {
+                    torrent.caption = String::decode_bencode_object(value)
+                        .context("caption")
+                        .map(Some)?;
+                },
+                (b"completed_on", value) => {
+                    torrent.completed_on = i64::decode_bencode_object(value)
+                        .context("completed_on")
+                        .map(Some)?;
+                },
+                (b"downloaded", value) => {
+                    torrent.downloaded = u64::decode_bencode_object(value)
+                        .context("downloaded")
+                        .map(Some)?;
+                },
+                (b"have", value) => {
+                    torrent.have = Bitfield::decode_bencode_object(value)
+                        .context("have")
+                        .map(Some)?;
+                },
+                (b"info", value) => {
+                    let bytes = value.try_into_bytes().context("info")?;
+                    let hash = <[u8; 20]>::try_from(bytes).map_err(|_| {
+                        decoding::Error::unexpected_token(
+                            "20-byte hash",
+                            format!("{} bytes", bytes.len()),
+                        )
+                        .context("info")
+                    })?;
+                    torrent.info_hash = Some(hash);
+                },
+                (b"label", value) => {
+                    torrent.label = String::decode_bencode_object(value)
+                        .context("label")
+                        .map(Some)?;
+                },
+                (b"labels", value) => {
+                    torrent.labels = Vec::decode_bencode_object(value)
+                        .context("labels")
+                        .map(Some)?;
+                },
+                (b"path", value) => {
+                    torrent.path = String::decode_bencode_object(value)
+                        .context("path")
+                        .map(Some)?;
+                },
+                (b"prio", value) => {
+                    torrent.prio = value
+                        .try_into_bytes()
+                        .context("prio")
+                        .map(|bytes| Some(bytes.to_vec()))?;
+                },
+                (b"started", value) => {
+                    torrent.started = i64::decode_bencode_object(value)
+                        .context("started")
+                        .map(Some)?;
+                },
+                (b"trackers", value) => {
+                    torrent.trackers = Vec::decode_bencode_object(value)
+                        .context("trackers")
+                        .map(Some)?;
+                },
+                (b"uploaded", value) => {
+                    torrent.uploaded = u64::decode_bencode_object(value)
+                        .context("uploaded")
+                        .map(Some)?;
+                },
+                (key, value) => decode_extra(&mut torrent.extra, key, value)?,
+            }
+        }
+
+        Ok(torrent)
+    }
+}
+
+impl FromBencode for UtorrentSettings {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut settings = UtorrentSettings::default();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (FILEGUARD, value) => settings.fileguard = Some(decode_fileguard(value)?),
+                (key, value) => decode_extra(&mut settings.settings, key, value)?,
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+impl ToBencode for UtorrentResume {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            if let Some(fileguard) = &self.fileguard {
+                e.emit_pair(FILEGUARD, fileguard)?;
+            }
+            for (key, torrent) in &self.torrents {
+                e.emit_pair(key, torrent)?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ToBencode for UtorrentTorrent {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            if let Some(added_on) = self.added_on {
+                e.emit_pair(b"added_on", added_on)?;
+            }
+            if let Some(caption) = &self.caption {
+                e.emit_pair(b"caption", caption)?;
+            }
+            if let Some(completed_on) = self.completed_on {
+                e.emit_pair(b"completed_on", completed_on)?;
+            }
+            if let Some(downloaded) = self.downloaded {
+                e.emit_pair(b"downloaded", downloaded)?;
+            }
+            if let Some(have) = &self.have {
+                e.emit_pair(b"have", have)?;
+            }
+            if let Some(info_hash) = &self.info_hash {
+                e.emit_pair(b"info", AsString(&info_hash[..]))?;
+            }
+            if let Some(label) = &self.label {
+                e.emit_pair(b"label", label)?;
+            }
+            if let Some(labels) = &self.labels {
+                e.emit_pair(b"labels", labels)?;
+            }
+            if let Some(path) = &self.path {
+                e.emit_pair(b"path", path)?;
+            }
+            if let Some(prio) = &self.prio {
+                e.emit_pair(b"prio", AsString(prio))?;
+            }
+            if let Some(started) = self.started {
+                e.emit_pair(b"started", started)?;
+            }
+            if let Some(trackers) = &self.trackers {
+                e.emit_pair(b"trackers", trackers)?;
+            }
+            if let Some(uploaded) = self.uploaded {
+                e.emit_pair(b"uploaded", uploaded)?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ToBencode for UtorrentSettings {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            if let Some(fileguard) = &self.fileguard {
+                e.emit_pair(FILEGUARD, fileguard)?;
+            }
+            for (key, value) in &self.settings {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_files_round_trip() {
+        let mut encoded = b"d10:.fileguard40:0123456789ABCDEF0123456789ABCDEF01234567\
+                            9:b.torrentd8:added_oni1e7:caption1:b10:downloadedi7e4:have1:\xa0\
+                            4:info20:"
+            .to_vec();
+        encoded.extend_from_slice(&[0x11; 20]);
+        encoded.extend_from_slice(
+            b"6:labelsl1:x1:ye4:prio2:\x08\x004:rateli1ee8:trackersl8:http://a0:\
+              8:http://b8:http://ce8:uploadedi9ee3:recd1:ai1eee",
+        );
+
+        let resume = UtorrentResume::from_bencode(&encoded).unwrap();
+        let torrent = &resume.torrents[&b"b.torrent"[..]];
+        assert_eq!(torrent.info_hash, Some([0x11; 20]));
+        assert_eq!(torrent.prio, Some(vec![8, 0]));
+        assert!(torrent.has_piece(0) && !torrent.has_piece(1) && torrent.has_piece(2));
+        assert!(!torrent.has_piece(8));
+        assert_eq!(
+            torrent.tracker_tiers(),
+            vec![
+                vec!["http://a".to_string()],
+                vec!["http://b".to_string(), "http://c".to_string()]
+            ]
+        );
+        assert!(torrent.extra.contains_key(&b"rate"[..]));
+        assert!(resume.extra.contains_key(&b"rec"[..]));
+        assert_eq!(resume.to_bencode().unwrap(), encoded);
+    }
+
+    #[test]
+    fn settings_are_values() {
+        let encoded = b"d10:.fileguard1:X9:bind_porti6881e8:dir_save3:C:\\e";
+        let mut settings = UtorrentSettings::from_bencode(encoded).unwrap();
+        assert_eq!(settings.get_int("bind_port"), Some(6881));
+        assert_eq!(settings.get_str("dir_save"), Some("C:\\"));
+        assert_eq!(settings.get_int("dir_save"), None);
+        assert_eq!(settings.to_bencode().unwrap(), &encoded[..]);
+
+        settings.fileguard = None;
+        settings.set("bind_port", 1000i64);
+        assert_eq!(
+            settings.to_bencode().unwrap(),
+            &b"d9:bind_porti1000e8:dir_save3:C:\\e"[..]
+        );
+    }
+
+    #[test]
+    fn invalid_torrents_are_rejected() {
+        assert!(UtorrentResume::from_bencode(b"d9:a.torrentd4:info1:xee").is_err());
+        assert!(UtorrentResume::from_bencode(b"d10:.fileguardi1ee").is_err());
+        // Entries which aren't dicts aren't torrents
+        let resume = UtorrentResume::from_bencode(b"d9:a.torrenti1ee").unwrap();
+        assert!(resume.torrents.is_empty());
+    }
+}
@@ -0,0 +1,259 @@
+//! The file tree and piece layers of v2 torrents, as specified in
+//! [BEP 52](http://bittorrent.org/beps/bep_0052.html).
+
+use std::{collections::BTreeMap, convert::TryFrom};
+
+use crate::{
+    decoding::{self, FromBencode, Object, ResultExt},
+    encoding::{self, AsString, SingleItemEncoder, ToBencode},
+    value::Value,
+};
+
+use super::metainfo::{decode_extra, Extra};
+
+/// A SHA-256 hash, as used by v2 torrents.
+pub type Sha256Hash = [u8; 32];
+
+/// The `file tree` of a v2 torrent: the files and directories of one
+/// directory, keyed by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileTree {
+    /// The entries of the directory, in ascending order of their names.
+    pub entries: BTreeMap<String, FileTreeEntry>,
+}
+
+/// An entry of a [`FileTree`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileTreeEntry {
+    /// A file, encoded as a dict with a single empty key.
+    File(TreeFile),
+    /// A subdirectory.
+    Directory(FileTree),
+}
+
+/// A file in the `file tree` of a v2 torrent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeFile {
+    /// The length of the file in bytes.
+    pub length: u64,
+    /// The root of the Merkle tree of the file's 16 KiB blocks. Empty files
+    /// don't have one.
+    pub pieces_root: Option<Sha256Hash>,
+    /// All other fields of the file dict.
+    pub extra: Extra,
+}
+
+impl FileTree {
+    /// All files in the tree, depth-first in the order of their names, with
+    /// their path components.
+    pub fn files(&self) -> impl Iterator<Item = (Vec<&str>, &TreeFile)> {
+        fn collect<'t>(
+            tree: &'t FileTree,
+            path: &mut Vec<&'t str>,
+            files: &mut Vec<(Vec<&'t str>, &'t TreeFile)>,
+        ) {
+            for (name, entry) in &tree.entries {
+                path.push(name);
+                match entry {
+                    FileTreeEntry::File(file) => files.push((path.clone(), file)),
+                    FileTreeEntry::Directory(tree) => collect(tree, path, files),
+                }
+                path.pop();
+            }
+        }
+
+        let mut files = Vec::new();
+        collect(self, &mut Vec::new(), &mut files);
+        files.into_iter()
+    }
+
+    /// The total length of all files in bytes.
+    pub fn total_length(&self) -> u64 {
+        self.files().map(|(_, file)| file.length).sum()
+    }
+}
+
+/// Read a SHA-256 hash, checking its length.
+pub(super) fn decode_hash(bytes: &[u8]) -> Result<Sha256Hash, decoding::Error> {
+    Sha256Hash::try_from(bytes).map_err(|_| {
+        decoding::Error::unexpected_token("32-byte hash", format!("{} bytes", bytes.len()))
+    })
+}
+
+/// Decode the `piece layers` dict, mapping the `pieces root` of each file larger
+/// than a piece to the concatenated hashes of its pieces.
+pub(super) fn decode_piece_layers(
+    object: Object,
+) -> Result<BTreeMap<Sha256Hash, Vec<u8>>, decoding::Error> {
+    let mut layers = BTreeMap::new();
+    let mut dict = object.try_into_dictionary()?;
+    while let Some((root, value)) = dict.next_pair()? {
+        let root = decode_hash(root)?;
+        let hashes = value.try_into_bytes()?;
+        if hashes.len() % 32 != 0 {
+            return Err(decoding::Error::unexpected_token(
+                "a multiple of 32 bytes",
+                format!("{} bytes", hashes.len()),
+            ));
+        }
+        layers.insert(root, hashes.to_vec());
+    }
+    Ok(layers)
+}
+
+impl FromBencode for FileTree {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut entries = BTreeMap::new();
+        let mut dict = object.try_into_dictionary()?;
+        while let Some((name, value)) = dict.next_pair()? {
+            let name = String::from_utf8(name.to_vec())?;
+            let entry = FileTreeEntry::decode_bencode_object(value).context(&name)?;
+            entries.insert(name, entry);
+        }
+        Ok(FileTree { entries })
+    }
+}
+
+impl FromBencode for FileTreeEntry {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut file = None;
+        let mut entries = BTreeMap::new();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some((name, value)) = dict.next_pair()? {
+            // The empty key sorts first, so a file's dict can't have other keys
+            if file.is_some() {
+                return Err(decoding::Error::unexpected_field(String::from_utf8_lossy(
+                    name,
+                )));
+            }
+            if name.is_empty() {
+                file = TreeFile::decode_bencode_object(value).map(Some)?;
+                continue;
+            }
+
+            let name = String::from_utf8(name.to_vec())?;
+            let entry = FileTreeEntry::decode_bencode_object(value).context(&name)?;
+            entries.insert(name, entry);
+        }
+
+        Ok(match file {
+            Some(file) => FileTreeEntry::File(file),
+            None => FileTreeEntry::Directory(FileTree { entries }),
+        })
+    }
+}
+
+impl FromBencode for TreeFile {
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        let mut length = None;
+        let mut pieces_root = None;
+        let mut extra = Extra::new();
+
+        let mut dict = object.try_into_dictionary()?;
+        while let Some(pair) = dict.next_pair()? {
+            match pair {
+                (b"length", value) => {
+                    length = u64::decode_bencode_object(value)
+                        .context("length")
+                        .map(Some)?;
+                },
+                (b"pieces root", value) => {
+                    pieces_root = value
+                        .try_into_bytes()
+                        .and_then(decode_hash)
+                        .context("pieces root")
+                        .map(Some)?;
+                },
+                (key, value) => decode_extra(&mut extra, key, value)?,
+            }
+        }
+
+        Ok(TreeFile {
+            length: length.ok_or_else(|| decoding::Error::missing_field("length"))?,
+            pieces_root,
+            extra,
+        })
+    }
+}
+
+impl ToBencode for FileTree {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_dict(|mut e| {
+            for (name, entry) in &self.entries {
+                e.emit_pair(name.as_bytes(), entry)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ToBencode for FileTreeEntry {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        match self {
+            FileTreeEntry::File(file) => encoder.emit_dict(|mut e| e.emit_pair(b"", file)),
+            FileTreeEntry::Directory(tree) => tree.encode(encoder),
+        }
+    }
+}
+
+impl ToBencode for TreeFile {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_unsorted_dict(|e| {
+            e.emit_pair(b"length", self.length)?;
+            if let Some(root) = &self.pieces_root {
+                e.emit_pair(b"pieces root", AsString(&root[..]))?;
+            }
+            for (key, value) in &self.extra {
+                e.emit_pair(key, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_trees_round_trip() {
+        let root = [7; 32];
+        let mut encoded =
+            b"d3:dird1:ad0:d6:lengthi0eee1:bd0:d6:lengthi5e11:pieces root32:".to_vec();
+        encoded.extend_from_slice(&root);
+        encoded.extend_from_slice(b"eee5:top.xd0:d6:lengthi1eeee");
+
+        let tree = FileTree::from_bencode(&encoded).unwrap();
+        let files: Vec<_> = tree
+            .files()
+            .map(|(path, file)| (path, file.length, file.pieces_root))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                (vec!["dir", "a"], 0, None),
+                (vec!["dir", "b"], 5, Some(root)),
+                (vec!["top.x"], 1, None),
+            ]
+        );
+        assert_eq!(tree.total_length(), 6);
+        assert_eq!(tree.to_bencode().unwrap(), encoded);
+    }
+
+    #[test]
+    fn invalid_file_trees_are_rejected() {
+        // A file next to another entry
+        assert!(FileTree::from_bencode(b"d1:ad0:d6:lengthi1ee1:bdeee").is_err());
+        // A short pieces root
+        assert!(FileTree::from_bencode(b"d1:ad0:d6:lengthi1e11:pieces root1:xeee").is_err());
+        // A file without length
+        assert!(FileTree::from_bencode(b"d1:ad0:deee").is_err());
+    }
+}
@@ -0,0 +1,485 @@
+//! Checking decoded torrents for problems which decoding doesn't catch.
+
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::torrent::{check_component, HybridError, Info, Layout, MetaInfo};
+
+/// How serious a [`Problem`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Clients are likely to handle the torrent, but it's unusual.
+    Warning,
+    /// Clients will reject the torrent or fail to download it.
+    Error,
+}
+
+/// A problem found by [`MetaInfo::validate`] or [`MetaInfo::lint`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// Where the problem is, such as `info.piece length` or the path of a file.
+    pub location: String,
+    /// What the problem is.
+    pub kind: ProblemKind,
+}
+
+/// The kinds of [`Problem`]s.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// The piece length is zero.
+    ZeroPieceLength,
+    /// The piece length isn't a power of two. This is required for v2
+    /// torrents and customary for v1 torrents.
+    PieceLengthNotPowerOfTwo(u64),
+    /// The piece length of a v2 torrent is less than 16 KiB.
+    PieceLengthTooSmall(u64),
+    /// The length of `pieces` isn't a multiple of 20 bytes.
+    TruncatedPieces(usize),
+    /// The number of v1 pieces doesn't match the total length of the files.
+    WrongPieceCount {
+        /// The number of pieces the files need.
+        expected: u64,
+        /// The number of hashes in `pieces`.
+        found: u64,
+    },
+    /// A file has no path components.
+    EmptyPath,
+    /// A name or path component can't be used as a file name, as checked by
+    /// [`check_component`](crate::torrent::check_component).
+    InvalidPathComponent(String),
+    /// Two files have the same path.
+    DuplicatePath,
+    /// A non-empty v2 file has no `pieces root`.
+    MissingPiecesRoot,
+    /// A v2 file larger than a piece has no entry in `piece layers`.
+    MissingPieceLayer,
+    /// The piece layer of a v2 file doesn't have one hash per piece.
+    WrongPieceLayerSize {
+        /// The number of bytes the layer needs.
+        expected: u64,
+        /// The number of bytes of the layer.
+        found: u64,
+    },
+    /// The v1 and v2 metadata of a hybrid torrent disagree.
+    InconsistentHybrid(HybridError),
+    /// The name is empty, so clients have no file or directory to save the
+    /// torrent as.
+    EmptyName,
+    /// The torrent has more files than [`ClientLimits::max_files`].
+    ///
+    /// [`ClientLimits::max_files`]: crate::torrent::ClientLimits::max_files
+    TooManyFiles(usize),
+    /// A path, including the name of the torrent, has more characters than
+    /// [`ClientLimits::max_path_length`].
+    ///
+    /// [`ClientLimits::max_path_length`]: crate::torrent::ClientLimits::max_path_length
+    PathTooLong(usize),
+    /// A name or path component has more bytes than
+    /// [`ClientLimits::max_component_length`].
+    ///
+    /// [`ClientLimits::max_component_length`]: crate::torrent::ClientLimits::max_component_length
+    ComponentTooLong(String),
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl Display for ProblemKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ProblemKind::ZeroPieceLength => write!(f, "piece length is zero"),
+            ProblemKind::PieceLengthNotPowerOfTwo(length) => {
+                write!(f, "piece length {} is not a power of two", length)
+            },
+            ProblemKind::PieceLengthTooSmall(length) => {
+                write!(f, "piece length {} is less than 16 KiB", length)
+            },
+            ProblemKind::TruncatedPieces(length) => {
+                write!(f, "{} bytes of hashes are not a multiple of 20", length)
+            },
+            ProblemKind::WrongPieceCount { expected, found } => write!(
+                f,
+                "{} piece hashes given, but the files need {}",
+                found, expected
+            ),
+            ProblemKind::EmptyPath => write!(f, "path is empty"),
+            ProblemKind::InvalidPathComponent(component) => {
+                write!(f, "{:?} is not a valid file name", component)
+            },
+            ProblemKind::DuplicatePath => write!(f, "path is used by more than one file"),
+            ProblemKind::MissingPiecesRoot => write!(f, "file has no pieces root"),
+            ProblemKind::MissingPieceLayer => write!(f, "file has no piece layer"),
+            ProblemKind::WrongPieceLayerSize { expected, found } => write!(
+                f,
+                "piece layer has {} bytes, but the file needs {}",
+                found, expected
+            ),
+            ProblemKind::InconsistentHybrid(error) => write!(f, "{}", error),
+            ProblemKind::EmptyName => write!(f, "name is empty"),
+            ProblemKind::TooManyFiles(count) => write!(f, "torrent has {} files", count),
+            ProblemKind::PathTooLong(length) => {
+                write!(f, "path has {} characters", length)
+            },
+            ProblemKind::ComponentTooLong(component) => {
+                write!(f, "{:?} has {} bytes", component, component.len())
+            },
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.location, self.kind)
+    }
+}
+
+/// Collects the problems of a torrent.
+pub(super) struct Problems(pub(super) Vec<Problem>);
+
+impl Problems {
+    pub(super) fn push(
+        &mut self,
+        severity: Severity,
+        location: impl Into<String>,
+        kind: ProblemKind,
+    ) {
+        self.0.push(Problem {
+            severity,
+            location: location.into(),
+            kind,
+        });
+    }
+
+    /// Check that the piece length is a power of two, which only v2 torrents
+    /// require.
+    pub(super) fn check_piece_length(&mut self, info: &Info) {
+        let piece_length = info.piece_length;
+        if piece_length == 0 {
+            self.push(
+                Severity::Error,
+                "info.piece length",
+                ProblemKind::ZeroPieceLength,
+            );
+        } else if !piece_length.is_power_of_two() {
+            let severity = if info.is_v2() {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            self.push(
+                severity,
+                "info.piece length",
+                ProblemKind::PieceLengthNotPowerOfTwo(piece_length),
+            );
+        }
+    }
+
+    fn check_path<'p>(
+        &mut self,
+        location: &str,
+        path: impl IntoIterator<Item = &'p str>,
+        seen: &mut BTreeSet<String>,
+    ) {
+        let mut components = 0;
+        for component in path {
+            components += 1;
+            if check_component(component).is_err() {
+                self.push(
+                    Severity::Error,
+                    location,
+                    ProblemKind::InvalidPathComponent(component.to_string()),
+                );
+            }
+        }
+        if components == 0 {
+            self.push(Severity::Error, location, ProblemKind::EmptyPath);
+        } else if !seen.insert(location.to_string()) {
+            self.push(Severity::Error, location, ProblemKind::DuplicatePath);
+        }
+    }
+}
+
+impl MetaInfo {
+    /// Check the torrent for problems, returning them in the order the fields
+    /// are checked: the piece length, the v1 metadata, then the v2 metadata.
+    ///
+    /// Decoding already ensures that the required fields are present and have
+    /// the right types. This checks that their values make sense together. An
+    /// empty list means no problems were found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::{
+    ///     decoding::FromBencode,
+    ///     torrent::{MetaInfo, ProblemKind, Severity},
+    /// };
+    ///
+    /// let torrent = MetaInfo::from_bencode(
+    ///     b"d4:infod6:lengthi5e4:name2:..12:piece lengthi16384e6:pieces0:ee",
+    /// )
+    /// .unwrap();
+    ///
+    /// let problems = torrent.validate();
+    /// assert_eq!(problems.len(), 2);
+    /// assert_eq!(problems[0].severity, Severity::Error);
+    /// assert_eq!(
+    ///     problems[0].to_string(),
+    ///     "error: info.pieces: 0 piece hashes given, but the files need 1"
+    /// );
+    /// assert_eq!(
+    ///     problems[1].kind,
+    ///     ProblemKind::InvalidPathComponent("..".to_string())
+    /// );
+    /// ```
+    pub fn validate(&self) -> Vec<Problem> {
+        let info = &self.info;
+        let mut problems = Problems(Vec::new());
+
+        let piece_length = info.piece_length;
+        problems.check_piece_length(info);
+        if info.is_v2() && piece_length < 16 * 1024 {
+            problems.push(
+                Severity::Error,
+                "info.piece length",
+                ProblemKind::PieceLengthTooSmall(piece_length),
+            );
+        }
+
+        if let (Some(pieces), Some(layout)) = (&info.pieces, &info.layout) {
+            let length = match layout {
+                Layout::SingleFile { length } => *length,
+                Layout::MultiFile { files } => files.iter().map(|file| file.length).sum(),
+            };
+            match info.piece_hashes() {
+                Err(_) => problems.push(
+                    Severity::Error,
+                    "info.pieces",
+                    ProblemKind::TruncatedPieces(pieces.len()),
+                ),
+                Ok(hashes) if piece_length > 0 => {
+                    let expected = (length + piece_length - 1) / piece_length;
+                    let found = hashes.len() as u64;
+                    if expected != found {
+                        problems.push(
+                            Severity::Error,
+                            "info.pieces",
+                            ProblemKind::WrongPieceCount { expected, found },
+                        );
+                    }
+                },
+                Ok(_) => {},
+            }
+        }
+
+        if check_component(&info.name).is_err() {
+            problems.push(
+                Severity::Error,
+                "info.name",
+                ProblemKind::InvalidPathComponent(info.name.clone()),
+            );
+        }
+
+        if let Some(Layout::MultiFile { files }) = &info.layout {
+            let mut seen = BTreeSet::new();
+            for file in files {
+                let location = file.path.join("/");
+                problems.check_path(&location, file.path.iter().map(String::as_str), &mut seen);
+            }
+        }
+
+        if let Some(tree) = &info.file_tree {
+            let mut seen = BTreeSet::new();
+            for (path, file) in tree.files() {
+                let location = path.join("/");
+                problems.check_path(&location, path.iter().copied(), &mut seen);
+
+                let root = match (&file.pieces_root, file.length) {
+                    (_, 0) => continue,
+                    (Some(root), _) => root,
+                    (None, _) => {
+                        problems.push(Severity::Error, location, ProblemKind::MissingPiecesRoot);
+                        continue;
+                    },
+                };
+                if piece_length == 0 || file.length <= piece_length {
+                    continue;
+                }
+                let expected = (file.length + piece_length - 1) / piece_length * 32;
+                match self
+                    .piece_layers
+                    .as_ref()
+                    .and_then(|layers| layers.get(root))
+                {
+                    Some(layer) if layer.len() as u64 == expected => {},
+                    Some(layer) => problems.push(
+                        Severity::Error,
+                        location,
+                        ProblemKind::WrongPieceLayerSize {
+                            expected,
+                            found: layer.len() as u64,
+                        },
+                    ),
+                    None => {
+                        problems.push(Severity::Error, location, ProblemKind::MissingPieceLayer)
+                    },
+                }
+            }
+        }
+
+        if info.is_v1() && info.is_v2() && piece_length > 0 {
+            if let Err(error) = self.check_hybrid() {
+                let location = match error.path() {
+                    "" => "info".to_string(),
+                    path => path.to_string(),
+                };
+                problems.push(
+                    Severity::Error,
+                    location,
+                    ProblemKind::InconsistentHybrid(error),
+                );
+            }
+        }
+
+        problems.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{decoding::FromBencode, torrent::TorrentBuilder};
+
+    fn kinds(encoded: &[u8]) -> Vec<(Severity, String, ProblemKind)> {
+        MetaInfo::from_bencode(encoded)
+            .unwrap()
+            .validate()
+            .into_iter()
+            .map(|problem| (problem.severity, problem.location, problem.kind))
+            .collect()
+    }
+
+    #[test]
+    fn valid_torrents_have_no_problems() {
+        static DEBIAN: &[u8] =
+            include_bytes!("../../examples/torrent_files/debian-9.4.0-amd64-netinst.iso.torrent");
+        assert_eq!(kinds(DEBIAN), vec![]);
+    }
+
+    #[test]
+    fn v1_problems_are_found() {
+        assert_eq!(
+            kinds(b"d4:infod6:lengthi5e4:name1:a12:piece lengthi3e6:pieces10:aaaaaaaaaaee"),
+            vec![
+                (
+                    Severity::Warning,
+                    "info.piece length".to_string(),
+                    ProblemKind::PieceLengthNotPowerOfTwo(3)
+                ),
+                (
+                    Severity::Error,
+                    "info.pieces".to_string(),
+                    ProblemKind::TruncatedPieces(10)
+                ),
+            ]
+        );
+        assert_eq!(
+            kinds(b"d4:infod6:lengthi5e4:name1:a12:piece lengthi0e6:pieces0:ee"),
+            vec![(
+                Severity::Error,
+                "info.piece length".to_string(),
+                ProblemKind::ZeroPieceLength
+            )]
+        );
+    }
+
+    #[test]
+    fn path_problems_are_found() {
+        let problems = kinds(
+            b"d4:infod5:filesld6:lengthi1e4:pathl1:aeed6:lengthi1e4:pathl1:aeed6:lengthi1e\
+              4:pathl2:..1:beed6:lengthi1e4:pathleee4:name1:d12:piece lengthi16384e\
+              6:pieces20:aaaaaaaaaaaaaaaaaaaaee",
+        );
+        let kinds: Vec<_> = problems.into_iter().map(|(_, _, kind)| kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ProblemKind::DuplicatePath,
+                ProblemKind::InvalidPathComponent("..".to_string()),
+                ProblemKind::EmptyPath,
+            ]
+        );
+    }
+
+    #[test]
+    fn v2_problems_are_found() {
+        let root = "a".repeat(32);
+        let encoded = format!(
+            "d4:infod9:file treed1:ad0:d6:lengthi40000e11:pieces root32:{}ee1:bd0:d6:lengthi1eeee\
+             12:meta versioni2e4:name1:d12:piece lengthi16384eee",
+            root
+        );
+        assert_eq!(
+            kinds(encoded.as_bytes()),
+            vec![
+                (
+                    Severity::Error,
+                    "a".to_string(),
+                    ProblemKind::MissingPieceLayer
+                ),
+                (
+                    Severity::Error,
+                    "b".to_string(),
+                    ProblemKind::MissingPiecesRoot
+                ),
+            ]
+        );
+
+        let encoded = format!(
+            "{}12:piece layersd32:{}32:{}ee",
+            &encoded[..encoded.len() - 1],
+            root,
+            "b".repeat(32)
+        );
+        assert_eq!(
+            kinds(encoded.as_bytes())[0].2,
+            ProblemKind::WrongPieceLayerSize {
+                expected: 96,
+                found: 32
+            }
+        );
+    }
+
+    #[test]
+    fn built_hybrid_torrents_are_valid() {
+        let path = std::env::temp_dir().join(format!("bendy-validate-{}", std::process::id()));
+        std::fs::write(&path, vec![1; 40_000]).unwrap();
+        let torrent = TorrentBuilder::new(&path)
+            .with_piece_length(16 * 1024)
+            .with_version(crate::torrent::TorrentVersion::Hybrid)
+            .build();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut torrent = torrent.unwrap();
+        assert_eq!(torrent.validate(), vec![]);
+
+        torrent.info.pieces.as_mut().unwrap().truncate(20);
+        let problems = torrent.validate();
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(
+            problems[1].kind,
+            ProblemKind::InconsistentHybrid(_)
+        ));
+    }
+}
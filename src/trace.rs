@@ -0,0 +1,57 @@
+//! Spans and events for observing encoding and decoding with `tracing`.
+//!
+//! Every call counted by [`instrument`](crate::instrument) runs in a `bencode`
+//! span at the debug level, with the phase and the bytes read or written, and
+//! ends with an event carrying its duration. Calls made while another is
+//! running are part of the outer span. Decoders also emit debug events for
+//! errors, with the offset and nesting depth of the failing token, and for
+//! each fixup of a lenient decoding mode, such as an unsorted key it accepted.
+
+use std::{cell::Cell, time::Instant};
+
+use crate::instrument::Phase;
+
+/// Run `call`, which returns its result and the number of bytes it read or
+/// wrote, in a span for `phase`
+pub(crate) fn traced<T>(phase: Phase, call: impl FnOnce() -> (T, usize)) -> (T, usize) {
+    if ACTIVE.with(Cell::get) {
+        return call();
+    }
+
+    let phase = match phase {
+        Phase::Decoding => "decoding",
+        Phase::Encoding => "encoding",
+    };
+    let span = tracing::debug_span!("bencode", phase, bytes = tracing::field::Empty).entered();
+    let active = Active::enter();
+    let start = Instant::now();
+    let (result, bytes) = call();
+    let elapsed = start.elapsed();
+    drop(active);
+
+    span.record("bytes", bytes);
+    tracing::debug!(bytes, ?elapsed, "finished {}", phase);
+    (result, bytes)
+}
+
+thread_local! {
+    /// Whether a traced call is running on this thread
+    static ACTIVE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks a traced call as running until it is dropped, even if the call
+/// panics
+struct Active;
+
+impl Active {
+    fn enter() -> Self {
+        ACTIVE.with(|active| active.set(true));
+        Active
+    }
+}
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| active.set(false));
+    }
+}
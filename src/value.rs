@@ -3,12 +3,25 @@
 //!
 //! `Value` implements `FromBencode`, `ToBencode`. If the `serde` feature is
 //! enabled, it also implements `Serialize` and `Deserialize`.
+//!
+//! Values can be built from and converted back into primitives using `From`
+//! and `TryFrom`:
+//!
+//! ```
+//! use std::convert::TryFrom;
+//!
+//! use bendy::value::Value;
+//!
+//! let value = Value::from(42);
+//! assert_eq!(i64::try_from(value).unwrap(), 42);
+//!
+//! let value = Value::from(vec![Value::from("foo"), Value::from(1)]);
+//! assert!(String::try_from(value).is_err());
+//! ```
 
-use alloc::{
-    borrow::{Cow, ToOwned},
-    collections::BTreeMap,
-    vec::Vec,
-};
+#[cfg(not(feature = "std"))]
+use alloc::string::ToString;
+use alloc::{collections::BTreeMap, vec::Vec};
 
 #[cfg(feature = "serde")]
 use std::{
@@ -23,7 +36,7 @@ use serde_ as serde;
 #[cfg(feature = "serde")]
 use serde::{
     ser::{SerializeMap, SerializeSeq},
-    Serialize,
+    Deserialize, Serialize,
 };
 
 use crate::{
@@ -31,13 +44,78 @@ use crate::{
     encoding::{SingleItemEncoder, ToBencode},
 };
 
+mod access;
+#[cfg(feature = "arena")]
+mod arena;
+mod borrowed;
+mod builder;
+mod byte_string;
+mod conversions;
+mod intern;
+mod iter;
+#[cfg(feature = "json")]
+mod json;
+mod macros;
+mod merge;
+mod parse;
+mod partial_eq;
+mod path;
+mod pointer;
+mod pretty;
+mod raw;
+#[cfg(feature = "serde")]
+mod readable;
+mod redact;
+mod shared;
+mod text;
+#[cfg(feature = "toml")]
+mod toml;
+mod tree;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+#[cfg(feature = "arena")]
+pub use self::arena::ArenaValue;
+#[doc(hidden)]
+pub use self::macros::__dict_key;
+#[cfg(feature = "serde")]
+pub(crate) use self::raw::RAW_VALUE_TOKEN;
+#[cfg(feature = "serde")]
+pub use self::readable::HumanReadable;
+pub use self::{
+    access::TypeError,
+    builder::ValueBuilder,
+    byte_string::ByteString,
+    intern::{InternStats, KeyInterner},
+    iter::WithPaths,
+    merge::MergePolicy,
+    parse::ParseError,
+    path::PathError,
+    pointer::PointerError,
+    pretty::{BytesDisplay, Pretty, PrettyOptions},
+    raw::RawValue,
+    redact::redact_encoded,
+    shared::SharedValue,
+    text::BytesAsText,
+};
+
 /// An owned or borrowed bencoded value.
-#[derive(PartialEq, Eq, Clone, Debug)]
+///
+/// Values are totally ordered and hashable, so they can be stored in a
+/// `BTreeSet` or `HashSet`. Values of different variants are ordered by
+/// variant in declaration order, so every byte string sorts before every
+/// dictionary, which sorts before every integer, which sorts before every
+/// list. Values of the same variant are ordered by their contents: byte
+/// strings lexicographically, integers numerically, and lists and
+/// dictionaries element by element (dictionaries comparing their sorted
+/// key/value pairs). Whether a byte string is borrowed or owned never
+/// affects comparisons or hashes.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
 pub enum Value<'a> {
     /// An owned or borrowed byte string
-    Bytes(Cow<'a, [u8]>),
+    Bytes(ByteString<'a>),
     /// A dictionary mapping byte strings to values
-    Dict(BTreeMap<Cow<'a, [u8]>, Value<'a>>),
+    Dict(BTreeMap<ByteString<'a>, Value<'a>>),
     /// A signed integer
     Integer(i64),
     /// A list of values
@@ -48,16 +126,102 @@ impl<'a> Value<'a> {
     /// Convert this Value into an owned Value with static lifetime
     pub fn into_owned(self) -> Value<'static> {
         match self {
-            Value::Bytes(bytes) => Value::Bytes(Cow::Owned(bytes.into_owned())),
+            Value::Bytes(bytes) => Value::Bytes(bytes.into_owned()),
             Value::Dict(dict) => Value::Dict(
                 dict.into_iter()
-                    .map(|(key, value)| (Cow::Owned(key.into_owned()), value.into_owned()))
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
                     .collect(),
             ),
             Value::Integer(integer) => Value::Integer(integer),
             Value::List(list) => Value::List(list.into_iter().map(Value::into_owned).collect()),
         }
     }
+
+    /// Encode this value in canonical form: dict keys in ascending byte order and
+    /// integers without leading zeros or a negative zero.
+    ///
+    /// The output depends only on the contents of the value, not on how it was
+    /// built or decoded, so it is suitable for computing hashes like a torrent's
+    /// info hash. Unlike [`to_bencode`](ToBencode::to_bencode), this cannot fail.
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.write_canonical(&mut output);
+        output
+    }
+
+    /// The number of bytes [`to_bencode`](ToBencode::to_bencode) would produce for
+    /// this value, computed without encoding it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::{encoding::ToBencode, value::Value};
+    ///
+    /// let value = Value::from(vec![Value::from("spam"), Value::from(-42)]);
+    /// assert_eq!(value.encoded_len(), value.to_bencode().unwrap().len());
+    /// ```
+    pub fn encoded_len(&self) -> usize {
+        fn digits(mut number: u64) -> usize {
+            let mut digits = 1;
+            while number >= 10 {
+                number /= 10;
+                digits += 1;
+            }
+            digits
+        }
+
+        fn bytes_len(bytes: &[u8]) -> usize {
+            digits(bytes.len() as u64) + 1 + bytes.len()
+        }
+
+        match self {
+            Value::Bytes(bytes) => bytes_len(bytes),
+            Value::Dict(dict) => {
+                2 + dict
+                    .iter()
+                    .map(|(key, value)| bytes_len(key) + value.encoded_len())
+                    .sum::<usize>()
+            },
+            Value::Integer(integer) => {
+                let sign = if *integer < 0 { 1 } else { 0 };
+                2 + sign + digits(integer.unsigned_abs())
+            },
+            Value::List(list) => 2 + list.iter().map(Value::encoded_len).sum::<usize>(),
+        }
+    }
+
+    fn write_canonical(&self, output: &mut Vec<u8>) {
+        fn write_bytes(output: &mut Vec<u8>, bytes: &[u8]) {
+            output.extend_from_slice(bytes.len().to_string().as_bytes());
+            output.push(b':');
+            output.extend_from_slice(bytes);
+        }
+
+        match self {
+            Value::Bytes(bytes) => write_bytes(output, bytes),
+            Value::Dict(dict) => {
+                output.push(b'd');
+                // `BTreeMap` iterates in ascending key order
+                for (key, value) in dict {
+                    write_bytes(output, key);
+                    value.write_canonical(output);
+                }
+                output.push(b'e');
+            },
+            Value::Integer(integer) => {
+                output.push(b'i');
+                output.extend_from_slice(integer.to_string().as_bytes());
+                output.push(b'e');
+            },
+            Value::List(list) => {
+                output.push(b'l');
+                for value in list {
+                    value.write_canonical(output);
+                }
+                output.push(b'e');
+            },
+        }
+    }
 }
 
 impl<'a> ToBencode for Value<'a> {
@@ -66,40 +230,16 @@ impl<'a> ToBencode for Value<'a> {
     const MAX_DEPTH: usize = usize::max_value() / 4;
 
     fn encode(&self, encoder: SingleItemEncoder) -> Result<(), crate::encoding::Error> {
-        match self {
-            Value::Bytes(bytes) => encoder.emit_bytes(bytes),
-            Value::Dict(dict) => dict.encode(encoder),
-            Value::Integer(integer) => integer.encode(encoder),
-            Value::List(list) => list.encode(encoder),
-        }
+        tree::encode(self, encoder)
     }
 }
 
 impl<'a> FromBencode for Value<'a> {
-    const EXPECTED_RECURSION_DEPTH: usize = <Self as ToBencode>::MAX_DEPTH;
-    
+    // Decoding doesn't recurse, but dropping, comparing or formatting a value
+    // does, so this keeps the default depth limit
+
     fn decode_bencode_object(object: Object) -> Result<Self, crate::decoding::Error> {
-        match object {
-            Object::Bytes(bytes) => Ok(Value::Bytes(Cow::Owned(bytes.to_owned()))),
-            Object::Dict(mut decoder) => {
-                let mut dict = BTreeMap::new();
-                while let Some((key, value)) = decoder.next_pair()? {
-                    dict.insert(
-                        Cow::Owned(key.to_owned()),
-                        Value::decode_bencode_object(value)?,
-                    );
-                }
-                Ok(Value::Dict(dict))
-            },
-            Object::Integer(text) => Ok(Value::Integer(text.parse()?)),
-            Object::List(mut decoder) => {
-                let mut list = Vec::new();
-                while let Some(object) = decoder.next_object()? {
-                    list.push(Value::decode_bencode_object(object)?);
-                }
-                Ok(Value::List(list))
-            },
-        }
+        tree::build(&mut tree::Owned, object)
     }
 }
 
@@ -155,34 +295,73 @@ mod serde_impls {
             formatter.write_str("any valid BEncode value")
         }
 
+        fn visit_bool<E>(self, value: bool) -> Result<Value<'a>, E> {
+            Ok(Value::Integer(if value { 1 } else { 0 }))
+        }
+
         fn visit_i64<E>(self, value: i64) -> Result<Value<'a>, E> {
             Ok(Value::Integer(value))
         }
 
-        fn visit_u64<E>(self, value: u64) -> Result<Value<'a>, E> {
-            Ok(Value::Integer(value.try_into().unwrap()))
+        fn visit_u64<E>(self, value: u64) -> Result<Value<'a>, E>
+        where
+            E: serde::de::Error,
+        {
+            match value.try_into() {
+                Ok(integer) => Ok(Value::Integer(integer)),
+                Err(_) => Err(E::invalid_value(
+                    serde::de::Unexpected::Unsigned(value),
+                    &"an integer in the range of i64",
+                )),
+            }
         }
 
         fn visit_borrowed_bytes<E>(self, value: &'de [u8]) -> Result<Value<'a>, E>
         where
             E: serde::de::Error,
         {
-            Ok(Value::Bytes(Cow::Borrowed(value)))
+            Ok(Value::Bytes(ByteString::from(value)))
         }
 
         fn visit_borrowed_str<E>(self, value: &'de str) -> Result<Value<'a>, E>
         where
             E: serde::de::Error,
         {
-            Ok(Value::Bytes(Cow::Borrowed(value.as_bytes())))
+            Ok(Value::Bytes(ByteString::from(value.as_bytes())))
+        }
+
+        fn visit_bytes<E>(self, value: &[u8]) -> Result<Value<'a>, E> {
+            Ok(Value::Bytes(ByteString::copy_from(value)))
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Value<'a>, E> {
+            Ok(Value::Bytes(ByteString::copy_from(value.as_bytes())))
         }
 
         fn visit_string<E>(self, value: String) -> Result<Value<'a>, E> {
-            Ok(Value::Bytes(Cow::Owned(value.into_bytes())))
+            Ok(Value::Bytes(ByteString::from(value.into_bytes())))
         }
 
         fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Value<'a>, E> {
-            Ok(Value::Bytes(Cow::Owned(value)))
+            Ok(Value::Bytes(ByteString::from(value)))
+        }
+
+        // Units and options follow the representation used by `bendy::serde`:
+        // `None` and `()` are empty lists, `Some(t)` is a list containing `t`.
+        fn visit_unit<E>(self) -> Result<Value<'a>, E> {
+            Ok(Value::List(Vec::new()))
+        }
+
+        fn visit_none<E>(self) -> Result<Value<'a>, E> {
+            Ok(Value::List(Vec::new()))
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Value<'a>, D::Error>
+        where
+            D: serde::de::Deserializer<'de>,
+        {
+            let value = Value::deserialize(deserializer)?;
+            Ok(Value::List(vec![value]))
         }
 
         fn visit_seq<V>(self, mut access: V) -> Result<Value<'a>, V::Error>
@@ -201,12 +380,59 @@ mod serde_impls {
             V: serde::de::MapAccess<'de>,
         {
             let mut map = BTreeMap::new();
-            while let Some((k, v)) = access.next_entry::<&Bytes, _>()? {
-                map.insert(Cow::Borrowed(k.as_ref()), v);
+            while let Some((Key(k), v)) = access.next_entry()? {
+                map.insert(k, v);
             }
             Ok(Value::Dict(map))
         }
     }
+
+    /// A dictionary key which borrows from the input where possible, but can also
+    /// be produced by deserializers that only hand out transient or owned strings.
+    struct Key<'a>(ByteString<'a>);
+
+    impl<'de: 'a, 'a> serde::de::Deserialize<'de> for Key<'a> {
+        fn deserialize<D>(deserializer: D) -> Result<Key<'a>, D::Error>
+        where
+            D: serde::de::Deserializer<'de>,
+        {
+            deserializer.deserialize_bytes(KeyVisitor(PhantomData))
+        }
+    }
+
+    struct KeyVisitor<'a>(PhantomData<&'a ()>);
+
+    impl<'de: 'a, 'a> serde::de::Visitor<'de> for KeyVisitor<'a> {
+        type Value = Key<'a>;
+
+        fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+            formatter.write_str("a byte string dictionary key")
+        }
+
+        fn visit_borrowed_bytes<E>(self, value: &'de [u8]) -> Result<Key<'a>, E> {
+            Ok(Key(ByteString::from(value)))
+        }
+
+        fn visit_borrowed_str<E>(self, value: &'de str) -> Result<Key<'a>, E> {
+            Ok(Key(ByteString::from(value.as_bytes())))
+        }
+
+        fn visit_bytes<E>(self, value: &[u8]) -> Result<Key<'a>, E> {
+            Ok(Key(ByteString::copy_from(value)))
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Key<'a>, E> {
+            Ok(Key(ByteString::copy_from(value.as_bytes())))
+        }
+
+        fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Key<'a>, E> {
+            Ok(Key(ByteString::from(value)))
+        }
+
+        fn visit_string<E>(self, value: String) -> Result<Key<'a>, E> {
+            Ok(Key(ByteString::from(value.into_bytes())))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -278,8 +504,14 @@ mod tests {
 
     #[test]
     fn bytes() {
-        case(Value::Bytes(Cow::Borrowed(&[1, 2, 3])), b"3:\x01\x02\x03");
-        case(Value::Bytes(Cow::Owned(vec![1, 2, 3])), b"3:\x01\x02\x03");
+        case(
+            Value::Bytes(ByteString::from(&[1, 2, 3])),
+            b"3:\x01\x02\x03",
+        );
+        case(
+            Value::Bytes(ByteString::from(vec![1, 2, 3])),
+            b"3:\x01\x02\x03",
+        );
     }
 
     #[test]
@@ -287,8 +519,8 @@ mod tests {
         case(Value::Dict(BTreeMap::new()), "de");
 
         let mut dict = BTreeMap::new();
-        dict.insert(Cow::Borrowed("foo".as_bytes()), Value::Integer(1));
-        dict.insert(Cow::Borrowed("bar".as_bytes()), Value::Integer(2));
+        dict.insert(ByteString::from("foo".as_bytes()), Value::Integer(1));
+        dict.insert(ByteString::from("bar".as_bytes()), Value::Integer(2));
         case(Value::Dict(dict), "d3:bari2e3:fooi1ee");
     }
 
@@ -304,9 +536,156 @@ mod tests {
         case(
             Value::List(vec![
                 Value::Integer(0),
-                Value::Bytes(Cow::Borrowed(&[1, 2, 3])),
+                Value::Bytes(ByteString::from(&[1, 2, 3])),
             ]),
             b"li0e3:\x01\x02\x03e",
         );
     }
+
+    #[test]
+    fn canonical_bytes_match_encoding() {
+        let mut dict = BTreeMap::new();
+        dict.insert(ByteString::from(&b"zz"[..]), Value::Integer(i64::MIN));
+        dict.insert(ByteString::from(b"a".to_vec()), Value::Integer(0));
+        dict.insert(
+            ByteString::from(&b"m"[..]),
+            Value::List(vec![
+                Value::Bytes(ByteString::from(b"")),
+                Value::Dict(BTreeMap::new()),
+            ]),
+        );
+        let value = Value::Dict(dict);
+
+        let canonical = value.to_canonical_bytes();
+        assert_eq!(
+            canonical,
+            &b"d1:ai0e1:ml0:dee2:zzi-9223372036854775808ee"[..]
+        );
+        assert_eq!(canonical, value.to_bencode().unwrap());
+        assert_eq!(Value::from_bencode(&canonical).unwrap(), value);
+    }
+
+    #[test]
+    fn encoded_len_matches_encoding() {
+        let mut dict = BTreeMap::new();
+        dict.insert(
+            ByteString::from(&b"long"[..]),
+            Value::Bytes(ByteString::from(vec![0; 1234])),
+        );
+        dict.insert(ByteString::from(&b""[..]), Value::List(Vec::new()));
+
+        for value in &[
+            Value::Integer(0),
+            Value::Integer(9),
+            Value::Integer(10),
+            Value::Integer(-10),
+            Value::Integer(i64::MAX),
+            Value::Integer(i64::MIN),
+            Value::Bytes(ByteString::from(b"")),
+            Value::List(vec![Value::Dict(dict.clone()), Value::Integer(-1)]),
+            Value::Dict(dict),
+        ] {
+            assert_eq!(value.encoded_len(), value.to_bencode().unwrap().len());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    mod serde {
+        use super::*;
+
+        use serde_::de::{
+            value::{Error, MapDeserializer, StrDeserializer, U64Deserializer},
+            Deserialize, IntoDeserializer,
+        };
+
+        #[test]
+        fn transient_strings_deserialize_as_owned_bytes() {
+            let deserializer: StrDeserializer<Error> = "foo".into_deserializer();
+            assert_eq!(
+                Value::deserialize(deserializer).unwrap(),
+                Value::Bytes(ByteString::from(b"foo".to_vec()))
+            );
+        }
+
+        #[test]
+        fn owned_keys_deserialize() {
+            let mut map = BTreeMap::new();
+            map.insert(String::from("b"), 2i64);
+            map.insert(String::from("a"), 1i64);
+            let deserializer: MapDeserializer<_, Error> = MapDeserializer::new(map.into_iter());
+
+            let mut expected = BTreeMap::new();
+            expected.insert(ByteString::from(&b"a"[..]), Value::Integer(1));
+            expected.insert(ByteString::from(&b"b"[..]), Value::Integer(2));
+            assert_eq!(
+                Value::deserialize(deserializer).unwrap(),
+                Value::Dict(expected)
+            );
+        }
+
+        #[test]
+        fn unsigned_integers_out_of_range_should_fail() {
+            let deserializer: U64Deserializer<Error> = 1u64.into_deserializer();
+            assert_eq!(Value::deserialize(deserializer).unwrap(), Value::Integer(1));
+
+            let deserializer: U64Deserializer<Error> = u64::MAX.into_deserializer();
+            assert!(Value::deserialize(deserializer).is_err());
+        }
+
+        #[test]
+        fn options_deserialize_as_lists() {
+            let value: Option<i64> = Some(1);
+            let bytes = crate::serde::to_bytes(&value).unwrap();
+            assert_eq!(
+                crate::serde::from_bytes::<Value>(&bytes).unwrap(),
+                Value::List(vec![Value::Integer(1)])
+            );
+        }
+    }
+
+    #[test]
+    fn ordering_across_variants() {
+        let bytes = Value::Bytes(ByteString::from(b"z"));
+        let dict = Value::Dict(BTreeMap::new());
+        let integer = Value::Integer(i64::MIN);
+        let list = Value::List(Vec::new());
+
+        assert!(bytes < dict);
+        assert!(dict < integer);
+        assert!(integer < list);
+    }
+
+    #[test]
+    fn ordering_within_variants() {
+        assert!(Value::Integer(-1) < Value::Integer(1));
+        assert!(Value::Bytes(ByteString::from(b"ab")) < Value::Bytes(ByteString::from(b"b")));
+        assert!(
+            Value::List(vec![Value::Integer(1)])
+                < Value::List(vec![Value::Integer(1), Value::Integer(0)])
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn borrowed_and_owned_values_hash_identically() {
+        use std::collections::HashSet;
+
+        let mut set = HashSet::new();
+        set.insert(Value::Bytes(ByteString::from(b"foo")));
+        assert!(set.contains(&Value::Bytes(ByteString::from(b"foo".to_vec()))));
+        assert!(!set.insert(Value::Bytes(ByteString::from(b"foo".to_vec()))));
+    }
+
+    #[test]
+    fn values_can_be_set_members() {
+        use alloc::collections::BTreeSet;
+
+        let set: BTreeSet<_> = vec![Value::Integer(2), Value::Integer(1), Value::Integer(2)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            set.into_iter().collect::<Vec<_>>(),
+            vec![Value::Integer(1), Value::Integer(2)]
+        );
+    }
 }
@@ -0,0 +1,201 @@
+//! Typed accessors for the contents of a [`Value`].
+
+#[cfg(not(feature = "std"))]
+use alloc::string::{String, ToString};
+use alloc::{collections::BTreeMap, format, vec::Vec};
+use core::{
+    fmt::{self, Display, Formatter},
+    str,
+};
+
+use crate::value::{ByteString, Value};
+
+/// An error returned when a value doesn't have the type an accessor expects.
+///
+/// Besides the expected and the actual type, the error records where in a larger
+/// structure the value was found, as a [JSON pointer](crate::value::Value::pointer).
+/// Accessors leave the path empty; callers can add the keys and indices they
+/// navigated through with [`TypeError::at`].
+///
+/// # Examples
+///
+/// ```
+/// use bendy::value::{TypeError, Value};
+///
+/// let torrent = Value::parse_pretty(r#"{"info": {"piece length": "big"}}"#).unwrap();
+/// let length = torrent.pointer("/info/piece length").unwrap();
+///
+/// let error: TypeError = length
+///     .as_int()
+///     .map_err(|e| e.at("piece length").at("info"))
+///     .unwrap_err();
+/// assert_eq!(error.path(), "/info/piece length");
+/// assert_eq!(error.expected(), "Num");
+/// assert_eq!(error.found(), "String");
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TypeError {
+    path: String,
+    expected: &'static str,
+    found: &'static str,
+}
+
+impl TypeError {
+    fn new(expected: &'static str, found: &'static str) -> Self {
+        TypeError {
+            path: String::new(),
+            expected,
+            found,
+        }
+    }
+
+    /// Record that the value was found under the given dict key or list index,
+    /// by prepending it to the path.
+    #[must_use]
+    pub fn at(mut self, segment: impl Display) -> Self {
+        // Escape the segment as JSON pointers do
+        let segment = segment.to_string().replace('~', "~0").replace('/', "~1");
+        self.path = format!("/{}{}", segment, self.path);
+        self
+    }
+
+    /// The location of the value as a JSON pointer, or an empty string for the
+    /// value the accessor was called on.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The name of the type the accessor expected.
+    pub fn expected(&self) -> &'static str {
+        self.expected
+    }
+
+    /// The name of the type the value actually has.
+    pub fn found(&self) -> &'static str {
+        self.found
+    }
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)?;
+        if !self.path.is_empty() {
+            write!(f, " at {:?}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TypeError {}
+
+impl<'a> Value<'a> {
+    /// Get the integer, if this value is one.
+    pub fn as_int(&self) -> Result<i64, TypeError> {
+        match self {
+            Value::Integer(integer) => Ok(*integer),
+            other => Err(TypeError::new("Num", other.token_name())),
+        }
+    }
+
+    /// Get the contents of the byte string, if this value is one.
+    pub fn as_bytes(&self) -> Result<&[u8], TypeError> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            other => Err(TypeError::new("String", other.token_name())),
+        }
+    }
+
+    /// Get the contents of the byte string as text, if this value is a byte
+    /// string containing valid UTF-8.
+    pub fn as_str(&self) -> Result<&str, TypeError> {
+        let bytes = self
+            .as_bytes()
+            .map_err(|err| TypeError::new("UTF-8 String", err.found))?;
+        str::from_utf8(bytes).map_err(|_| TypeError::new("UTF-8 String", "non-UTF-8 String"))
+    }
+
+    /// Get the items of the list, if this value is one.
+    pub fn as_list(&self) -> Result<&Vec<Value<'a>>, TypeError> {
+        match self {
+            Value::List(list) => Ok(list),
+            other => Err(TypeError::new("List", other.token_name())),
+        }
+    }
+
+    /// Get the entries of the dict, if this value is one.
+    pub fn as_dict(&self) -> Result<&BTreeMap<ByteString<'a>, Value<'a>>, TypeError> {
+        match self {
+            Value::Dict(dict) => Ok(dict),
+            other => Err(TypeError::new("Dict", other.token_name())),
+        }
+    }
+
+    /// Like [`as_list`](Value::as_list), but returns a mutable reference.
+    pub fn as_list_mut(&mut self) -> Result<&mut Vec<Value<'a>>, TypeError> {
+        match self {
+            Value::List(list) => Ok(list),
+            other => Err(TypeError::new("List", other.token_name())),
+        }
+    }
+
+    /// Like [`as_dict`](Value::as_dict), but returns a mutable reference.
+    pub fn as_dict_mut(&mut self) -> Result<&mut BTreeMap<ByteString<'a>, Value<'a>>, TypeError> {
+        match self {
+            Value::Dict(dict) => Ok(dict),
+            other => Err(TypeError::new("Dict", other.token_name())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::ToString;
+
+    #[test]
+    fn accessors_return_matching_contents() {
+        let value = Value::parse_pretty(r#"[1, "a", <ff>, [], {}]"#).unwrap();
+        let list = value.as_list().unwrap();
+
+        assert_eq!(list[0].as_int(), Ok(1));
+        assert_eq!(list[1].as_str(), Ok("a"));
+        assert_eq!(list[2].as_bytes(), Ok(&[0xff][..]));
+        assert!(list[3].as_list().unwrap().is_empty());
+        assert!(list[4].as_dict().unwrap().is_empty());
+    }
+
+    #[test]
+    fn errors_describe_the_mismatch() {
+        let error = Value::from("1").as_int().unwrap_err();
+        assert_eq!(
+            (error.expected(), error.found(), error.path()),
+            ("Num", "String", "")
+        );
+        assert_eq!(error.to_string(), "expected Num, found String");
+
+        let error = Value::from(&[0xff][..]).as_str().unwrap_err();
+        assert_eq!(error.found(), "non-UTF-8 String");
+        let error = Value::from(1).as_str().unwrap_err();
+        assert_eq!(error.found(), "Num");
+
+        assert_eq!(Value::from(1).as_dict_mut().unwrap_err().expected(), "Dict");
+        assert_eq!(Value::from(1).as_list_mut().unwrap_err().expected(), "List");
+    }
+
+    #[test]
+    fn paths_are_escaped_pointers() {
+        let error = Value::from(1)
+            .as_bytes()
+            .unwrap_err()
+            .at(0)
+            .at("a/b~")
+            .at("info");
+        assert_eq!(error.path(), "/info/a~1b~0/0");
+        assert_eq!(
+            error.to_string(),
+            r#"expected String, found Num at "/info/a~1b~0/0""#
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Value trees allocated in a [`bumpalo`] arena.
+//!
+//! Decoding a [`Value`] allocates separately for every list, dict and byte string
+//! in the input. When parsing millions of small messages (e.g. in a DHT crawler)
+//! these allocations dominate. An [`ArenaValue`] instead borrows all byte strings
+//! and keys from the input buffer and places its lists and dicts in a [`Bump`]
+//! arena, so decoding a message costs a handful of pointer bumps.
+//!
+//! # Lifetimes
+//!
+//! An `ArenaValue<'bump, 'ser>` borrows the arena for `'bump` and the input
+//! buffer for `'ser`. Nothing is freed individually; once all values decoded
+//! into an arena are gone, call [`Bump::reset`] to reuse its memory for the next
+//! batch of messages:
+//!
+//! ```
+//! use bumpalo::Bump;
+//! use bendy::value::ArenaValue;
+//!
+//! let mut bump = Bump::new();
+//! for message in &[&b"d1:y1:qe"[..], &b"d1:y1:re"[..]] {
+//!     {
+//!         let value = ArenaValue::from_bencode_in(&bump, message).unwrap();
+//!         assert!(value.get(b"y").is_some());
+//!     }
+//!     bump.reset();
+//! }
+//! ```
+
+use core::{iter, slice};
+
+use bumpalo::{collections::Vec as BumpVec, Bump};
+
+use crate::{
+    decoding::{Decoder, Error, FromBencode, Object},
+    encoding::{SingleItemEncoder, ToBencode},
+    state_tracker::StructureError,
+    value::{
+        tree::{self, Node, TreeBuilder, Walk},
+        ByteString, Value,
+    },
+};
+
+/// A bencoded value whose lists and dicts live in an arena and whose byte
+/// strings borrow from the input buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArenaValue<'bump, 'ser> {
+    /// A byte string borrowed from the input
+    Bytes(&'ser [u8]),
+    /// A dictionary, as key/value pairs sorted by key
+    Dict(&'bump [(&'ser [u8], ArenaValue<'bump, 'ser>)]),
+    /// A signed integer
+    Integer(i64),
+    /// A list of values
+    List(&'bump [ArenaValue<'bump, 'ser>]),
+}
+
+impl<'bump, 'ser> ArenaValue<'bump, 'ser> {
+    /// Decode a single value from the start of `bytes`, allocating its lists and
+    /// dicts in `bump`.
+    pub fn from_bencode_in(bump: &'bump Bump, bytes: &'ser [u8]) -> Result<Self, Error> {
+        let mut decoder =
+            Decoder::new(bytes).with_max_depth(<Value as FromBencode>::EXPECTED_RECURSION_DEPTH);
+        let object = decoder.next_object()?;
+
+        match object {
+            Some(object) => Self::decode_in(bump, object),
+            None => Err(Error::from(StructureError::UnexpectedEof)),
+        }
+    }
+
+    /// Convert an already decoded object, allocating its lists and dicts in
+    /// `bump`. Use this to decode with a custom [`Decoder`] configuration.
+    pub fn decode_in(bump: &'bump Bump, object: Object<'_, 'ser>) -> Result<Self, Error> {
+        tree::build(&mut InArena(bump), object)
+    }
+
+    /// Look up a key if this value is a dict. Returns `None` for missing keys and
+    /// for values which aren't dicts.
+    pub fn get(&self, key: &[u8]) -> Option<&ArenaValue<'bump, 'ser>> {
+        match self {
+            ArenaValue::Dict(pairs) => pairs
+                .binary_search_by(|(k, _)| (*k).cmp(key))
+                .ok()
+                .map(|index| &pairs[index].1),
+            _ => None,
+        }
+    }
+
+    /// Copy this value out of the arena into a regular [`Value`], which still
+    /// borrows its byte strings from the input.
+    pub fn to_value(&self) -> Value<'ser> {
+        match *self {
+            ArenaValue::Bytes(bytes) => Value::Bytes(ByteString::from(bytes)),
+            ArenaValue::Integer(integer) => Value::Integer(integer),
+            ArenaValue::List(list) => Value::List(list.iter().map(Self::to_value).collect()),
+            ArenaValue::Dict(pairs) => Value::Dict(
+                pairs
+                    .iter()
+                    .map(|(key, value)| (ByteString::from(*key), value.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<'bump, 'ser> ToBencode for ArenaValue<'bump, 'ser> {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), crate::encoding::Error> {
+        tree::encode(self, encoder)
+    }
+}
+
+impl<'t, 'bump: 't, 'ser: 'bump> Walk<'t> for ArenaValue<'bump, 'ser> {
+    type Items = slice::Iter<'t, ArenaValue<'bump, 'ser>>;
+    type Entries = iter::Map<
+        slice::Iter<'t, (&'ser [u8], ArenaValue<'bump, 'ser>)>,
+        fn(&'t (&'ser [u8], ArenaValue<'bump, 'ser>)) -> (&'t [u8], &'t ArenaValue<'bump, 'ser>),
+    >;
+
+    fn node(&'t self) -> Node<'t, Self::Items, Self::Entries> {
+        match *self {
+            ArenaValue::Bytes(bytes) => Node::Bytes(bytes),
+            ArenaValue::Integer(integer) => Node::Integer(integer),
+            ArenaValue::List(list) => Node::List(list.iter()),
+            ArenaValue::Dict(pairs) => Node::Dict(pairs.iter().map(|(key, value)| (*key, value))),
+        }
+    }
+}
+
+/// Builds an [`ArenaValue`] in a bump allocator for [`tree::build`]
+struct InArena<'bump>(&'bump Bump);
+
+impl<'bump, 'ser: 'bump> TreeBuilder<'ser> for InArena<'bump> {
+    type Value = ArenaValue<'bump, 'ser>;
+    type List = BumpVec<'bump, ArenaValue<'bump, 'ser>>;
+    type Dict = BumpVec<'bump, (&'ser [u8], ArenaValue<'bump, 'ser>)>;
+
+    fn bytes(&mut self, bytes: &'ser [u8]) -> Self::Value {
+        ArenaValue::Bytes(bytes)
+    }
+
+    fn integer(&mut self, value: i64) -> Self::Value {
+        ArenaValue::Integer(value)
+    }
+
+    fn list(&mut self) -> Self::List {
+        BumpVec::new_in(self.0)
+    }
+
+    fn dict(&mut self) -> Self::Dict {
+        BumpVec::new_in(self.0)
+    }
+
+    fn push(&mut self, list: &mut Self::List, item: Self::Value) {
+        list.push(item);
+    }
+
+    fn insert(&mut self, dict: &mut Self::Dict, key: &'ser [u8], value: Self::Value) {
+        dict.push((key, value));
+    }
+
+    fn finish_list(&mut self, list: Self::List) -> Self::Value {
+        ArenaValue::List(list.into_bump_slice())
+    }
+
+    fn finish_dict(&mut self, dict: Self::Dict) -> Self::Value {
+        ArenaValue::Dict(dict.into_bump_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static MESSAGE: &[u8] = b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe";
+
+    #[test]
+    fn decode_matches_value() {
+        let bump = Bump::new();
+        let value = ArenaValue::from_bencode_in(&bump, MESSAGE).unwrap();
+
+        assert_eq!(value.to_value(), Value::from_bencode(MESSAGE).unwrap());
+        assert_eq!(value.get(b"q"), Some(&ArenaValue::Bytes(b"ping")));
+        assert_eq!(
+            value.get(b"a").and_then(|a| a.get(b"id")),
+            Some(&ArenaValue::Bytes(b"abcdefghij0123456789"))
+        );
+        assert_eq!(value.get(b"missing"), None);
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let bump = Bump::new();
+        let value = ArenaValue::from_bencode_in(&bump, MESSAGE).unwrap();
+        assert_eq!(value.to_bencode().unwrap(), MESSAGE);
+
+        let value = ArenaValue::from_bencode_in(&bump, b"li-1el0:ee").unwrap();
+        assert_eq!(
+            value,
+            ArenaValue::List(&[
+                ArenaValue::Integer(-1),
+                ArenaValue::List(&[ArenaValue::Bytes(b"")])
+            ])
+        );
+        assert_eq!(value.to_bencode().unwrap(), b"li-1el0:ee");
+    }
+
+    #[test]
+    fn invalid_input_should_fail() {
+        let bump = Bump::new();
+        assert!(ArenaValue::from_bencode_in(&bump, b"").is_err());
+        assert!(ArenaValue::from_bencode_in(&bump, b"d1:b0:1:a0:e").is_err());
+        assert!(ArenaValue::from_bencode_in(&bump, b"l").is_err());
+    }
+}
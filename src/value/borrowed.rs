@@ -0,0 +1,132 @@
+//! Zero-copy decoding of [`Value`]s.
+//!
+//! [`Value::from_bencode`](crate::decoding::FromBencode::from_bencode) copies every
+//! byte string into a fresh allocation, because the trait cannot tie the result to
+//! the lifetime of the input. [`Value::from_bencode_borrowed`] instead leaves every
+//! byte string and dict key borrowed from the input buffer. Since those are stored
+//! as [`ByteString`](crate::value::ByteString)s, the usual tree API keeps
+//! working: reads never copy, and a byte string is only copied the first time it
+//! is mutated through [`Value::bytes_mut`].
+//!
+//! ```
+//! use bendy::value::Value;
+//!
+//! let buffer = b"d4:name3:fooe".to_vec();
+//! let value = Value::from_bencode_borrowed(&buffer).unwrap();
+//! assert!(value.is_borrowed());
+//!
+//! // Release the buffer while keeping the value around.
+//! let value = value.make_owned();
+//! drop(buffer);
+//! assert!(!value.is_borrowed());
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{
+    decoding::{Decoder, Error, FromBencode, Object},
+    state_tracker::StructureError,
+    value::{tree, Value},
+};
+
+impl<'a> Value<'a> {
+    /// Decode a value from the start of `bytes`, borrowing all byte strings and
+    /// dict keys from the input instead of copying them.
+    pub fn from_bencode_borrowed(bytes: &'a [u8]) -> Result<Self, Error> {
+        let mut decoder = Decoder::new(bytes).with_max_depth(Self::EXPECTED_RECURSION_DEPTH);
+        let object = decoder.next_object()?;
+
+        object.map_or(
+            Err(Error::from(StructureError::UnexpectedEof)),
+            Self::decode_borrowed,
+        )
+    }
+
+    /// Convert an already decoded object into a value which borrows all byte
+    /// strings and dict keys from the input.
+    pub fn decode_borrowed(object: Object<'_, 'a>) -> Result<Self, Error> {
+        tree::build(&mut tree::Borrowed, object)
+    }
+
+    /// Whether any byte string or dict key in this value still borrows from
+    /// the buffer the value was decoded from.
+    pub fn is_borrowed(&self) -> bool {
+        match self {
+            Value::Bytes(bytes) => bytes.is_borrowed(),
+            Value::Dict(dict) => dict
+                .iter()
+                .any(|(key, value)| key.is_borrowed() || value.is_borrowed()),
+            Value::Integer(_) => false,
+            Value::List(list) => list.iter().any(Value::is_borrowed),
+        }
+    }
+
+    /// Get mutable access to the contents of a byte string, copying them out of
+    /// the input buffer first if they are still borrowed. Returns `None` if
+    /// this value isn't a byte string.
+    pub fn bytes_mut(&mut self) -> Option<&mut Vec<u8>> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes.to_mut()),
+            _ => None,
+        }
+    }
+
+    /// Copy everything still borrowed from the input buffer, so the buffer can
+    /// be released. Byte strings which were already copied, e.g. by
+    /// [`bytes_mut`](Value::bytes_mut), are moved rather than copied again.
+    pub fn make_owned(self) -> Value<'static> {
+        self.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    #[test]
+    fn decoding_borrows_the_input() {
+        let buffer = b"d1:ai1e1:bl3:fooee";
+        let value = Value::from_bencode_borrowed(buffer).unwrap();
+
+        assert_eq!(value, Value::from_bencode(buffer).unwrap());
+        assert!(value.is_borrowed());
+        match &value {
+            Value::Dict(dict) => {
+                let key = dict.keys().next().unwrap();
+                assert_eq!(key.as_ptr(), buffer[3..].as_ptr());
+            },
+            _ => panic!("expected a dict"),
+        }
+    }
+
+    #[test]
+    fn mutation_copies_the_string() {
+        let buffer = b"3:foo";
+        let mut value = Value::from_bencode_borrowed(buffer).unwrap();
+
+        value.bytes_mut().unwrap().push(b'!');
+        assert!(!value.is_borrowed());
+        assert_eq!(value, "foo!");
+        assert_eq!(buffer, b"3:foo");
+
+        assert!(Value::Integer(1).bytes_mut().is_none());
+    }
+
+    #[test]
+    fn make_owned_detaches_from_the_buffer() {
+        let buffer = b"l3:fooi1ee".to_vec();
+        let value = Value::from_bencode_borrowed(&buffer).unwrap().make_owned();
+        drop(buffer);
+
+        assert!(!value.is_borrowed());
+        assert_eq!(value, Value::List(vec![Value::from("foo"), Value::from(1)]));
+    }
+
+    #[test]
+    fn invalid_input_should_fail() {
+        assert!(Value::from_bencode_borrowed(b"").is_err());
+        assert!(Value::from_bencode_borrowed(b"d1:b0:1:a0:e").is_err());
+    }
+}
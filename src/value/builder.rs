@@ -0,0 +1,212 @@
+//! Assembling [`Value`]s from a stream of [`Token`]s.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    decoding::{Error, FromBencode},
+    state_tracker::{StateTracker, StructureError, Token},
+    value::{ByteString, Value},
+};
+
+enum Frame<'a> {
+    List(Vec<Value<'a>>),
+    Dict(BTreeMap<ByteString<'a>, Value<'a>>, Option<&'a [u8]>),
+}
+
+/// Builds a [`Value`] from a stream of [`Token`]s.
+///
+/// The token stream is validated the same way the decoder validates its input:
+/// dict keys must be byte strings in strictly ascending order, integers must be in
+/// canonical form and lists and dicts must be closed. Byte strings are borrowed from
+/// the tokens.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{state_tracker::Token, value::{Value, ValueBuilder}};
+///
+/// let mut builder = ValueBuilder::new();
+/// for token in vec![Token::Dict, Token::String(b"id"), Token::Num("7"), Token::End] {
+///     builder.push(token).unwrap();
+/// }
+///
+/// assert_eq!(builder.finish().unwrap(), Value::parse_pretty(r#"{"id": 7}"#).unwrap());
+/// ```
+pub struct ValueBuilder<'a> {
+    state: StateTracker<&'a [u8], Error>,
+    stack: Vec<Frame<'a>>,
+    result: Option<Value<'a>>,
+}
+
+impl<'a> Default for ValueBuilder<'a> {
+    fn default() -> Self {
+        let mut state = StateTracker::new();
+        state.set_max_depth(<Value as FromBencode>::EXPECTED_RECURSION_DEPTH);
+
+        ValueBuilder {
+            state,
+            stack: Vec::new(),
+            result: None,
+        }
+    }
+}
+
+impl<'a> ValueBuilder<'a> {
+    /// Create a builder expecting the tokens of a single value.
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Set the maximum nesting depth of the value.
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.state.set_max_depth(max_depth);
+        self
+    }
+
+    /// Whether a complete value has been built.
+    pub fn is_complete(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Add the next token of the value. Once an error was returned, every further
+    /// call returns an error as well.
+    pub fn push(&mut self, token: Token<'a>) -> Result<(), Error> {
+        self.state.check_error()?;
+        if self.result.is_some() {
+            return self
+                .state
+                .latch_err(Err(Error::from(StructureError::invalid_state(
+                    "No more tokens after a complete value",
+                ))));
+        }
+        self.state.observe_token(&token)?;
+
+        let value = match token {
+            Token::List => {
+                self.stack.push(Frame::List(Vec::new()));
+                return Ok(());
+            },
+            Token::Dict => {
+                self.stack.push(Frame::Dict(BTreeMap::new(), None));
+                return Ok(());
+            },
+            Token::String(bytes) => {
+                if let Some(Frame::Dict(_, key @ None)) = self.stack.last_mut() {
+                    *key = Some(bytes);
+                    return Ok(());
+                }
+                Value::Bytes(ByteString::from(bytes))
+            },
+            Token::Num(text) => {
+                let integer = parse_canonical(text);
+                Value::Integer(self.state.latch_err(integer)?)
+            },
+            Token::End => match self.stack.pop() {
+                Some(Frame::List(list)) => Value::List(list),
+                Some(Frame::Dict(dict, _)) => Value::Dict(dict),
+                None => unreachable!("the state tracker rejects unbalanced ends"),
+            },
+        };
+
+        match self.stack.last_mut() {
+            Some(Frame::List(list)) => list.push(value),
+            Some(Frame::Dict(dict, key)) => {
+                let key = key
+                    .take()
+                    .expect("the state tracker ensures a key was read");
+                dict.insert(ByteString::from(key), value);
+            },
+            None => self.result = Some(value),
+        }
+        Ok(())
+    }
+
+    /// Return the built value, or an error if the tokens didn't form a complete
+    /// value.
+    pub fn finish(mut self) -> Result<Value<'a>, Error> {
+        self.state.observe_eof()?;
+        self.result
+            .ok_or_else(|| Error::from(StructureError::UnexpectedEof))
+    }
+}
+
+/// Parse an integer, rejecting leading zeros, `-0` and any sign but `-`.
+fn parse_canonical(text: &str) -> Result<i64, Error> {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let canonical = match digits.as_bytes() {
+        [b'0'] => digits.len() == text.len(),
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    };
+
+    if !canonical {
+        return Err(Error::from(StructureError::SyntaxError(
+            "Non-canonical integer".into(),
+        )));
+    }
+    Ok(text.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::decoding::Decoder;
+
+    fn build(tokens: &[Token<'static>]) -> Result<Value<'static>, Error> {
+        let mut builder = ValueBuilder::new();
+        for token in tokens {
+            builder.push(*token)?;
+        }
+        builder.finish()
+    }
+
+    #[test]
+    fn decoder_tokens_build_the_decoded_value() {
+        let encoded = b"d1:ad1:bi-1ee1:cl0:lei0eee";
+        let mut builder = ValueBuilder::new();
+        for token in Decoder::new(encoded).tokens() {
+            assert!(!builder.is_complete());
+            builder.push(token.unwrap()).unwrap();
+        }
+
+        assert!(builder.is_complete());
+        assert_eq!(
+            builder.finish().unwrap(),
+            Value::from_bencode(encoded).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_streams_are_rejected() {
+        use Token::*;
+
+        assert!(build(&[]).is_err());
+        assert!(build(&[List]).is_err());
+        assert!(build(&[End]).is_err());
+        assert!(build(&[Num("1"), Num("2")]).is_err());
+        assert!(build(&[Dict, Num("1"), Num("2"), End]).is_err());
+        assert!(build(&[Dict, String(b"b"), Num("1"), String(b"a"), Num("2"), End]).is_err());
+        assert!(build(&[Dict, String(b"a"), End]).is_err());
+        assert!(build(&[List, List, End, End]).is_ok());
+    }
+
+    #[test]
+    fn non_canonical_integers_are_rejected() {
+        for text in &["0", "-1", "10", "-9223372036854775808"] {
+            assert!(build(&[Token::Num(text)]).is_ok(), "{}", text);
+        }
+        for text in &["", "-", "-0", "01", "+1", "1a", "9223372036854775808"] {
+            assert!(build(&[Token::Num(text)]).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn max_depth_is_enforced() {
+        let mut builder = ValueBuilder::new().with_max_depth(1);
+        builder.push(Token::List).unwrap();
+        assert!(builder.push(Token::List).is_err());
+        assert!(builder.push(Token::End).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! The byte strings of [`Value`](crate::value::Value)s, for both string
+//! values and dictionary keys.
+//!
+//! Most byte strings in bencode are short: dictionary keys, peer IDs,
+//! transaction IDs and tokens. Owned byte strings of up to 23 bytes are kept
+//! inline instead of in a separate heap allocation, which saves an allocation
+//! for each of them and a good part of the memory of decoded KRPC messages.
+
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+use core::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+/// The number of bytes of an owned byte string which are stored inline
+const INLINE_LEN: usize = 23;
+
+/// An owned or borrowed byte string which keeps short owned strings inline.
+///
+/// Byte strings dereference to `[u8]`. Whether a byte string is borrowed,
+/// inline or on the heap never affects comparisons or hashes.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::value::ByteString;
+///
+/// let borrowed = ByteString::from(&b"peer id"[..]);
+/// assert!(borrowed.is_borrowed());
+///
+/// let owned = borrowed.clone().into_owned();
+/// assert!(!owned.is_borrowed());
+/// assert_eq!(owned, borrowed);
+/// assert_eq!(&owned[..], b"peer id");
+/// ```
+#[derive(Clone)]
+pub struct ByteString<'a>(Repr<'a>);
+
+#[derive(Clone)]
+enum Repr<'a> {
+    Borrowed(&'a [u8]),
+    Inline(u8, [u8; INLINE_LEN]),
+    Heap(Vec<u8>),
+}
+
+impl<'a> ByteString<'a> {
+    /// The bytes of the string
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.0 {
+            Repr::Borrowed(bytes) => bytes,
+            Repr::Inline(len, bytes) => &bytes[..usize::from(*len)],
+            Repr::Heap(bytes) => bytes,
+        }
+    }
+
+    /// Whether the string borrows from the input it was decoded from
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.0, Repr::Borrowed(_))
+    }
+
+    /// Copy the string if it's borrowed, to give it a static lifetime
+    pub fn into_owned(self) -> ByteString<'static> {
+        match self.0 {
+            Repr::Borrowed(bytes) => ByteString::copy_from(bytes),
+            Repr::Inline(len, bytes) => ByteString(Repr::Inline(len, bytes)),
+            Repr::Heap(bytes) => ByteString(Repr::Heap(bytes)),
+        }
+    }
+
+    /// Get mutable access to the bytes, moving them into a `Vec` first unless
+    /// they already are in one
+    pub fn to_mut(&mut self) -> &mut Vec<u8> {
+        if !matches!(self.0, Repr::Heap(_)) {
+            self.0 = Repr::Heap(self.as_bytes().to_vec());
+        }
+        match &mut self.0 {
+            Repr::Heap(bytes) => bytes,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Convert the string into a `Vec`, copying it unless it's on the heap
+    pub fn into_vec(self) -> Vec<u8> {
+        match self.0 {
+            Repr::Heap(bytes) => bytes,
+            _ => self.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl ByteString<'static> {
+    /// Copy `bytes` into an owned string
+    pub fn copy_from(bytes: &[u8]) -> Self {
+        if bytes.len() <= INLINE_LEN {
+            let mut inline = [0; INLINE_LEN];
+            inline[..bytes.len()].copy_from_slice(bytes);
+            ByteString(Repr::Inline(bytes.len() as u8, inline))
+        } else {
+            ByteString(Repr::Heap(bytes.to_vec()))
+        }
+    }
+}
+
+impl<'a> Default for ByteString<'a> {
+    fn default() -> Self {
+        ByteString(Repr::Borrowed(&[]))
+    }
+}
+
+impl<'a> Deref for ByteString<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<'a> AsRef<[u8]> for ByteString<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<'a> Borrow<[u8]> for ByteString<'a> {
+    fn borrow(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<'a> From<&'a [u8]> for ByteString<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        ByteString(Repr::Borrowed(bytes))
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u8; N]> for ByteString<'a> {
+    fn from(bytes: &'a [u8; N]) -> Self {
+        ByteString::from(&bytes[..])
+    }
+}
+
+impl<'a> From<&'a str> for ByteString<'a> {
+    fn from(string: &'a str) -> Self {
+        ByteString::from(string.as_bytes())
+    }
+}
+
+impl<'a> From<Vec<u8>> for ByteString<'a> {
+    fn from(bytes: Vec<u8>) -> Self {
+        if bytes.len() <= INLINE_LEN {
+            ByteString::copy_from(&bytes)
+        } else {
+            ByteString(Repr::Heap(bytes))
+        }
+    }
+}
+
+impl<'a> From<String> for ByteString<'a> {
+    fn from(string: String) -> Self {
+        ByteString::from(string.into_bytes())
+    }
+}
+
+impl<'a> From<Cow<'a, [u8]>> for ByteString<'a> {
+    fn from(bytes: Cow<'a, [u8]>) -> Self {
+        match bytes {
+            Cow::Borrowed(bytes) => ByteString::from(bytes),
+            Cow::Owned(bytes) => ByteString::from(bytes),
+        }
+    }
+}
+
+impl<'a> From<ByteString<'a>> for Cow<'a, [u8]> {
+    fn from(bytes: ByteString<'a>) -> Self {
+        match bytes.0 {
+            Repr::Borrowed(bytes) => Cow::Borrowed(bytes),
+            _ => Cow::Owned(bytes.into_vec()),
+        }
+    }
+}
+
+impl<'a> From<ByteString<'a>> for Vec<u8> {
+    fn from(bytes: ByteString<'a>) -> Self {
+        bytes.into_vec()
+    }
+}
+
+impl<'a, 'b> PartialEq<ByteString<'b>> for ByteString<'a> {
+    fn eq(&self, other: &ByteString<'b>) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl<'a> Eq for ByteString<'a> {}
+
+impl<'a> PartialEq<[u8]> for ByteString<'a> {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_bytes() == other
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b [u8]> for ByteString<'a> {
+    fn eq(&self, other: &&'b [u8]) -> bool {
+        self.as_bytes() == *other
+    }
+}
+
+impl<'a> PartialOrd for ByteString<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for ByteString<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl<'a> Hash for ByteString<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl<'a> fmt::Debug for ByteString<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_bytes(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn short_strings_are_inline() {
+        let short = ByteString::from(vec![1; INLINE_LEN]);
+        assert!(matches!(short.0, Repr::Inline(23, _)));
+        assert_eq!(short, &[1; INLINE_LEN][..]);
+
+        let long = ByteString::from(vec![1; INLINE_LEN + 1]);
+        assert!(matches!(long.0, Repr::Heap(_)));
+
+        let copied = ByteString::from(&b"abc"[..]).into_owned();
+        assert!(matches!(copied.0, Repr::Inline(3, _)));
+        assert_eq!(copied.into_vec(), b"abc");
+    }
+
+    #[test]
+    fn representations_compare_equal() {
+        let borrowed = ByteString::from(&b"abc"[..]);
+        let inline = borrowed.clone().into_owned();
+        assert_eq!(borrowed, inline);
+        assert_eq!(borrowed.cmp(&inline), Ordering::Equal);
+
+        let (shorter, longer) = (ByteString::from(&b"ab"[..]), ByteString::copy_from(b"b"));
+        assert!(shorter < longer);
+    }
+}
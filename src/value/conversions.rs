@@ -0,0 +1,269 @@
+//! Conversions between [`Value`] and the primitive types it can represent.
+//!
+//! Constructing a value from a primitive never fails. Converting a value back into a
+//! primitive fails with an [`UnexpectedToken`] error if the variant doesn't match, or an
+//! [`IntegerOverflow`] error if the content doesn't fit into the requested type.
+//!
+//! [`UnexpectedToken`]: crate::decoding::ErrorKind::UnexpectedToken
+//! [`IntegerOverflow`]: crate::decoding::ErrorKind::IntegerOverflow
+
+use alloc::{borrow::Cow, collections::BTreeMap, string::String, vec::Vec};
+use core::convert::{TryFrom, TryInto};
+
+use crate::{
+    decoding::Error,
+    value::{ByteString, Value},
+};
+
+impl<'a> Value<'a> {
+    /// The name of the bencode token this value starts with, as used in
+    /// [`Error::unexpected_token`].
+    pub(crate) fn token_name(&self) -> &'static str {
+        match self {
+            Value::Bytes(_) => "String",
+            Value::Dict(_) => "Dict",
+            Value::Integer(_) => "Num",
+            Value::List(_) => "List",
+        }
+    }
+}
+
+macro_rules! impl_from_integer {
+    ($($type:ty)*) => {$(
+        impl<'a> From<$type> for Value<'a> {
+            fn from(integer: $type) -> Self {
+                Value::Integer(i64::from(integer))
+            }
+        }
+    )*}
+}
+
+impl_from_integer!(u8 u16 u32 i8 i16 i32 i64);
+
+impl<'a> From<bool> for Value<'a> {
+    fn from(flag: bool) -> Self {
+        Value::Integer(if flag { 1 } else { 0 })
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(string: &'a str) -> Self {
+        Value::Bytes(ByteString::from(string.as_bytes()))
+    }
+}
+
+impl<'a> From<String> for Value<'a> {
+    fn from(string: String) -> Self {
+        Value::Bytes(ByteString::from(string.into_bytes()))
+    }
+}
+
+impl<'a> From<&'a [u8]> for Value<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Value::Bytes(ByteString::from(bytes))
+    }
+}
+
+impl<'a> From<Vec<u8>> for Value<'a> {
+    fn from(bytes: Vec<u8>) -> Self {
+        Value::Bytes(ByteString::from(bytes))
+    }
+}
+
+impl<'a> From<ByteString<'a>> for Value<'a> {
+    fn from(bytes: ByteString<'a>) -> Self {
+        Value::Bytes(bytes)
+    }
+}
+
+impl<'a> From<Cow<'a, [u8]>> for Value<'a> {
+    fn from(bytes: Cow<'a, [u8]>) -> Self {
+        Value::Bytes(ByteString::from(bytes))
+    }
+}
+
+impl<'a> From<Vec<Value<'a>>> for Value<'a> {
+    fn from(list: Vec<Value<'a>>) -> Self {
+        Value::List(list)
+    }
+}
+
+impl<'a> From<BTreeMap<ByteString<'a>, Value<'a>>> for Value<'a> {
+    fn from(dict: BTreeMap<ByteString<'a>, Value<'a>>) -> Self {
+        Value::Dict(dict)
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for i64 {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Integer(integer) => Ok(integer),
+            other => Err(Error::unexpected_token("Num", other.token_name())),
+        }
+    }
+}
+
+macro_rules! impl_try_from_value_for_integer {
+    ($($type:ty)*) => {$(
+        impl<'a> TryFrom<Value<'a>> for $type {
+            type Error = Error;
+
+            fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+                let integer = i64::try_from(value)?;
+                integer
+                    .try_into()
+                    .map_err(|_| Error::integer_overflow(integer))
+            }
+        }
+    )*}
+}
+
+impl_try_from_value_for_integer!(u8 u16 u32 u64 usize i8 i16 i32 isize);
+
+impl<'a> TryFrom<Value<'a>> for ByteString<'a> {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bytes(bytes) => Ok(bytes),
+            other => Err(Error::unexpected_token("String", other.token_name())),
+        }
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for Cow<'a, [u8]> {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        ByteString::try_from(value).map(Cow::from)
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for Vec<u8> {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        ByteString::try_from(value).map(ByteString::into_vec)
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for String {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        let bytes = Vec::try_from(value)?;
+        String::from_utf8(bytes).map_err(Error::malformed_content)
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for Vec<Value<'a>> {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        match value {
+            Value::List(list) => Ok(list),
+            other => Err(Error::unexpected_token("List", other.token_name())),
+        }
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for BTreeMap<ByteString<'a>, Value<'a>> {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Dict(dict) => Ok(dict),
+            other => Err(Error::unexpected_token("Dict", other.token_name())),
+        }
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for bool {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        match i64::try_from(value)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(Error::malformed_content(InvalidBool(other))),
+        }
+    }
+}
+
+/// Raised when converting an integer other than `0` or `1` into a `bool`.
+#[derive(Debug, Clone, Copy)]
+struct InvalidBool(i64);
+
+impl core::fmt::Display for InvalidBool {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "invalid integer value for bool: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidBool {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    #[test]
+    fn primitives_convert_into_values() {
+        assert_eq!(Value::from(42), Value::Integer(42));
+        assert_eq!(Value::from(7u8), Value::Integer(7));
+        assert_eq!(Value::from(true), Value::Integer(1));
+        assert_eq!(Value::from("foo"), Value::Bytes(ByteString::from(b"foo")));
+        assert_eq!(
+            Value::from(String::from("foo")),
+            Value::Bytes(ByteString::from(b"foo".to_vec()))
+        );
+        assert_eq!(
+            Value::from(vec![1u8, 2, 3]),
+            Value::Bytes(ByteString::from(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            Value::from(vec![Value::from(1), Value::from("a")]),
+            Value::List(vec![
+                Value::Integer(1),
+                Value::Bytes(ByteString::from(b"a"))
+            ])
+        );
+    }
+
+    #[test]
+    fn values_convert_into_primitives() {
+        assert_eq!(i64::try_from(Value::Integer(-3)).unwrap(), -3);
+        assert_eq!(u16::try_from(Value::Integer(300)).unwrap(), 300);
+        assert!(!bool::try_from(Value::Integer(0)).unwrap());
+        assert_eq!(String::try_from(Value::from("foo")).unwrap(), "foo");
+        assert_eq!(Vec::<u8>::try_from(Value::from("foo")).unwrap(), b"foo");
+        assert_eq!(
+            Vec::<Value>::try_from(Value::List(vec![Value::Integer(1)])).unwrap(),
+            vec![Value::Integer(1)]
+        );
+    }
+
+    #[test]
+    fn mismatched_variants_should_fail() {
+        assert!(i64::try_from(Value::from("foo")).is_err());
+        assert!(String::try_from(Value::Integer(1)).is_err());
+        assert!(Vec::<Value>::try_from(Value::Integer(1)).is_err());
+        assert!(BTreeMap::try_from(Value::List(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn out_of_range_integers_should_fail() {
+        assert!(u8::try_from(Value::Integer(256)).is_err());
+        assert!(u64::try_from(Value::Integer(-1)).is_err());
+        assert!(bool::try_from(Value::Integer(2)).is_err());
+    }
+
+    #[test]
+    fn invalid_utf8_should_fail() {
+        assert!(String::try_from(Value::from(vec![0xffu8])).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Interning of dict keys while decoding [`Value`]s.
+//!
+//! Protocol messages tend to reuse a small, fixed vocabulary of dict keys. Decoding
+//! them with [`FromBencode`] copies every key of every dict.
+//! A [`KeyInterner`] holds a set of known keys with `'static` lifetime, and
+//! [`Value::from_bencode_interned`] borrows those instead of copying them. Keys
+//! which aren't known are copied as usual.
+//!
+//! ```
+//! use bendy::value::{KeyInterner, Value};
+//!
+//! let mut interner = KeyInterner::krpc();
+//! let value = Value::from_bencode_interned(b"d1:t2:aa1:y1:q1:z0:e", &mut interner).unwrap();
+//!
+//! assert_eq!(interner.stats().hits(), 2);
+//! assert_eq!(interner.stats().misses(), 1);
+//! ```
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+use crate::{
+    decoding::{Decoder, Error, FromBencode, Object},
+    state_tracker::StructureError,
+    value::{
+        tree::{self, TreeBuilder},
+        ByteString, Value,
+    },
+};
+
+/// Keys used by the KRPC protocol of the BitTorrent DHT (BEP 5).
+const KRPC_KEYS: &[&[u8]] = &[
+    b"a",
+    b"e",
+    b"id",
+    b"implied_port",
+    b"info_hash",
+    b"ip",
+    b"nodes",
+    b"nodes6",
+    b"port",
+    b"q",
+    b"r",
+    b"t",
+    b"target",
+    b"token",
+    b"v",
+    b"values",
+    b"want",
+    b"y",
+];
+
+/// Counters describing how often a [`KeyInterner`] could reuse a known key.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct InternStats {
+    hits: u64,
+    misses: u64,
+}
+
+impl InternStats {
+    /// The number of keys which were found in the interner.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// The number of keys which weren't found and had to be copied.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// The fraction of keys which were found in the interner, between `0.0` and
+    /// `1.0`. Returns `0.0` if no keys were looked up yet.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// A set of known dict keys, shared by all values decoded using it.
+#[derive(Clone, Debug, Default)]
+pub struct KeyInterner {
+    keys: BTreeSet<&'static [u8]>,
+    stats: InternStats,
+}
+
+impl KeyInterner {
+    /// Create an interner without any known keys.
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Create an interner which knows the keys used in KRPC messages.
+    pub fn krpc() -> Self {
+        Self::new().with_keys(KRPC_KEYS.iter().copied())
+    }
+
+    /// Add known keys to the interner.
+    #[must_use]
+    pub fn with_keys(mut self, keys: impl IntoIterator<Item = &'static [u8]>) -> Self {
+        self.keys.extend(keys);
+        self
+    }
+
+    /// The hit and miss counters accumulated so far.
+    pub fn stats(&self) -> InternStats {
+        self.stats
+    }
+
+    /// Reset the hit and miss counters.
+    pub fn reset_stats(&mut self) {
+        self.stats = InternStats::default();
+    }
+
+    fn intern(&mut self, key: &[u8]) -> ByteString<'static> {
+        match self.keys.get(key) {
+            Some(known) => {
+                self.stats.hits += 1;
+                ByteString::from(*known)
+            },
+            None => {
+                self.stats.misses += 1;
+                ByteString::copy_from(key)
+            },
+        }
+    }
+}
+
+impl Value<'static> {
+    /// Decode a value from the start of `bytes` like
+    /// [`from_bencode`](FromBencode::from_bencode), but take dict keys from
+    /// `interner` instead of copying them where possible.
+    pub fn from_bencode_interned(bytes: &[u8], interner: &mut KeyInterner) -> Result<Self, Error> {
+        let mut decoder = Decoder::new(bytes).with_max_depth(Self::EXPECTED_RECURSION_DEPTH);
+        let object = decoder.next_object()?;
+
+        match object {
+            Some(object) => Self::decode_interned(object, interner),
+            None => Err(Error::from(StructureError::UnexpectedEof)),
+        }
+    }
+
+    /// Convert an already decoded object like
+    /// [`decode_bencode_object`](FromBencode::decode_bencode_object), but take
+    /// dict keys from `interner` instead of copying them where possible.
+    pub fn decode_interned(object: Object, interner: &mut KeyInterner) -> Result<Self, Error> {
+        tree::build(interner, object)
+    }
+}
+
+impl<'ser> TreeBuilder<'ser> for KeyInterner {
+    type Value = Value<'static>;
+    type List = Vec<Value<'static>>;
+    type Dict = BTreeMap<ByteString<'static>, Value<'static>>;
+
+    fn bytes(&mut self, bytes: &'ser [u8]) -> Self::Value {
+        Value::Bytes(ByteString::copy_from(bytes))
+    }
+
+    fn integer(&mut self, value: i64) -> Self::Value {
+        Value::Integer(value)
+    }
+
+    fn list(&mut self) -> Self::List {
+        Vec::new()
+    }
+
+    fn dict(&mut self) -> Self::Dict {
+        BTreeMap::new()
+    }
+
+    fn push(&mut self, list: &mut Self::List, item: Self::Value) {
+        list.push(item);
+    }
+
+    fn insert(&mut self, dict: &mut Self::Dict, key: &'ser [u8], value: Self::Value) {
+        dict.insert(self.intern(key), value);
+    }
+
+    fn finish_list(&mut self, list: Self::List) -> Self::Value {
+        Value::List(list)
+    }
+
+    fn finish_dict(&mut self, dict: Self::Dict) -> Self::Value {
+        Value::Dict(dict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_keys_are_borrowed() {
+        let mut interner = KeyInterner::new().with_keys(core::iter::once(&b"y"[..]));
+        let message = b"ld1:y1:q1:z1:qed1:y1:ree";
+        let value = Value::from_bencode_interned(message, &mut interner).unwrap();
+
+        assert_eq!(value, Value::from_bencode(message).unwrap());
+        match &value {
+            Value::List(list) => match &list[0] {
+                Value::Dict(dict) => {
+                    let mut keys = dict.keys();
+                    assert!(keys
+                        .next()
+                        .map_or(false, |key| key.is_borrowed() && key[..] == b"y"[..]));
+                    assert!(keys.next().map_or(false, |key| !key.is_borrowed()));
+                },
+                other => panic!("expected a dict, got {:?}", other),
+            },
+            other => panic!("expected a list, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stats_count_lookups() {
+        let mut interner = KeyInterner::krpc();
+        assert_eq!(interner.stats().hit_rate(), 0.0);
+
+        Value::from_bencode_interned(b"d1:ad2:id0:e1:q4:ping1:x0:1:y1:qe", &mut interner).unwrap();
+        assert_eq!(interner.stats().hits(), 4);
+        assert_eq!(interner.stats().misses(), 1);
+        assert!((interner.stats().hit_rate() - 0.8).abs() < 1e-9);
+
+        interner.reset_stats();
+        assert_eq!(interner.stats(), InternStats::default());
+    }
+
+    #[test]
+    fn invalid_input_should_fail() {
+        let mut interner = KeyInterner::krpc();
+        assert!(Value::from_bencode_interned(b"", &mut interner).is_err());
+        assert!(Value::from_bencode_interned(b"d1:y0:1:a0:e", &mut interner).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Depth-first iteration over value trees.
+
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+use alloc::{format, vec, vec::Vec};
+
+use crate::value::Value;
+
+/// Visits a value and all values nested in it in depth-first order, with list
+/// items in order and dict entries in ascending key order.
+struct Walk<'v, 'a> {
+    stack: Vec<&'v Value<'a>>,
+}
+
+impl<'v, 'a> Iterator for Walk<'v, 'a> {
+    type Item = &'v Value<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.stack.pop()?;
+        match value {
+            Value::Dict(dict) => self.stack.extend(dict.values().rev()),
+            Value::List(list) => self.stack.extend(list.iter().rev()),
+            Value::Bytes(_) | Value::Integer(_) => {},
+        }
+        Some(value)
+    }
+}
+
+/// An iterator over a value and all values nested in it, together with their
+/// paths. Returned by [`Value::iter_with_paths`].
+pub struct WithPaths<'v, 'a> {
+    stack: Vec<(String, &'v Value<'a>)>,
+}
+
+impl<'v, 'a> Iterator for WithPaths<'v, 'a> {
+    type Item = (String, &'v Value<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, value) = self.stack.pop()?;
+        match value {
+            Value::Dict(dict) => self.stack.extend(dict.iter().rev().map(|(key, value)| {
+                let key = String::from_utf8_lossy(key);
+                let key = key.replace('~', "~0").replace('/', "~1");
+                (format!("{}/{}", path, key), value)
+            })),
+            Value::List(list) => self.stack.extend(
+                list.iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(index, value)| (format!("{}/{}", path, index), value)),
+            ),
+            Value::Bytes(_) | Value::Integer(_) => {},
+        }
+        Some((path, value))
+    }
+}
+
+impl<'a> Value<'a> {
+    fn walk(&self) -> Walk<'_, 'a> {
+        Walk { stack: vec![self] }
+    }
+
+    /// Iterate over the contents of every byte string in this value, including
+    /// the value itself, in depth-first order. Dict keys are not included.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let torrent = Value::parse_pretty(
+    ///     r#"{"announce": "http://a", "announce-list": [["http://b"], ["udp://c"]]}"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let urls: Vec<_> = torrent.iter_strings().collect();
+    /// assert_eq!(urls, vec![&b"http://a"[..], b"http://b", b"udp://c"]);
+    /// ```
+    pub fn iter_strings(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.walk().filter_map(|value| match value {
+            Value::Bytes(bytes) => Some(&bytes[..]),
+            _ => None,
+        })
+    }
+
+    /// Iterate over every integer in this value, including the value itself, in
+    /// depth-first order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let info = Value::parse_pretty(
+    ///     r#"{"files": [{"length": 12, "path": ["a"]}, {"length": 30, "path": ["b"]}]}"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(info.iter_ints().sum::<i64>(), 42);
+    /// ```
+    pub fn iter_ints(&self) -> impl Iterator<Item = i64> + '_ {
+        self.walk().filter_map(|value| match value {
+            Value::Integer(integer) => Some(*integer),
+            _ => None,
+        })
+    }
+
+    /// Iterate over this value and every value nested in it, in depth-first
+    /// order, together with their location as a [JSON pointer](Value::pointer).
+    ///
+    /// The value itself comes first, with an empty path. List items follow in
+    /// order and dict entries in ascending key order. Invalid UTF-8 in dict keys
+    /// is replaced with `U+FFFD`, so the paths of such entries can't be resolved
+    /// again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let value = Value::parse_pretty(r#"{"a/b": [1, 2]}"#).unwrap();
+    /// let paths: Vec<_> = value.iter_with_paths().map(|(path, _)| path).collect();
+    ///
+    /// assert_eq!(paths, vec!["", "/a~1b", "/a~1b/0", "/a~1b/1"]);
+    /// ```
+    pub fn iter_with_paths(&self) -> WithPaths<'_, 'a> {
+        WithPaths {
+            stack: vec![(String::new(), self)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::value::ByteString;
+
+    #[test]
+    fn walk_is_depth_first() {
+        let value = Value::parse_pretty(r#"{"b": [1, [2]], "a": 0, "c": 3}"#).unwrap();
+        assert_eq!(value.iter_ints().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(Value::Integer(7).iter_ints().collect::<Vec<_>>(), vec![7]);
+        assert_eq!(value.iter_strings().count(), 0);
+    }
+
+    #[test]
+    fn paths_resolve_to_their_values() {
+        let value =
+            Value::parse_pretty(r#"{"info": {"files": [{"path": ["x", "y"]}], "~": <>}}"#).unwrap();
+
+        let mut count = 0;
+        for (path, found) in value.iter_with_paths() {
+            assert_eq!(value.pointer(&path), Some(found), "{}", path);
+            count += 1;
+        }
+        assert_eq!(count, 8);
+    }
+
+    #[test]
+    fn non_utf8_keys_are_replaced() {
+        let mut dict = alloc::collections::BTreeMap::new();
+        dict.insert(ByteString::from(&[0xff][..]), Value::Integer(1));
+        let paths: Vec<_> = Value::Dict(dict)
+            .iter_with_paths()
+            .map(|(path, _)| path)
+            .collect();
+
+        assert_eq!(paths, vec!["", "/\u{fffd}"]);
+    }
+}
@@ -0,0 +1,137 @@
+//! Conversions between [`Value`] and [`serde_json::Value`].
+//!
+//! JSON has no byte strings, so converting a value into JSON needs a policy for
+//! byte strings (including dict keys) which are not valid UTF-8, see
+//! [`BytesAsText`]. The `TryFrom` implementation uses [`BytesAsText::Strict`].
+//!
+//! Converting JSON into a value maps strings to their UTF-8 bytes, booleans to
+//! `0` and `1` and objects to dicts. `null` and numbers which don't fit into an
+//! `i64` cannot be represented and cause an error.
+
+use alloc::collections::BTreeMap;
+use core::convert::{TryFrom, TryInto};
+
+use serde_json::{Map, Number, Value as JsonValue};
+
+use crate::{
+    decoding::Error,
+    value::{ByteString, BytesAsText, Value},
+};
+
+impl<'a> Value<'a> {
+    /// Convert this value into JSON, representing byte strings according to
+    /// `bytes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::{BytesAsText, Value};
+    ///
+    /// let value = Value::parse_pretty(r#"{"name": "foo", "hash": <ff00>}"#).unwrap();
+    ///
+    /// assert!(value.to_json(BytesAsText::Strict).is_err());
+    /// assert_eq!(
+    ///     value.to_json(BytesAsText::Hex).unwrap(),
+    ///     serde_json::json!({"6e616d65": "666f6f", "68617368": "ff00"})
+    /// );
+    /// ```
+    pub fn to_json(&self, bytes: BytesAsText) -> Result<JsonValue, Error> {
+        match self {
+            Value::Bytes(string) => bytes.convert(string).map(JsonValue::String),
+            Value::Dict(dict) => dict
+                .iter()
+                .map(|(key, value)| Ok((bytes.convert(key)?, value.to_json(bytes)?)))
+                .collect::<Result<Map<_, _>, Error>>()
+                .map(JsonValue::Object),
+            Value::Integer(integer) => Ok(JsonValue::Number(Number::from(*integer))),
+            Value::List(list) => list
+                .iter()
+                .map(|value| value.to_json(bytes))
+                .collect::<Result<_, _>>()
+                .map(JsonValue::Array),
+        }
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for JsonValue {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        value.to_json(BytesAsText::Strict)
+    }
+}
+
+impl TryFrom<JsonValue> for Value<'static> {
+    type Error = Error;
+
+    fn try_from(json: JsonValue) -> Result<Self, Self::Error> {
+        match json {
+            JsonValue::Null => Err(Error::unexpected_token("any value", "Null")),
+            JsonValue::Bool(flag) => Ok(Value::from(flag)),
+            JsonValue::Number(number) => match (number.as_i64(), number.as_u64()) {
+                (Some(integer), _) => Ok(Value::Integer(integer)),
+                (None, Some(integer)) => integer
+                    .try_into()
+                    .map(Value::Integer)
+                    .map_err(Error::malformed_content),
+                (None, None) => Err(Error::unexpected_token("Num", "Float")),
+            },
+            JsonValue::String(string) => Ok(Value::from(string)),
+            JsonValue::Array(array) => array
+                .into_iter()
+                .map(Value::try_from)
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+            JsonValue::Object(object) => object
+                .into_iter()
+                .map(|(key, value)| {
+                    Ok((ByteString::from(key.into_bytes()), Value::try_from(value)?))
+                })
+                .collect::<Result<BTreeMap<_, _>, Error>>()
+                .map(Value::Dict),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn json_round_trips() {
+        let json = json!({"a": [1, -2, "three"], "b": {"c": {}}, "d": []});
+        let value = Value::try_from(json.clone()).unwrap();
+
+        assert_eq!(
+            value,
+            Value::parse_pretty(r#"{"a": [1, -2, "three"], "b": {"c": {}}, "d": []}"#).unwrap()
+        );
+        assert_eq!(JsonValue::try_from(value).unwrap(), json);
+    }
+
+    #[test]
+    fn unrepresentable_json_fails() {
+        assert_eq!(Value::try_from(json!(true)).unwrap(), Value::Integer(1));
+        assert!(Value::try_from(json!(null)).is_err());
+        assert!(Value::try_from(json!(1.5)).is_err());
+        assert!(Value::try_from(json!(u64::MAX)).is_err());
+        assert!(Value::try_from(json!([1, null])).is_err());
+    }
+
+    #[test]
+    fn byte_strings_follow_the_policy() {
+        let value = Value::parse_pretty(r#"{<ff>: "a\xffb"}"#).unwrap();
+
+        assert!(value.to_json(BytesAsText::Strict).is_err());
+        assert_eq!(
+            value.to_json(BytesAsText::Lossy).unwrap(),
+            json!({"\u{fffd}": "a\u{fffd}b"})
+        );
+        assert_eq!(
+            value.to_json(BytesAsText::Hex).unwrap(),
+            json!({"ff": "61ff62"})
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! The `bdict!` and `blist!` constructor macros.
+
+use crate::value::ByteString;
+
+/// Copy a dict key used in [`bdict!`](crate::bdict).
+#[doc(hidden)]
+pub fn __dict_key<K: AsRef<[u8]> + ?Sized>(key: &K) -> ByteString<'static> {
+    ByteString::from(key.as_ref().to_vec())
+}
+
+/// Construct a [`Value::Dict`](crate::value::Value::Dict) from `key => value`
+/// pairs.
+///
+/// Keys can be anything that implements `AsRef<[u8]>` and are copied into the dict.
+/// Values can be anything that converts into a [`Value`](crate::value::Value),
+/// including other `bdict!` and `blist!` invocations. If a key occurs more than
+/// once, the last value wins.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{bdict, blist, value::Value};
+///
+/// let length = 305_135_616;
+/// let info = bdict! {
+///     "length" => length,
+///     "name" => "debian.iso",
+///     "files" => blist![],
+/// };
+///
+/// assert_eq!(
+///     info,
+///     Value::parse_pretty(r#"{"files": [], "length": 305135616, "name": "debian.iso"}"#).unwrap()
+/// );
+/// ```
+#[macro_export]
+macro_rules! bdict {
+    () => {
+        $crate::value::Value::Dict(::core::default::Default::default())
+    };
+    ($($key:expr => $value:expr),+ $(,)?) => {
+        $crate::value::Value::Dict(::core::iter::Iterator::collect(
+            ::core::iter::IntoIterator::into_iter([$((
+                $crate::value::__dict_key(&$key),
+                $crate::value::Value::from($value),
+            )),+]),
+        ))
+    };
+}
+
+/// Construct a [`Value::List`](crate::value::Value::List) from its items.
+///
+/// Items can be anything that converts into a [`Value`](crate::value::Value),
+/// including other `bdict!` and `blist!` invocations.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{blist, value::Value};
+///
+/// let tier = "udp://tracker.example:1337";
+/// let value = blist![blist![tier], 42];
+///
+/// assert_eq!(value, Value::parse_pretty(r#"[["udp://tracker.example:1337"], 42]"#).unwrap());
+/// ```
+#[macro_export]
+macro_rules! blist {
+    () => {
+        $crate::value::Value::List(::core::default::Default::default())
+    };
+    ($($item:expr),+ $(,)?) => {
+        $crate::value::Value::List(::core::iter::Iterator::collect(
+            ::core::iter::IntoIterator::into_iter([$($crate::value::Value::from($item)),+]),
+        ))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::value::Value;
+
+    #[test]
+    fn macros_build_nested_values() {
+        let value = crate::bdict! {
+            "a" => 1,
+            b"b".to_vec() => crate::blist![crate::bdict! {}, -1, "x"],
+        };
+
+        let mut expected = crate::bdict! {};
+        expected.set("/a", 1.into()).unwrap();
+        expected
+            .set("/b", Value::parse_pretty(r#"[{}, -1, "x"]"#).unwrap())
+            .unwrap();
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn later_keys_win() {
+        assert_eq!(
+            crate::bdict! { "a" => 1, "a" => 2 },
+            Value::parse_pretty(r#"{"a": 2}"#).unwrap()
+        );
+        assert_eq!(crate::blist![], Value::List(Default::default()));
+    }
+}
@@ -0,0 +1,100 @@
+//! Recursive merging of one value into another.
+
+use crate::value::Value;
+
+/// How [`Value::merge`] combines two lists found at the same position.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MergePolicy {
+    /// Replace the existing list with the other one.
+    Replace,
+    /// Append the items of the other list to the existing one.
+    Append,
+}
+
+impl<'a> Value<'a> {
+    /// Merge `other` into this value.
+    ///
+    /// If both values are dicts, every key of `other` is merged recursively into
+    /// the entry with the same key, or inserted if there is none. If both values are
+    /// lists, they are combined according to `policy`. In every other case this
+    /// value is replaced by a copy of `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::{MergePolicy, Value};
+    ///
+    /// let mut template =
+    ///     Value::parse_pretty(r#"{"announce-list": [["a"]], "info": {"private": 0}}"#).unwrap();
+    /// let overrides =
+    ///     Value::parse_pretty(r#"{"announce-list": [["b"]], "info": {"private": 1}}"#).unwrap();
+    ///
+    /// template.merge(&overrides, MergePolicy::Append);
+    /// assert_eq!(
+    ///     template,
+    ///     Value::parse_pretty(r#"{"announce-list": [["a"], ["b"]], "info": {"private": 1}}"#)
+    ///         .unwrap()
+    /// );
+    /// ```
+    pub fn merge(&mut self, other: &Value<'a>, policy: MergePolicy) {
+        match (self, other) {
+            (Value::Dict(dict), Value::Dict(other)) => {
+                for (key, value) in other {
+                    match dict.get_mut(key) {
+                        Some(existing) => existing.merge(value, policy),
+                        None => {
+                            dict.insert(key.clone(), value.clone());
+                        },
+                    }
+                }
+            },
+            (Value::List(list), Value::List(other)) if policy == MergePolicy::Append => {
+                list.extend(other.iter().cloned());
+            },
+            (this, other) => *this = other.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Value<'static> {
+        Value::parse_pretty(text).unwrap()
+    }
+
+    #[test]
+    fn dicts_merge_recursively() {
+        let mut value = parse(r#"{"a": 1, "b": {"c": 2, "d": 3}}"#);
+        value.merge(
+            &parse(r#"{"b": {"d": 4, "e": 5}, "f": 6}"#),
+            MergePolicy::Replace,
+        );
+        assert_eq!(
+            value,
+            parse(r#"{"a": 1, "b": {"c": 2, "d": 4, "e": 5}, "f": 6}"#)
+        );
+    }
+
+    #[test]
+    fn lists_follow_the_policy() {
+        let mut value = parse(r#"{"l": [1, 2]}"#);
+        value.merge(&parse(r#"{"l": [3]}"#), MergePolicy::Replace);
+        assert_eq!(value, parse(r#"{"l": [3]}"#));
+
+        value.merge(&parse(r#"{"l": [4]}"#), MergePolicy::Append);
+        assert_eq!(value, parse(r#"{"l": [3, 4]}"#));
+    }
+
+    #[test]
+    fn mismatched_variants_are_replaced() {
+        let mut value = parse(r#"{"a": [1], "b": "x"}"#);
+        value.merge(&parse(r#"{"a": {"c": 1}, "b": 2}"#), MergePolicy::Append);
+        assert_eq!(value, parse(r#"{"a": {"c": 1}, "b": 2}"#));
+
+        let mut value = parse("[1]");
+        value.merge(&parse(r#"{"a": 1}"#), MergePolicy::Append);
+        assert_eq!(value, parse(r#"{"a": 1}"#));
+    }
+}
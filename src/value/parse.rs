@@ -0,0 +1,432 @@
+//! Parser for the textual representation produced by [`Value::pretty`].
+//!
+//! The grammar accepted by the parser is:
+//!
+//! ```text
+//! value   = integer | string | hex | list | dict
+//! integer = ["-"] digit+
+//! string  = '"' (character | escape)* '"'
+//! escape  = '\"' | '\\' | '\n' | '\r' | '\t' | '\x' hexdigit hexdigit
+//! hex     = "<" (hexdigit hexdigit)* ">"
+//! list    = "[" [value ("," value)* [","]] "]"
+//! dict    = "{" [pair ("," pair)* [","]] "}"
+//! pair    = (string | hex) ":" value
+//! ```
+//!
+//! Whitespace is allowed between any two tokens. Dictionary keys may appear in any
+//! order, but must be unique. Output that was truncated by one of the limits in
+//! [`PrettyOptions`](crate::value::PrettyOptions) is rejected, and so are lists
+//! and dicts nested more than 2048 levels deep, like bencode decoded into a
+//! [`Value`].
+
+#[cfg(not(feature = "std"))]
+use alloc::string::{String, ToString};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{
+    fmt::{self, Display, Formatter},
+    str::{self, FromStr},
+};
+
+use crate::{
+    decoding::FromBencode,
+    value::{ByteString, Value},
+};
+
+/// How deeply lists and dicts may be nested, the same as for decoding
+const MAX_DEPTH: usize = <Value as FromBencode>::EXPECTED_RECURSION_DEPTH;
+
+/// An error encountered while parsing the textual representation of a value.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    message: String,
+    offset: usize,
+}
+
+impl ParseError {
+    fn new(message: impl ToString, offset: usize) -> Self {
+        ParseError {
+            message: message.to_string(),
+            offset,
+        }
+    }
+
+    /// The byte offset in the input at which the error was detected.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+impl<'a> Value<'a> {
+    /// Parse a value from the format produced by [`Value::pretty`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::{PrettyOptions, Value};
+    ///
+    /// let value = Value::parse_pretty(r#"{"name": "foo", "sizes": [1, 2, <ff00>]}"#).unwrap();
+    ///
+    /// let text = value.pretty(PrettyOptions::new()).to_string();
+    /// assert_eq!(Value::parse_pretty(&text).unwrap(), value);
+    /// ```
+    pub fn parse_pretty(text: &str) -> Result<Value<'static>, ParseError> {
+        let mut parser = Parser {
+            input: text.as_bytes(),
+            offset: 0,
+        };
+
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.offset != parser.input.len() {
+            return Err(parser.error("unexpected trailing characters"));
+        }
+
+        Ok(value)
+    }
+}
+
+impl<'a> FromStr for Value<'a> {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Value::parse_pretty(text)
+    }
+}
+
+struct Parser<'i> {
+    input: &'i [u8],
+    offset: usize,
+}
+
+impl<'i> Parser<'i> {
+    fn error(&self, message: impl ToString) -> ParseError {
+        ParseError::new(message, self.offset)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.offset += 1;
+            Ok(())
+        } else {
+            Err(self.error(format_args!("expected {:?}", expected as char)))
+        }
+    }
+
+    /// Parse a value, keeping the unfinished lists and dicts it contains on a
+    /// stack instead of recursing into them
+    fn value(&mut self) -> Result<Value<'static>, ParseError> {
+        let mut stack: Vec<Frame> = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            let mut value = match self.peek() {
+                Some(b'"') => Value::Bytes(ByteString::from(self.string()?)),
+                Some(b'<') => Value::Bytes(ByteString::from(self.hex()?)),
+                Some(b'[') | Some(b'{') => {
+                    self.open(&mut stack)?;
+                    match self.close(&mut stack)? {
+                        Some(empty) => empty,
+                        None => continue,
+                    }
+                },
+                Some(b'-') | Some(b'0'..=b'9') => self.integer()?,
+                Some(b'.') => return Err(self.error("truncated output cannot be parsed")),
+                Some(_) => return Err(self.error("expected a value")),
+                None => return Err(self.error("unexpected end of input")),
+            };
+
+            // Add the finished value to its container, and finish every
+            // container that ends after it
+            loop {
+                self.skip_whitespace();
+                if self.input[self.offset..].starts_with(b"...") {
+                    return Err(self.error("truncated output cannot be parsed"));
+                }
+
+                let terminator = match stack.last_mut() {
+                    None => return Ok(value),
+                    Some(Frame::List(list)) => {
+                        list.push(value);
+                        b']'
+                    },
+                    Some(Frame::Dict(dict, key, key_offset)) => {
+                        let key = ByteString::from(core::mem::take(key));
+                        if dict.insert(key, value).is_some() {
+                            return Err(ParseError::new("duplicate key", *key_offset));
+                        }
+                        b'}'
+                    },
+                };
+
+                match self.peek() {
+                    Some(b',') => self.offset += 1,
+                    Some(byte) if byte == terminator => {},
+                    _ => {
+                        return Err(
+                            self.error(format_args!("expected ',' or {:?}", terminator as char))
+                        )
+                    },
+                }
+                value = match self.close(&mut stack)? {
+                    Some(finished) => finished,
+                    None => break,
+                };
+            }
+        }
+    }
+
+    /// Consume the opening bracket of a list or dict, and push the container
+    /// onto the stack
+    fn open(&mut self, stack: &mut Vec<Frame>) -> Result<(), ParseError> {
+        if stack.len() == MAX_DEPTH {
+            return Err(self.error(format_args!("nested more than {} levels deep", MAX_DEPTH)));
+        }
+        let frame = match self.peek() {
+            Some(b'[') => Frame::List(Vec::new()),
+            _ => Frame::Dict(BTreeMap::new(), Vec::new(), 0),
+        };
+        self.offset += 1;
+        stack.push(frame);
+        Ok(())
+    }
+
+    /// Consume the terminator of the innermost container and return it if the
+    /// terminator comes next, or otherwise consume the key of its next value
+    /// if it's a dict. A trailing comma before the terminator is permitted.
+    fn close(&mut self, stack: &mut Vec<Frame>) -> Result<Option<Value<'static>>, ParseError> {
+        self.skip_whitespace();
+        match stack.last_mut() {
+            Some(Frame::List(_)) if self.peek() == Some(b']') => {},
+            Some(Frame::Dict(..)) if self.peek() == Some(b'}') => {},
+            Some(Frame::Dict(_, key, key_offset)) => {
+                *key_offset = self.offset;
+                *key = match self.peek() {
+                    Some(b'"') => self.string()?,
+                    Some(b'<') => self.hex()?,
+                    Some(b'.') => return Err(self.error("truncated output cannot be parsed")),
+                    _ => return Err(self.error("expected a byte string key")),
+                };
+                self.expect(b':')?;
+                return Ok(None);
+            },
+            _ => return Ok(None),
+        }
+        self.offset += 1;
+        Ok(stack.pop().map(Frame::finish))
+    }
+
+    fn integer(&mut self) -> Result<Value<'static>, ParseError> {
+        let start = self.offset;
+        if self.peek() == Some(b'-') {
+            self.offset += 1;
+        }
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.offset += 1;
+        }
+
+        let text = str::from_utf8(&self.input[start..self.offset])
+            .expect("only ASCII characters were consumed");
+        text.parse()
+            .map(Value::Integer)
+            .map_err(|_| ParseError::new("invalid integer", start))
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, ParseError> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.offset += 1;
+                    return Ok(bytes);
+                },
+                Some(b'\\') => {
+                    self.offset += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => b'"',
+                        Some(b'\\') => b'\\',
+                        Some(b'n') => b'\n',
+                        Some(b'r') => b'\r',
+                        Some(b't') => b'\t',
+                        Some(b'x') => {
+                            self.offset += 1;
+                            let byte = self.hex_byte()?;
+                            bytes.push(byte);
+                            continue;
+                        },
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    self.offset += 1;
+                    bytes.push(escaped);
+                },
+                Some(byte) => {
+                    self.offset += 1;
+                    bytes.push(byte);
+                },
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<Vec<u8>, ParseError> {
+        self.expect(b'<')?;
+        let mut bytes = Vec::new();
+
+        while self.peek() != Some(b'>') {
+            bytes.push(self.hex_byte()?);
+        }
+        self.offset += 1;
+
+        Ok(bytes)
+    }
+
+    fn hex_byte(&mut self) -> Result<u8, ParseError> {
+        let digits = match self.input.get(self.offset..self.offset + 2) {
+            Some(digits) if digits.iter().all(u8::is_ascii_hexdigit) => digits,
+            _ => return Err(self.error("expected two hex digits")),
+        };
+        let digits = str::from_utf8(digits).expect("hex digits are ASCII");
+        let byte = u8::from_str_radix(digits, 16).expect("hex digits were validated");
+        self.offset += 2;
+        Ok(byte)
+    }
+}
+
+/// An unfinished list or dict, with the key of the next value of a dict and
+/// its offset
+enum Frame {
+    List(Vec<Value<'static>>),
+    Dict(
+        BTreeMap<ByteString<'static>, Value<'static>>,
+        Vec<u8>,
+        usize,
+    ),
+}
+
+impl Frame {
+    fn finish(self) -> Value<'static> {
+        match self {
+            Frame::List(list) => Value::List(list),
+            Frame::Dict(dict, ..) => Value::Dict(dict),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::{string::ToString, vec};
+
+    use crate::value::{BytesDisplay, PrettyOptions};
+
+    fn sample() -> Value<'static> {
+        let mut dict = BTreeMap::new();
+        dict.insert(ByteString::from(&b"a"[..]), Value::Integer(-12));
+        dict.insert(
+            ByteString::from(&[0xff, 0x00][..]),
+            Value::Bytes(ByteString::from(&[0xde, 0xad])),
+        );
+        dict.insert(
+            ByteString::from(&b"list"[..]),
+            Value::List(vec![
+                Value::from("quote \" and \\ and \n"),
+                Value::List(Vec::new()),
+                Value::Dict(BTreeMap::new()),
+                Value::Integer(i64::MAX),
+            ]),
+        );
+        Value::Dict(dict)
+    }
+
+    #[test]
+    fn pretty_output_round_trips() {
+        for options in &[
+            PrettyOptions::new(),
+            PrettyOptions::compact(),
+            PrettyOptions::new().with_indent(7),
+            PrettyOptions::compact().with_bytes(BytesDisplay::Hex),
+        ] {
+            let text = sample().pretty(*options).to_string();
+            assert_eq!(Value::parse_pretty(&text).unwrap(), sample(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn relaxed_input_is_accepted() {
+        assert_eq!(
+            " [ 1 ,2, ] ".parse::<Value>().unwrap(),
+            Value::List(vec![Value::Integer(1), Value::Integer(2)])
+        );
+        assert_eq!(
+            Value::parse_pretty(r#"{"b": 1, "a": "\x00"}"#).unwrap(),
+            Value::parse_pretty(r#"{"a": <00>, "b": 1}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_input_is_rejected() {
+        assert_eq!(Value::parse_pretty("[1 2]").unwrap_err().offset(), 3);
+        assert!(Value::parse_pretty("").is_err());
+        assert!(Value::parse_pretty("[1,,2]").is_err());
+        assert!(Value::parse_pretty("\"abc").is_err());
+        assert!(Value::parse_pretty("<abc>").is_err());
+        assert!(Value::parse_pretty("{1: 2}").is_err());
+        assert!(Value::parse_pretty(r#"{"a": 1, "a": 2}"#).is_err());
+        assert!(Value::parse_pretty("99999999999999999999").is_err());
+        assert!(Value::parse_pretty("1 2").is_err());
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let text = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert!(Value::parse_pretty(&text).is_ok());
+
+        let text = "[".repeat(MAX_DEPTH) + "{" + &"]".repeat(MAX_DEPTH);
+        let error = Value::parse_pretty(&text).unwrap_err();
+        assert_eq!(error.offset(), MAX_DEPTH);
+        assert!(error
+            .to_string()
+            .contains("nested more than 2048 levels deep"));
+
+        assert!(Value::parse_pretty(&"[".repeat(50_000)).is_err());
+    }
+
+    #[test]
+    fn truncated_output_is_rejected() {
+        let options = PrettyOptions::compact().with_max_items(1);
+        let text = sample().pretty(options).to_string();
+        assert!(Value::parse_pretty(&text).is_err());
+
+        let text = Value::from("hello")
+            .pretty(PrettyOptions::new().with_max_bytes(2))
+            .to_string();
+        assert!(Value::parse_pretty(&text).is_err());
+
+        let text = sample()
+            .pretty(PrettyOptions::new().with_max_depth(1))
+            .to_string();
+        assert!(Value::parse_pretty(&text).is_err());
+    }
+}
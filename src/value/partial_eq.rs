@@ -0,0 +1,131 @@
+//! Comparisons between [`Value`] and primitive types.
+//!
+//! These allow concise checks like `value == 42` or `value == "name"`. A value only
+//! compares equal to a primitive if it has the matching variant, so the byte string
+//! `"1"` is not equal to the integer `1`.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::value::Value;
+
+impl<'a> PartialEq<[u8]> for Value<'a> {
+    fn eq(&self, other: &[u8]) -> bool {
+        match self {
+            Value::Bytes(bytes) => bytes.as_ref() == other,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> PartialEq<&[u8]> for Value<'a> {
+    fn eq(&self, other: &&[u8]) -> bool {
+        *self == **other
+    }
+}
+
+impl<'a> PartialEq<Vec<u8>> for Value<'a> {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        *self == other[..]
+    }
+}
+
+impl<'a> PartialEq<str> for Value<'a> {
+    fn eq(&self, other: &str) -> bool {
+        *self == *other.as_bytes()
+    }
+}
+
+impl<'a> PartialEq<&str> for Value<'a> {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl<'a> PartialEq<String> for Value<'a> {
+    fn eq(&self, other: &String) -> bool {
+        *self == **other
+    }
+}
+
+impl<'a> PartialEq<Value<'a>> for str {
+    fn eq(&self, other: &Value<'a>) -> bool {
+        other == self
+    }
+}
+
+impl<'a> PartialEq<Value<'a>> for &str {
+    fn eq(&self, other: &Value<'a>) -> bool {
+        other == self
+    }
+}
+
+impl<'a> PartialEq<Value<'a>> for [u8] {
+    fn eq(&self, other: &Value<'a>) -> bool {
+        other == self
+    }
+}
+
+impl<'a> PartialEq<Value<'a>> for &[u8] {
+    fn eq(&self, other: &Value<'a>) -> bool {
+        other == self
+    }
+}
+
+macro_rules! impl_partial_eq_integer {
+    ($($type:ty)*) => {$(
+        impl<'a> PartialEq<$type> for Value<'a> {
+            fn eq(&self, other: &$type) -> bool {
+                match self {
+                    Value::Integer(integer) => i128::from(*integer) == *other as i128,
+                    _ => false,
+                }
+            }
+        }
+
+        impl<'a> PartialEq<Value<'a>> for $type {
+            fn eq(&self, other: &Value<'a>) -> bool {
+                other == self
+            }
+        }
+    )*}
+}
+
+impl_partial_eq_integer!(u8 u16 u32 u64 usize i8 i16 i32 i64 isize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    use crate::value::ByteString;
+
+    #[test]
+    fn integers_compare_by_value() {
+        assert_eq!(Value::Integer(42), 42);
+        assert_eq!(42u8, Value::Integer(42));
+        assert_eq!(Value::Integer(-1), -1i64);
+        assert_ne!(Value::Integer(-1), u64::MAX);
+        assert_ne!(Value::Integer(1), 2);
+    }
+
+    #[test]
+    fn strings_compare_by_content() {
+        let value = Value::Bytes(ByteString::from(b"name".to_vec()));
+        assert_eq!(value, "name");
+        assert_eq!("name", value);
+        assert_eq!(value, b"name"[..]);
+        assert_eq!(value, &b"name"[..]);
+        assert_eq!(value, String::from("name"));
+        assert_eq!(value, b"name".to_vec());
+        assert_ne!(value, "other");
+    }
+
+    #[test]
+    fn mismatched_variants_are_not_equal() {
+        assert_ne!(Value::Bytes(ByteString::from(b"1")), 1);
+        assert_ne!(Value::Integer(1), "1");
+        assert_ne!(Value::List(vec![Value::Integer(1)]), 1);
+        assert_ne!(Value::List(Vec::new()), &b""[..]);
+    }
+}
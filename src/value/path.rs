@@ -0,0 +1,268 @@
+//! Path expressions selecting any number of nested values.
+//!
+//! A path is a sequence of segments:
+//!
+//! - `name` selects the dict entry with key `name`. The first segment is written
+//!   as is, later ones are preceded by a `.`.
+//! - `*` selects every entry of a dict.
+//! - `[3]` selects the list item with the given index.
+//! - `[*]` selects every item of a list.
+//!
+//! So `info.files[*].path` selects the path of every file in a multi-file torrent.
+//! Keys containing `.`, `[`, `]` or `*` can't be expressed; use
+//! [`Value::pointer`] for those. Segments that don't match anything, e.g. a
+//! missing key or a list segment applied to a dict, are skipped silently.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use crate::value::{pointer::index, Value};
+
+/// An error encountered while parsing a path expression.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PathError {
+    message: &'static str,
+    offset: usize,
+}
+
+impl PathError {
+    /// The byte offset in the path at which the error was detected.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PathError {}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Segment<'p> {
+    Key(&'p str),
+    AnyEntry,
+    Index(usize),
+    AnyItem,
+}
+
+fn parse(path: &str) -> Result<Vec<Segment<'_>>, PathError> {
+    let error = |message, offset| Err(PathError { message, offset });
+    let mut segments = Vec::new();
+    let mut offset = 0;
+
+    while offset < path.len() {
+        let rest = &path[offset..];
+        if rest.starts_with('[') {
+            let end = match rest.find(']') {
+                Some(end) => end,
+                None => return error("unterminated '['", offset),
+            };
+            segments.push(match &rest[1..end] {
+                "*" => Segment::AnyItem,
+                text => match index(text) {
+                    Some(index) => Segment::Index(index),
+                    None => return error("expected a list index or '*'", offset + 1),
+                },
+            });
+            offset += end + 1;
+            continue;
+        }
+
+        let name = if offset == 0 {
+            rest
+        } else if let Some(name) = rest.strip_prefix('.') {
+            offset += 1;
+            name
+        } else {
+            return error("expected '.' or '['", offset);
+        };
+        let len = name.find(['.', '[', ']']).unwrap_or(name.len());
+        segments.push(match &name[..len] {
+            "" => return error("expected a key", offset),
+            "*" => Segment::AnyEntry,
+            key if key.contains('*') => return error("unexpected '*' in key", offset),
+            key => Segment::Key(key),
+        });
+        offset += len;
+    }
+
+    Ok(segments)
+}
+
+impl<'a> Value<'a> {
+    /// Call `f` with every value the path expression selects, and return how many
+    /// values were selected. See the [module documentation](self) for the syntax.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let mut torrent = Value::parse_pretty(
+    ///     r#"{"info": {"files": [{"path": ["a"]}, {"path": ["b", "c"]}]}}"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let count = torrent
+    ///     .for_each_mut("info.files[*].path", |path| {
+    ///         path.as_list_mut().unwrap().insert(0, Value::from("prefix"));
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(count, 2);
+    /// assert_eq!(
+    ///     torrent.pointer("/info/files/1/path"),
+    ///     Some(&Value::parse_pretty(r#"["prefix", "b", "c"]"#).unwrap())
+    /// );
+    /// ```
+    pub fn for_each_mut<F>(&mut self, path: &str, mut f: F) -> Result<usize, PathError>
+    where
+        F: FnMut(&mut Value<'a>),
+    {
+        let segments = parse(path)?;
+        Ok(self.visit_mut(&segments, &mut f))
+    }
+
+    /// Like [`for_each_mut`](Value::for_each_mut), but with shared references.
+    pub fn for_each<F>(&self, path: &str, mut f: F) -> Result<usize, PathError>
+    where
+        F: FnMut(&Value<'a>),
+    {
+        let segments = parse(path)?;
+        Ok(self.visit(&segments, &mut f))
+    }
+
+    fn visit<F>(&self, segments: &[Segment], f: &mut F) -> usize
+    where
+        F: FnMut(&Value<'a>),
+    {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => {
+                f(self);
+                return 1;
+            },
+        };
+
+        match (segment, self) {
+            (Segment::Key(key), Value::Dict(dict)) => dict
+                .get(key.as_bytes())
+                .map_or(0, |value| value.visit(rest, f)),
+            (Segment::AnyEntry, Value::Dict(dict)) => {
+                dict.values().map(|value| value.visit(rest, f)).sum()
+            },
+            (Segment::Index(index), Value::List(list)) => {
+                list.get(*index).map_or(0, |value| value.visit(rest, f))
+            },
+            (Segment::AnyItem, Value::List(list)) => {
+                list.iter().map(|value| value.visit(rest, f)).sum()
+            },
+            _ => 0,
+        }
+    }
+
+    fn visit_mut<F>(&mut self, segments: &[Segment], f: &mut F) -> usize
+    where
+        F: FnMut(&mut Value<'a>),
+    {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => {
+                f(self);
+                return 1;
+            },
+        };
+
+        match (segment, self) {
+            (Segment::Key(key), Value::Dict(dict)) => dict
+                .get_mut(key.as_bytes())
+                .map_or(0, |value| value.visit_mut(rest, f)),
+            (Segment::AnyEntry, Value::Dict(dict)) => dict
+                .values_mut()
+                .map(|value| value.visit_mut(rest, f))
+                .sum(),
+            (Segment::Index(index), Value::List(list)) => list
+                .get_mut(*index)
+                .map_or(0, |value| value.visit_mut(rest, f)),
+            (Segment::AnyItem, Value::List(list)) => {
+                list.iter_mut().map(|value| value.visit_mut(rest, f)).sum()
+            },
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    #[test]
+    fn paths_parse_into_segments() {
+        assert_eq!(parse(""), Ok(vec![]));
+        assert_eq!(
+            parse("info.files[*].path[0]"),
+            Ok(vec![
+                Segment::Key("info"),
+                Segment::Key("files"),
+                Segment::AnyItem,
+                Segment::Key("path"),
+                Segment::Index(0),
+            ])
+        );
+        assert_eq!(
+            parse("[1].*.piece length"),
+            Ok(vec![
+                Segment::Index(1),
+                Segment::AnyEntry,
+                Segment::Key("piece length")
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_paths_are_rejected() {
+        assert_eq!(parse("a[").unwrap_err().offset(), 1);
+        assert_eq!(parse("a[01]").unwrap_err().offset(), 2);
+        assert_eq!(parse("a[-1]").unwrap_err().offset(), 2);
+        assert_eq!(parse("a..b").unwrap_err().offset(), 2);
+        assert_eq!(parse(".a").unwrap_err().offset(), 0);
+        assert_eq!(parse("a]").unwrap_err().offset(), 1);
+        assert_eq!(parse("a[0]b").unwrap_err().offset(), 4);
+        assert_eq!(parse("a*").unwrap_err().offset(), 0);
+    }
+
+    #[test]
+    fn wildcards_select_all_matches() {
+        let value =
+            Value::parse_pretty(r#"{"a": [{"x": 1}, {"x": 2}, {"y": 3}], "b": {"x": 4}}"#).unwrap();
+
+        let mut found = vec![];
+        assert_eq!(value.for_each("a[*].x", |v| found.push(v.clone())), Ok(2));
+        assert_eq!(found, vec![Value::Integer(1), Value::Integer(2)]);
+
+        assert_eq!(value.for_each("*.x", |_| {}), Ok(1));
+        assert_eq!(value.for_each("*[*].*", |_| {}), Ok(3));
+        assert_eq!(value.for_each("a[2].y", |_| {}), Ok(1));
+        assert_eq!(value.for_each("a[3]", |_| {}), Ok(0));
+        assert_eq!(value.for_each("b[*]", |_| {}), Ok(0));
+        assert_eq!(value.for_each("", |_| {}), Ok(1));
+    }
+
+    #[test]
+    fn for_each_mut_edits_in_place() {
+        let mut value = Value::parse_pretty(r#"{"l": [1, 2, 3]}"#).unwrap();
+        value
+            .for_each_mut("l[*]", |v| *v = Value::Integer(v.as_int().unwrap() * 10))
+            .unwrap();
+        assert_eq!(
+            value,
+            Value::parse_pretty(r#"{"l": [10, 20, 30]}"#).unwrap()
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Addressing nested values with [JSON pointers](https://tools.ietf.org/html/rfc6901).
+//!
+//! A pointer is either empty, referring to the whole value, or a sequence of
+//! segments each prefixed by `/`. A segment selects a dict entry by key, or a list
+//! item by its decimal index. Within a segment, `~1` stands for `/` and `~0` for
+//! `~`. So `/info/files/0/path` refers to the path of the first file in a
+//! multi-file torrent.
+
+#[cfg(not(feature = "std"))]
+use alloc::string::{String, ToString};
+use alloc::{borrow::Cow, collections::BTreeMap};
+use core::fmt::{self, Display, Formatter};
+
+use crate::value::{ByteString, Value};
+
+/// An error encountered while modifying a value through a pointer.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PointerError {
+    reason: &'static str,
+    path: String,
+}
+
+impl PointerError {
+    fn new(reason: &'static str, path: &str) -> Self {
+        PointerError {
+            reason,
+            path: path.to_string(),
+        }
+    }
+
+    /// The prefix of the pointer at which the error was detected.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Display for PointerError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} at {:?}", self.reason, self.path)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PointerError {}
+
+/// Split a pointer into its unescaped segments, or `None` if it is malformed.
+fn segments(pointer: &str) -> Option<impl Iterator<Item = (usize, Cow<'_, str>)>> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return None;
+    }
+
+    let mut offset = 0;
+    Some(pointer.split('/').skip(1).map(move |segment| {
+        offset += segment.len() + 1;
+        let segment = if segment.contains('~') {
+            Cow::Owned(segment.replace("~1", "/").replace("~0", "~"))
+        } else {
+            Cow::Borrowed(segment)
+        };
+        (offset, segment)
+    }))
+}
+
+/// Parse a list index, rejecting leading zeros, signs and `-` as RFC 6901 does.
+pub(super) fn index(segment: &str) -> Option<usize> {
+    if segment.is_empty()
+        || (segment.len() > 1 && segment.starts_with('0'))
+        || !segment.bytes().all(|byte| byte.is_ascii_digit())
+    {
+        return None;
+    }
+    segment.parse().ok()
+}
+
+impl<'a> Value<'a> {
+    /// Look up the value the pointer refers to. Returns `None` if the pointer is
+    /// malformed or doesn't refer to an existing value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let value = Value::parse_pretty(r#"{"info": {"files": [{"length": 12}]}}"#).unwrap();
+    /// assert_eq!(value.pointer("/info/files/0/length"), Some(&Value::Integer(12)));
+    /// assert_eq!(value.pointer("/info/files/1"), None);
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&Value<'a>> {
+        segments(pointer)?.try_fold(self, |value, (_, segment)| match value {
+            Value::Dict(dict) => dict.get(segment.as_bytes()),
+            Value::List(list) => list.get(index(&segment)?),
+            _ => None,
+        })
+    }
+
+    /// Like [`pointer`](Value::pointer), but returns a mutable reference.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value<'a>> {
+        segments(pointer)?.try_fold(self, |value, (_, segment)| match value {
+            Value::Dict(dict) => dict.get_mut(segment.as_bytes()),
+            Value::List(list) => list.get_mut(index(&segment)?),
+            _ => None,
+        })
+    }
+
+    /// Store `new_value` at the location the pointer refers to and return the
+    /// value it replaced, if any.
+    ///
+    /// Missing dict entries along the way are created as empty dicts. A list
+    /// segment must either be the index of an existing item or `-`, which appends
+    /// a new item. Setting the empty pointer replaces the whole value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let mut value = Value::parse_pretty("{}").unwrap();
+    /// value.set("/info/private", 1.into()).unwrap();
+    /// assert_eq!(value, Value::parse_pretty(r#"{"info": {"private": 1}}"#).unwrap());
+    ///
+    /// assert!(value.set("/info/private/nested", 1.into()).is_err());
+    /// ```
+    pub fn set(
+        &mut self,
+        pointer: &str,
+        new_value: Value<'a>,
+    ) -> Result<Option<Value<'a>>, PointerError> {
+        let (parent, last) = match pointer.rfind('/') {
+            Some(split) => pointer.split_at(split),
+            None if pointer.is_empty() => return Ok(Some(core::mem::replace(self, new_value))),
+            None => return Err(PointerError::new("pointer must start with '/'", "")),
+        };
+        let (_, last) = segments(last)
+            .and_then(|mut segments| segments.next())
+            .expect("split at '/'");
+
+        let mut target = self;
+        for (offset, segment) in
+            segments(parent).ok_or_else(|| PointerError::new("pointer must start with '/'", ""))?
+        {
+            target = match target {
+                Value::Dict(dict) => dict
+                    .entry(ByteString::copy_from(segment.as_bytes()))
+                    .or_insert_with(|| Value::Dict(BTreeMap::new())),
+                Value::List(list) => match index(&segment).filter(|&i| i < list.len()) {
+                    Some(i) => &mut list[i],
+                    None => {
+                        return Err(PointerError::new(
+                            "list index out of range",
+                            &pointer[..offset],
+                        ))
+                    },
+                },
+                _ => {
+                    return Err(PointerError::new(
+                        "value is not a dict or list",
+                        &pointer[..offset - segment.len() - 1],
+                    ))
+                },
+            };
+        }
+
+        match target {
+            Value::Dict(dict) => Ok(dict.insert(ByteString::copy_from(last.as_bytes()), new_value)),
+            Value::List(list) if last == "-" => {
+                list.push(new_value);
+                Ok(None)
+            },
+            Value::List(list) => match index(&last).filter(|&i| i < list.len()) {
+                Some(i) => Ok(Some(core::mem::replace(&mut list[i], new_value))),
+                None => Err(PointerError::new("list index out of range", pointer)),
+            },
+            _ => Err(PointerError::new("value is not a dict or list", parent)),
+        }
+    }
+
+    /// Remove the value the pointer refers to and return it. Returns `None` if the
+    /// pointer is empty, malformed or doesn't refer to an existing value.
+    pub fn remove_pointer(&mut self, pointer: &str) -> Option<Value<'a>> {
+        let split = pointer.rfind('/')?;
+        let (_, last) = segments(&pointer[split..])?.next()?;
+
+        match self.pointer_mut(&pointer[..split])? {
+            Value::Dict(dict) => dict.remove(last.as_bytes()),
+            Value::List(list) => {
+                let index = index(&last).filter(|&index| index < list.len())?;
+                Some(list.remove(index))
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Value<'static> {
+        Value::parse_pretty(text).unwrap()
+    }
+
+    #[test]
+    fn pointer_navigates_dicts_and_lists() {
+        let value = parse(r#"{"a/b": {"~": [1, [2]]}, "": 3}"#);
+
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/"), Some(&Value::Integer(3)));
+        assert_eq!(value.pointer("/a~1b/~0/1/0"), Some(&Value::Integer(2)));
+        assert_eq!(value.pointer("/a~1b/~0/01"), None);
+        assert_eq!(value.pointer("/a~1b/~0/2"), None);
+        assert_eq!(value.pointer("/missing"), None);
+        assert_eq!(value.pointer("a~1b"), None);
+    }
+
+    #[test]
+    fn set_creates_intermediate_dicts() {
+        let mut value = parse("{}");
+        assert_eq!(value.set("/a/b/c", 1.into()), Ok(None));
+        assert_eq!(value.set("/a/b/c", 2.into()), Ok(Some(Value::Integer(1))));
+        assert_eq!(value, parse(r#"{"a": {"b": {"c": 2}}}"#));
+
+        assert_eq!(
+            value.set("", 3.into()),
+            Ok(Some(parse(r#"{"a": {"b": {"c": 2}}}"#)))
+        );
+        assert_eq!(value, Value::Integer(3));
+    }
+
+    #[test]
+    fn set_indexes_lists() {
+        let mut value = parse(r#"{"l": [[1]]}"#);
+        assert_eq!(value.set("/l/0/0", 2.into()), Ok(Some(Value::Integer(1))));
+        assert_eq!(value.set("/l/0/-", 3.into()), Ok(None));
+        assert_eq!(value, parse(r#"{"l": [[2, 3]]}"#));
+
+        assert_eq!(value.set("/l/5", 1.into()).unwrap_err().path(), "/l/5");
+        assert_eq!(value.set("/l/5/a", 1.into()).unwrap_err().path(), "/l/5");
+    }
+
+    #[test]
+    fn set_fails_on_scalars() {
+        let mut value = parse(r#"{"a": {"b": 1}}"#);
+        assert_eq!(value.set("/a/b/c", 1.into()).unwrap_err().path(), "/a/b");
+        assert_eq!(value.set("/a/b/c/d", 1.into()).unwrap_err().path(), "/a/b");
+        assert!(value.set("a", 1.into()).is_err());
+        assert_eq!(value, parse(r#"{"a": {"b": 1}}"#));
+    }
+
+    #[test]
+    fn remove_pointer_removes_entries() {
+        let mut value = parse(r#"{"a": {"b": 1}, "l": [1, 2, 3]}"#);
+        assert_eq!(value.remove_pointer("/a/b"), Some(Value::Integer(1)));
+        assert_eq!(value.remove_pointer("/l/1"), Some(Value::Integer(2)));
+        assert_eq!(value.remove_pointer("/l/2"), None);
+        assert_eq!(value.remove_pointer("/a/b"), None);
+        assert_eq!(value.remove_pointer(""), None);
+        assert_eq!(value, parse(r#"{"a": {}, "l": [1, 3]}"#));
+    }
+}
@@ -0,0 +1,414 @@
+//! A configurable, human-readable renderer for [`Value`]s.
+//!
+//! The output looks like this:
+//!
+//! ```text
+//! {
+//!   "announce": "http://bttracker.debian.org:6969/announce",
+//!   "info": {
+//!     "length": 305135616,
+//!     "pieces": <5c3e9a26>... (11640 bytes)
+//!   }
+//! }
+//! ```
+//!
+//! Integers are printed in decimal. Byte strings are printed either as quoted,
+//! escaped text or as hex digits between angle brackets, depending on the
+//! [`BytesDisplay`] policy. Lists use square brackets and dictionaries use braces.
+//! Output which is not truncated by any of the limits in [`PrettyOptions`] can
+//! be parsed back into the same value with [`Value::parse_pretty`].
+
+use core::{
+    fmt::{self, Display, Formatter, Write},
+    str,
+};
+
+use crate::value::Value;
+
+/// How byte strings are rendered by the pretty-printer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BytesDisplay {
+    /// Render valid UTF-8 as a quoted string, and anything else as hex.
+    Utf8,
+    /// Always render byte strings as hex.
+    Hex,
+}
+
+/// Settings for [`Value::pretty`].
+///
+/// # Examples
+///
+/// ```
+/// use bendy::value::{BytesDisplay, PrettyOptions, Value};
+///
+/// let value = Value::from(vec![Value::from("foo"), Value::from(1)]);
+///
+/// assert_eq!(
+///     value.pretty(PrettyOptions::new()).to_string(),
+///     "[\n  \"foo\",\n  1\n]"
+/// );
+/// assert_eq!(
+///     value
+///         .pretty(PrettyOptions::compact().with_bytes(BytesDisplay::Hex))
+///         .to_string(),
+///     "[<666f6f>, 1]"
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PrettyOptions {
+    indent: usize,
+    bytes: BytesDisplay,
+    max_depth: Option<usize>,
+    max_items: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions {
+            indent: 2,
+            bytes: BytesDisplay::Utf8,
+            max_depth: None,
+            max_items: None,
+            max_bytes: None,
+        }
+    }
+}
+
+impl PrettyOptions {
+    /// Create the default options: two-space indentation, UTF-8 byte strings and
+    /// no limits.
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Create options which render the whole value on a single line.
+    pub fn compact() -> Self {
+        Self::new().with_indent(0)
+    }
+
+    /// Set the number of spaces each nesting level is indented by. An indent of
+    /// zero renders the value on a single line.
+    #[must_use]
+    pub fn with_indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Set how byte strings are rendered.
+    #[must_use]
+    pub fn with_bytes(mut self, bytes: BytesDisplay) -> Self {
+        self.bytes = bytes;
+        self
+    }
+
+    /// Set the maximum nesting depth to render. Lists and dicts below that depth
+    /// are rendered as `[...]` and `{...}`.
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Set the maximum number of items rendered per list or dict. Any further
+    /// items are summarised with a `... (N more)` line.
+    #[must_use]
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+
+    /// Set the maximum number of bytes rendered per byte string value. Longer
+    /// strings are cut off and followed by their total length. Dict keys are
+    /// never truncated.
+    #[must_use]
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+}
+
+/// A [`Value`] rendered with a set of [`PrettyOptions`]. Created by
+/// [`Value::pretty`]; use its `Display` implementation to obtain the output.
+#[derive(Clone, Copy, Debug)]
+pub struct Pretty<'v, 'a> {
+    value: &'v Value<'a>,
+    options: PrettyOptions,
+}
+
+impl<'a> Value<'a> {
+    /// Render this value in a human-readable form, suitable for logs and CLI
+    /// output. See [`PrettyOptions`] for the available settings.
+    pub fn pretty(&self, options: PrettyOptions) -> Pretty<'_, 'a> {
+        Pretty {
+            value: self,
+            options,
+        }
+    }
+}
+
+impl<'v, 'a> Display for Pretty<'v, 'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Printer {
+            out: f,
+            options: &self.options,
+        }
+        .value(self.value, 0)
+    }
+}
+
+struct Printer<'o, W> {
+    out: W,
+    options: &'o PrettyOptions,
+}
+
+impl<'o, W: Write> Printer<'o, W> {
+    fn value(&mut self, value: &Value, depth: usize) -> fmt::Result {
+        match value {
+            Value::Integer(integer) => write!(self.out, "{}", integer),
+            Value::Bytes(bytes) => self.bytes(bytes, self.options.max_bytes),
+            Value::List(list) => {
+                if list.is_empty() {
+                    return self.out.write_str("[]");
+                }
+                if self.depth_exceeded(depth) {
+                    return self.out.write_str("[...]");
+                }
+                self.out.write_char('[')?;
+                self.items(list.iter(), list.len(), depth, |p, item| {
+                    p.value(item, depth + 1)
+                })?;
+                self.out.write_char(']')
+            },
+            Value::Dict(dict) => {
+                if dict.is_empty() {
+                    return self.out.write_str("{}");
+                }
+                if self.depth_exceeded(depth) {
+                    return self.out.write_str("{...}");
+                }
+                self.out.write_char('{')?;
+                self.items(dict.iter(), dict.len(), depth, |p, (k, v)| {
+                    p.bytes(k, None)?;
+                    p.out.write_str(": ")?;
+                    p.value(v, depth + 1)
+                })?;
+                self.out.write_char('}')
+            },
+        }
+    }
+
+    fn depth_exceeded(&self, depth: usize) -> bool {
+        matches!(self.options.max_depth, Some(max) if depth >= max)
+    }
+
+    fn items<I, F>(&mut self, items: I, len: usize, depth: usize, mut item_cb: F) -> fmt::Result
+    where
+        I: Iterator,
+        F: FnMut(&mut Self, I::Item) -> fmt::Result,
+    {
+        let shown = self.options.max_items.map_or(len, |max| max.min(len));
+
+        for (index, item) in items.take(shown).enumerate() {
+            if index > 0 {
+                self.out.write_char(',')?;
+            }
+            self.separator(depth + 1, index == 0)?;
+            item_cb(self, item)?;
+        }
+
+        if shown < len {
+            if shown > 0 {
+                self.out.write_char(',')?;
+            }
+            self.separator(depth + 1, shown == 0)?;
+            write!(self.out, "... ({} more)", len - shown)?;
+        }
+
+        if self.options.indent > 0 {
+            self.newline(depth)?;
+        }
+        Ok(())
+    }
+
+    /// Write the whitespace preceding an item at the given depth
+    fn separator(&mut self, depth: usize, first: bool) -> fmt::Result {
+        if self.options.indent > 0 {
+            self.newline(depth)
+        } else if first {
+            Ok(())
+        } else {
+            self.out.write_char(' ')
+        }
+    }
+
+    fn newline(&mut self, depth: usize) -> fmt::Result {
+        self.out.write_char('\n')?;
+        for _ in 0..depth * self.options.indent {
+            self.out.write_char(' ')?;
+        }
+        Ok(())
+    }
+
+    fn bytes(&mut self, bytes: &[u8], limit: Option<usize>) -> fmt::Result {
+        let shown = &bytes[..limit.map_or(bytes.len(), |limit| limit.min(bytes.len()))];
+
+        match (self.options.bytes, str::from_utf8(bytes)) {
+            (BytesDisplay::Utf8, Ok(_)) => {
+                // Truncation may split a multi-byte character; escape what's left of it
+                self.out.write_char('"')?;
+                write_escaped(&mut self.out, shown)?;
+                self.out.write_char('"')?;
+            },
+            _ => {
+                self.out.write_char('<')?;
+                for byte in shown {
+                    write!(self.out, "{:02x}", byte)?;
+                }
+                self.out.write_char('>')?;
+            },
+        }
+
+        if shown.len() < bytes.len() {
+            write!(self.out, "... ({} bytes)", bytes.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a byte string as the content of a quoted string, escaping quotes,
+/// backslashes, control characters and invalid UTF-8.
+fn write_escaped(out: &mut impl Write, mut bytes: &[u8]) -> fmt::Result {
+    while !bytes.is_empty() {
+        let (valid, invalid) = match str::from_utf8(bytes) {
+            Ok(valid) => (valid, &[][..]),
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                let invalid_len = err.error_len().unwrap_or(rest.len());
+                (
+                    str::from_utf8(valid).expect("checked by from_utf8"),
+                    &rest[..invalid_len],
+                )
+            },
+        };
+
+        for c in valid.chars() {
+            match c {
+                '"' => out.write_str("\\\"")?,
+                '\\' => out.write_str("\\\\")?,
+                '\n' => out.write_str("\\n")?,
+                '\r' => out.write_str("\\r")?,
+                '\t' => out.write_str("\\t")?,
+                c if c.is_control() && (c as u32) < 0x80 => write!(out, "\\x{:02x}", c as u32)?,
+                c => out.write_char(c)?,
+            }
+        }
+        for byte in invalid {
+            write!(out, "\\x{:02x}", byte)?;
+        }
+
+        bytes = &bytes[valid.len() + invalid.len()..];
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::{collections::BTreeMap, string::ToString, vec, vec::Vec};
+
+    use crate::value::ByteString;
+
+    fn sample() -> Value<'static> {
+        let mut info = BTreeMap::new();
+        info.insert(ByteString::from(&b"length"[..]), Value::Integer(12));
+        info.insert(
+            ByteString::from(&b"pieces"[..]),
+            Value::Bytes(ByteString::from(&[0xde, 0xad, 0xbe, 0xef])),
+        );
+
+        let mut dict = BTreeMap::new();
+        dict.insert(ByteString::from(&b"info"[..]), Value::Dict(info));
+        dict.insert(
+            ByteString::from(&b"list"[..]),
+            Value::List(vec![Value::from("a\"b"), Value::List(Vec::new())]),
+        );
+        Value::Dict(dict)
+    }
+
+    #[test]
+    fn default_output_is_indented() {
+        assert_eq!(
+            sample().pretty(PrettyOptions::new()).to_string(),
+            concat!(
+                "{\n",
+                "  \"info\": {\n",
+                "    \"length\": 12,\n",
+                "    \"pieces\": <deadbeef>\n",
+                "  },\n",
+                "  \"list\": [\n",
+                "    \"a\\\"b\",\n",
+                "    []\n",
+                "  ]\n",
+                "}"
+            )
+        );
+    }
+
+    #[test]
+    fn compact_output_is_single_line() {
+        assert_eq!(
+            sample().pretty(PrettyOptions::compact()).to_string(),
+            r#"{"info": {"length": 12, "pieces": <deadbeef>}, "list": ["a\"b", []]}"#
+        );
+    }
+
+    #[test]
+    fn hex_policy_applies_to_keys_and_values() {
+        let options = PrettyOptions::compact().with_bytes(BytesDisplay::Hex);
+
+        let value = Value::from(vec![Value::from("ok")]);
+        assert_eq!(value.pretty(options).to_string(), "[<6f6b>]");
+
+        let mut dict = BTreeMap::new();
+        dict.insert(ByteString::from(&[0xff, 0x00][..]), Value::from("ok"));
+        assert_eq!(
+            Value::Dict(dict).pretty(options).to_string(),
+            "{<ff00>: <6f6b>}"
+        );
+    }
+
+    #[test]
+    fn limits_truncate_output() {
+        let options = PrettyOptions::compact()
+            .with_max_depth(1)
+            .with_max_items(1)
+            .with_max_bytes(2);
+
+        assert_eq!(
+            sample().pretty(options).to_string(),
+            r#"{"info": {...}, ... (1 more)}"#
+        );
+        assert_eq!(
+            Value::from("hello").pretty(options).to_string(),
+            r#""he"... (5 bytes)"#
+        );
+        assert_eq!(
+            Value::from(vec![Value::from(1), Value::from(2)])
+                .pretty(PrettyOptions::new().with_max_items(0))
+                .to_string(),
+            "[\n  ... (2 more)\n]"
+        );
+    }
+
+    #[test]
+    fn control_characters_are_escaped() {
+        assert_eq!(
+            Value::from("a\n\\\u{1}")
+                .pretty(PrettyOptions::new())
+                .to_string(),
+            r#""a\n\\\x01""#
+        );
+    }
+}
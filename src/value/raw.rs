@@ -0,0 +1,270 @@
+//! A bencoded value kept in its exact encoded form.
+
+use alloc::{format, vec::Vec};
+
+#[cfg(feature = "serde")]
+use serde_ as serde;
+
+use crate::{
+    decoding::{Decoder, Error, FromBencode, Object},
+    encoding::{SingleItemEncoder, ToBencode},
+    state_tracker::StructureError,
+    value::Value,
+};
+
+/// The newtype struct name that tells the bencode serializer and deserializer to
+/// pass a [`RawValue`] through verbatim.
+#[cfg(feature = "serde")]
+pub(crate) const RAW_VALUE_TOKEN: &str = "$bendy::private::RawValue";
+
+/// A single bencoded value, stored as the exact bytes it was decoded from.
+///
+/// Decoding a `RawValue` captures the encoded bytes of whatever value is found, and
+/// encoding it writes those bytes back unchanged. This is useful to preserve parts
+/// of a structure bit-for-bit, e.g. the `info` dict of a torrent, whose hash
+/// identifies the torrent. With the `serde` feature enabled, `RawValue` can also be
+/// used as a field of a serde struct.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{
+///     decoding::{FromBencode, Object},
+///     encoding::{AsString, Error, SingleItemEncoder, ToBencode},
+///     value::RawValue,
+/// };
+///
+/// struct Torrent {
+///     announce: Vec<u8>,
+///     info: RawValue,
+/// }
+///
+/// impl FromBencode for Torrent {
+///     fn decode_bencode_object(object: Object) -> Result<Self, bendy::decoding::Error> {
+///         let mut announce = None;
+///         let mut info = None;
+///
+///         let mut dict = object.try_into_dictionary()?;
+///         while let Some((key, value)) = dict.next_pair()? {
+///             match key {
+///                 b"announce" => announce = Some(AsString::decode_bencode_object(value)?.0),
+///                 b"info" => info = Some(RawValue::decode_bencode_object(value)?),
+///                 _ => {},
+///             }
+///         }
+///
+///         Ok(Torrent {
+///             announce: announce.ok_or_else(|| bendy::decoding::Error::missing_field("announce"))?,
+///             info: info.ok_or_else(|| bendy::decoding::Error::missing_field("info"))?,
+///         })
+///     }
+/// }
+///
+/// impl ToBencode for Torrent {
+///     const MAX_DEPTH: usize = 1;
+///
+///     fn encode(&self, encoder: SingleItemEncoder) -> Result<(), Error> {
+///         encoder.emit_dict(|mut e| {
+///             e.emit_pair(b"announce", AsString(&self.announce))?;
+///             e.emit_pair(b"info", &self.info)
+///         })
+///     }
+/// }
+///
+/// let encoded = b"d8:announce3:url4:infod6:lengthi1e4:name1:xee";
+/// let torrent = Torrent::from_bencode(encoded).unwrap();
+///
+/// assert_eq!(torrent.info.as_bytes(), b"d6:lengthi1e4:name1:xe");
+/// assert_eq!(torrent.to_bencode().unwrap(), &encoded[..]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RawValue {
+    bytes: Vec<u8>,
+}
+
+impl RawValue {
+    /// Wrap the encoding of a single value. Fails if `bytes` is not exactly one
+    /// valid bencode value.
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Result<Self, Error> {
+        let bytes = bytes.into();
+
+        let mut decoder =
+            Decoder::new(&bytes).with_max_depth(<Value as FromBencode>::EXPECTED_RECURSION_DEPTH);
+        match decoder.next_object()? {
+            // Dropping the object validates the remainder of a list or dict
+            Some(_) => {},
+            None => return Err(Error::from(StructureError::UnexpectedEof)),
+        }
+        if decoder.next_object()?.is_some() {
+            return Err(Error::trailing_data());
+        }
+
+        Ok(RawValue { bytes })
+    }
+
+    /// The encoded bytes of the value.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Consume the `RawValue`, returning the encoded bytes of the value.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Decode the value into a [`Value`] borrowing from this `RawValue`.
+    pub fn to_value(&self) -> Value<'_> {
+        Value::from_bencode_borrowed(&self.bytes).expect("RawValue contains a valid value")
+    }
+}
+
+impl FromBencode for RawValue {
+    const EXPECTED_RECURSION_DEPTH: usize = <Value as FromBencode>::EXPECTED_RECURSION_DEPTH;
+
+    fn decode_bencode_object(object: Object) -> Result<Self, Error> {
+        let bytes = match object {
+            // The decoder only accepts canonical encodings, so scalars can be
+            // re-encoded exactly
+            Object::Bytes(bytes) => {
+                let mut raw = format!("{}:", bytes.len()).into_bytes();
+                raw.extend_from_slice(bytes);
+                raw
+            },
+            Object::Integer(text) => format!("i{}e", text).into_bytes(),
+            Object::List(list) => list.into_raw()?.to_vec(),
+            Object::Dict(dict) => dict.into_raw()?.to_vec(),
+        };
+
+        Ok(RawValue { bytes })
+    }
+}
+
+impl ToBencode for RawValue {
+    // The raw bytes are emitted as a single unit
+    const MAX_DEPTH: usize = 0;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), crate::encoding::Error> {
+        encoder.emit_raw(&self.bytes)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::*;
+
+    use core::fmt::{self, Formatter};
+
+    use serde::{
+        de::{Deserializer, Visitor},
+        Deserialize, Serialize, Serializer,
+    };
+
+    impl Serialize for RawValue {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer
+                .serialize_newtype_struct(RAW_VALUE_TOKEN, serde_bytes::Bytes::new(&self.bytes))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for RawValue {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_newtype_struct(RAW_VALUE_TOKEN, RawValueVisitor)
+        }
+    }
+
+    struct RawValueVisitor;
+
+    impl<'de> Visitor<'de> for RawValueVisitor {
+        type Value = RawValue;
+
+        fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+            formatter.write_str("an encoded bencode value")
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<RawValue, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_byte_buf(self)
+        }
+
+        fn visit_bytes<E>(self, value: &[u8]) -> Result<RawValue, E>
+        where
+            E: serde::de::Error,
+        {
+            self.visit_byte_buf(value.to_vec())
+        }
+
+        fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<RawValue, E>
+        where
+            E: serde::de::Error,
+        {
+            RawValue::from_bytes(value).map_err(E::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoding_preserves_bytes() {
+        for encoded in &[&b"i-12e"[..], b"3:abc", b"le", b"ld1:ai1eeli2eee"] {
+            let raw = RawValue::from_bencode(encoded).unwrap();
+            assert_eq!(raw.as_bytes(), *encoded);
+            assert_eq!(raw.to_bencode().unwrap(), *encoded);
+            assert_eq!(raw.to_value(), Value::from_bencode(encoded).unwrap());
+        }
+    }
+
+    #[test]
+    fn nested_raw_values_round_trip() {
+        let encoded = b"ld1:ai1eei2ee";
+        let list = Vec::<RawValue>::from_bencode(encoded).unwrap();
+
+        assert_eq!(list[0].as_bytes(), b"d1:ai1ee");
+        assert_eq!(list[1].as_bytes(), b"i2e");
+        assert_eq!(list.to_bencode().unwrap(), &encoded[..]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_fields_round_trip() {
+        use serde_derive::{Deserialize, Serialize};
+
+        #[derive(Debug, Deserialize, Serialize)]
+        #[serde(crate = "serde_")]
+        struct Torrent {
+            announce: String,
+            info: RawValue,
+        }
+
+        let encoded = b"d8:announce3:url4:infod6:lengthi1e4:name1:xee";
+        let torrent: Torrent = crate::serde::from_bytes(encoded).unwrap();
+
+        assert_eq!(torrent.info.as_bytes(), b"d6:lengthi1e4:name1:xe");
+        assert_eq!(crate::serde::to_bytes(&torrent).unwrap(), &encoded[..]);
+        assert_eq!(
+            crate::serde::from_bytes::<RawValue>(b"i1e")
+                .unwrap()
+                .as_bytes(),
+            b"i1e"
+        );
+    }
+
+    #[test]
+    fn from_bytes_validates() {
+        assert!(RawValue::from_bytes(&b"d1:ai1ee"[..]).is_ok());
+        assert!(RawValue::from_bytes(&b""[..]).is_err());
+        assert!(RawValue::from_bytes(&b"i1ei2e"[..]).is_err());
+        assert!(RawValue::from_bytes(&b"d1:b0:1:a0:e"[..]).is_err());
+        assert!(RawValue::from_bytes(&b"l"[..]).is_err());
+    }
+}
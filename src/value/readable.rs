@@ -0,0 +1,125 @@
+//! Serializing values into text-only formats.
+
+use core::fmt::{self, Debug, Formatter};
+
+use serde_ as serde;
+
+use serde::{
+    ser::{Error as _, SerializeMap},
+    Serialize, Serializer,
+};
+
+use crate::value::{BytesAsText, Value};
+
+/// A view of a [`Value`] which serializes byte strings as text.
+///
+/// `Value`'s own `Serialize` implementation emits byte strings as bytes, which
+/// formats like YAML and TOML can only represent as lists of numbers. A
+/// `HumanReadable` instead emits every byte string and dict key as a string,
+/// converted according to its [`BytesAsText`] policy, which makes it suitable
+/// for `serde_yaml::to_value`, `toml::Value::try_from` and similar converters.
+/// Serialization fails if the policy is [`BytesAsText::Strict`] and a byte
+/// string isn't valid UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "json")] {
+/// use bendy::value::{BytesAsText, Value};
+///
+/// let value = Value::parse_pretty(r#"{"name": "foo", "pieces": <c0ffee>}"#).unwrap();
+///
+/// assert_eq!(
+///     serde_json::to_string(&value.human_readable(BytesAsText::Hex)).unwrap(),
+///     r#"{"6e616d65":"666f6f","706965636573":"c0ffee"}"#
+/// );
+/// assert!(serde_json::to_string(&value.human_readable(BytesAsText::Strict)).is_err());
+/// # }
+/// ```
+#[derive(Clone, Copy)]
+pub struct HumanReadable<'v, 'a> {
+    value: &'v Value<'a>,
+    bytes: BytesAsText,
+}
+
+impl<'a> Value<'a> {
+    /// Wrap this value for serialization into a text-only format, representing
+    /// byte strings according to `bytes`. See [`HumanReadable`].
+    pub fn human_readable(&self, bytes: BytesAsText) -> HumanReadable<'_, 'a> {
+        HumanReadable { value: self, bytes }
+    }
+}
+
+impl<'v, 'a> HumanReadable<'v, 'a> {
+    fn nested(&self, value: &'v Value<'a>) -> Self {
+        HumanReadable {
+            value,
+            bytes: self.bytes,
+        }
+    }
+}
+
+impl<'v, 'a> Debug for HumanReadable<'v, 'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("HumanReadable")
+            .field("value", self.value)
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
+
+impl<'v, 'a> Serialize for HumanReadable<'v, 'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let text = |bytes: &[u8]| self.bytes.convert(bytes).map_err(S::Error::custom);
+
+        match self.value {
+            Value::Bytes(bytes) => serializer.serialize_str(&text(bytes)?),
+            Value::Integer(integer) => serializer.serialize_i64(*integer),
+            Value::List(list) => serializer.collect_seq(list.iter().map(|item| self.nested(item))),
+            Value::Dict(dict) => {
+                let mut map = serializer.serialize_map(Some(dict.len()))?;
+                for (key, value) in dict {
+                    map.serialize_entry(&text(key)?, &self.nested(value))?;
+                }
+                map.end()
+            },
+        }
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn byte_strings_serialize_as_text() {
+        let value = Value::parse_pretty(r#"{<ff>: ["a\xffb", 1]}"#).unwrap();
+
+        assert!(serde_json::to_value(value.human_readable(BytesAsText::Strict)).is_err());
+        assert_eq!(
+            serde_json::to_value(value.human_readable(BytesAsText::Lossy)).unwrap(),
+            json!({"\u{fffd}": ["a\u{fffd}b", 1]})
+        );
+        assert_eq!(
+            serde_json::to_value(value.human_readable(BytesAsText::Hex)).unwrap(),
+            json!({"ff": ["61ff62", 1]})
+        );
+    }
+
+    #[test]
+    fn conversion_matches_to_json() {
+        let value = Value::parse_pretty(r#"{"a": [{"b": <00>}, -3], "c": {}}"#).unwrap();
+
+        for bytes in &[BytesAsText::Strict, BytesAsText::Lossy, BytesAsText::Hex] {
+            assert_eq!(
+                serde_json::to_value(value.human_readable(*bytes)).unwrap(),
+                value.to_json(*bytes).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Replacing the values of sensitive or bulky dict entries with placeholders.
+
+#[cfg(not(feature = "std"))]
+use alloc::string::ToString;
+use alloc::{format, vec::Vec};
+
+use crate::{
+    decoding::{Decoder, Error},
+    state_tracker::Token,
+    value::{ByteString, Value},
+};
+
+/// The placeholder byte string for a redacted value whose encoding was `len`
+/// bytes long.
+fn placeholder(len: usize) -> Vec<u8> {
+    format!("<redacted {} bytes>", len).into_bytes()
+}
+
+impl<'a> Value<'a> {
+    /// Replace the value of every dict entry whose key is one of `keys`, at any
+    /// depth, with a placeholder byte string like `<redacted 20480 bytes>`, and
+    /// return the number of values replaced. The size in the placeholder is that
+    /// of the replaced value's encoding.
+    ///
+    /// This is meant for logging structures such as torrents without dumping
+    /// their `pieces`. Use [`redact_encoded`] to do the same on encoded data
+    /// without building a value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::Value;
+    ///
+    /// let mut torrent =
+    ///     Value::parse_pretty(r#"{"info": {"name": "a", "pieces": <000102030405>}}"#).unwrap();
+    ///
+    /// assert_eq!(torrent.redact(&[b"pieces"]), 1);
+    /// assert_eq!(
+    ///     torrent,
+    ///     Value::parse_pretty(r#"{"info": {"name": "a", "pieces": "<redacted 8 bytes>"}}"#)
+    ///         .unwrap()
+    /// );
+    /// ```
+    pub fn redact(&mut self, keys: &[&[u8]]) -> usize {
+        match self {
+            Value::Dict(dict) => dict
+                .iter_mut()
+                .map(|(key, value)| {
+                    if keys.contains(&&key[..]) {
+                        let len = value.encoded_len();
+                        *value = Value::Bytes(ByteString::from(placeholder(len)));
+                        1
+                    } else {
+                        value.redact(keys)
+                    }
+                })
+                .sum(),
+            Value::List(list) => list.iter_mut().map(|value| value.redact(keys)).sum(),
+            Value::Bytes(_) | Value::Integer(_) => 0,
+        }
+    }
+}
+
+/// Like [`Value::redact`], but works directly on encoded data, token by token.
+///
+/// Returns the encoding with every redacted value replaced by its placeholder.
+/// The input is validated like the decoder validates any input; it may contain
+/// several values in a row.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::value::redact_encoded;
+///
+/// let redacted = redact_encoded(b"d4:name1:a6:piecesli1ei2eee", &[b"pieces"]).unwrap();
+/// assert_eq!(redacted, &b"d4:name1:a6:pieces18:<redacted 8 bytes>e"[..]);
+/// ```
+pub fn redact_encoded(input: &[u8], keys: &[&[u8]]) -> Result<Vec<u8>, Error> {
+    fn token_len(token: &Token) -> usize {
+        match token {
+            Token::String(bytes) => bytes.len().to_string().len() + 1 + bytes.len(),
+            Token::Num(text) => text.len() + 2,
+            Token::List | Token::Dict | Token::End => 1,
+        }
+    }
+
+    fn write_token(output: &mut Vec<u8>, token: &Token) {
+        match token {
+            Token::String(bytes) => write_bytes(output, bytes),
+            Token::Num(text) => {
+                output.push(b'i');
+                output.extend_from_slice(text.as_bytes());
+                output.push(b'e');
+            },
+            Token::List => output.push(b'l'),
+            Token::Dict => output.push(b'd'),
+            Token::End => output.push(b'e'),
+        }
+    }
+
+    fn write_bytes(output: &mut Vec<u8>, bytes: &[u8]) {
+        output.extend_from_slice(bytes.len().to_string().as_bytes());
+        output.push(b':');
+        output.extend_from_slice(bytes);
+    }
+
+    // For each open list or dict, whether it is a dict whose next token is a key
+    let mut expecting_key = Vec::new();
+    // Set after a redacted key, until its value starts
+    let mut redact_next = false;
+    // The nesting depth and encoded length of the value being redacted
+    let mut skipping: Option<(usize, usize)> = None;
+    let mut output = Vec::with_capacity(input.len());
+
+    for token in Decoder::new(input).tokens() {
+        let token = token?;
+
+        if let Some((depth, len)) = &mut skipping {
+            *len += token_len(&token);
+            match token {
+                Token::List | Token::Dict => *depth += 1,
+                Token::End => *depth -= 1,
+                Token::String(_) | Token::Num(_) => {},
+            }
+            if *depth > 0 {
+                continue;
+            }
+            write_bytes(&mut output, &placeholder(*len));
+            skipping = None;
+        } else if let Some(expecting @ true) = expecting_key.last_mut() {
+            if let Token::String(key) = token {
+                *expecting = false;
+                redact_next = keys.contains(&key);
+            } else {
+                // The end of the dict
+                expecting_key.pop();
+            }
+            write_token(&mut output, &token);
+            if token != Token::End {
+                continue;
+            }
+        } else if redact_next {
+            redact_next = false;
+            match token {
+                Token::List | Token::Dict => {
+                    skipping = Some((1, 1));
+                    continue;
+                },
+                _ => write_bytes(&mut output, &placeholder(token_len(&token))),
+            }
+        } else {
+            write_token(&mut output, &token);
+            match token {
+                Token::List => {
+                    expecting_key.push(false);
+                    continue;
+                },
+                Token::Dict => {
+                    expecting_key.push(true);
+                    continue;
+                },
+                Token::End => {
+                    expecting_key.pop();
+                },
+                Token::String(_) | Token::Num(_) => {},
+            }
+        }
+
+        // A value is complete; if it was the value of a dict entry, a key follows
+        if let Some(expecting) = expecting_key.last_mut() {
+            *expecting = true;
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{decoding::FromBencode, encoding::ToBencode};
+
+    static KEYS: &[&[u8]] = &[b"pieces", b"secret"];
+
+    #[test]
+    fn nested_values_are_redacted() {
+        let encoded = b"d4:infod6:piecesli1ei2ee6:secretd1:ai1eee4:listld6:secreti12eeee";
+        let mut value = Value::from_bencode(encoded).unwrap();
+
+        assert_eq!(value.redact(KEYS), 3);
+        assert_eq!(
+            value,
+            Value::parse_pretty(
+                r#"{
+                    "info": {"pieces": "<redacted 8 bytes>", "secret": "<redacted 8 bytes>"},
+                    "list": [{"secret": "<redacted 4 bytes>"}]
+                }"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn streaming_matches_value_redaction() {
+        for encoded in &[
+            &b"d4:infod6:piecesli1ei2ee6:secretd1:ai1eee4:listld6:secreti12eeee"[..],
+            b"d1:ad6:secret0:e1:bdee",
+            b"ld6:piecesdeei1e6:piecese",
+            b"d6:secretd6:secreti1ee1:z1:ze",
+            b"i1e",
+        ] {
+            let mut value = Value::from_bencode(encoded).unwrap();
+            value.redact(KEYS);
+            assert_eq!(
+                redact_encoded(encoded, KEYS).unwrap(),
+                value.to_bencode().unwrap(),
+                "{:?}",
+                encoded
+            );
+        }
+    }
+
+    #[test]
+    fn streaming_validates_input() {
+        assert!(redact_encoded(b"d6:secretle", KEYS).is_err());
+        assert!(redact_encoded(b"d1:b0:1:a0:e", KEYS).is_err());
+    }
+}
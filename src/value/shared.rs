@@ -0,0 +1,234 @@
+//! A value tree with structural sharing.
+
+use alloc::{
+    collections::{btree_map, BTreeMap},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{iter, slice};
+
+use crate::{
+    decoding::{self, FromBencode, Object},
+    encoding::{self, SingleItemEncoder, ToBencode},
+    value::{
+        tree::{self, Node, TreeBuilder, Walk},
+        ByteString, Value,
+    },
+};
+
+/// A bencoded value whose byte strings, lists and dicts are reference counted.
+///
+/// Cloning a `SharedValue`, or any subtree of it, takes constant time and shares
+/// the contents with the original. This suits caches which hand out parsed
+/// metadata to many consumers. Contents are copied lazily: the `_mut` accessors
+/// copy a list or dict only if it is still shared with another value.
+///
+/// # Examples
+///
+/// ```
+/// use bendy::{decoding::FromBencode, value::SharedValue};
+///
+/// let torrent = SharedValue::from_bencode(b"d4:infod6:lengthi12eee").unwrap();
+///
+/// // Handing out the `info` dict doesn't copy it
+/// let info = torrent.get(b"info").unwrap().clone();
+/// assert_eq!(info.get(b"length"), Some(&SharedValue::Integer(12)));
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
+pub enum SharedValue {
+    /// A shared byte string
+    Bytes(Arc<[u8]>),
+    /// A shared dictionary mapping byte strings to values
+    Dict(Arc<BTreeMap<Arc<[u8]>, SharedValue>>),
+    /// A signed integer
+    Integer(i64),
+    /// A shared list of values
+    List(Arc<Vec<SharedValue>>),
+}
+
+impl SharedValue {
+    /// Look up a key if this value is a dict. Returns `None` for missing keys and
+    /// for values which aren't dicts.
+    pub fn get(&self, key: &[u8]) -> Option<&SharedValue> {
+        match self {
+            SharedValue::Dict(dict) => dict.get(key),
+            _ => None,
+        }
+    }
+
+    /// Get mutable access to the entries of a dict, copying them first if they are
+    /// shared with another value. Returns `None` if this value isn't a dict.
+    pub fn dict_mut(&mut self) -> Option<&mut BTreeMap<Arc<[u8]>, SharedValue>> {
+        match self {
+            SharedValue::Dict(dict) => Some(Arc::make_mut(dict)),
+            _ => None,
+        }
+    }
+
+    /// Get mutable access to the items of a list, copying them first if they are
+    /// shared with another value. Returns `None` if this value isn't a list.
+    pub fn list_mut(&mut self) -> Option<&mut Vec<SharedValue>> {
+        match self {
+            SharedValue::List(list) => Some(Arc::make_mut(list)),
+            _ => None,
+        }
+    }
+
+    /// Convert into a [`Value`] which borrows its byte strings from this value.
+    pub fn to_value(&self) -> Value<'_> {
+        match self {
+            SharedValue::Bytes(bytes) => Value::Bytes(ByteString::from(&bytes[..])),
+            SharedValue::Dict(dict) => Value::Dict(
+                dict.iter()
+                    .map(|(key, value)| (ByteString::from(&key[..]), value.to_value()))
+                    .collect(),
+            ),
+            SharedValue::Integer(integer) => Value::Integer(*integer),
+            SharedValue::List(list) => {
+                Value::List(list.iter().map(SharedValue::to_value).collect())
+            },
+        }
+    }
+}
+
+impl<'a> From<&Value<'a>> for SharedValue {
+    fn from(value: &Value<'a>) -> Self {
+        match value {
+            Value::Bytes(bytes) => SharedValue::Bytes(Arc::from(&bytes[..])),
+            Value::Dict(dict) => SharedValue::Dict(Arc::new(
+                dict.iter()
+                    .map(|(key, value)| (Arc::from(&key[..]), SharedValue::from(value)))
+                    .collect(),
+            )),
+            Value::Integer(integer) => SharedValue::Integer(*integer),
+            Value::List(list) => {
+                SharedValue::List(Arc::new(list.iter().map(SharedValue::from).collect()))
+            },
+        }
+    }
+}
+
+impl<'a> From<Value<'a>> for SharedValue {
+    fn from(value: Value<'a>) -> Self {
+        SharedValue::from(&value)
+    }
+}
+
+impl ToBencode for SharedValue {
+    const MAX_DEPTH: usize = <Value as ToBencode>::MAX_DEPTH;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        tree::encode(self, encoder)
+    }
+}
+
+impl<'t> Walk<'t> for SharedValue {
+    type Items = slice::Iter<'t, SharedValue>;
+    type Entries = iter::Map<
+        btree_map::Iter<'t, Arc<[u8]>, SharedValue>,
+        fn((&'t Arc<[u8]>, &'t SharedValue)) -> (&'t [u8], &'t SharedValue),
+    >;
+
+    fn node(&'t self) -> Node<'t, Self::Items, Self::Entries> {
+        match self {
+            SharedValue::Bytes(bytes) => Node::Bytes(bytes),
+            SharedValue::Dict(dict) => {
+                Node::Dict(dict.iter().map(|(key, value)| (&key[..], value)))
+            },
+            SharedValue::Integer(integer) => Node::Integer(*integer),
+            SharedValue::List(list) => Node::List(list.iter()),
+        }
+    }
+}
+
+impl FromBencode for SharedValue {
+    const EXPECTED_RECURSION_DEPTH: usize = <Value as FromBencode>::EXPECTED_RECURSION_DEPTH;
+
+    fn decode_bencode_object(object: Object) -> Result<Self, decoding::Error> {
+        tree::build(&mut Shared, object)
+    }
+}
+
+/// Builds a [`SharedValue`] for [`tree::build`]
+struct Shared;
+
+impl<'ser> TreeBuilder<'ser> for Shared {
+    type Value = SharedValue;
+    type List = Vec<SharedValue>;
+    type Dict = BTreeMap<Arc<[u8]>, SharedValue>;
+
+    fn bytes(&mut self, bytes: &'ser [u8]) -> Self::Value {
+        SharedValue::Bytes(Arc::from(bytes))
+    }
+
+    fn integer(&mut self, value: i64) -> Self::Value {
+        SharedValue::Integer(value)
+    }
+
+    fn list(&mut self) -> Self::List {
+        Vec::new()
+    }
+
+    fn dict(&mut self) -> Self::Dict {
+        BTreeMap::new()
+    }
+
+    fn push(&mut self, list: &mut Self::List, item: Self::Value) {
+        list.push(item);
+    }
+
+    fn insert(&mut self, dict: &mut Self::Dict, key: &'ser [u8], value: Self::Value) {
+        dict.insert(Arc::from(key), value);
+    }
+
+    fn finish_list(&mut self, list: Self::List) -> Self::Value {
+        SharedValue::List(Arc::new(list))
+    }
+
+    fn finish_dict(&mut self, dict: Self::Dict) -> Self::Value {
+        SharedValue::Dict(Arc::new(dict))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static ENCODED: &[u8] = b"d4:infod5:filesld6:lengthi1eee4:name1:xe4:listli1eee";
+
+    #[test]
+    fn clones_share_contents() {
+        let value = SharedValue::from_bencode(ENCODED).unwrap();
+        let info = value.get(b"info").unwrap().clone();
+
+        match (value.get(b"info"), &info) {
+            (Some(SharedValue::Dict(a)), SharedValue::Dict(b)) => assert!(Arc::ptr_eq(a, b)),
+            other => panic!("expected dicts, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn mutation_copies_shared_contents() {
+        let original = SharedValue::from_bencode(ENCODED).unwrap();
+        let mut copy = original.clone();
+
+        copy.dict_mut()
+            .unwrap()
+            .insert(Arc::from(&b"new"[..]), SharedValue::Integer(1));
+        assert_eq!(original.get(b"new"), None);
+        assert_eq!(copy.get(b"new"), Some(&SharedValue::Integer(1)));
+        assert_eq!(copy.get(b"info"), original.get(b"info"));
+
+        assert!(SharedValue::Integer(1).list_mut().is_none());
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let value = Value::from_bencode(ENCODED).unwrap();
+        let shared = SharedValue::from(&value);
+
+        assert_eq!(shared.to_value(), value);
+        assert_eq!(shared.to_bencode().unwrap(), ENCODED);
+        assert_eq!(SharedValue::from_bencode(ENCODED).unwrap(), shared);
+    }
+}
@@ -0,0 +1,64 @@
+//! Representing byte strings as text.
+
+#[cfg(any(
+    feature = "serde",
+    feature = "json",
+    feature = "yaml",
+    feature = "toml"
+))]
+use alloc::string::String;
+#[cfg(any(
+    feature = "serde",
+    feature = "json",
+    feature = "yaml",
+    feature = "toml"
+))]
+use core::str;
+
+#[cfg(any(
+    feature = "serde",
+    feature = "json",
+    feature = "yaml",
+    feature = "toml"
+))]
+use crate::decoding::Error;
+
+/// How byte strings, including dict keys, are represented in text-only formats
+/// such as JSON, YAML or TOML.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BytesAsText {
+    /// Convert byte strings to text, failing if any isn't valid UTF-8.
+    Strict,
+    /// Convert byte strings to text, replacing invalid UTF-8 sequences with
+    /// `U+FFFD REPLACEMENT CHARACTER`.
+    Lossy,
+    /// Convert every byte string to a string of lowercase hex digits.
+    Hex,
+}
+
+// Only the serde, JSON, YAML and TOML conversions represent bytes as text
+#[cfg(any(
+    feature = "serde",
+    feature = "json",
+    feature = "yaml",
+    feature = "toml"
+))]
+impl BytesAsText {
+    pub(super) fn convert(self, bytes: &[u8]) -> Result<String, Error> {
+        use core::fmt::Write;
+
+        match self {
+            BytesAsText::Strict => str::from_utf8(bytes)
+                .map(String::from)
+                .map_err(Error::malformed_content),
+            BytesAsText::Lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
+            BytesAsText::Hex => {
+                let mut hex = String::with_capacity(bytes.len() * 2);
+                for byte in bytes {
+                    write!(hex, "{:02x}", byte).expect("writing to a string can't fail");
+                }
+                Ok(hex)
+            },
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Conversions between [`Value`] and [`toml::Value`].
+//!
+//! Converting a value into TOML represents byte strings (including dict keys)
+//! as strings, with a policy for those which are not valid UTF-8, see
+//! [`BytesAsText`]. The `TryFrom` implementation uses [`BytesAsText::Strict`].
+//!
+//! Converting TOML into a value maps strings to their UTF-8 bytes, booleans to
+//! `0` and `1`, datetimes to their text and tables to dicts. Floats cannot be
+//! represented and cause an error.
+
+use alloc::collections::BTreeMap;
+use core::convert::TryFrom;
+
+use toml::{Table, Value as TomlValue};
+
+use crate::{
+    decoding::Error,
+    value::{ByteString, BytesAsText, Value},
+};
+
+impl<'a> Value<'a> {
+    /// Convert this value into TOML, representing byte strings according to
+    /// `bytes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::{BytesAsText, Value};
+    ///
+    /// let value = Value::parse_pretty(r#"{"name": "foo", "hash": <ff00>}"#).unwrap();
+    ///
+    /// assert!(value.to_toml(BytesAsText::Strict).is_err());
+    /// let toml = value.to_toml(BytesAsText::Hex).unwrap();
+    /// assert_eq!(
+    ///     toml::to_string(&toml).unwrap(),
+    ///     "68617368 = \"ff00\"\n6e616d65 = \"666f6f\"\n"
+    /// );
+    /// ```
+    pub fn to_toml(&self, bytes: BytesAsText) -> Result<TomlValue, Error> {
+        match self {
+            Value::Bytes(string) => bytes.convert(string).map(TomlValue::String),
+            Value::Dict(dict) => dict
+                .iter()
+                .map(|(key, value)| Ok((bytes.convert(key)?, value.to_toml(bytes)?)))
+                .collect::<Result<Table, Error>>()
+                .map(TomlValue::Table),
+            Value::Integer(integer) => Ok(TomlValue::Integer(*integer)),
+            Value::List(list) => list
+                .iter()
+                .map(|value| value.to_toml(bytes))
+                .collect::<Result<_, _>>()
+                .map(TomlValue::Array),
+        }
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for TomlValue {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        value.to_toml(BytesAsText::Strict)
+    }
+}
+
+impl TryFrom<TomlValue> for Value<'static> {
+    type Error = Error;
+
+    fn try_from(toml: TomlValue) -> Result<Self, Self::Error> {
+        match toml {
+            TomlValue::String(string) => Ok(Value::from(string)),
+            TomlValue::Integer(integer) => Ok(Value::Integer(integer)),
+            TomlValue::Float(_) => Err(Error::unexpected_token("Num", "Float")),
+            TomlValue::Boolean(flag) => Ok(Value::from(flag)),
+            TomlValue::Datetime(datetime) => Ok(Value::from(datetime.to_string())),
+            TomlValue::Array(array) => array
+                .into_iter()
+                .map(Value::try_from)
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+            TomlValue::Table(table) => table
+                .into_iter()
+                .map(|(key, value)| {
+                    Ok((ByteString::from(key.into_bytes()), Value::try_from(value)?))
+                })
+                .collect::<Result<BTreeMap<_, _>, Error>>()
+                .map(Value::Dict),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toml(text: &str) -> TomlValue {
+        TomlValue::Table(text.parse().unwrap())
+    }
+
+    #[test]
+    fn toml_round_trips() {
+        let toml = toml("a = [1, -2, \"three\"]\nd = []\n[b.c]\n");
+        let value = Value::try_from(toml.clone()).unwrap();
+
+        assert_eq!(
+            value,
+            Value::parse_pretty(r#"{"a": [1, -2, "three"], "b": {"c": {}}, "d": []}"#).unwrap()
+        );
+        // `toml::Value` has an inherent `try_from` for serializable types
+        assert_eq!(<TomlValue as TryFrom<_>>::try_from(value).unwrap(), toml);
+    }
+
+    #[test]
+    fn unrepresentable_toml_fails() {
+        assert_eq!(
+            Value::try_from(toml("a = true\nb = 1979-05-27T07:32:00Z")).unwrap(),
+            Value::parse_pretty(r#"{"a": 1, "b": "1979-05-27T07:32:00Z"}"#).unwrap()
+        );
+        assert!(Value::try_from(toml("a = 1.5")).is_err());
+        assert!(Value::try_from(toml("a = [1, 2.5]")).is_err());
+    }
+
+    #[test]
+    fn byte_strings_follow_the_policy() {
+        let value = Value::parse_pretty(r#"{<ff>: "a\xffb"}"#).unwrap();
+
+        assert!(value.to_toml(BytesAsText::Strict).is_err());
+        assert_eq!(
+            value.to_toml(BytesAsText::Lossy).unwrap(),
+            toml("\"\u{fffd}\" = \"a\u{fffd}b\"")
+        );
+        assert_eq!(
+            value.to_toml(BytesAsText::Hex).unwrap(),
+            toml("ff = \"61ff62\"")
+        );
+    }
+}
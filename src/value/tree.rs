@@ -0,0 +1,321 @@
+//! Decoding and encoding value trees with an explicit stack.
+//!
+//! Decoding nested lists and dicts by recursing into
+//! [`decode_bencode_object`](crate::decoding::FromBencode::decode_bencode_object)
+//! takes a stack frame per level, so deeply nested input can overflow the stack
+//! long before it reaches the depth limit of the
+//! [`Decoder`](crate::decoding::Decoder). [`build`] instead reads the tokens of
+//! a list or dict in a loop and keeps the unfinished containers in a `Vec`,
+//! which is bounded by that depth limit. [`encode`] does the same for the
+//! iterators over the unfinished containers of a tree, so a tree that could be
+//! built can always be encoded again.
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::{btree_map, BTreeMap},
+    vec,
+    vec::Vec,
+};
+use core::{iter, slice};
+#[cfg(feature = "std")]
+use std::collections::{btree_map, BTreeMap};
+
+use crate::{
+    decoding::{Error, Object},
+    encoding::{self, SingleItemEncoder},
+    state_tracker::{StructureError, Token},
+    value::{ByteString, Value},
+};
+
+/// The operations [`build`] needs to assemble one kind of value tree
+pub(super) trait TreeBuilder<'ser> {
+    type Value;
+    type List;
+    type Dict;
+
+    fn bytes(&mut self, bytes: &'ser [u8]) -> Self::Value;
+    fn integer(&mut self, value: i64) -> Self::Value;
+    fn list(&mut self) -> Self::List;
+    fn dict(&mut self) -> Self::Dict;
+    fn push(&mut self, list: &mut Self::List, item: Self::Value);
+    fn insert(&mut self, dict: &mut Self::Dict, key: &'ser [u8], value: Self::Value);
+    fn finish_list(&mut self, list: Self::List) -> Self::Value;
+    fn finish_dict(&mut self, dict: Self::Dict) -> Self::Value;
+}
+
+/// An unfinished list or dict, with the key of the next value of a dict
+enum Frame<'ser, L, D> {
+    List(L),
+    Dict(D, Option<&'ser [u8]>),
+}
+
+/// Convert `object`, including everything nested in it, into a value without
+/// recursing
+pub(super) fn build<'ser, B: TreeBuilder<'ser>>(
+    builder: &mut B,
+    object: Object<'_, 'ser>,
+) -> Result<B::Value, Error> {
+    match object {
+        Object::Bytes(bytes) => Ok(builder.bytes(bytes)),
+        Object::Integer(text) => Ok(builder.integer(parse_integer(text)?)),
+        Object::List(mut decoder) => {
+            let list = builder.list();
+            build_from(builder, Frame::List(list), || decoder.next_token())
+        },
+        Object::Dict(mut decoder) => {
+            let dict = builder.dict();
+            build_from(builder, Frame::Dict(dict, None), || decoder.next_token())
+        },
+    }
+}
+
+/// Fill `first` and the containers nested in it with the tokens returned by
+/// `next_token`, which ends with the end of `first`. The decoder has already
+/// checked that dict keys are strings and that every value follows a key.
+fn build_from<'ser, B: TreeBuilder<'ser>>(
+    builder: &mut B,
+    first: Frame<'ser, B::List, B::Dict>,
+    mut next_token: impl FnMut() -> Result<Option<Token<'ser>>, Error>,
+) -> Result<B::Value, Error> {
+    let mut stack = vec![first];
+    loop {
+        let token = next_token()?.ok_or(StructureError::UnexpectedEof)?;
+        let value = match token {
+            Token::List => {
+                stack.push(Frame::List(builder.list()));
+                continue;
+            },
+            Token::Dict => {
+                stack.push(Frame::Dict(builder.dict(), None));
+                continue;
+            },
+            Token::String(bytes) => {
+                if let Some(Frame::Dict(_, key @ None)) = stack.last_mut() {
+                    *key = Some(bytes);
+                    continue;
+                }
+                builder.bytes(bytes)
+            },
+            Token::Num(text) => builder.integer(parse_integer(text)?),
+            Token::End => match stack.pop() {
+                Some(Frame::List(list)) => builder.finish_list(list),
+                Some(Frame::Dict(dict, _)) => builder.finish_dict(dict),
+                None => unreachable!("The first container ends the tokens"),
+            },
+        };
+
+        match stack.last_mut() {
+            Some(Frame::List(list)) => builder.push(list, value),
+            Some(Frame::Dict(dict, key)) => {
+                let key = key
+                    .take()
+                    .expect("The decoder checks that values follow keys");
+                builder.insert(dict, key, value)
+            },
+            None => return Ok(value),
+        }
+    }
+}
+
+/// Parse an integer whose syntax the decoder already checked
+fn parse_integer(text: &str) -> Result<i64, Error> {
+    text.parse().map_err(|_| Error::integer_overflow(text))
+}
+
+/// A list or dict of a tree being encoded, or one of its leaves
+pub(super) enum Node<'t, L, D> {
+    Bytes(&'t [u8]),
+    Integer(i64),
+    List(L),
+    Dict(D),
+}
+
+/// A tree which [`encode`] can walk
+pub(super) trait Walk<'t>: 't {
+    /// The items of a list
+    type Items: Iterator<Item = &'t Self>;
+    /// The entries of a dict, in order
+    type Entries: Iterator<Item = (&'t [u8], &'t Self)>;
+
+    fn node(&'t self) -> Node<'t, Self::Items, Self::Entries>;
+}
+
+/// An open list or dict of the tree being encoded
+enum Open<L, D> {
+    List(L),
+    Dict(D),
+}
+
+/// Encode `root`, including everything nested in it, without recursing. The
+/// encoder still checks its depth limit and the order of dict keys.
+pub(super) fn encode<'t, T: Walk<'t>>(
+    root: &'t T,
+    encoder: SingleItemEncoder,
+) -> Result<(), encoding::Error> {
+    let encoder = encoder.into_encoder();
+    let mut open = Vec::new();
+    let mut next = Some(root);
+    loop {
+        if let Some(value) = next.take() {
+            match value.node() {
+                Node::Bytes(bytes) => encoder.emit_bytes(bytes)?,
+                Node::Integer(integer) => encoder.emit_int(integer)?,
+                Node::List(items) => {
+                    encoder.emit_token(Token::List)?;
+                    open.push(Open::List(items));
+                },
+                Node::Dict(entries) => {
+                    encoder.emit_token(Token::Dict)?;
+                    open.push(Open::Dict(entries));
+                },
+            }
+        }
+
+        next = match open.last_mut() {
+            Some(Open::List(items)) => items.next(),
+            Some(Open::Dict(entries)) => match entries.next() {
+                Some((key, value)) => {
+                    encoder.emit_token(Token::String(key))?;
+                    Some(value)
+                },
+                None => None,
+            },
+            None => return Ok(()),
+        };
+        if next.is_none() {
+            open.pop();
+            encoder.emit_token(Token::End)?;
+        }
+    }
+}
+
+impl<'t, 'a: 't> Walk<'t> for Value<'a> {
+    type Items = slice::Iter<'t, Value<'a>>;
+    type Entries = iter::Map<
+        btree_map::Iter<'t, ByteString<'a>, Value<'a>>,
+        fn((&'t ByteString<'a>, &'t Value<'a>)) -> (&'t [u8], &'t Value<'a>),
+    >;
+
+    fn node(&'t self) -> Node<'t, Self::Items, Self::Entries> {
+        match self {
+            Value::Bytes(bytes) => Node::Bytes(bytes),
+            Value::Integer(integer) => Node::Integer(*integer),
+            Value::List(list) => Node::List(list.iter()),
+            Value::Dict(dict) => Node::Dict(dict.iter().map(|(key, value)| (&key[..], value))),
+        }
+    }
+}
+
+/// Builds a [`Value`] which copies all byte strings and dict keys
+pub(super) struct Owned;
+
+/// Builds a [`Value`] which borrows all byte strings and dict keys
+pub(super) struct Borrowed;
+
+macro_rules! value_builder {
+    ($builder:ident, $lifetime:lifetime, $string:path) => {
+        impl<'ser> TreeBuilder<'ser> for $builder {
+            type Value = Value<$lifetime>;
+            type List = Vec<Value<$lifetime>>;
+            type Dict = BTreeMap<ByteString<$lifetime>, Value<$lifetime>>;
+
+            fn bytes(&mut self, bytes: &'ser [u8]) -> Self::Value {
+                Value::Bytes($string(bytes))
+            }
+
+            fn integer(&mut self, value: i64) -> Self::Value {
+                Value::Integer(value)
+            }
+
+            fn list(&mut self) -> Self::List {
+                Vec::new()
+            }
+
+            fn dict(&mut self) -> Self::Dict {
+                BTreeMap::new()
+            }
+
+            fn push(&mut self, list: &mut Self::List, item: Self::Value) {
+                list.push(item);
+            }
+
+            fn insert(&mut self, dict: &mut Self::Dict, key: &'ser [u8], value: Self::Value) {
+                dict.insert($string(key), value);
+            }
+
+            fn finish_list(&mut self, list: Self::List) -> Self::Value {
+                Value::List(list)
+            }
+
+            fn finish_dict(&mut self, dict: Self::Dict) -> Self::Value {
+                Value::Dict(dict)
+            }
+        }
+    };
+}
+
+value_builder!(Owned, 'static, ByteString::copy_from);
+value_builder!(Borrowed, 'ser, ByteString::from);
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    use crate::{
+        decoding::{Decoder, FromBencode},
+        encoding::ToBencode,
+    };
+
+    use super::*;
+
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut encoded = vec![b'l'; depth];
+        encoded.extend_from_slice(b"d1:ai1ee");
+        encoded.extend(vec![b'e'; depth]);
+        encoded
+    }
+
+    #[test]
+    fn values_are_nested_only_as_deep_as_they_can_be_dropped() {
+        let depth = <Value as FromBencode>::EXPECTED_RECURSION_DEPTH - 1;
+        let value = Value::from_bencode(&nested(depth)).unwrap();
+        assert_eq!(value.to_bencode().unwrap(), nested(depth));
+        drop(value);
+
+        assert!(Value::from_bencode(&nested(depth + 1)).is_err());
+    }
+
+    #[test]
+    fn deeply_nested_values_do_not_overflow_the_stack() {
+        const DEPTH: usize = 100_000;
+        let encoded = nested(DEPTH);
+
+        let mut decoder = Decoder::new(&encoded).with_max_depth(DEPTH + 1);
+        let object = decoder.next_object().unwrap().unwrap();
+        let mut level = build(&mut Owned, object).unwrap();
+        assert_eq!(level.to_bencode().unwrap(), encoded);
+
+        // Above the default depth limit, dropping the whole value would
+        // recurse like any other nested `Vec`, so unwrap the levels one by one
+        for _ in 0..DEPTH {
+            level = match level {
+                Value::List(mut list) => list.pop().unwrap(),
+                other => panic!("expected a list, got {:?}", other),
+            };
+        }
+        assert_eq!(level, Value::from_bencode(b"d1:ai1ee").unwrap());
+
+        // Skipping unread items doesn't recurse either
+        let mut decoder = Decoder::new(&encoded).with_max_depth(DEPTH + 1);
+        drop(decoder.next_object().unwrap());
+        assert!(decoder.next_object().unwrap().is_none());
+    }
+
+    #[test]
+    fn the_depth_limit_still_applies() {
+        let mut decoder = Decoder::new(b"llleee").with_max_depth(2);
+        let object = decoder.next_object().unwrap().unwrap();
+        assert!(build(&mut Owned, object).is_err());
+    }
+}
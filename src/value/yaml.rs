@@ -0,0 +1,152 @@
+//! Conversions between [`Value`] and [`serde_yaml::Value`].
+//!
+//! Converting a value into YAML represents byte strings (including dict keys)
+//! as strings, with a policy for those which are not valid UTF-8, see
+//! [`BytesAsText`]. The `TryFrom` implementation uses [`BytesAsText::Strict`].
+//!
+//! Converting YAML into a value maps strings to their UTF-8 bytes, booleans to
+//! `0` and `1` and mappings to dicts. `null`, tagged values, numbers which
+//! don't fit into an `i64` and mapping keys which aren't strings cannot be
+//! represented and cause an error.
+
+use alloc::collections::BTreeMap;
+use core::convert::{TryFrom, TryInto};
+
+use serde_yaml::{Mapping, Number, Value as YamlValue};
+
+use crate::{
+    decoding::Error,
+    value::{ByteString, BytesAsText, Value},
+};
+
+impl<'a> Value<'a> {
+    /// Convert this value into YAML, representing byte strings according to
+    /// `bytes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bendy::value::{BytesAsText, Value};
+    ///
+    /// let value = Value::parse_pretty(r#"{"name": "foo", "hash": <ff00>}"#).unwrap();
+    ///
+    /// assert!(value.to_yaml(BytesAsText::Strict).is_err());
+    /// let yaml = value.to_yaml(BytesAsText::Hex).unwrap();
+    /// assert_eq!(
+    ///     serde_yaml::to_string(&yaml).unwrap(),
+    ///     "'68617368': ff00\n6e616d65: 666f6f\n"
+    /// );
+    /// ```
+    pub fn to_yaml(&self, bytes: BytesAsText) -> Result<YamlValue, Error> {
+        match self {
+            Value::Bytes(string) => bytes.convert(string).map(YamlValue::String),
+            Value::Dict(dict) => dict
+                .iter()
+                .map(|(key, value)| {
+                    Ok((
+                        YamlValue::String(bytes.convert(key)?),
+                        value.to_yaml(bytes)?,
+                    ))
+                })
+                .collect::<Result<Mapping, Error>>()
+                .map(YamlValue::Mapping),
+            Value::Integer(integer) => Ok(YamlValue::Number(Number::from(*integer))),
+            Value::List(list) => list
+                .iter()
+                .map(|value| value.to_yaml(bytes))
+                .collect::<Result<_, _>>()
+                .map(YamlValue::Sequence),
+        }
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for YamlValue {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        value.to_yaml(BytesAsText::Strict)
+    }
+}
+
+impl TryFrom<YamlValue> for Value<'static> {
+    type Error = Error;
+
+    fn try_from(yaml: YamlValue) -> Result<Self, Self::Error> {
+        match yaml {
+            YamlValue::Null => Err(Error::unexpected_token("any value", "Null")),
+            YamlValue::Bool(flag) => Ok(Value::from(flag)),
+            YamlValue::Number(number) => match (number.as_i64(), number.as_u64()) {
+                (Some(integer), _) => Ok(Value::Integer(integer)),
+                (None, Some(integer)) => integer
+                    .try_into()
+                    .map(Value::Integer)
+                    .map_err(Error::malformed_content),
+                (None, None) => Err(Error::unexpected_token("Num", "Float")),
+            },
+            YamlValue::String(string) => Ok(Value::from(string)),
+            YamlValue::Sequence(sequence) => sequence
+                .into_iter()
+                .map(Value::try_from)
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+            YamlValue::Mapping(mapping) => mapping
+                .into_iter()
+                .map(|(key, value)| match key {
+                    YamlValue::String(key) => {
+                        Ok((ByteString::from(key.into_bytes()), Value::try_from(value)?))
+                    },
+                    _ => Err(Error::unexpected_token("String", "another key")),
+                })
+                .collect::<Result<BTreeMap<_, _>, Error>>()
+                .map(Value::Dict),
+            YamlValue::Tagged(_) => Err(Error::unexpected_token("any value", "Tagged")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(text: &str) -> YamlValue {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn yaml_round_trips() {
+        let yaml = yaml("{a: [1, -2, three], b: {c: {}}, d: []}");
+        let value = Value::try_from(yaml.clone()).unwrap();
+
+        assert_eq!(
+            value,
+            Value::parse_pretty(r#"{"a": [1, -2, "three"], "b": {"c": {}}, "d": []}"#).unwrap()
+        );
+        assert_eq!(YamlValue::try_from(value).unwrap(), yaml);
+    }
+
+    #[test]
+    fn unrepresentable_yaml_fails() {
+        assert_eq!(Value::try_from(yaml("true")).unwrap(), Value::Integer(1));
+        assert!(Value::try_from(yaml("null")).is_err());
+        assert!(Value::try_from(yaml("1.5")).is_err());
+        assert!(Value::try_from(yaml("18446744073709551615")).is_err());
+        assert!(Value::try_from(yaml("[1, null]")).is_err());
+        assert!(Value::try_from(yaml("{1: a}")).is_err());
+        assert!(Value::try_from(yaml("!tag a")).is_err());
+    }
+
+    #[test]
+    fn byte_strings_follow_the_policy() {
+        let value = Value::parse_pretty(r#"{<ff>: "a\xffb"}"#).unwrap();
+
+        assert!(value.to_yaml(BytesAsText::Strict).is_err());
+        assert_eq!(
+            value.to_yaml(BytesAsText::Lossy).unwrap(),
+            yaml("{\"\u{fffd}\": \"a\u{fffd}b\"}")
+        );
+        assert_eq!(
+            value.to_yaml(BytesAsText::Hex).unwrap(),
+            yaml("{ff: 61ff62}")
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Checks that decoding into borrowed objects, validating and encoding into
+//! recycled buffers don't allocate.
+//!
+//! Allocations are counted per thread, so that tests running concurrently
+//! don't see each other's allocations.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use bendy::{
+    decoding::{self, Decoder, Error, Object},
+    encoding::{self, AsString, SingleItemEncoder, ToBencode},
+};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The number of allocations made by `f` on the current thread
+fn allocations<T>(f: impl FnOnce() -> T) -> (usize, T) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}
+
+/// Visit every object and return the number of leaf values
+fn walk(object: Object) -> Result<usize, Error> {
+    Ok(match object {
+        Object::List(mut list) => {
+            let mut leaves = 0;
+            while let Some(item) = list.next_object()? {
+                leaves += walk(item)?;
+            }
+            leaves
+        },
+        Object::Dict(mut dict) => {
+            let mut leaves = 0;
+            while let Some((_, value)) = dict.next_pair()? {
+                leaves += walk(value)?;
+            }
+            leaves
+        },
+        Object::Integer(_) | Object::Bytes(_) => 1,
+    })
+}
+
+fn decode(encoded: &[u8]) -> Result<usize, Error> {
+    let mut decoder = Decoder::new(encoded);
+    let mut leaves = 0;
+    while let Some(object) = decoder.next_object()? {
+        leaves += walk(object)?;
+    }
+    Ok(leaves)
+}
+
+#[test]
+fn decoding_torrents_does_not_allocate() {
+    static DEBIAN: &[u8] =
+        include_bytes!("../examples/torrent_files/debian-9.4.0-amd64-netinst.iso.torrent");
+
+    let (count, leaves) = allocations(|| decode(DEBIAN).unwrap());
+    assert!(leaves > 0);
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn decoding_nested_structures_does_not_allocate() {
+    let encoded = b"d1:ald1:bld1:cli-1e3:abceeeeeeld1:d0:ee";
+
+    let (count, leaves) = allocations(|| decode(encoded).unwrap());
+    assert_eq!(leaves, 3);
+    assert_eq!(count, 0);
+
+    let nested = [&[b'l'; 16][..], &[b'e'; 16][..]].concat();
+    let (count, leaves) = allocations(|| decode(&nested).unwrap());
+    assert_eq!(leaves, 0);
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn validating_does_not_allocate() {
+    static DEBIAN: &[u8] =
+        include_bytes!("../examples/torrent_files/debian-9.4.0-amd64-netinst.iso.torrent");
+
+    let (count, validity) = allocations(|| decoding::validate(DEBIAN, 16).unwrap());
+    assert!(validity.is_canonical());
+    assert_eq!(count, 0);
+}
+
+/// A KRPC response, encoded with sorted dicts
+struct Response {
+    id: [u8; 20],
+    values: [u16; 3],
+}
+
+impl ToBencode for Response {
+    const MAX_DEPTH: usize = 3;
+
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), encoding::Error> {
+        encoder.emit_dict(|mut e| {
+            e.emit_pair_with(b"r", |e| {
+                e.emit_dict(|mut e| {
+                    e.emit_pair(b"id", AsString(&self.id[..]))?;
+                    e.emit_pair_with(b"values", |e| {
+                        e.emit_list(|e| self.values.iter().try_for_each(|&value| e.emit(value)))
+                    })
+                })
+            })?;
+            e.emit_pair(b"t", "aa")?;
+            e.emit_pair(b"y", "r")
+        })
+    }
+}
+
+#[test]
+fn encoding_into_recycled_buffers_does_not_allocate() {
+    let response = Response {
+        id: [b'a'; 20],
+        values: [1, 22, 333],
+    };
+    let mut buffer = Vec::with_capacity(128);
+
+    let (count, result) = allocations(|| response.to_bencode_into(&mut buffer));
+    result.unwrap();
+    assert_eq!(
+        buffer,
+        &b"d1:rd2:id20:aaaaaaaaaaaaaaaaaaaa6:valuesli1ei22ei333eee1:t2:aa1:y1:re"[..]
+    );
+    assert_eq!(count, 0);
+}
@@ -0,0 +1,57 @@
+//! Checks the counters of the `instrument` feature.
+//!
+//! The counters are shared by all threads, so this binary has a single test.
+
+use std::panic;
+
+use bendy::{
+    decoding::FromBencode,
+    encoding::{Error, SingleItemEncoder, ToBencode},
+    instrument::{self, CountingAllocator},
+    value::Value,
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+
+struct Panics;
+
+impl ToBencode for Panics {
+    const MAX_DEPTH: usize = 0;
+
+    fn encode(&self, _: SingleItemEncoder) -> Result<(), Error> {
+        panic!("encoding failed")
+    }
+}
+
+#[test]
+fn calls_bytes_and_allocations_are_counted() {
+    let encoded = b"d3:cow3:moo4:spaml1:a1:bee";
+
+    instrument::reset_stats();
+    let value = Value::from_bencode(encoded).unwrap();
+    let stats = instrument::stats();
+    assert_eq!(stats.decoding.calls, 1);
+    assert_eq!(stats.decoding.bytes, encoded.len() as u64);
+    assert!(stats.decoding.allocations > 0);
+    assert!(stats.decoding.allocated_bytes > 0);
+    assert_eq!(stats.encoding, Default::default());
+
+    let mut buffer = Vec::with_capacity(64);
+    value.to_bencode_into(&mut buffer).unwrap();
+    value.to_bencode_into(&mut buffer).unwrap();
+    let stats = instrument::stats();
+    assert_eq!(stats.encoding.calls, 2);
+    assert_eq!(stats.encoding.bytes, 2 * encoded.len() as u64);
+    assert_eq!(stats.decoding.calls, 1);
+
+    instrument::reset_stats();
+    assert_eq!(instrument::stats(), Default::default());
+
+    // A panicking call must not leave later calls counted as nested ones
+    assert!(panic::catch_unwind(|| Panics.to_bencode()).is_err());
+    Value::from_bencode(encoded).unwrap();
+    let stats = instrument::stats();
+    assert_eq!(stats.decoding.calls, 1);
+    assert!(stats.decoding.allocations > 0);
+}
@@ -0,0 +1,130 @@
+//! Checks the spans and events of the `tracing` feature.
+
+use std::{
+    fmt::Debug,
+    panic,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bendy::{
+    decoding::{Decoder, EmptyPolicy, FromBencode},
+    encoding::{Error, SingleItemEncoder, ToBencode},
+    value::Value,
+};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+/// Records the fields of every span and event as `name=value` lines
+#[derive(Clone, Default)]
+struct Recorder {
+    lines: Arc<Mutex<Vec<String>>>,
+    ids: Arc<AtomicU64>,
+}
+
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0 += &format!(" {}={:?}", field.name(), value);
+    }
+}
+
+impl Recorder {
+    fn record(&self, name: &str, visit: impl FnOnce(&mut Fields)) {
+        let mut fields = Fields(name.to_owned());
+        visit(&mut fields);
+        self.lines.lock().unwrap().push(fields.0);
+    }
+
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.lines.lock().unwrap())
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &span::Attributes) -> span::Id {
+        self.record(span.metadata().name(), |fields| span.record(fields));
+        span::Id::from_u64(self.ids.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _: &span::Id, values: &span::Record) {
+        self.record("record", |fields| values.record(fields));
+    }
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &Event) {
+        self.record("event", |fields| event.record(fields));
+    }
+
+    fn enter(&self, _: &span::Id) {}
+
+    fn exit(&self, _: &span::Id) {}
+}
+
+struct Panics;
+
+impl ToBencode for Panics {
+    const MAX_DEPTH: usize = 0;
+
+    fn encode(&self, _: SingleItemEncoder) -> Result<(), Error> {
+        panic!("encoding failed")
+    }
+}
+
+#[test]
+fn calls_errors_and_fixups_are_traced() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let value = Value::from_bencode(b"l3:fooi1ee").unwrap();
+        let lines = recorder.take();
+        assert_eq!(lines[0], "bencode phase=\"decoding\"");
+        assert_eq!(lines[1], "record bytes=10");
+        assert!(lines[2].starts_with("event message=finished decoding bytes=10 elapsed="));
+        assert_eq!(lines.len(), 3);
+
+        value.to_bencode().unwrap();
+        let lines = recorder.take();
+        assert_eq!(lines[0], "bencode phase=\"encoding\"");
+        assert_eq!(lines[1], "record bytes=10");
+
+        Decoder::new(b"d1:bi1e1:ai2ee")
+            .with_sorted_keys(false)
+            .tokens()
+            .for_each(drop);
+        assert_eq!(
+            recorder.take(),
+            ["event message=accepted an unsorted key depth=1"]
+        );
+
+        Decoder::new(b"l0:e")
+            .with_empty_strings(EmptyPolicy::Warn)
+            .tokens()
+            .for_each(drop);
+        assert_eq!(
+            recorder.take(),
+            ["event message=accepted an empty byte string depth=1"]
+        );
+
+        Decoder::new(b"li1ex").tokens().for_each(drop);
+        assert_eq!(
+            recorder.take(),
+            ["event message=decoding failed code=\"E0103\" offset=4 depth=1"]
+        );
+
+        // A panicking call must not leave later calls traced as nested ones
+        assert!(panic::catch_unwind(|| Panics.to_bencode()).is_err());
+        recorder.take();
+        Value::from_bencode(b"i1e").unwrap();
+        assert_eq!(recorder.take()[0], "bencode phase=\"decoding\"");
+    });
+}